@@ -0,0 +1,45 @@
+//! Measures typing and deleting at both ends of a 5 MB file on a single
+//! line, as minified JavaScript or JSON is, with the screen redrawn after
+//! every key. Run with `cargo bench`.
+use revise::{Document, Event, Revise, Size};
+use std::time::{Duration, Instant};
+use termion::event::Key;
+
+const BYTES: usize = 5_000_000;
+const EDITS: usize = 100;
+
+fn time(to: Key) -> Duration {
+    let item = r#"{"id":42,"name":"value","tags":["a","b"]},"#;
+    let text = item.repeat(BYTES / item.len());
+    let document = Document::from_reader(text.as_bytes()).expect("valid UTF-8");
+    let mut revise = Revise::from_document(
+        document,
+        Size {
+            width: 80,
+            height: 22,
+        },
+    );
+    let backend = revise.backend_mut();
+
+    backend.push_event(Event::Key(to));
+
+    for _ in 0..EDITS {
+        backend.push_event(Event::Key(Key::Char('x')));
+        backend.push_event(Event::Key(Key::Backspace));
+    }
+
+    backend.push_event(Event::Key(Key::Ctrl('q')));
+    backend.push_event(Event::Key(Key::Ctrl('q')));
+
+    let start = Instant::now();
+
+    revise.run().expect("the editor runs");
+
+    start.elapsed() / (EDITS * 2) as u32
+}
+
+fn main() {
+    println!("edit a single line of {BYTES} bytes");
+    println!("  at the start: {:?} per key", time(Key::Home));
+    println!("  at the end:   {:?} per key", time(Key::End));
+}
@@ -1,36 +1,109 @@
+use crate::args::Arguments;
+use crate::backend::{Backend, TestBackend};
+use crate::case::Case;
+use crate::clipboard::Clipboard;
+use crate::clock::LocalTime;
+use crate::config::Config;
+use crate::diff::{LineChange, LineDiff};
+use crate::document::{bracket_pair, reindent, LineShift, SaveError};
+use crate::encoding::Encoding;
+use crate::finder;
+use crate::git::Change;
+use crate::highlight;
+use crate::hooks::{HookEvent, Hooks};
+use crate::jump_list::JumpList;
+use crate::keymap::{Action, KeyCombo, Keymap};
+use crate::palette::ColorSupport;
+use crate::recent::RecentFiles;
+use crate::row::Overlay;
+use crate::spelling::Dictionary;
+use crate::swap::SwapFile;
+use crate::temp;
+use crate::theme::Theme;
+use crate::width::{printable, skip_width, take_width, text_width};
 use crate::Document;
+use crate::Event;
+use crate::Modifiers;
 use crate::Row;
+use crate::Size;
 use crate::Terminal;
 
-use cli_clipboard::{ClipboardContext, ClipboardProvider};
-use std::env::{self, Args};
+use std::cmp::{self, Ordering};
+use std::collections::{HashMap, VecDeque};
+use std::env;
 use std::error::Error as Err;
-use std::io::Error as IOError;
+use std::fs;
+use std::io::{self, Error as IOError, ErrorKind, Write};
+use std::mem;
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
 use std::time::{Duration, Instant};
-use termion::color;
-use termion::event::Key;
+use termion::event::{Key, MouseButton, MouseEvent};
+use termion::{color, cursor, style};
 use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-const STATUS_FG_COLOR: color::Rgb = color::Rgb(63, 63, 63);
-const STATUS_BG_COLOR: color::Rgb = color::Rgb(239, 239, 239);
+/// How many recent files the welcome screen lists.
+const WELCOME_RECENT_FILES: usize = 5;
+/// The status bar always has this many columns for the file name, even
+/// when the rest of it does not fit.
+const MIN_FILENAME_WIDTH: usize = 16;
 const QUIT_TIME: u8 = 1;
+const SCROLL_LINES: usize = 3;
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
+/// The longest the screen goes without a refresh while waiting for input.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_COUNT: usize = 9999;
+/// Opening files from this size on reports its progress.
+const PROGRESS_SIZE: u64 = 8 * 1024 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// The longest refreshes are put off while input is coming in faster than
+/// it is handled, as when a key is held.
+const MAX_REFRESH_DELAY: Duration = Duration::from_millis(100);
+/// Writes files the user has no permission to write, as `sudo cp`.
+const ELEVATE: &str = "sudo";
+/// How long the status bar stays inverted for the visual bell.
+const BELL_DURATION: Duration = Duration::from_millis(150);
+/// Searches count at most this many matches for the status bar.
+const MAX_MATCH_COUNT: usize = 10_000;
+/// How many rows past the view the bracket under the cursor is matched
+/// over while drawing.
+const BRACKET_SCAN_MARGIN: usize = 500;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum SearchDirection {
     Forward,
     Backward,
 }
 
-#[derive(Default, Clone)]
+/// A location in a document, `x` being the grapheme index in row `y`.
+#[derive(Default, Clone, PartialEq, Eq, Debug)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
 
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 struct StatusMessage {
     text: String,
     time: Instant,
+    /// Whether the message is the help shown at startup, which stays for
+    /// its own duration.
+    help: bool,
 }
 
 impl StatusMessage {
@@ -38,20 +111,265 @@ impl StatusMessage {
         Self {
             time: Instant::now(),
             text: message,
+            help: false,
+        }
+    }
+
+    fn help(message: String) -> Self {
+        Self {
+            help: true,
+            ..Self::from(message)
+        }
+    }
+}
+
+/// A cursor besides the main one, with the other end of its selection.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Cursor {
+    position: Position,
+    anchor: Option<Position>,
+}
+
+impl Cursor {
+    /// Returns where the selection starts, or the position without one.
+    fn start(&self) -> &Position {
+        self.anchor
+            .as_ref()
+            .map_or(&self.position, |anchor| cmp::min(anchor, &self.position))
+    }
+
+    /// Returns the start and the end of the selection, if there is one.
+    fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.anchor.as_ref()?;
+
+        Some((
+            cmp::min(anchor, &self.position).clone(),
+            cmp::max(anchor, &self.position).clone(),
+        ))
+    }
+}
+
+/// The keys of a macro being recorded.
+struct Recording {
+    register: char,
+    events: Vec<Event>,
+    /// Where the last key sequence read as a whole starts, to leave out
+    /// the one that stops the recording.
+    command_start: usize,
+}
+
+/// A selection expanded to the next larger unit of text.
+struct Expansion {
+    /// The selection anchor and the cursor before expanding.
+    anchor: Option<Position>,
+    cursor: Position,
+    /// What was selected after.
+    selection: (Position, Position),
+}
+
+/// A rectangular selection from the line and display column it was
+/// started at to the ones it was extended to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Block {
+    anchor: (usize, usize),
+    head: (usize, usize),
+}
+
+impl Block {
+    fn lines(&self) -> RangeInclusive<usize> {
+        cmp::min(self.anchor.0, self.head.0)..=cmp::max(self.anchor.0, self.head.0)
+    }
+
+    fn columns(&self) -> (usize, usize) {
+        (
+            cmp::min(self.anchor.1, self.head.1),
+            cmp::max(self.anchor.1, self.head.1),
+        )
+    }
+}
+
+/// The editing state of an open file. The active buffer's state lives in
+/// the editor's own fields, the other buffers are parked in `buffers`.
+#[derive(Default)]
+struct Buffer {
+    document: Document,
+    cursor_position: Position,
+    offset: Position,
+    selection_anchor: Option<Position>,
+    swap: Option<SwapFile>,
+    swap_pending: bool,
+    marks: HashMap<char, Position>,
+    jumps: JumpList,
+    folds: Vec<(usize, usize)>,
+}
+
+/// Where a view of a buffer is shown on the screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SplitDirection {
+    Stacked,
+    SideBySide,
+}
+
+/// The editing mode with `modal` enabled. Without it the editor always
+/// inserts what is typed, as in `Insert`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Mode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl Mode {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Normal => "NORMAL",
+            Self::Insert => "INSERT",
+            Self::Visual => "VISUAL",
         }
     }
 }
 
-pub struct Revise {
+/// The second pane of a split screen. The focused pane's view is the
+/// editor's cursor, offset and folds, the other one is kept here.
+struct Split {
+    direction: SplitDirection,
+    buffer: usize,
+    cursor_position: Position,
+    offset: Position,
+    folds: Vec<(usize, usize)>,
+    focus_first: bool,
+}
+
+/// Two buffers compared with `--diff`, shown side by side and scrolled
+/// together.
+struct Diff {
+    old: usize,
+    new: usize,
+    lines: LineDiff,
+}
+
+/// A list shown over the text to choose one of its items from.
+struct Picker {
+    title: String,
+    items: Vec<String>,
+    /// The indices of the items matching the query, in the order shown.
+    shown: Vec<usize>,
+    selected: usize,
+    /// Typed text narrowing the items down, when the list can be filtered.
+    query: Option<String>,
+}
+
+impl Picker {
+    fn filter(&mut self) {
+        let query = self.query.as_deref().unwrap_or_default();
+
+        self.shown = finder::rank(&self.items, query);
+        self.selected = 0;
+    }
+}
+
+pub struct Revise<B: Backend = Terminal> {
     should_quit: bool,
-    terminal: Terminal,
+    terminal: B,
     cursor_position: Position,
     offset: Position,
     document: Document,
     status_message: StatusMessage,
     quit_times: u8,
     highlighted_word: Option<String>,
-    clipboard: ClipboardContext,
+    clipboard: Clipboard,
+    typing: bool,
+    selection_anchor: Option<Position>,
+    bracket_match: Option<(Position, Option<Position>)>,
+    config: Config,
+    theme: Theme,
+    keymap: Keymap,
+    last_keypress: Instant,
+    /// When an auto-save was last due, so that one that failed or was
+    /// skipped waits a full interval before it is tried again.
+    autosave_attempt: Instant,
+    /// When the screen was last refreshed, `None` before the first time.
+    refreshed_at: Option<Instant>,
+    swap: Option<SwapFile>,
+    swap_pending: bool,
+    screen: Vec<String>,
+    chord: Option<KeyCombo>,
+    count: Option<usize>,
+    mode: Mode,
+    /// Whether typed characters replace the ones under the cursor.
+    overwrite: bool,
+    /// Whether tabs and spaces are drawn as visible symbols.
+    show_whitespace: bool,
+    /// The words spelling is checked against, loaded while `spell_check`
+    /// is on.
+    dictionary: Option<Dictionary>,
+    /// The display column vertical movement aims for, with the position it
+    /// left the cursor at. Moving the cursor any other way drops it.
+    desired_column: Option<(Position, usize)>,
+    /// Further cursors at which keys typed in Insert mode apply as well.
+    cursors: Vec<Cursor>,
+    /// The block selected with `Alt-Shift` and the arrow keys, in which
+    /// typing and deleting apply to every line at the same columns.
+    block: Option<Block>,
+    /// The first shown line of the key binding help while it is open.
+    help: Option<usize>,
+    picker: Option<Picker>,
+    /// Whether the cursor stays on the last line as the file is read again
+    /// when it changes on disk, to watch a log.
+    follow: bool,
+    /// When the visual bell last rang, to invert the status bar briefly.
+    bell: Option<Instant>,
+    /// The macros recorded with `Ctrl-K Q`, by register.
+    macros: HashMap<char, Vec<Event>>,
+    recording: Option<Recording>,
+    /// While searching, the number of the match the cursor is on, if it was
+    /// counted, and how many there are.
+    search_matches: Option<(Option<usize>, usize)>,
+    /// The keys of macros being replayed, read before the terminal.
+    replay: VecDeque<Event>,
+    /// The registers of the macros being replayed, innermost last, with how
+    /// many of their keys are left in `replay`.
+    replaying: Vec<(char, usize)>,
+    /// Input read while a macro was replayed, handled once it is done.
+    typed_ahead: VecDeque<Event>,
+    /// The title last given to the terminal window.
+    title: Option<String>,
+    /// The selections expanded with `Alt-=`, to shrink them back.
+    expansions: Vec<Expansion>,
+    /// Where to write the cursor position on exit, `-` for the standard
+    /// output.
+    print_cursor: Option<String>,
+    /// The directory given on the command line, to browse on start.
+    start_directory: Option<String>,
+    recent: RecentFiles,
+    hooks: Hooks,
+    /// Reads the local time, for the clock and `insert_date`.
+    now: fn() -> Option<LocalTime>,
+    /// The command saving with elevated rights goes through, `ELEVATE`.
+    elevate: String,
+    /// An operator such as `d` waiting for its second key in Normal mode,
+    /// with its count.
+    pending_operator: Option<(char, usize)>,
+    marks: HashMap<char, Position>,
+    jumps: JumpList,
+    /// Folded regions as their first and last line, in order. The first
+    /// line stays in view with a marker, the others are hidden.
+    folds: Vec<(usize, usize)>,
+    search_history: Vec<String>,
+    buffers: Vec<Buffer>,
+    active: usize,
+    /// The buffer that was active before the current one, for `Ctrl-^`.
+    alternate: usize,
+    split: Option<Split>,
+    diff: Option<Diff>,
 }
 
 #[derive(Debug, Error)]
@@ -59,496 +377,9655 @@ pub struct Revise {
 pub struct CopyError;
 
 impl Revise {
+    /// Starts the editor on the terminal, with the file named on the
+    /// command line or piped input.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the terminal cannot be switched to raw mode
     pub fn new() -> Result<Self, Box<dyn Err>> {
-        let mut args: Args = env::args();
-        let mut initial_status =
-            String::from("HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-Q = quit");
-        let document = if args.len() > 1 {
-            let filename = args.nth(1);
-
-            match filename {
-                Some(f) => {
-                    let doc = Document::open(f.as_str());
-
-                    if let Ok(content) = doc {
-                        content
-                    } else {
-                        initial_status = format!("ERR: Could not open file: {f}");
-                        Document::default()
-                    }
-                }
-                None => Document::default(),
+        // Every problem found while starting is told at once, once the
+        // editor is ready.
+        let mut errors = Vec::new();
+        let arguments = match Arguments::parse(env::args().skip(1)) {
+            Ok(arguments) => arguments,
+            Err(err) => {
+                errors.push(err.to_string());
+                Arguments::default()
             }
-        } else {
-            Document::default()
         };
-        let terminal = Terminal::new()?;
-        let clipboard = ClipboardContext::new()?;
-
-        Ok(Self {
-            should_quit: false,
-            terminal,
-            cursor_position: Position::default(),
-            offset: Position::default(),
-            document,
-            status_message: StatusMessage::from(initial_status),
-            quit_times: QUIT_TIME,
-            highlighted_word: None,
-            clipboard,
-        })
-    }
-
-    pub fn run(&mut self) -> Result<(), Box<dyn Err>> {
-        loop {
-            if let Err(error) = self.refresh_screen() {
-                match self.clipboard.clear() {
-                    Ok(_) => return Err(error),
-                    Err(e) => return Err(e),
-                }
-            }
-
-            if self.should_quit {
-                break;
+        let mut config = match Config::load() {
+            Ok(config) => config,
+            Err(err) => {
+                errors.push(err.to_string());
+                Config::default()
             }
+        };
 
-            if let Err(error) = self.process_keypress() {
-                match self.clipboard.clear() {
-                    Ok(_) => return Err(Box::new(error)),
-                    Err(e) => return Err(e),
-                }
-            }
+        if let Err(err) = config.apply(&arguments) {
+            errors.push(err.to_string());
         }
 
-        Ok(())
-    }
+        let (keymap, keymap_errors) = Keymap::new(&config.keys);
 
-    pub fn draw_row(&self, row: &Row) {
-        let start = self.offset.x;
-        let width = self.terminal.size().width as usize;
-        let end = start.saturating_add(width);
-        let row = row.render(start, end);
+        errors.extend(keymap_errors.iter().map(ToString::to_string));
 
-        println!("{row}\r");
-    }
+        let mut revise = Self::with_terminal(Terminal::new()?, Document::default(), config, keymap);
 
-    fn process_keypress(&mut self) -> Result<(), IOError> {
-        let pressed_key = Terminal::read_key()?;
+        revise.update_clipboard();
+        revise.update_colors();
+        revise.update_dictionary();
+        revise.recent = RecentFiles::load();
 
-        match pressed_key {
-            Key::Ctrl('c') => match self.copy_content() {
-                Ok(_) => (),
-                Err(err) => self.status_message = StatusMessage::from(format!("{err}")),
-            },
-            Key::Ctrl('v') => match self.paste_content() {
-                Ok(v) => {
-                    for c in v.chars().rev() {
-                        match self.document.insert(&self.cursor_position, c) {
-                            Ok(_) => (),
-                            Err(err) => {
-                                self.status_message =
-                                    StatusMessage::from(format!("Failed to paste content: {err}"))
-                            }
-                        }
-                    }
-                }
-                Err(err) => {
-                    self.status_message =
-                        StatusMessage::from(format!("Failed to paste content: {err}"))
-                }
-            },
-            Key::Ctrl('q') => return self.quit(),
-            Key::Ctrl('s') => self.save(),
-            Key::Ctrl('f') => self.search(),
-            Key::Char(c) => match self.document.insert(&self.cursor_position, c) {
-                Ok(_) => self.move_cursor(Key::Right),
-                Err(err) => {
-                    self.status_message =
-                        StatusMessage::from(format!("Failed to paste content: {err}"))
+        let mut document = match &arguments.filename {
+            Some(f) if Path::new(f).is_dir() => {
+                revise.start_directory = Some(f.clone());
+                Document::default()
+            }
+            Some(f) => {
+                let doc = revise.open_document(f.as_str());
+
+                if let Ok(content) = doc {
+                    revise.remember_file(f);
+                    content
+                } else {
+                    errors.push(format!("Could not open file: {f}"));
+                    Document::default()
                 }
-            },
-            Key::Delete => match self.document.delete(&self.cursor_position) {
-                Ok(_) => (),
+            }
+            None if arguments.diff.is_some() => Document::default(),
+            None if !termion::is_tty(&io::stdin()) => match Document::from_reader(io::stdin()) {
+                Ok(document) => document,
                 Err(err) => {
-                    self.status_message =
-                        StatusMessage::from(format!("Failed to remove content: {err}"))
+                    errors.push(format!("Could not read stdin: {err}"));
+                    Document::default()
                 }
             },
-            Key::Backspace => {
-                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
-                    self.move_cursor(Key::Left);
+            None => Document::default(),
+        };
 
-                    match self.document.delete(&self.cursor_position) {
-                        Ok(_) => (),
-                        Err(err) => {
-                            self.status_message =
-                                StatusMessage::from(format!("Failed to remove character: {err}"))
-                        }
-                    }
-                }
-            }
-            Key::Up
-            | Key::Down
-            | Key::Left
-            | Key::Right
-            | Key::PageUp
-            | Key::PageDown
-            | Key::End
-            | Key::Home => self.move_cursor(pressed_key),
-            _ => (),
+        document.read_only |= arguments.readonly;
+        revise.print_cursor = arguments.print_cursor;
+        revise.swap = document.filename.as_deref().map(SwapFile::for_file);
+        revise.document = document;
+
+        if let Some((old, new)) = &arguments.diff {
+            revise.start_diff(old, new);
         }
 
-        self.scroll();
+        if let Some(line) = arguments.line {
+            revise.start_at(line, arguments.column);
+        }
 
-        if self.quit_times < QUIT_TIME {
-            self.quit_times = QUIT_TIME;
-            self.status_message = StatusMessage::from(String::new());
+        // A large file tells how many lines it has instead of the help.
+        if !errors.is_empty() {
+            revise.status_message = StatusMessage::from(format!("ERR: {}", errors.join("; ")));
+        } else if revise.status_message.text.is_empty() && revise.config.show_help {
+            revise.status_message = StatusMessage::help(revise.config.help_text.clone());
         }
 
-        Ok(())
+        Ok(revise)
     }
+}
 
-    fn quit(&mut self) -> Result<(), IOError> {
-        if self.quit_times > 0 && self.document.is_changed() {
-            self.status_message = StatusMessage::from(format!(
-                "WARNING! File has unsaved changes. Press Ctrl-Q {} more time to quit.",
-                self.quit_times
-            ));
-            self.quit_times -= 1;
+impl Revise<TestBackend> {
+    /// Creates an editor over `document` drawing to a `TestBackend` of
+    /// the given `size`, for embedding the editor or scripting edits.
+    pub fn from_document(document: Document, size: Size) -> Self {
+        Self::with_backend(TestBackend::new(size), document)
+    }
+}
 
-            return Ok(());
-        }
-        self.should_quit = true;
+impl<B: Backend> Revise<B> {
+    /// Creates an editor over `document` using `backend` for all terminal I/O.
+    pub fn with_backend(backend: B, document: Document) -> Self {
+        Self::with_terminal(backend, document, Config::default(), Keymap::default())
+    }
 
-        Ok(())
+    fn with_terminal(terminal: B, document: Document, config: Config, keymap: Keymap) -> Self {
+        let mode = if config.modal {
+            Mode::Normal
+        } else {
+            Mode::Insert
+        };
+
+        Self {
+            should_quit: false,
+            terminal,
+            cursor_position: Position::default(),
+            offset: Position::default(),
+            swap: document.filename.as_deref().map(SwapFile::for_file),
+            document,
+            status_message: StatusMessage::from(String::new()),
+            quit_times: QUIT_TIME,
+            highlighted_word: None,
+            clipboard: Clipboard::default(),
+            typing: false,
+            selection_anchor: None,
+            bracket_match: None,
+            theme: config.theme().unwrap_or_default(),
+            keymap,
+            config,
+            last_keypress: Instant::now(),
+            autosave_attempt: Instant::now(),
+            refreshed_at: None,
+            swap_pending: false,
+            screen: Vec::new(),
+            chord: None,
+            count: None,
+            mode,
+            overwrite: false,
+            show_whitespace: false,
+            dictionary: None,
+            desired_column: None,
+            cursors: Vec::new(),
+            block: None,
+            help: None,
+            picker: None,
+            follow: false,
+            bell: None,
+            macros: HashMap::new(),
+            recording: None,
+            search_matches: None,
+            replay: VecDeque::new(),
+            replaying: Vec::new(),
+            typed_ahead: VecDeque::new(),
+            title: None,
+            expansions: Vec::new(),
+            print_cursor: None,
+            start_directory: None,
+            recent: RecentFiles::default(),
+            hooks: Hooks::default(),
+            now: LocalTime::now,
+            elevate: ELEVATE.to_owned(),
+            pending_operator: None,
+            marks: HashMap::new(),
+            jumps: JumpList::default(),
+            folds: Vec::new(),
+            search_history: Vec::new(),
+            buffers: vec![Buffer::default()],
+            active: 0,
+            alternate: 0,
+            split: None,
+            diff: None,
+        }
     }
 
-    fn refresh_screen(&mut self) -> Result<(), Box<dyn Err>> {
-        Terminal::cursor_hide();
-        Terminal::cursor_position(&Position::default());
+    /// Registers `hook` to be called with every `HookEvent`. An error it
+    /// returns is shown in the message bar, as is a panic in it when the
+    /// embedding build unwinds; a build with `panic = "abort"` aborts.
+    pub fn add_hook(&mut self, hook: impl Fn(&HookEvent) -> Result<(), Box<dyn Err>> + 'static) {
+        self.hooks.add(Box::new(hook));
+    }
 
-        if self.should_quit {
-            Terminal::clear_screen();
-        } else {
-            match self.document.highlight(
-                &self.highlighted_word,
-                Some(
-                    self.offset
-                        .y
-                        .saturating_add(self.terminal.size().height as usize),
-                ),
-            ) {
-                Ok(_) => {
-                    self.draw_rows();
-                    self.draw_status_bar();
-                    self.draw_message_bar();
-                    Terminal::cursor_position(&Position {
-                        x: self.cursor_position.x.saturating_sub(self.offset.x),
-                        y: self.cursor_position.y.saturating_sub(self.offset.y),
-                    });
-                }
-                Err(err) => return Err(err),
-            }
+    fn fire(&mut self, event: &HookEvent) {
+        if let Some(err) = self.hooks.fire(event).into_iter().next() {
+            self.status_message = StatusMessage::from(format!("Hook failed: {err}"));
         }
+    }
 
-        Terminal::cursor_show();
+    /// Tells the hooks about an edit made through the API, if the text
+    /// changed since it counted `edits` or the cursor moved from `cursor`.
+    fn fire_edit(&mut self, edits: usize, cursor: &Position) {
+        if self.document.edits() != edits {
+            self.fire(&HookEvent::TextChanged);
+        }
 
-        match Terminal::flush() {
-            Ok(_) => Ok(()),
-            Err(err) => return Err(Box::new(err)),
+        if self.cursor_position != *cursor {
+            self.fire(&HookEvent::CursorMoved(&self.cursor_position.clone()));
         }
     }
 
-    fn draw_rows(&self) {
-        let height = self.terminal.size().height;
+    pub fn backend(&self) -> &B {
+        &self.terminal
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.terminal
+    }
 
-        for terminal_row in 0..height {
-            Terminal::clear_current_line();
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
 
-            if let Some(row) = self
-                .document
-                .row(self.offset.y.saturating_add(terminal_row as usize))
-            {
-                self.draw_row(row);
-            } else if self.document.is_empty() && terminal_row == height / 3 {
-                self.draw_welcome_message();
-            } else {
-                println!("~\r");
-            }
-        }
+    pub fn cursor_position(&self) -> &Position {
+        &self.cursor_position
     }
 
-    fn draw_welcome_message(&self) {
-        let mut welcome_message = format!("Revise | v{VERSION}");
-        let width = self.terminal.size().width as usize;
-        let len = welcome_message.len();
-        let padding = width.saturating_sub(len) / 2;
-        let spaces = " ".repeat(padding.saturating_sub(1));
+    /// Returns the text of the line under the cursor, if there is one.
+    pub fn current_line(&self) -> Option<&str> {
+        self.document
+            .row(self.cursor_position.y)
+            .map(|row| row.as_string().as_str())
+    }
+
+    /// Returns the selected text, if anything is selected.
+    pub fn selected_text(&self) -> Option<String> {
+        self.selection()
+            .map(|(from, to)| self.document.text(&from, &to))
+    }
 
-        welcome_message = format!("~{spaces}{welcome_message}");
-        welcome_message.truncate(width);
+    /// Returns the word of letters, digits and underscores the cursor is on
+    /// or directly after.
+    pub fn word_under_cursor(&self) -> Option<&str> {
+        let (start, end) = self.word_bounds()?;
 
-        println!("{welcome_message}\r");
+        Some(self.document.row(self.cursor_position.y)?.slice(start, end))
     }
 
-    fn move_cursor(&mut self, key: Key) {
-        let terminal_height = self.terminal.size().height as usize;
-        let Position { mut y, mut x } = self.cursor_position;
-        let height = self.document.len();
-        let mut width = if let Some(row) = self.document.row(y) {
-            row.len()
+    /// Returns where the word under the cursor starts and ends on its line.
+    fn word_bounds(&self) -> Option<(usize, usize)> {
+        let Position { x, y } = self.cursor_position;
+        let row = self.document.row(y)?;
+        let is_word = |index: usize| {
+            row.grapheme(index)
+                .is_some_and(|grapheme| grapheme.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        };
+        let at = if is_word(x) {
+            x
         } else {
-            0
+            x.checked_sub(1).filter(|&x| is_word(x))?
         };
+        let start = (0..=at).rev().take_while(|&index| is_word(index)).last()?;
+        let end = (at..).take_while(|&index| is_word(index)).last()? + 1;
 
-        match key {
-            Key::Up => y = y.saturating_sub(1),
-            Key::Down => {
-                if y < height {
-                    y = y.saturating_add(1);
-                }
-            }
-            Key::Left => {
-                if x > 0 {
-                    x -= 1;
-                } else if y > 0 {
-                    y -= 1;
+        Some((start, end))
+    }
 
-                    if let Some(row) = self.document.row(y) {
-                        x = row.len();
-                    } else {
-                        x = 0;
-                    }
-                }
-            }
-            Key::Right => {
-                if x < width {
-                    x += 1;
-                } else if y < height {
-                    y += 1;
-                    x = 0;
-                }
-            }
-            Key::PageUp => {
-                y = if y > terminal_height {
-                    y.saturating_sub(terminal_height)
-                } else {
-                    0
-                }
-            }
-            Key::PageDown => {
-                y = if y.saturating_add(terminal_height) < height {
-                    y.saturating_add(terminal_height)
-                } else {
-                    height
-                }
-            }
-            Key::Home => x = 0,
-            Key::End => x = width,
-            _ => (),
-        }
+    /// Types `text` at the cursor as a single undo step, leaving the cursor
+    /// after the inserted text, and tells the hooks.
+    pub fn insert_text(&mut self, text: &str) {
+        let (edits, cursor) = (self.document.edits(), self.cursor_position.clone());
 
-        width = if let Some(row) = self.document.row(y) {
-            row.len()
-        } else {
-            0
-        };
+        self.type_text(text);
+        self.fire_edit(edits, &cursor);
+    }
 
-        if x > width {
-            x = width;
-        }
+    fn type_text(&mut self, text: &str) {
+        self.checkpoint();
+        self.insert_chars(text);
+        self.checkpoint();
+    }
 
-        self.cursor_position = Position { x, y }
+    fn insert_chars(&mut self, text: &str) {
+        self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+        self.move_cursor(Key::Null);
     }
 
-    fn scroll(&mut self) {
-        let Position { x, y } = self.cursor_position;
-        let width = self.terminal.size().width as usize;
-        let height = self.terminal.size().height as usize;
-        let offset = &mut self.offset;
+    /// Inserts text pasted into the terminal as it is, without the
+    /// indentation and closing pairs typing would add. Like typing, it
+    /// replaces the selection, in the same undo step.
+    fn insert_pasted(&mut self, text: &str) {
+        self.checkpoint();
 
-        if y < offset.y {
-            offset.y = y;
-        } else if y >= offset.y.saturating_add(height) {
-            offset.y = y.saturating_sub(height).saturating_add(1);
+        if let Some((start, end)) = self.selection() {
+            self.document.delete_range(&start, &end);
+            self.cursor_position = start;
         }
 
-        if x < offset.x {
-            offset.x = x;
-        } else if x >= offset.x.saturating_add(width) {
-            offset.x = x.saturating_sub(width).saturating_add(1);
-        }
+        self.selection_anchor = None;
+        self.cursor_position = self.document.insert_str(&self.cursor_position, text);
+        self.move_cursor(Key::Null);
+        self.checkpoint();
     }
 
-    fn draw_status_bar(&self) {
-        let mut status;
-        let width = self.terminal.size().width as usize;
-        let changed_indicator = if self.document.is_changed() {
-            " (changed)"
-        } else {
-            ""
-        };
-        let mut filename = "[No Name]".to_owned();
+    /// Removes the text between `start` and `end` as a single undo step and
+    /// moves the cursor to `start`, and tells the hooks.
+    pub fn delete_range(&mut self, start: &Position, end: &Position) {
+        let (edits, cursor) = (self.document.edits(), self.cursor_position.clone());
 
-        if let Some(name) = &self.document.filename {
-            filename = name.clone();
-            filename.truncate(20);
-        }
+        self.remove_range(start, end);
+        self.fire_edit(edits, &cursor);
+    }
 
-        status = format!(
-            "{filename} - {} lines{changed_indicator}",
-            self.document.len(),
-        );
-        let line_indicator = format!(
-            "{} | {}/{}",
-            self.document.file_type(),
-            self.cursor_position.y.saturating_add(1),
-            self.document.len(),
-        );
-        let len = status.len() + line_indicator.len();
+    fn remove_range(&mut self, start: &Position, end: &Position) {
+        let (start, end) = (cmp::min(start, end), cmp::max(start, end));
 
-        status.push_str(&" ".repeat(width.saturating_sub(len)));
-        status = format!("{status}{line_indicator}");
-        status.truncate(width);
-        Terminal::set_bg_color(STATUS_BG_COLOR);
-        Terminal::set_fg_color(STATUS_FG_COLOR);
-        println!("{status}\r");
-        Terminal::reset_fg_color();
-        Terminal::reset_bg_color();
+        self.checkpoint();
+        self.document.delete_range(start, end);
+        self.cursor_position = start.clone();
+        self.move_cursor(Key::Null);
+        self.checkpoint();
     }
 
-    fn draw_message_bar(&self) {
-        Terminal::clear_current_line();
-        let message = &self.status_message;
+    /// Replaces the text between `start` and `end` with `text` as a single
+    /// undo step, leaving the cursor after it, and tells the hooks.
+    pub fn replace_range(&mut self, start: &Position, end: &Position, text: &str) {
+        let (edits, cursor) = (self.document.edits(), self.cursor_position.clone());
+
+        self.substitute_range(start, end, text);
+        self.fire_edit(edits, &cursor);
+    }
+
+    fn substitute_range(&mut self, start: &Position, end: &Position, text: &str) {
+        let (start, end) = (cmp::min(start, end), cmp::max(start, end));
+
+        self.checkpoint();
+        self.document.delete_range(start, end);
+        self.cursor_position = start.clone();
+        self.insert_chars(text);
+        self.checkpoint();
+    }
+
+    /// Pipes the selection, or the whole document, through a shell command
+    /// and replaces it with what the command prints, and tells the hooks.
+    ///
+    /// # Errors
+    ///
+    /// Will return the command's error output if it cannot be run or fails,
+    /// leaving the document unchanged
+    pub fn filter_through_command(&mut self, command: &str) -> Result<(), String> {
+        let (start, end) = self.selection().unwrap_or_else(|| {
+            let y = self.document.len().saturating_sub(1);
+
+            (
+                Position::default(),
+                Position {
+                    x: self.document.row(y).map_or(0, Row::len),
+                    y,
+                },
+            )
+        });
+        let whole = self.selection().is_none();
+        let text = self.document.text(&start, &end);
+        // Commands expect lines to end in a newline, which is taken off
+        // their output again when the text did not have one.
+        let terminated = text.ends_with('\n');
+        let input = if terminated {
+            text.clone()
+        } else {
+            format!("{text}\n")
+        };
+        let output = run_filter(command, input)?;
+        let output = match output.strip_suffix('\n') {
+            Some(output) if !terminated => output,
+            _ => &output,
+        };
+
+        if output == text {
+            return Ok(());
+        }
 
-        if message.time.elapsed() < Duration::new(5, 0) {
-            let mut text = message.text.clone();
+        let (edits, cursor) = (self.document.edits(), self.cursor_position.clone());
 
-            text.truncate(self.terminal.size().width as usize);
-            print!("{text}");
+        self.selection_anchor = None;
+        self.substitute_range(&start, &end, output);
+
+        if whole {
+            self.cursor_position = cursor.clone();
+            self.move_cursor(Key::Null);
         }
+
+        self.fire_edit(edits, &cursor);
+
+        Ok(())
     }
 
-    fn prompt<C>(&mut self, prompt: &str, mut callback: C) -> Result<Option<String>, Box<dyn Err>>
-    where
-        C: FnMut(&mut Self, Key, &String),
-    {
-        let mut result = String::new();
+    /// Runs the editor until the user quits.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if reading input or drawing fails
+    pub fn run(&mut self) -> Result<(), Box<dyn Err>> {
+        if let Some(filename) = self
+            .start_directory
+            .take()
+            .and_then(|directory| self.browse(&directory))
+        {
+            match self.open_document(&filename) {
+                Ok(document) => {
+                    self.remember_file(&filename);
+                    self.swap = Some(SwapFile::for_file(&filename));
+                    self.document = document;
+                }
+                Err(err) => {
+                    self.status_message =
+                        StatusMessage::from(format!("Could not open file {filename}: {err}"));
+                }
+            }
+        }
+
+        self.check_swap()?;
 
         loop {
-            self.status_message = StatusMessage::from(format!("{prompt}{result}"));
-            self.refresh_screen()?;
+            self.warn_disk_change();
 
-            let key = Terminal::read_key()?;
+            if self.should_quit || !self.defers_refresh() {
+                if let Err(error) = self.refresh_screen() {
+                    self.clipboard.clear().ok();
+                    return Err(error);
+                }
+            }
 
-            match key {
-                Key::Backspace => result.truncate(result.len().saturating_sub(1)),
-                Key::Char('\n') => break,
-                Key::Char(c) => {
-                    if !c.is_control() {
-                        result.push(c);
+            if self.should_quit {
+                break;
+            }
+
+            if let Err(error) = self.process_keypress() {
+                self.clipboard.clear().ok();
+                return Err(Box::new(error));
+            }
+        }
+
+        let parked = self
+            .buffers
+            .iter()
+            .filter_map(|buffer| buffer.swap.as_ref());
+
+        for swap in self.swap.iter().chain(parked) {
+            swap.remove()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes where the cursor was left as `line:column`, counting from 1,
+    /// when asked to on the command line. The terminal is restored first,
+    /// so that the standard output can take it.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the position cannot be written
+    pub fn report_cursor(self) -> Result<(), IOError> {
+        let Some(target) = self.print_cursor.clone() else {
+            return Ok(());
+        };
+        let report = format!(
+            "{}:{}\n",
+            self.cursor_position.y.saturating_add(1),
+            self.cursor_position.x.saturating_add(1)
+        );
+
+        drop(self);
+
+        if target == "-" {
+            io::stdout().write_all(report.as_bytes())
+        } else {
+            std::fs::write(target, report)
+        }
+    }
+
+    /// Offers to recover unsaved changes left by a crash or by another
+    /// session editing the same file.
+    fn check_swap(&mut self) -> Result<(), Box<dyn Err>> {
+        let Some(swap) = self.swap.take() else {
+            return Ok(());
+        };
+
+        if !swap.exists() {
+            self.swap = Some(swap);
+            return Ok(());
+        }
+
+        let path = swap.path().display().to_string();
+        let question = format!(
+            "Found {path}, the file may be open elsewhere. (R)ecover, (D)elete or (I)gnore: "
+        );
+        let answer = self.prompt(&question, |_, _, _| {})?;
+
+        match answer.map(|answer| answer.to_ascii_lowercase()).as_deref() {
+            Some("r") => {
+                self.status_message = StatusMessage::from(match swap.recover(&mut self.document) {
+                    Ok(()) => format!("Recovered from {path}."),
+                    Err(err) => format!("Cannot recover from {path}: {err}"),
+                });
+            }
+            Some("d") => swap.remove()?,
+            _ => {
+                self.status_message =
+                    StatusMessage::from(format!("Left {path} in place, changes are not mirrored."));
+                return Ok(());
+            }
+        }
+
+        self.swap = Some(swap);
+
+        Ok(())
+    }
+
+    /// Renders the visible part of the row at `index`, with the selection
+    /// and bracket highlights applied.
+    pub fn render_row(&self, row: &Row, index: usize) -> String {
+        let tab_width = self.tab_width();
+        let start = self.offset.x;
+        let width = self.view().width.saturating_sub(self.gutter_width());
+        let end = start.saturating_add(width);
+        let columns = |(from, to): (Position, Position)| {
+            if index < from.y || index > to.y {
+                return None;
+            }
+
+            let first = if index == from.y { from.x } else { 0 };
+            let last = if index == to.y { to.x } else { row.len() };
+
+            Some((first, last))
+        };
+        let selection = self.selection().and_then(columns);
+        let mut brackets = Vec::new();
+
+        if let Some((bracket, matching)) = &self.bracket_match {
+            if bracket.y == index {
+                brackets.push((bracket.x, matching.is_some()));
+            }
+
+            if let Some(matching) = matching.as_ref().filter(|matching| matching.y == index) {
+                brackets.push((matching.x, true));
+            }
+        }
+
+        let mut cursors: Vec<usize> = self
+            .cursors
+            .iter()
+            .filter(|cursor| cursor.anchor.is_none() && cursor.position.y == index)
+            .map(|cursor| cursor.position.x)
+            .collect();
+        let mut selections: Vec<(usize, usize)> = self
+            .cursors
+            .iter()
+            .filter_map(|cursor| cursor.selection().and_then(columns))
+            .collect();
+
+        if let Some(block) = self.block.filter(|block| block.lines().contains(&index)) {
+            match block.columns() {
+                (left, right) if left == right => {
+                    if index != self.cursor_position.y && row.width(tab_width) >= left {
+                        cursors.push(row.index_at(left, tab_width));
                     }
                 }
-                Key::Esc => {
-                    result.truncate(0);
-                    break;
-                }
-                _ => (),
+                (left, right) => selections.push((
+                    row.index_at(left, tab_width),
+                    row.index_at(right, tab_width),
+                )),
             }
-            callback(self, key, &result);
         }
 
-        self.status_message = StatusMessage::from(String::new());
+        row.render(
+            start,
+            end,
+            &Overlay {
+                selection,
+                brackets,
+                cursors,
+                selections,
+                ruler: self.ruler(),
+                line_background: self.line_background(
+                    self.active,
+                    index,
+                    self.config.cursor_line && index == self.cursor_position.y,
+                ),
+                cursor_column: self.config.cursor_column.then(|| self.cursor_column()),
+                whitespace: self.whitespace(),
+                indent_guides: self.indent_guides(tab_width),
+                tab_width,
+                colorless: self.colorless(),
+            },
+            &self.theme,
+        )
+    }
 
-        if result.is_empty() {
-            return Ok(None);
+    /// Returns whether the terminal is drawn without colors, leaving only
+    /// styles such as inverted text to mark things with.
+    fn colorless(&self) -> bool {
+        self.config.color_support() == ColorSupport::None
+    }
+
+    fn select_all(&mut self) {
+        let y = self.document.len().saturating_sub(1);
+
+        if let Some(row) = self.document.row(y) {
+            self.selection_anchor = Some(Position::default());
+            self.cursor_position = Position { x: row.len(), y };
         }
+    }
 
-        Ok(Some(result))
+    /// Shows the number of lines, words and characters in the selection, or
+    /// in the whole document when nothing is selected.
+    fn word_count(&mut self) {
+        let y = self.document.len().saturating_sub(1);
+        let (scope, (start, end)) = match self.selection() {
+            Some(selection) => ("Selection", selection),
+            None => {
+                let x = self.document.row(y).map_or(0, Row::len);
+
+                ("Document", (Position::default(), Position { x, y }))
+            }
+        };
+        let stats = self.document.stats(&start, &end);
+
+        self.status_message = StatusMessage::from(format!(
+            "{scope}: {} lines, {} words, {} characters",
+            stats.lines, stats.words, stats.chars
+        ));
     }
 
-    fn save(&mut self) {
-        if self.document.filename.is_none() {
-            let new_name = self.prompt("Save as: ", |_, _, _| {}).unwrap_or_default();
+    /// Returns the selected span as ordered `(start, end)` positions.
+    fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.selection_anchor.clone()?;
+        let cursor = self.cursor_position.clone();
 
-            if new_name.is_none() {
-                self.status_message = StatusMessage::from("Save aborted.".to_owned());
-                return;
+        if anchor == cursor {
+            return None;
+        }
+
+        Some((
+            cmp::min(anchor.clone(), cursor.clone()),
+            cmp::max(anchor, cursor),
+        ))
+    }
+
+    fn process_keypress(&mut self) -> Result<(), IOError> {
+        let Some(event) = self.next_event()? else {
+            self.process_idle();
+            return Ok(());
+        };
+
+        self.last_keypress = Instant::now();
+        let typing =
+            self.mode == Mode::Insert && matches!(event, Event::Key(Key::Char(c)) if c != '\n');
+
+        if !(typing && self.typing) {
+            self.checkpoint();
+        }
+
+        self.typing = typing;
+
+        // Digits typed with Alt, or on their own outside Insert mode, build
+        // up a repeat count. A leading 0 is a key of its own in Normal mode.
+        let digit = match event {
+            Event::Key(Key::Alt(digit @ '0'..='9')) => Some(digit),
+            Event::Key(Key::Char(digit @ '0'..='9'))
+                if self.mode != Mode::Insert && (digit != '0' || self.count.is_some()) =>
+            {
+                Some(digit)
+            }
+            _ => None,
+        };
+
+        if let Some(digit) = digit {
+            if self.chord.is_none() && self.keymap.action(&event).is_none() {
+                let count = self
+                    .count
+                    .unwrap_or_default()
+                    .saturating_mul(10)
+                    .saturating_add(usize::from(digit as u8 - b'0'));
+                let count = cmp::min(count, MAX_COUNT);
+
+                self.count = Some(count);
+                self.status_message = StatusMessage::from(format!("Repeat: {count}"));
+
+                return Ok(());
             }
-            self.document.filename = new_name;
         }
 
-        if self.document.save().is_ok() {
-            self.status_message = StatusMessage::from("File saved successfully.".to_owned());
+        let given = self.count.take();
+        let count = match given {
+            Some(count) => {
+                self.status_message = StatusMessage::from(String::new());
+                cmp::max(count, 1)
+            }
+            None => 1,
+        };
+        let action = if let Some(prefix) = self.chord.take() {
+            let Some(action) = self.keymap.chord_action(prefix, &event) else {
+                self.status_message = StatusMessage::from("Key chord is not bound.".to_owned());
+                return Ok(());
+            };
+
+            Some(action)
+        } else if let Some(prefix) = self.keymap.prefix(&event) {
+            // The count applies to the action the chord ends in.
+            self.chord = Some(prefix);
+            self.count = given;
+            return Ok(());
         } else {
-            self.status_message = StatusMessage::from("Error writing file!".to_owned());
+            self.keymap.action(&event)
+        };
+
+        let active = self.active;
+        let (cursor, edits) = (self.cursor_position.clone(), self.document.edits());
+        let times = match (action, &event) {
+            (Some(action), _) if !action.is_repeatable() => 1,
+            (None, Event::Mouse(_) | Event::Paste(_) | Event::Resize) => 1,
+            (None, Event::Key(_)) if self.mode != Mode::Insert => 1,
+            _ => count,
+        };
+
+        if self.document.read_only && self.is_edit(action, &event) {
+            self.status_message = StatusMessage::from("Buffer is read-only.".to_owned());
+            self.ring_bell();
+            return Ok(());
         }
-    }
 
-    fn search(&mut self) {
-        let old_position = self.cursor_position.clone();
-        let mut direction = SearchDirection::Forward;
-        let query = self
-            .prompt(
-                "Search (ESC to cancel, Arrows to navigate): ",
-                |revise, key, query| {
-                    let mut moved = false;
+        if !matches!(action, Some(Action::Quit)) && !matches!(event, Event::Resize) {
+            self.rearm_quit();
+        }
 
-                    match key {
-                        Key::Right | Key::Down => {
-                            direction = SearchDirection::Forward;
-                            revise.move_cursor(Key::Right);
-                            moved = true;
-                        }
-                        Key::Left | Key::Up => direction = SearchDirection::Backward,
-                        _ => direction = SearchDirection::Forward,
-                    }
+        if !self.keeps_cursors(action, &event) {
+            self.cursors.clear();
+        }
 
-                    if let Some(position) =
-                        revise
-                            .document
-                            .find(query, &revise.cursor_position, direction)
-                    {
-                        revise.cursor_position = position;
-                        revise.scroll();
-                    } else if moved {
-                        revise.move_cursor(Key::Left);
-                    }
+        if !self.keeps_block(action, &event) {
+            self.block = None;
+        }
 
-                    revise.highlighted_word = Some(query.to_owned());
+        for _ in 0..times {
+            match action {
+                Some(Action::Quit) => return self.quit(),
+                Some(Action::ReplayMacro) => self.replay_macro(count),
+                Some(action) => self.perform(action),
+                None => match &event {
+                    Event::Key(key) if self.mode != Mode::Insert => {
+                        self.process_modal_key(*key, count);
+                    }
+                    Event::Key(key) if self.block.is_some() => self.process_block_key(*key),
+                    Event::Key(key) if !self.cursors.is_empty() => {
+                        self.process_key_at_cursors(*key);
+                    }
+                    Event::Key(key) => self.process_key(*key),
+                    Event::Modified(key, modifiers) => self.process_modified_key(*key, *modifiers),
+                    Event::Mouse(mouse) => self.process_mouse(*mouse),
+                    Event::Paste(text) => self.insert_pasted(text),
+                    Event::Resize => self.clear_screen(),
                 },
-            )
-            .unwrap_or_default();
+            }
+        }
 
-        if query.is_none() {
-            self.cursor_position = old_position;
-            self.scroll();
+        if self.active == active {
+            self.follow_line_shifts();
         }
 
-        self.highlighted_word = None;
+        self.scroll();
+        self.swap_pending |= self.document.is_changed();
+
+        if self.active == active && self.document.edits() != edits {
+            self.fire(&HookEvent::TextChanged);
+        }
+
+        if self.active != active || self.cursor_position != cursor {
+            self.fire(&HookEvent::CursorMoved(&self.cursor_position.clone()));
+        }
+
+        Ok(())
     }
 
-    fn copy_content(&mut self) -> Result<(), Box<dyn Err>> {
-        let row = self.document.row(self.cursor_position.y);
+    /// Returns whether the keypress would change the document, either
+    /// through `action` or as an unbound key that edits.
+    fn is_edit(&self, action: Option<Action>, event: &Event) -> bool {
+        match (action, event, self.mode) {
+            (Some(action), _, _) => action.is_edit(),
+            (None, Event::Paste(_), _) => true,
+            (
+                None,
+                Event::Key(Key::Char(_) | Key::Backspace | Key::Delete | Key::BackTab),
+                Mode::Insert,
+            ) => true,
+            (None, Event::Key(Key::Char('d' | 'x' | 'p' | 'P')), Mode::Normal) => true,
+            (None, Event::Key(Key::Char('d' | 'x')), Mode::Visual) => true,
+            _ => false,
+        }
+    }
 
-        match row {
-            Some(v) => self.clipboard.set_contents(v.as_string().to_owned()),
-            None => Err(Box::new(CopyError)),
+    /// Returns whether the further cursors stay after the keypress: they
+    /// follow the keys that type, delete or move in Insert mode, and go
+    /// away on anything else.
+    fn keeps_cursors(&self, action: Option<Action>, event: &Event) -> bool {
+        match (action, event) {
+            (Some(action), _) => matches!(
+                action,
+                Action::AddCursorAbove | Action::AddCursorBelow | Action::AddNextOccurrence
+            ),
+            (None, Event::Key(key)) => {
+                self.mode == Mode::Insert
+                    && matches!(
+                        key,
+                        Key::Char(_)
+                            | Key::Backspace
+                            | Key::Delete
+                            | Key::Up
+                            | Key::Down
+                            | Key::Left
+                            | Key::Right
+                            | Key::Home
+                            | Key::End
+                    )
+            }
+            (None, Event::Resize) => true,
+            _ => false,
         }
     }
 
-    fn paste_content(&mut self) -> Result<String, Box<dyn Err>> {
-        let content = self.clipboard.get_contents();
+    /// Returns whether the block selection stays after the keypress: it is
+    /// extended with `Alt-Shift` and the arrow keys, edited by typing and
+    /// deleting in Insert mode, and dropped by anything else.
+    fn keeps_block(&self, action: Option<Action>, event: &Event) -> bool {
+        match (action, event) {
+            (Some(_), _) => false,
+            (None, Event::Key(Key::Char('\n'))) => false,
+            (None, Event::Key(Key::Char(_) | Key::Backspace | Key::Delete)) => {
+                self.mode == Mode::Insert
+            }
+            (None, Event::Modified(Key::Up | Key::Down | Key::Left | Key::Right, modifiers)) => {
+                *modifiers == Modifiers::ALT_SHIFT
+            }
+            (None, Event::Resize) => true,
+            _ => false,
+        }
+    }
 
-        match content {
-            Ok(mut v) => {
-                if v.is_empty() {
-                    v = String::from(" ");
-                    self.cursor_position.y = self.cursor_position.y.saturating_add(1);
-                }
+    /// Waits for input, returning `None` when none came before the next
+    /// tick so that the screen is refreshed and idle work is done.
+    fn next_event(&mut self) -> Result<Option<Event>, IOError> {
+        if !self.replay.is_empty() && self.replay_interrupted() {
+            return Ok(None);
+        }
+
+        if let Some(event) = self.pop_replay() {
+            return Ok(Some(event));
+        }
+
+        let event = match self.typed_ahead.pop_front() {
+            Some(event) => Some(event),
+            None => {
+                let timeout = self.poll_timeout();
+
+                self.terminal.read_event_timeout(timeout)?
+            }
+        };
+        let starts_command = self.chord.is_none() && self.count.is_none();
+
+        if let (Some(recording), Some(event)) = (&mut self.recording, &event) {
+            if starts_command {
+                recording.command_start = recording.events.len();
+            }
+
+            recording.events.push(event.clone());
+        }
+
+        Ok(event)
+    }
+
+    /// Reads the next event of a prompt, from the macro being replayed if
+    /// there is one, and records it.
+    fn read_event(&mut self) -> Result<Event, IOError> {
+        if let Some(event) = self.pop_replay() {
+            return Ok(event);
+        }
+
+        let event = match self.typed_ahead.pop_front() {
+            Some(event) => event,
+            None => self.terminal.read_event()?,
+        };
+
+        if let Some(recording) = &mut self.recording {
+            recording.events.push(event.clone());
+        }
+
+        Ok(event)
+    }
+
+    /// Takes the next key of the macros being replayed, keeping track of
+    /// which of them it belongs to.
+    fn pop_replay(&mut self) -> Option<Event> {
+        let Some(event) = self.replay.pop_front() else {
+            self.replaying.clear();
+            return None;
+        };
+
+        // A macro whose keys are all read stays listed until the next key,
+        // so that one replaying itself with its last key is still caught.
+        while self.replaying.last().is_some_and(|&(_, left)| left == 0) {
+            self.replaying.pop();
+        }
+
+        if let Some((_, left)) = self.replaying.last_mut() {
+            *left -= 1;
+        }
+
+        Some(event)
+    }
+
+    /// Returns whether `Esc` was pressed while replaying a macro, which
+    /// stops it. Other input is kept for after the macro.
+    fn replay_interrupted(&mut self) -> bool {
+        if !self.terminal.has_pending_input() {
+            return false;
+        }
+
+        match self.terminal.read_event_timeout(Duration::ZERO) {
+            Ok(Some(Event::Key(Key::Esc))) => {
+                self.stop_replay();
+                self.status_message = StatusMessage::from("Macro interrupted.".to_owned());
 
-                Ok(v)
+                true
             }
-            Err(err) => Err(err),
+            Ok(Some(event)) => {
+                self.typed_ahead.push_back(event);
+
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn stop_replay(&mut self) {
+        self.replay.clear();
+        self.replaying.clear();
+    }
+
+    fn read_key(&mut self) -> Result<Key, IOError> {
+        loop {
+            if let Event::Key(key) = self.read_event()? {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// Returns how long to wait for input: until it is time to auto-save,
+    /// to update the swap file or to clear the message, and at most a tick.
+    fn poll_timeout(&self) -> Duration {
+        let autosave = self
+            .autosave_interval()
+            .map(|interval| interval.saturating_sub(self.autosave_idle()));
+        let swap = Some(SWAP_INTERVAL)
+            .filter(|_| self.swap_pending && self.swap.is_some())
+            .map(|interval| interval.saturating_sub(self.last_keypress.elapsed()));
+        let idle = autosave
+            .into_iter()
+            .chain(swap)
+            .filter(|remaining| !remaining.is_zero());
+        let message = self
+            .message_duration()
+            .map(|duration| duration.saturating_sub(self.status_message.time.elapsed()))
+            .filter(|remaining| !remaining.is_zero() && !self.status_message.text.is_empty());
+        let bell = self
+            .bell
+            .map(|rung| BELL_DURATION.saturating_sub(rung.elapsed()))
+            .filter(|remaining| !remaining.is_zero());
+
+        idle.chain(message)
+            .chain(bell)
+            .fold(TICK_INTERVAL, cmp::min)
+    }
+
+    /// Returns the auto-save interval if the document is one to auto-save:
+    /// named, changed and writable.
+    fn autosave_interval(&self) -> Option<Duration> {
+        self.config.autosave_interval().filter(|_| {
+            self.document.filename.is_some()
+                && self.document.is_changed()
+                && !self.document.read_only
+        })
+    }
+
+    /// Returns how long ago the last keypress or auto-save attempt was.
+    fn autosave_idle(&self) -> Duration {
+        self.last_keypress
+            .elapsed()
+            .min(self.autosave_attempt.elapsed())
+    }
+
+    fn process_idle(&mut self) {
+        if self.swap_pending && self.last_keypress.elapsed() >= SWAP_INTERVAL {
+            self.write_swap();
+        }
+
+        if self
+            .autosave_interval()
+            .is_some_and(|interval| self.autosave_idle() >= interval)
+        {
+            self.autosave_attempt = Instant::now();
+
+            // A file changed on disk is left to `warn_disk_change`, rather
+            // than overwritten without asking.
+            if !self.document.changed_on_disk() {
+                self.auto_save();
+            }
+        }
+    }
+
+    fn write_swap(&mut self) {
+        self.swap_pending = false;
+
+        if let Some(Err(err)) = self.swap.as_ref().map(|swap| swap.write(&self.document)) {
+            self.status_message = StatusMessage::from(format!("Cannot write swap file: {err}"));
+        }
+    }
+
+    /// Drops the swap file once the document matches the file on disk.
+    fn discard_swap(&mut self) {
+        self.swap_pending = false;
+
+        if let Some(Err(err)) = self.swap.as_ref().map(SwapFile::remove) {
+            self.status_message = StatusMessage::from(format!("Cannot remove swap file: {err}"));
         }
     }
+
+    /// Removes the byte order mark the buffer starts with as a single undo
+    /// step, returning whether there was one.
+    fn remove_bom(&mut self) -> bool {
+        if !self.document.has_bom() {
+            return false;
+        }
+
+        self.checkpoint();
+        self.document.strip_bom();
+
+        if self.cursor_position.y == 0 {
+            self.cursor_position.x = self.cursor_position.x.saturating_sub(1);
+        }
+
+        self.checkpoint();
+
+        true
+    }
+
+    /// Compares the document with what is committed again after a save,
+    /// which may follow a commit.
+    fn reload_committed(&mut self) {
+        if self.config.git_gutter {
+            self.document.load_committed();
+        }
+    }
+
+    fn auto_save(&mut self) {
+        let saved = self
+            .document
+            .backup()
+            .map_err(SaveError::from)
+            .and_then(|backup| {
+                self.document.save(self.insert_final_newline())?;
+
+                Ok(backup)
+            });
+
+        self.status_message = StatusMessage::from(match saved {
+            Ok(Some(backup)) => format!("File auto-saved, backup written to {backup}."),
+            Ok(None) => "File auto-saved.".to_owned(),
+            Err(err) => format!("Auto-save failed: {err}"),
+        });
+
+        if !self.document.is_changed() {
+            self.discard_swap();
+        }
+    }
+
+    fn perform(&mut self, action: Action) {
+        if !matches!(
+            action,
+            Action::Copy
+                | Action::Cut
+                | Action::WordCount
+                | Action::ToggleComment
+                | Action::Filter
+                | Action::AddNextOccurrence
+                | Action::UpperCase
+                | Action::LowerCase
+                | Action::TitleCase
+                | Action::ToggleCase
+                | Action::ExpandSelection
+                | Action::ShrinkSelection
+                | Action::RecordMacro
+                | Action::ReplayMacro
+        ) {
+            self.selection_anchor = None;
+
+            if self.mode == Mode::Visual {
+                self.mode = Mode::Normal;
+            }
+        }
+
+        match action {
+            Action::Copy => match self.copy_content() {
+                Ok(_) => self.report_local_clipboard(),
+                Err(err) => self.status_message = StatusMessage::from(format!("{err}")),
+            },
+            Action::Cut => match self.cut_content() {
+                Ok(_) => self.report_local_clipboard(),
+                Err(err) => self.status_message = StatusMessage::from(format!("{err}")),
+            },
+            Action::Paste => self.paste(false),
+            Action::PasteIndented => self.paste_indented(),
+            Action::ExpandSelection => self.expand_selection(),
+            Action::RecordMacro => self.record_macro(),
+            Action::ReplayMacro => self.replay_macro(1),
+            Action::ShrinkSelection => self.shrink_selection(),
+            Action::SelectAll => self.select_all(),
+            Action::DuplicateLine => self.document.duplicate_row(self.cursor_position.y),
+            Action::ToggleComment => self.toggle_comment(),
+            Action::MatchBracket => self.match_bracket(),
+            Action::WordCount => self.word_count(),
+            Action::Command => self.command_line(),
+            Action::Open => self.open(),
+            Action::NextBuffer => self.cycle_buffer(SearchDirection::Forward),
+            Action::PreviousBuffer => self.cycle_buffer(SearchDirection::Backward),
+            Action::AlternateBuffer => self.switch_buffer(self.alternate),
+            Action::SplitStacked => self.toggle_split(SplitDirection::Stacked),
+            Action::SplitSideBySide => self.toggle_split(SplitDirection::SideBySide),
+            Action::SwitchPane => self.switch_pane(),
+            Action::SetMark => self.set_mark(),
+            Action::JumpToMark => self.jump_to_mark(),
+            Action::JumpBack => {
+                let position = self.jumps.back(&self.cursor_position);
+
+                self.walk_jumps(position, "oldest");
+            }
+            Action::JumpForward => {
+                let position = self.jumps.forward();
+
+                self.walk_jumps(position, "newest");
+            }
+            Action::CenterView => self.center_view(),
+            Action::ToggleReadOnly => self.toggle_read_only(),
+            Action::Filter => self.filter(),
+            Action::AddCursorAbove => self.add_cursor(false),
+            Action::AddCursorBelow => self.add_cursor(true),
+            Action::AddNextOccurrence => self.add_next_occurrence(),
+            Action::ToggleFold => self.toggle_fold(),
+            Action::FoldAll => self.fold_all(),
+            Action::UnfoldAll => self.folds.clear(),
+            Action::Help => self.show_help(),
+            Action::ToggleWhitespace => self.toggle_whitespace(),
+            Action::InsertDate => self.insert_date(),
+            Action::UpperCase => self.change_case(Case::Upper),
+            Action::LowerCase => self.change_case(Case::Lower),
+            Action::TitleCase => self.change_case(Case::Title),
+            Action::ToggleCase => self.change_case(Case::Toggle),
+            Action::Increment => self.add_to_number(1),
+            Action::Decrement => self.add_to_number(-1),
+            Action::AddWord => self.add_word(""),
+            Action::ToggleFollow => self.toggle_follow(),
+            Action::HalfPageUp => self.scroll_half_page(Key::Up),
+            Action::HalfPageDown => self.scroll_half_page(Key::Down),
+            Action::Revert => self.revert(),
+            Action::RecentFiles => self.open_recent(),
+            Action::FindFile => self.find_file(),
+            Action::FindInFiles => self.find_in_files(),
+            Action::GotoReference => self.goto_reference(),
+            Action::JoinLines => {
+                if let Some(x) = self.document.join_line(self.cursor_position.y) {
+                    self.cursor_position.x = x;
+                }
+            }
+            Action::MoveLineUp => self.move_line_up(),
+            Action::MoveLineDown => self.move_line_down(),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Save => self.save(),
+            Action::Find => self.search(),
+            Action::Quit => (),
+        }
+    }
+
+    /// Adds a cursor on the line above the topmost cursor, or below the
+    /// bottommost one, at the column of the main cursor.
+    fn add_cursor(&mut self, below: bool) {
+        let lines = self
+            .cursors
+            .iter()
+            .map(|cursor| cursor.position.y)
+            .chain([self.cursor_position.y]);
+        let line = if below {
+            lines
+                .max()
+                .map(|y| y.saturating_add(1))
+                .filter(|&y| y < self.document.len())
+        } else {
+            lines.min().and_then(|y| y.checked_sub(1))
+        };
+        let Some(y) = line else {
+            return;
+        };
+        let x = cmp::min(
+            self.cursor_position.x,
+            self.document.row(y).map_or(0, Row::len),
+        );
+
+        self.cursors.push(Cursor {
+            position: Position { x, y },
+            anchor: None,
+        });
+        self.report_cursors();
+    }
+
+    /// Selects the word under the cursor, or else selects the next
+    /// occurrence of the selected text with a new main cursor, keeping the
+    /// current selection on a further one. Searching goes on from the top
+    /// at the bottom of the document.
+    fn add_next_occurrence(&mut self) {
+        let Some((start, end)) = self.selection() else {
+            if let Some((start, end)) = self.word_bounds() {
+                let y = self.cursor_position.y;
+
+                self.selection_anchor = Some(Position { x: start, y });
+                self.cursor_position = Position { x: end, y };
+            }
+
+            return;
+        };
+
+        if start.y != end.y {
+            self.status_message =
+                StatusMessage::from("Only text within one line can be matched.".to_owned());
+            return;
+        }
+
+        let query = self.document.text(&start, &end);
+        let found = match self.document.find(&query, &end, SearchDirection::Forward) {
+            Ok(found) => found,
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("Cannot read the file: {err}"));
+                return;
+            }
+        };
+        let found = found.map(|found| found.position).filter(|position| {
+            *position != start && self.cursors.iter().all(|cursor| cursor.start() != position)
+        });
+        let Some(position) = found else {
+            self.status_message = StatusMessage::from("No more matches.".to_owned());
+            return;
+        };
+
+        self.cursors.push(Cursor {
+            position: mem::take(&mut self.cursor_position),
+            anchor: self.selection_anchor.take(),
+        });
+        self.cursor_position = Position {
+            x: position.x.saturating_add(end.x - start.x),
+            y: position.y,
+        };
+        self.selection_anchor = Some(position);
+        self.report_cursors();
+    }
+
+    fn report_cursors(&mut self) {
+        self.status_message =
+            StatusMessage::from(format!("{} cursors.", self.cursors.len().saturating_add(1)));
+    }
+
+    /// Applies a key typed in Insert mode at every cursor, from the last
+    /// one in the document to the first, so that each edit leaves the
+    /// cursors before it where they are. The cursors already done are kept
+    /// as distances from the end of the document, which edits before them
+    /// do not change. Typing or deleting replaces the selected text.
+    fn process_key_at_cursors(&mut self, key: Key) {
+        let main = Cursor {
+            position: self.cursor_position.clone(),
+            anchor: self.selection_anchor.take(),
+        };
+        let mut desired_column = self.desired_column.take();
+        let mut cursors = mem::take(&mut self.cursors);
+
+        cursors.push(main.clone());
+        cursors.sort_by(|a, b| a.start().cmp(b.start()));
+        cursors.dedup_by(|a, b| a.position == b.position);
+
+        let mut done = Vec::with_capacity(cursors.len());
+
+        for cursor in cursors.into_iter().rev() {
+            let is_main = cursor == main;
+
+            self.cursor_position = cursor.position;
+            self.selection_anchor = cursor.anchor;
+            self.desired_column = if is_main { desired_column.take() } else { None };
+
+            match (self.selection(), key) {
+                (Some((start, end)), Key::Char(_) | Key::Backspace | Key::Delete) => {
+                    self.document.delete_range(&start, &end);
+                    self.cursor_position = start;
+                    self.selection_anchor = None;
+
+                    if matches!(key, Key::Char(_)) {
+                        self.process_key(key);
+                    }
+                }
+                _ => self.process_key(key),
+            }
+
+            if is_main {
+                desired_column = self.desired_column.take();
+            }
+
+            let Position { x, y } = self.cursor_position;
+
+            done.push((
+                self.document.len().saturating_sub(y),
+                self.document.row(y).map_or(0, Row::len).saturating_sub(x),
+                is_main,
+            ));
+        }
+
+        for (lines_after, graphemes_after, is_main) in done {
+            let y = self.document.len().saturating_sub(lines_after);
+            let x = self
+                .document
+                .row(y)
+                .map_or(0, Row::len)
+                .saturating_sub(graphemes_after);
+
+            if is_main {
+                self.cursor_position = Position { x, y };
+            } else {
+                self.cursors.push(Cursor {
+                    position: Position { x, y },
+                    anchor: None,
+                });
+            }
+        }
+
+        self.cursors.sort_by(|a, b| a.position.cmp(&b.position));
+        self.cursors.dedup();
+        self.cursors
+            .retain(|cursor| cursor.position != self.cursor_position);
+        self.desired_column = desired_column;
+    }
+
+    fn process_key(&mut self, pressed_key: Key) {
+        if matches!(pressed_key, Key::Char('\t') | Key::BackTab)
+            && self.indent_selection(pressed_key == Key::BackTab)
+        {
+            return;
+        }
+
+        self.selection_anchor = None;
+
+        match pressed_key {
+            Key::Char('\t') if self.expand_tabs() => {
+                let width = self.tab_width();
+                let spaces = width - self.cursor_column() % width;
+
+                for _ in 0..spaces {
+                    self.insert_at(&self.cursor_position.clone(), ' ');
+                    self.move_cursor(Key::Right);
+                }
+            }
+            Key::Esc => {
+                if self.config.modal {
+                    self.mode = Mode::Normal;
+                }
+
+                self.cancel();
+            }
+            Key::Char('\n') => {
+                let indent_unit = self.indent_unit();
+
+                match self
+                    .document
+                    .insert_newline(&self.cursor_position, &indent_unit)
+                {
+                    Ok(position) => {
+                        self.cursor_position = position;
+                        self.move_cursor(Key::Null);
+                    }
+                    Err(err) => {
+                        self.status_message =
+                            StatusMessage::from(format!("Failed to insert content: {err}"))
+                    }
+                }
+            }
+            Key::Insert => self.overwrite = !self.overwrite,
+            Key::Char(c) if self.overwrite && self.cursor_position.x < self.current_row_len() => {
+                self.delete_at(&self.cursor_position.clone());
+                self.insert_at(&self.cursor_position.clone(), c);
+                self.move_cursor(Key::Right);
+            }
+            Key::Char(c) if self.config.auto_pairs && self.auto_pair(c) => (),
+            Key::Char(c) => match self.document.insert(&self.cursor_position, c) {
+                Ok(_) => self.move_cursor(Key::Right),
+                Err(err) => {
+                    self.status_message =
+                        StatusMessage::from(format!("Failed to paste content: {err}"))
+                }
+            },
+            Key::Delete => match self.document.delete(&self.cursor_position) {
+                Ok(_) => (),
+                Err(err) => {
+                    self.status_message =
+                        StatusMessage::from(format!("Failed to remove content: {err}"))
+                }
+            },
+            Key::Backspace if self.cursor_position.x > 0 || self.cursor_position.y > 0 => {
+                if self.config.auto_pairs && self.is_inside_empty_pair() {
+                    self.delete_at(&self.cursor_position.clone());
+                }
+
+                for _ in 0..self.backspace_width() {
+                    self.move_cursor(Key::Left);
+
+                    if let Err(err) = self.document.delete(&self.cursor_position) {
+                        self.status_message =
+                            StatusMessage::from(format!("Failed to remove character: {err}"));
+                        break;
+                    }
+                }
+            }
+            Key::Up | Key::Down | Key::Left | Key::Right => {
+                let before = self.cursor_position.clone();
+
+                self.move_cursor(pressed_key);
+
+                if self.cursor_position == before {
+                    self.ring_bell();
+                }
+            }
+            Key::PageUp | Key::PageDown | Key::End | Key::Home => self.move_cursor(pressed_key),
+            _ => (),
+        }
+    }
+
+    /// Returns how many characters Backspace removes: the spaces back to the
+    /// previous tab stop within the indentation when indenting with spaces
+    /// and `smart_backspace` is set, or else one.
+    fn backspace_width(&self) -> usize {
+        let Position { x, y } = self.cursor_position;
+        let in_indentation = self
+            .document
+            .row(y)
+            .is_some_and(|row| row.slice(0, x).chars().all(|c| c == ' '));
+
+        if x == 0 || !in_indentation || !self.expand_tabs() || !self.config.smart_backspace {
+            return 1;
+        }
+
+        let tab_width = self.tab_width();
+
+        x - (x - 1) / tab_width * tab_width
+    }
+
+    /// Handles a plain key in the Normal and Visual modes of modal editing,
+    /// repeating it `count` times where that makes sense.
+    fn process_modal_key(&mut self, key: Key, count: usize) {
+        let pending_operator = self.pending_operator.take();
+
+        match (self.mode, key) {
+            (Mode::Normal, Key::Char(operator @ ('d' | 'y'))) => match pending_operator {
+                Some((pending, times)) if pending == operator => {
+                    let lines = times.saturating_mul(count);
+
+                    self.yank_lines(lines);
+
+                    if operator == 'd' {
+                        for _ in 0..lines {
+                            self.delete_line();
+                        }
+                    }
+                }
+                _ => self.pending_operator = Some((operator, count)),
+            },
+            (Mode::Normal, Key::Char(c @ ('p' | 'P'))) => {
+                for _ in 0..count {
+                    self.paste(c == 'p');
+                }
+            }
+            (_, Key::Char('h') | Key::Left) => self.move_cursor_times(Key::Left, count),
+            (_, Key::Char('j') | Key::Down) => self.move_cursor_times(Key::Down, count),
+            (_, Key::Char('k') | Key::Up) => self.move_cursor_times(Key::Up, count),
+            (_, Key::Char('l') | Key::Right) => self.move_cursor_times(Key::Right, count),
+            (_, Key::PageUp | Key::PageDown | Key::Home | Key::End) => {
+                self.move_cursor_times(key, count);
+            }
+            (Mode::Normal, Key::Char('i')) => self.mode = Mode::Insert,
+            (Mode::Normal, Key::Char('a')) => {
+                if self.cursor_position.x < self.current_row_len() {
+                    self.move_cursor(Key::Right);
+                }
+
+                self.mode = Mode::Insert;
+            }
+            (Mode::Normal, Key::Char('x')) => {
+                for _ in 0..count {
+                    if self.cursor_position.x < self.current_row_len() {
+                        self.delete_at(&self.cursor_position.clone());
+                    }
+                }
+            }
+            (Mode::Normal, Key::Char('v')) => {
+                self.selection_anchor = Some(self.cursor_position.clone());
+                self.mode = Mode::Visual;
+            }
+            (Mode::Visual, Key::Char('d' | 'x')) => {
+                if let Some((start, end)) = self.selection() {
+                    self.remove_range(&start, &end);
+                }
+
+                self.leave_visual_mode();
+            }
+            (Mode::Visual, Key::Char('y')) => {
+                if let Err(err) = self.copy_content() {
+                    self.status_message = StatusMessage::from(format!("{err}"));
+                }
+
+                self.leave_visual_mode();
+            }
+            (Mode::Visual, Key::Char('v')) => self.leave_visual_mode(),
+            (_, Key::Esc) => {
+                self.leave_visual_mode();
+                self.cancel();
+            }
+            _ => (),
+        }
+    }
+
+    fn move_cursor_times(&mut self, key: Key, count: usize) {
+        for _ in 0..count {
+            self.move_cursor(key);
+        }
+    }
+
+    fn current_row_len(&self) -> usize {
+        self.document
+            .row(self.cursor_position.y)
+            .map_or(0, Row::len)
+    }
+
+    fn delete_line(&mut self) {
+        self.document.delete_row(self.cursor_position.y);
+        self.cursor_position.x = 0;
+        self.move_cursor(Key::Null);
+    }
+
+    fn leave_visual_mode(&mut self) {
+        self.selection_anchor = None;
+        self.mode = Mode::Normal;
+    }
+
+    /// Drops the selection, the highlighted search match and the message,
+    /// as `Esc` does.
+    fn cancel(&mut self) {
+        self.selection_anchor = None;
+        self.highlighted_word = None;
+        self.status_message = StatusMessage::from(String::new());
+    }
+
+    /// Inserts the closing counterpart of a typed bracket or quote, or steps
+    /// over a closing one already under the cursor. Returns `false` if `c`
+    /// has to be inserted as usual.
+    fn auto_pair(&mut self, c: char) -> bool {
+        let Position { x, y } = self.cursor_position;
+        let Some(row) = self.document.row(y) else {
+            return false;
+        };
+        let next = row.grapheme(x).and_then(|next| next.chars().next());
+        let previous = x
+            .checked_sub(1)
+            .and_then(|x| row.grapheme(x))
+            .and_then(|previous| previous.chars().next());
+        let closing = match c {
+            '(' => ')',
+            '[' => ']',
+            '{' => '}',
+            '"' | '\'' => c,
+            _ => return self.close_block_comment(c) || self.step_over(c, next),
+        };
+
+        if c == closing && self.step_over(c, next) {
+            return true;
+        }
+
+        let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+        let is_lifetime = c == '\'' && matches!(previous, Some('&' | '<'));
+        let pairs = !is_word(next) && (c != closing || !(is_word(previous) || is_lifetime));
+
+        if !pairs || !self.is_code_at(&self.cursor_position) {
+            return false;
+        }
+
+        let at = self.cursor_position.clone();
+
+        self.insert_at(&at, closing);
+        self.insert_at(&at, c);
+        self.move_cursor(Key::Right);
+
+        true
+    }
+
+    /// Inserts the end of a block comment when `c` completes its start in
+    /// code, e.g. `*/` after `/*`, unless the rest of the line ends one.
+    fn close_block_comment(&mut self, c: char) -> bool {
+        let Some((open, close)) = self.document.block_comment() else {
+            return false;
+        };
+        let Position { x, y } = self.cursor_position;
+        let Some(row) = self.document.row(y) else {
+            return false;
+        };
+        let typed = format!("{}{c}", row.slice(0, x));
+        let start = Position {
+            x: (x + 1).saturating_sub(open.graphemes(true).count()),
+            y,
+        };
+
+        if !typed.ends_with(open)
+            || row.slice(x, row.len()).contains(close)
+            || !self.is_code_at(&start)
+        {
+            return false;
+        }
+
+        let at = self.cursor_position.clone();
+
+        for closing in close.chars().rev() {
+            self.insert_at(&at, closing);
+        }
+
+        self.insert_at(&at, c);
+        self.move_cursor(Key::Right);
+
+        true
+    }
+
+    fn step_over(&mut self, c: char, next: Option<char>) -> bool {
+        if !matches!(c, ')' | ']' | '}' | '"' | '\'') || next != Some(c) {
+            return false;
+        }
+
+        self.move_cursor(Key::Right);
+
+        true
+    }
+
+    /// Returns `true` if the cursor sits between an opening bracket or quote
+    /// and its counterpart, as left by `auto_pair`.
+    fn is_inside_empty_pair(&self) -> bool {
+        let Position { x, y } = self.cursor_position;
+        let Some(row) = self.document.row(y) else {
+            return false;
+        };
+        let Some(previous) = x.checked_sub(1).and_then(|x| row.grapheme(x)) else {
+            return false;
+        };
+
+        matches!(
+            (previous, row.grapheme(x)),
+            ("(", Some(")"))
+                | ("[", Some("]"))
+                | ("{", Some("}"))
+                | ("\"", Some("\""))
+                | ("'", Some("'"))
+        )
+    }
+
+    /// Returns `false` when `at` lies inside a string or comment, going by
+    /// the highlighting of the grapheme before it.
+    fn is_code_at(&self, at: &Position) -> bool {
+        let Some(row) = self.document.row(at.y) else {
+            return true;
+        };
+        let Some(before) = at.x.checked_sub(1) else {
+            return true;
+        };
+
+        // A quote that is not followed by more of the literal closes it.
+        row.is_code(before)
+            || (row.is_literal(before)
+                && matches!(row.grapheme(before), Some("\"" | "'"))
+                && !row.is_literal(at.x))
+    }
+
+    /// Handles the modified keys without a binding. `Alt-Shift` with an
+    /// arrow key extends the block selection, `Shift` with any other
+    /// modifiers and a movement key extends the selection, and `Ctrl-Home`
+    /// and `Ctrl-End` go to the ends of the document. Otherwise movement keys
+    /// move as they do unmodified, so that a held or sticky modifier does not
+    /// leave them doing nothing.
+    fn process_modified_key(&mut self, key: Key, modifiers: Modifiers) {
+        let arrow = matches!(key, Key::Up | Key::Down | Key::Left | Key::Right);
+        let movement = arrow || matches!(key, Key::PageUp | Key::PageDown | Key::End | Key::Home);
+
+        if arrow && modifiers == Modifiers::ALT_SHIFT {
+            self.selection_anchor = None;
+            self.extend_block(key);
+
+            return;
+        }
+
+        if modifiers.shift && movement {
+            if self.selection_anchor.is_none() {
+                self.selection_anchor = Some(self.cursor_position.clone());
+            }
+        } else {
+            self.selection_anchor = None;
+        }
+
+        if matches!(key, Key::Home | Key::End) && modifiers.ctrl {
+            let position = match key {
+                Key::Home => Position::default(),
+                _ => self.document_end(),
+            };
+
+            self.jumps.push(self.cursor_position.clone());
+            self.cursor_position = position;
+            self.scroll();
+        } else if movement {
+            self.move_cursor(key);
+        }
+    }
+
+    /// Returns the position after the last character of the document.
+    fn document_end(&self) -> Position {
+        let y = self.document.len().saturating_sub(1);
+
+        Position {
+            x: self.document.row(y).map_or(0, Row::len),
+            y,
+        }
+    }
+
+    /// Starts a block selection at the cursor or moves its free corner by
+    /// a line or a column. The corner can go past the end of short lines.
+    fn extend_block(&mut self, key: Key) {
+        let Position { x, y } = self.cursor_position;
+        let column = self
+            .document
+            .row(y)
+            .map_or(0, |row| row.width_to(x, self.tab_width()));
+        let last = self.document.len().saturating_sub(1);
+        let block = self.block.get_or_insert(Block {
+            anchor: (y, column),
+            head: (y, column),
+        });
+        let (line, column) = &mut block.head;
+
+        match key {
+            Key::Up => *line = line.saturating_sub(1),
+            Key::Down => *line = cmp::min(line.saturating_add(1), last),
+            Key::Left => *column = column.saturating_sub(1),
+            Key::Right => *column = column.saturating_add(1),
+            _ => (),
+        }
+
+        self.place_block_cursor();
+    }
+
+    fn place_block_cursor(&mut self) {
+        if let Some(Block {
+            head: (y, column), ..
+        }) = self.block
+        {
+            self.cursor_position = Position {
+                x: self
+                    .document
+                    .row(y)
+                    .map_or(0, |row| row.index_at(column, self.tab_width())),
+                y,
+            };
+        }
+    }
+
+    /// Replaces the block on every line with a typed character, or deletes
+    /// it. With no columns selected, `Backspace` and `Delete` take the
+    /// column before or after it. Lines too short for the block are left
+    /// alone, or padded with spaces when `pad_block_selection` is set.
+    fn process_block_key(&mut self, key: Key) {
+        let Some(block) = self.block else {
+            return;
+        };
+        let text = match key {
+            Key::Char('\t') => self.indent_unit(),
+            Key::Char(c) => c.to_string(),
+            _ => String::new(),
+        };
+        let (left, right) = match (block.columns(), key) {
+            ((left, right), Key::Backspace) if left == right => (left.saturating_sub(1), right),
+            ((left, right), Key::Delete) if left == right => (left, right.saturating_add(1)),
+            (columns, _) => columns,
+        };
+        let tab_width = self.tab_width();
+
+        for y in block.lines() {
+            let Some(width) = self.document.row(y).map(|row| row.width(tab_width)) else {
+                continue;
+            };
+
+            if width < right {
+                if !self.config.pad_block_selection {
+                    continue;
+                }
+
+                if !text.is_empty() {
+                    for _ in width..right {
+                        let end = self.document.row(y).map_or(0, Row::len);
+
+                        self.insert_at(&Position { x: end, y }, ' ');
+                    }
+                }
+            }
+
+            let Some(row) = self.document.row(y) else {
+                continue;
+            };
+            let start = Position {
+                x: row.index_at(left, tab_width),
+                y,
+            };
+            let end = Position {
+                x: row.index_at(right, tab_width),
+                y,
+            };
+
+            self.document.delete_range(&start, &end);
+
+            for c in text.chars().rev() {
+                self.insert_at(&start, c);
+            }
+        }
+
+        let column = left.saturating_add(Row::from(text.as_str()).width(self.tab_width()));
+
+        self.block = Some(Block {
+            anchor: (block.anchor.0, column),
+            head: (block.head.0, column),
+        });
+        self.place_block_cursor();
+    }
+
+    fn process_mouse(&mut self, mouse: MouseEvent) {
+        match mouse {
+            MouseEvent::Press(MouseButton::Left, x, y) => {
+                let (x, y) = (
+                    usize::from(x).saturating_sub(1),
+                    usize::from(y).saturating_sub(1),
+                );
+                let contains = |rect: Rect| {
+                    (rect.x..rect.x + rect.width).contains(&x)
+                        && (rect.y..rect.y + rect.height).contains(&y)
+                };
+
+                if self.split.is_some() && contains(self.other_view()) {
+                    self.switch_pane();
+                }
+
+                let view = self.view();
+
+                // Clicks on separators and the bars below the text are ignored.
+                if !contains(view) {
+                    return;
+                }
+
+                let (x, y) = (x - view.x, y - view.y);
+
+                let y = self.line_at(self.visible_line(self.offset.y).saturating_add(y));
+                let y = cmp::min(y, self.document.len());
+                let column = self
+                    .offset
+                    .x
+                    .saturating_add(x.saturating_sub(self.gutter_width()));
+
+                self.selection_anchor = None;
+                self.cursor_position = Position {
+                    x: self
+                        .document
+                        .row(y)
+                        .map_or(0, |row| row.index_at(column, self.tab_width())),
+                    y,
+                };
+                self.move_cursor(Key::Null);
+            }
+            MouseEvent::Press(MouseButton::WheelUp, _, _) => {
+                let top = self
+                    .visible_line(self.offset.y)
+                    .saturating_sub(SCROLL_LINES);
+
+                self.offset.y = self.line_at(top);
+                self.keep_cursor_in_view();
+            }
+            MouseEvent::Press(MouseButton::WheelDown, _, _) => {
+                let last = self.document.len().saturating_sub(1);
+                let top = self
+                    .visible_line(self.offset.y)
+                    .saturating_add(SCROLL_LINES);
+
+                self.offset.y = cmp::min(self.line_at(top), last);
+                self.keep_cursor_in_view();
+            }
+            _ => (),
+        }
+    }
+
+    /// Moves the cursor inside the visible rows so that `scroll` does not
+    /// undo a scroll that did not come from cursor movement.
+    fn keep_cursor_in_view(&mut self) {
+        let height = self.view().height;
+        let top = self.visible_line(self.offset.y);
+        let last = top.saturating_add(height).saturating_sub(1);
+        let line = self
+            .visible_line(self.cursor_position.y)
+            .clamp(top, last.max(top));
+
+        self.cursor_position.y = self.line_at(line);
+        self.move_cursor(Key::Null);
+    }
+
+    fn move_line_up(&mut self) {
+        let y = self.cursor_position.y;
+
+        if y > 0 && y < self.document.len() {
+            self.document.swap_rows(y - 1, y);
+            self.cursor_position.y -= 1;
+        }
+    }
+
+    fn move_line_down(&mut self) {
+        let y = self.cursor_position.y;
+
+        if y.saturating_add(1) < self.document.len() {
+            self.document.swap_rows(y, y + 1);
+            self.cursor_position.y += 1;
+        }
+    }
+
+    fn quit(&mut self) -> Result<(), IOError> {
+        let unsaved: Vec<String> = (0..self.buffers.len())
+            .filter(|index| self.buffer_document(*index).is_changed())
+            .map(|index| self.buffer_name(index))
+            .collect();
+
+        if self.quit_times > 0 && !unsaved.is_empty() {
+            self.status_message = StatusMessage::from(format!(
+                "WARNING! There are unsaved changes in {}. Press Ctrl-Q {} more time to quit.",
+                unsaved.join(", "),
+                self.quit_times
+            ));
+            self.quit_times -= 1;
+
+            return Ok(());
+        }
+        self.should_quit = true;
+
+        Ok(())
+    }
+
+    /// Takes back a pending quit warning once something else is done.
+    fn rearm_quit(&mut self) {
+        if self.quit_times < QUIT_TIME {
+            self.quit_times = QUIT_TIME;
+            self.status_message = StatusMessage::from(String::new());
+        }
+    }
+
+    /// Returns whether to put off refreshing the screen because more input
+    /// is waiting, so that a held key or a burst of typing is not slowed
+    /// down by highlighting and drawing every step of it. The screen is
+    /// refreshed once the input is handled, or after `MAX_REFRESH_DELAY`.
+    fn defers_refresh(&mut self) -> bool {
+        self.refreshed_at
+            .is_some_and(|refreshed_at| refreshed_at.elapsed() < MAX_REFRESH_DELAY)
+            && (!self.replay.is_empty()
+                || !self.typed_ahead.is_empty()
+                || self.terminal.has_pending_input())
+    }
+
+    fn refresh_screen(&mut self) -> Result<(), Box<dyn Err>> {
+        self.refreshed_at = Some(Instant::now());
+        self.terminal.cursor_hide();
+        self.terminal.cursor_position(&Position::default());
+
+        if self.should_quit {
+            self.clear_screen();
+        } else {
+            self.update_title();
+            self.sync_diff_panes();
+            self.load_visible_rows();
+            self.highlight_other_pane();
+            self.document.update_changes();
+
+            let until = self.line_at(
+                self.visible_line(self.offset.y)
+                    .saturating_add(self.view().height),
+            );
+
+            self.document.rainbow_brackets = self.config.rainbow_brackets;
+            self.document.set_undo_limit(self.config.undo_limit());
+
+            match self.document.highlight(
+                &self.highlight_term(),
+                self.dictionary.as_ref(),
+                Some(until),
+            ) {
+                Ok(_) => {
+                    self.bracket_match =
+                        self.find_bracket_match(self.view().height + BRACKET_SCAN_MARGIN);
+                    self.draw_rows();
+                    self.draw_tab_line();
+                    self.draw_status_bar();
+                    self.draw_message_bar();
+                    let view = self.view();
+
+                    self.terminal.cursor_position(&Position {
+                        x: self
+                            .cursor_column()
+                            .saturating_sub(self.offset.x)
+                            .saturating_add(self.gutter_width())
+                            .saturating_add(view.x),
+                        y: self
+                            .visible_line(self.cursor_position.y)
+                            .saturating_sub(self.visible_line(self.offset.y))
+                            .saturating_add(view.y),
+                    });
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.terminal.cursor_show();
+
+        match self.terminal.flush() {
+            Ok(_) => Ok(()),
+            Err(err) => Err(Box::new(err)),
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn draw_rows(&mut self) {
+        let mut lines = vec![String::new(); self.text_height()];
+        let view = self.view();
+
+        if let Some(split) = &self.split {
+            let other = self.other_view();
+            let (first, second) = if split.focus_first {
+                ((view, None), (other, Some(split)))
+            } else {
+                ((other, Some(split)), (view, None))
+            };
+
+            self.draw_pane(&mut lines, first.0, first.1);
+
+            match split.direction {
+                SplitDirection::Stacked => {
+                    if let Some(line) = lines.get_mut(first.0.height) {
+                        *line = "\u{2500}".repeat(first.0.width);
+                    }
+                }
+                SplitDirection::SideBySide => {
+                    for (y, line) in lines.iter_mut().enumerate() {
+                        let separator = cursor::Goto(first.0.width as u16 + 1, y as u16 + 1);
+
+                        line.push_str(&format!("{separator}\u{2502}"));
+                    }
+                }
+            }
+
+            self.draw_pane(&mut lines, second.0, second.1);
+        } else {
+            self.draw_pane(&mut lines, view, None);
+        }
+
+        if self.config.scrollbar {
+            self.draw_scrollbar(&mut lines, view);
+        }
+
+        if let Some(first) = self.help {
+            let help = self.help_lines();
+
+            for (y, line) in lines.iter_mut().enumerate() {
+                *line = help.get(first + y).cloned().unwrap_or_default();
+            }
+        }
+
+        if let Some(picker) = self.picker.as_ref().filter(|_| lines.len() > 2) {
+            let height = lines.len().saturating_sub(2);
+            let first = (picker.selected + 1).saturating_sub(height);
+            let width = self.terminal.size().width as usize;
+
+            lines[0] = take_width(&printable(&picker.title), width).to_owned();
+            lines[1] = picker.query.as_ref().map_or_else(String::new, |query| {
+                let query = format!(
+                    "> {}  ({}/{})",
+                    printable(query),
+                    picker.shown.len(),
+                    picker.items.len()
+                );
+
+                take_width(&query, width).to_owned()
+            });
+
+            for (y, line) in lines.iter_mut().enumerate().skip(2) {
+                let index = first + y - 2;
+                let item = picker
+                    .shown
+                    .get(index)
+                    .map(|item| printable(&picker.items[*item]));
+
+                *line = match item.as_deref().map(|item| take_width(item, width)) {
+                    Some(item) if index == picker.selected => format!(
+                        "{}{item}{}{}",
+                        style::Invert,
+                        " ".repeat(width.saturating_sub(text_width(item))),
+                        style::NoInvert
+                    ),
+                    Some(item) => item.to_owned(),
+                    None => String::new(),
+                };
+            }
+        }
+
+        for (y, line) in lines.into_iter().enumerate() {
+            self.draw_line(y, line);
+        }
+    }
+
+    /// Draws the position of `view` in the document in the rightmost column,
+    /// next to its lines.
+    fn draw_scrollbar(&self, lines: &mut [String], view: Rect) {
+        let len = cmp::max(self.document.len(), 1);
+        let height = view.height;
+
+        if height == 0 {
+            return;
+        }
+
+        let thumb = (height * height / len).clamp(1, height);
+        let scrollable = len.saturating_sub(height);
+        let start = match scrollable {
+            0 => 0,
+            _ => cmp::min(self.offset.y, scrollable) * (height - thumb) / scrollable,
+        };
+        let (bar, track) = if self.config.ascii_only {
+            ("#", "|")
+        } else {
+            ("\u{2588}", "\u{2502}")
+        };
+        let column = self.terminal.size().width;
+
+        for y in 0..height {
+            let symbol = if (start..start + thumb).contains(&y) {
+                bar
+            } else {
+                track
+            };
+
+            lines[view.y + y].push_str(&format!(
+                "{}{}{symbol}{}",
+                cursor::Goto(column, (view.y + y) as u16 + 1),
+                color::Fg(self.theme.line_number),
+                color::Fg(color::Reset)
+            ));
+        }
+    }
+
+    /// Shows the key bindings over the text until a key other than one
+    /// scrolling them is pressed.
+    fn show_help(&mut self) {
+        let last = self.help_lines().len().saturating_sub(self.text_height());
+        let mut first = 0;
+
+        loop {
+            self.help = Some(first);
+
+            if self.refresh_screen().is_err() {
+                break;
+            }
+
+            first = match self.read_key() {
+                Ok(Key::Up) => first.saturating_sub(1),
+                Ok(Key::Down) => cmp::min(first + 1, last),
+                Ok(Key::PageUp) => first.saturating_sub(self.text_height()),
+                Ok(Key::PageDown) => cmp::min(first + self.text_height(), last),
+                _ => break,
+            };
+        }
+
+        self.help = None;
+    }
+
+    /// Shows `items` over the text and returns the index of the one chosen
+    /// with Up, Down and Enter, or `None` if the list is left with Esc.
+    fn pick(&mut self, title: &str, items: Vec<String>) -> Option<usize> {
+        self.pick_from(title, items, None)
+    }
+
+    /// Works like `pick`, with typed text narrowing the items down to the
+    /// ones matching it when `query` is given.
+    fn pick_from(
+        &mut self,
+        title: &str,
+        items: Vec<String>,
+        query: Option<String>,
+    ) -> Option<usize> {
+        if items.is_empty() {
+            return None;
+        }
+
+        let hint = if query.is_some() {
+            "type to filter, Up and Down move, Enter chooses, Esc closes"
+        } else {
+            "Up and Down move, Enter chooses, Esc closes"
+        };
+
+        self.picker = Some(Picker {
+            title: format!("{title} ({hint})"),
+            shown: (0..items.len()).collect(),
+            items,
+            selected: 0,
+            query,
+        });
+
+        let page = self.text_height().saturating_sub(2);
+        let chosen = loop {
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+
+            let Ok(key) = self.read_key() else {
+                break None;
+            };
+            let Some(picker) = &mut self.picker else {
+                break None;
+            };
+            let last = picker.shown.len().saturating_sub(1);
+
+            match key {
+                Key::Up => picker.selected = picker.selected.saturating_sub(1),
+                Key::Down => picker.selected = cmp::min(picker.selected + 1, last),
+                Key::PageUp => picker.selected = picker.selected.saturating_sub(page),
+                Key::PageDown => picker.selected = cmp::min(picker.selected + page, last),
+                Key::Char('\n') => break picker.shown.get(picker.selected).copied(),
+                Key::Esc => break None,
+                Key::Backspace => {
+                    if let Some(query) = &mut picker.query {
+                        query.pop();
+                        picker.filter();
+                    }
+                }
+                Key::Char(c) if !c.is_control() => {
+                    if let Some(query) = &mut picker.query {
+                        query.push(c);
+                        picker.filter();
+                    }
+                }
+                _ => (),
+            }
+        };
+
+        self.picker = None;
+        chosen
+    }
+
+    fn help_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            "Key bindings (Up and Down scroll, any other key closes this)".to_owned(),
+            String::new(),
+        ];
+
+        lines.extend(
+            self.keymap
+                .bindings()
+                .into_iter()
+                .map(|(descriptor, action)| format!("  {descriptor:<16}{}", action.name())),
+        );
+        lines
+    }
+
+    /// Renders a pane into the screen `lines` it covers: the focused view
+    /// when `split` is `None`, otherwise the split's other view.
+    #[allow(clippy::cast_possible_truncation)]
+    fn draw_pane(&self, lines: &mut [String], rect: Rect, split: Option<&Split>) {
+        let (document, offset, folds) = match split {
+            Some(split) => (
+                self.buffer_document(split.buffer),
+                &split.offset,
+                &split.folds,
+            ),
+            None => (&self.document, &self.offset, &self.folds),
+        };
+        let gutter = self.gutter_width_of(document);
+        let top = visible_line(folds, offset.y);
+        let mut welcome = if document.is_empty() {
+            self.welcome_lines()
+        } else {
+            Vec::new()
+        };
+
+        if welcome.len() > rect.height {
+            welcome.clear();
+        }
+
+        let welcome_top = rect.height.saturating_sub(welcome.len()) / 3;
+        let tab_width = self.indentation_of(document).0;
+
+        for terminal_row in 0..rect.height {
+            let index = line_at(folds, top.saturating_add(terminal_row));
+            let room = |row: &Row| {
+                rect.width
+                    .saturating_sub(gutter)
+                    .saturating_sub(row.width(tab_width).saturating_sub(offset.x))
+            };
+            let segment = match document.row(index) {
+                Some(row) if split.is_none() => {
+                    format!(
+                        "{}{}{}",
+                        self.gutter(document, index, gutter),
+                        self.render_row(row, index),
+                        self.fold_marker(folds, index, room(row))
+                    )
+                }
+                Some(row) => {
+                    let end = offset.x.saturating_add(rect.width.saturating_sub(gutter));
+
+                    format!(
+                        "{}{}{}",
+                        self.gutter(document, index, gutter),
+                        row.render(
+                            offset.x,
+                            end,
+                            &Overlay {
+                                ruler: self.ruler(),
+                                line_background: split.and_then(|split| self.line_background(
+                                    split.buffer,
+                                    index,
+                                    false
+                                )),
+                                whitespace: self.whitespace(),
+                                indent_guides: self.indent_guides(tab_width),
+                                tab_width,
+                                colorless: self.colorless(),
+                                ..Overlay::default()
+                            },
+                            &self.theme
+                        ),
+                        self.fold_marker(folds, index, room(row))
+                    )
+                }
+                None => match terminal_row
+                    .checked_sub(welcome_top)
+                    .and_then(|line| welcome.get(line))
+                {
+                    Some(line) => self.empty_line(Some(line), rect.width),
+                    None => self.empty_line(None, rect.width),
+                },
+            };
+            let line = &mut lines[rect.y + terminal_row];
+
+            if rect.x > 0 {
+                line.push_str(&format!(
+                    "{}",
+                    cursor::Goto(rect.x as u16 + 1, (rect.y + terminal_row) as u16 + 1)
+                ));
+            }
+
+            line.push_str(&segment);
+        }
+    }
+
+    /// Reads the rows in and around both panes of a large file, which is
+    /// opened without them.
+    fn load_visible_rows(&mut self) {
+        let around = |y: usize, height: usize| {
+            y.saturating_sub(height)..y.saturating_add(height.saturating_mul(2))
+        };
+        let mut ranges = vec![around(self.offset.y, self.view().height)];
+        let mut result = Ok(());
+
+        if let Some(split) = &self.split {
+            let range = around(split.offset.y, self.other_view().height);
+
+            if split.buffer == self.active {
+                ranges.push(range);
+            } else {
+                result = self.buffers[split.buffer].document.load_rows(&[range]);
+            }
+        }
+
+        result = self.document.load_rows(&ranges).and(result);
+
+        if let Err(err) = result {
+            self.status_message = StatusMessage::from(format!("Cannot read the file: {err}"));
+        }
+    }
+
+    fn highlight_other_pane(&mut self) {
+        let Some(split) = &self.split else {
+            return;
+        };
+        let until = Some(line_at(
+            &split.folds,
+            visible_line(&split.folds, split.offset.y).saturating_add(self.other_view().height),
+        ));
+
+        if split.buffer == self.active {
+            self.document
+                .highlight(&self.highlight_term(), self.dictionary.as_ref(), until)
+                .ok();
+        } else {
+            let document = &mut self.buffers[split.buffer].document;
+
+            document.rainbow_brackets = self.config.rainbow_brackets;
+            document
+                .highlight(&None, self.dictionary.as_ref(), until)
+                .ok();
+            document.update_changes();
+        }
+    }
+
+    /// Returns the search query to highlight or, without one, the symbol
+    /// under the cursor.
+    fn highlight_term(&self) -> Option<highlight::Term> {
+        match &self.highlighted_word {
+            Some(word) => Some(highlight::Term::Search(word.clone())),
+            None if self.config.highlight_symbol => {
+                self.symbol_under_cursor().map(highlight::Term::Symbol)
+            }
+            None => None,
+        }
+    }
+
+    /// Returns the identifier the cursor is on or directly after.
+    fn symbol_under_cursor(&self) -> Option<String> {
+        self.word_under_cursor()
+            .filter(|word| !word.starts_with(|c: char| c.is_ascii_digit()))
+            .map(str::to_owned)
+    }
+
+    /// Returns the screen area of the focused view.
+    fn view(&self) -> Rect {
+        match &self.split {
+            Some(split) if !split.focus_first => self.pane_rects(split.direction).1,
+            Some(split) => self.pane_rects(split.direction).0,
+            None => Rect {
+                x: 0,
+                y: 0,
+                width: self.text_width(),
+                height: self.text_height(),
+            },
+        }
+    }
+
+    /// Returns the number of columns for the text, leaving out the one the
+    /// scrollbar takes.
+    fn text_width(&self) -> usize {
+        (self.terminal.size().width as usize).saturating_sub(usize::from(self.config.scrollbar))
+    }
+
+    /// Returns the screen area of the view that is not focused.
+    fn other_view(&self) -> Rect {
+        match &self.split {
+            Some(split) if split.focus_first => self.pane_rects(split.direction).1,
+            Some(split) => self.pane_rects(split.direction).0,
+            None => self.view(),
+        }
+    }
+
+    /// Returns the areas of the first and second pane, leaving a row or a
+    /// column between them for the separator.
+    fn pane_rects(&self, direction: SplitDirection) -> (Rect, Rect) {
+        let width = self.text_width();
+        let height = self.text_height();
+
+        match direction {
+            SplitDirection::Stacked => {
+                let first = height.saturating_sub(1) / 2;
+
+                (
+                    Rect {
+                        x: 0,
+                        y: 0,
+                        width,
+                        height: first,
+                    },
+                    Rect {
+                        x: 0,
+                        y: first + 1,
+                        width,
+                        height: height.saturating_sub(first + 1),
+                    },
+                )
+            }
+            SplitDirection::SideBySide => {
+                let first = width.saturating_sub(1) / 2;
+
+                (
+                    Rect {
+                        x: 0,
+                        y: 0,
+                        width: first,
+                        height,
+                    },
+                    Rect {
+                        x: first + 1,
+                        y: 0,
+                        width: width.saturating_sub(first + 1),
+                        height,
+                    },
+                )
+            }
+        }
+    }
+
+    /// Splits the screen in `direction` with both panes showing the active
+    /// buffer, or goes back to a single pane if it is already split that way.
+    fn toggle_split(&mut self, direction: SplitDirection) {
+        if self
+            .split
+            .as_ref()
+            .is_some_and(|split| split.direction == direction)
+        {
+            self.split = None;
+            return;
+        }
+
+        if let Some(split) = &mut self.split {
+            split.direction = direction;
+            return;
+        }
+
+        self.split = Some(Split {
+            direction,
+            buffer: self.active,
+            cursor_position: self.cursor_position.clone(),
+            offset: self.offset.clone(),
+            folds: self.folds.clone(),
+            focus_first: true,
+        });
+    }
+
+    /// Opens `old` and `new` read-only side by side, tinting the lines that
+    /// differ between them.
+    fn start_diff(&mut self, old: &str, new: &str) {
+        let (mut old_document, mut new_document) = match (Document::open(old), Document::open(new))
+        {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(err), _) | (_, Err(err)) => {
+                self.status_message =
+                    StatusMessage::from(format!("ERR: Could not compare the files: {err}"));
+                return;
+            }
+        };
+        let rows = |document: &Document| -> Vec<String> {
+            (0..document.len())
+                .filter_map(|index| document.row(index))
+                .map(|row| row.as_string().clone())
+                .collect()
+        };
+        let new_rows = rows(&new_document);
+        let lines = LineDiff::new(
+            &rows(&old_document),
+            &new_rows.iter().map(String::as_str).collect::<Vec<_>>(),
+        );
+        let (changed, added, removed) = lines.counts();
+
+        old_document.read_only = true;
+        new_document.read_only = true;
+        self.document = old_document;
+        self.buffers.truncate(1);
+        self.buffers.push(Buffer {
+            document: new_document,
+            ..Buffer::default()
+        });
+        self.active = 0;
+        self.diff = Some(Diff {
+            old: 0,
+            new: 1,
+            lines,
+        });
+        self.split = Some(Split {
+            direction: SplitDirection::SideBySide,
+            buffer: 1,
+            cursor_position: Position::default(),
+            offset: Position::default(),
+            folds: Vec::new(),
+            focus_first: true,
+        });
+
+        let approximate = self
+            .diff
+            .as_ref()
+            .is_some_and(|diff| diff.lines.approximate);
+
+        self.status_message = StatusMessage::from(match changed + added + removed {
+            0 => format!("{old} and {new} are the same."),
+            _ if approximate => format!(
+                "{changed} changed, {added} added and {removed} removed lines, \
+                 roughly: the files differ too much to align every line."
+            ),
+            _ => format!("{changed} changed, {added} added and {removed} removed lines."),
+        });
+    }
+
+    /// Returns the background of line `index` of the buffer at `buffer`: as
+    /// a diff marks it, or else of the cursor line when it is one.
+    fn line_background(
+        &self,
+        buffer: usize,
+        index: usize,
+        cursor_line: bool,
+    ) -> Option<color::Rgb> {
+        let change = self.diff.as_ref().and_then(|diff| {
+            let lines = match buffer {
+                _ if buffer == diff.old => &diff.lines.old,
+                _ if buffer == diff.new => &diff.lines.new,
+                _ => return None,
+            };
+
+            lines.get(index).copied().flatten()
+        });
+
+        match change {
+            Some(LineChange::Added) => Some(self.theme.diff_added),
+            Some(LineChange::Removed) => Some(self.theme.diff_removed),
+            Some(LineChange::Changed) => Some(self.theme.diff_changed),
+            None => cursor_line.then_some(self.theme.cursor_line),
+        }
+    }
+
+    /// Scrolls the other pane of a diff to the lines facing the ones in
+    /// view, and moves its cursor along.
+    fn sync_diff_panes(&mut self) {
+        let (Some(diff), Some(split)) = (&self.diff, &mut self.split) else {
+            return;
+        };
+        let from_old = match (self.active, split.buffer) {
+            (active, other) if active == diff.old && other == diff.new => true,
+            (active, other) if active == diff.new && other == diff.old => false,
+            _ => return,
+        };
+
+        split.offset = Position {
+            x: self.offset.x,
+            y: diff.lines.facing(self.offset.y, from_old),
+        };
+        split.cursor_position = Position {
+            x: self.cursor_position.x,
+            y: diff.lines.facing(self.cursor_position.y, from_old),
+        };
+    }
+
+    /// Moves the focus to the other pane of a split screen.
+    fn switch_pane(&mut self) {
+        let Some(split) = &mut self.split else {
+            return;
+        };
+        let buffer = mem::replace(&mut split.buffer, self.active);
+        let cursor_position =
+            mem::replace(&mut split.cursor_position, self.cursor_position.clone());
+        let offset = mem::replace(&mut split.offset, self.offset.clone());
+        let folds = mem::replace(&mut split.folds, mem::take(&mut self.folds));
+
+        split.focus_first = !split.focus_first;
+        self.switch_buffer(buffer);
+        self.offset = offset;
+        self.folds = folds;
+        self.selection_anchor = None;
+        // Edits made through the other pane may have removed the rows the
+        // cursor was on.
+        self.cursor_position = Position {
+            y: cmp::min(cursor_position.y, self.document.len()),
+            ..cursor_position
+        };
+        self.move_cursor(Key::Null);
+    }
+
+    /// Returns the lines shown in an empty buffer: the version, the keys
+    /// to get started with and the files opened last.
+    fn welcome_lines(&self) -> Vec<String> {
+        if !self.config.welcome {
+            return Vec::new();
+        }
+
+        let bindings = self.keymap.bindings();
+        let key = |action: Action| {
+            bindings
+                .iter()
+                .find(|(_, bound)| *bound == action)
+                .map(|(descriptor, _)| descriptor.clone())
+        };
+        let mut lines = vec![format!("Revise | v{VERSION}"), String::new()];
+        let recent = self.recent.files();
+        let mut hints = vec![
+            (Action::Open, "open a file"),
+            (Action::FindFile, "find a file"),
+            (Action::Help, "show the keys"),
+            (Action::Quit, "quit"),
+        ];
+
+        if !recent.is_empty() {
+            hints.insert(2, (Action::RecentFiles, "reopen a recent file"));
+        }
+
+        lines.extend(
+            hints
+                .into_iter()
+                .filter_map(|(action, hint)| Some(format!("{:<10} {hint:<20}", key(action)?))),
+        );
+
+        if !recent.is_empty() {
+            lines.push(String::new());
+            lines.push("Recent files".to_owned());
+            lines.extend(recent.iter().take(WELCOME_RECENT_FILES).map(|file| {
+                let file = fit_width(file, 31, self.ellipsis());
+
+                format!(
+                    "{file}{}",
+                    " ".repeat(31_usize.saturating_sub(text_width(&file)))
+                )
+            }));
+        }
+
+        lines
+    }
+
+    /// Returns the number of columns taken by line numbers, including the
+    /// space separating them from the text.
+    fn gutter_width(&self) -> usize {
+        self.gutter_width_of(&self.document)
+    }
+
+    /// Returns the display column at which lines go past `colorcolumn`.
+    fn ruler(&self) -> Option<usize> {
+        (self.config.colorcolumn > 0).then_some(self.config.colorcolumn)
+    }
+
+    /// Returns what marks text left out to fit the screen.
+    fn ellipsis(&self) -> &'static str {
+        if self.config.ascii_only {
+            "..."
+        } else {
+            "\u{2026}"
+        }
+    }
+
+    /// Returns the symbols drawn for a tab and a space while whitespace is
+    /// shown.
+    fn whitespace(&self) -> Option<(&'static str, &'static str)> {
+        match (self.show_whitespace, self.config.ascii_only) {
+            (false, _) => None,
+            (true, false) => Some(("\u{2192}", "\u{b7}")),
+            (true, true) => Some((">", ".")),
+        }
+    }
+
+    /// Returns the indentation width and the symbol drawn at each level
+    /// while indent guides are shown.
+    fn indent_guides(&self, tab_width: usize) -> Option<(usize, &'static str)> {
+        let symbol = if self.config.ascii_only {
+            "|"
+        } else {
+            "\u{2502}"
+        };
+
+        self.config.indent_guides.then_some((tab_width, symbol))
+    }
+
+    /// Returns the width of the columns left of the text: one for the
+    /// whitespace warnings, then the line numbers and a space.
+    fn gutter_width_of(&self, document: &Document) -> usize {
+        let numbers = if self.config.line_numbers {
+            document.len().max(1).to_string().len() + 1
+        } else {
+            0
+        };
+
+        numbers
+            + usize::from(self.config.whitespace_warnings)
+            + usize::from(self.shows_changes(document))
+    }
+
+    /// Returns whether the gutter marks the rows of `document` changed since
+    /// the version committed to Git.
+    fn shows_changes(&self, document: &Document) -> bool {
+        self.config.git_gutter && document.is_committed()
+    }
+
+    fn gutter(&self, document: &Document, index: usize, gutter: usize) -> String {
+        let mut result = String::new();
+        let mut digits = gutter;
+
+        if self.shows_changes(document) {
+            digits = digits.saturating_sub(1);
+
+            match document.change(index) {
+                Some(change) => {
+                    let (color, marker) = match change {
+                        Change::Added => (self.theme.added, '+'),
+                        Change::Modified => (self.theme.modified, '~'),
+                        Change::Deleted => (self.theme.deleted, '_'),
+                    };
+
+                    result.push_str(&format!(
+                        "{}{marker}{}",
+                        color::Fg(color),
+                        color::Fg(color::Reset)
+                    ));
+                }
+                None => result.push(' '),
+            }
+        }
+
+        if self.config.whitespace_warnings {
+            digits = digits.saturating_sub(1);
+
+            if document.row(index).is_some_and(Row::has_whitespace_warning) {
+                result.push_str(&format!(
+                    "{}{}{}",
+                    color::Fg(self.theme.whitespace_warning),
+                    if self.config.ascii_only {
+                        "!"
+                    } else {
+                        "\u{258c}"
+                    },
+                    color::Fg(color::Reset),
+                ));
+            } else {
+                result.push(' ');
+            }
+        }
+
+        if digits > 0 {
+            result.push_str(&format!(
+                "{}{:>digits$} {}",
+                color::Fg(self.theme.line_number),
+                index.saturating_add(1),
+                color::Fg(color::Reset),
+                digits = digits - 1,
+            ));
+        }
+
+        result
+    }
+
+    /// Draws `line` on the screen row `y` unless it is already shown there,
+    /// so that a refresh only emits the rows that changed since the last one.
+    fn draw_line(&mut self, y: usize, line: String) {
+        if self.screen.get(y) == Some(&line) {
+            return;
+        }
+
+        if self.screen.len() <= y {
+            self.screen.resize(y + 1, String::new());
+        }
+
+        self.terminal.cursor_position(&Position { x: 0, y });
+        self.terminal.clear_current_line();
+        self.terminal.print(&line);
+        self.screen[y] = line;
+    }
+
+    /// Clears the terminal and forgets what was drawn, so that the next
+    /// refresh redraws every row.
+    fn clear_screen(&mut self) {
+        self.terminal.clear_screen();
+        self.screen.clear();
+    }
+
+    /// Moves the cursor as the given navigation key would, keeping it
+    /// inside the document.
+    pub fn move_cursor(&mut self, key: Key) {
+        let terminal_height = self.view().height;
+        let Position { mut y, mut x } = self.cursor_position;
+        let column = match self.desired_column.take() {
+            Some((position, column)) if position == self.cursor_position => column,
+            _ => self.cursor_column(),
+        };
+        let height = self.document.len();
+        let mut width = if let Some(row) = self.document.row(y) {
+            row.len()
+        } else {
+            0
+        };
+
+        match key {
+            Key::Up => y = self.previous_line(y),
+            Key::Down if y < height => y = cmp::min(self.next_line(y), height),
+            Key::Left => {
+                if x > 0 {
+                    x -= 1;
+                } else if y > 0 {
+                    y = self.previous_line(y);
+
+                    if let Some(row) = self.document.row(y) {
+                        x = row.len();
+                    } else {
+                        x = 0;
+                    }
+                }
+            }
+            Key::Right => {
+                if x < width {
+                    x += 1;
+                } else if y < height {
+                    y = cmp::min(self.next_line(y), height);
+                    x = 0;
+                }
+            }
+            Key::PageUp => {
+                y = self.line_at(self.visible_line(y).saturating_sub(terminal_height));
+            }
+            Key::PageDown => {
+                let line = self.visible_line(y).saturating_add(terminal_height);
+
+                y = if line < self.visible_line(height) {
+                    self.line_at(line)
+                } else {
+                    height
+                }
+            }
+            Key::Home => {
+                let indentation = self.document.row(y).map_or(0, Row::indentation);
+
+                x = if x == indentation || indentation == width {
+                    0
+                } else {
+                    indentation
+                };
+            }
+            Key::End => x = width,
+            _ => (),
+        }
+
+        let vertical = matches!(key, Key::Up | Key::Down | Key::PageUp | Key::PageDown);
+
+        if y != self.cursor_position.y && vertical {
+            x = self
+                .document
+                .row(y)
+                .map_or(0, |row| row.index_at(column, self.tab_width()));
+        }
+
+        width = if let Some(row) = self.document.row(y) {
+            row.len()
+        } else {
+            0
+        };
+
+        if x > width {
+            x = width;
+        }
+
+        self.cursor_position = Position { x, y };
+
+        if vertical {
+            self.desired_column = Some((self.cursor_position.clone(), column));
+        }
+    }
+
+    /// Scrolls the view and moves the cursor by half the height of the
+    /// view, or by `scroll_amount` lines when set, in the direction of the
+    /// arrow `key`. The cursor keeps its row on the screen unless the view
+    /// reaches the start or the end of the document.
+    fn scroll_half_page(&mut self, key: Key) {
+        let height = self.view().height;
+        let amount = match self.config.scroll_amount {
+            0 => height / 2,
+            amount => amount,
+        };
+        let top = self.visible_line(self.offset.y);
+        let before = self.visible_line(self.cursor_position.y);
+
+        self.move_cursor_times(key, amount.max(1));
+
+        let after = self.visible_line(self.cursor_position.y);
+        let end = self.visible_line(self.document.len());
+        let top = if key == Key::Up {
+            top.saturating_sub(before.saturating_sub(after))
+        } else {
+            cmp::min(
+                top.saturating_add(after.saturating_sub(before)),
+                end.saturating_sub(height.saturating_sub(1)),
+            )
+            .max(top)
+        };
+
+        self.offset.y = self.line_at(top);
+        self.scroll();
+    }
+
+    /// Brings the cursor into view, unfolding the regions that hide it.
+    fn scroll(&mut self) {
+        let line = self.cursor_position.y;
+
+        self.folds
+            .retain(|(first, last)| line <= *first || line > *last);
+
+        let y = self.visible_line(line);
+        let x = self.cursor_column();
+        let x_end = self.document.row(y).map_or(x + 1, |row| {
+            row.width_to(self.cursor_position.x + 1, self.tab_width())
+                .max(x + 1)
+        });
+        let view = self.view();
+        let width = view.width.saturating_sub(self.gutter_width());
+        let height = view.height;
+        let margin = cmp::min(self.config.scrolloff, height.saturating_sub(1) / 2);
+        let end = self.visible_line(self.document.len());
+        let bottom = cmp::min(y.saturating_add(margin), end).max(y);
+        let top = self.visible_line(self.offset.y);
+
+        if y < top.saturating_add(margin) {
+            self.offset.y = self.line_at(y.saturating_sub(margin));
+        } else if bottom >= top.saturating_add(height) {
+            self.offset.y = self.line_at(bottom.saturating_sub(height).saturating_add(1));
+        }
+
+        let offset = &mut self.offset;
+
+        if x < offset.x {
+            offset.x = x;
+        } else if x_end > offset.x.saturating_add(width) {
+            offset.x = x_end.saturating_sub(width);
+        }
+    }
+
+    fn toggle_read_only(&mut self) {
+        if self.document.is_lazy() {
+            self.status_message =
+                StatusMessage::from("The file is too large to be edited.".to_owned());
+            return;
+        }
+
+        self.document.read_only = !self.document.read_only;
+
+        let state = if self.document.read_only {
+            "read-only"
+        } else {
+            "writable"
+        };
+
+        self.status_message = StatusMessage::from(format!("Buffer is {state}."));
+    }
+
+    /// Changes the case of the selection, or of the word under the cursor,
+    /// as a single undo step. The selection is kept over the changed text.
+    fn change_case(&mut self, case: Case) {
+        let y = self.cursor_position.y;
+        let (start, end) = match (self.selection(), self.word_bounds()) {
+            (Some(selection), _) => selection,
+            (None, Some((start, end))) => (Position { x: start, y }, Position { x: end, y }),
+            (None, None) => {
+                self.status_message =
+                    StatusMessage::from("No selection or word to change.".to_owned());
+                return;
+            }
+        };
+
+        self.checkpoint();
+
+        let end = self
+            .document
+            .map_range(&start, &end, |text| case.apply(text));
+
+        if let Some(anchor) = &mut self.selection_anchor {
+            if *anchor < self.cursor_position {
+                self.cursor_position = end;
+            } else {
+                *anchor = end;
+            }
+        }
+
+        self.move_cursor(Key::Null);
+        self.checkpoint();
+        self.status_message = StatusMessage::from(format!("Changed to {}.", case.name()));
+    }
+
+    /// Adds `delta` to the number under or after the cursor, leaving the
+    /// cursor on its last digit.
+    fn add_to_number(&mut self, delta: i128) {
+        match self.document.add_to_number(&self.cursor_position, delta) {
+            Some(x) => self.cursor_position.x = x,
+            None => self.status_message = StatusMessage::from("No number to change.".to_owned()),
+        }
+    }
+
+    /// Types the current local time at the cursor in `date_format`.
+    fn insert_date(&mut self) {
+        match (self.now)() {
+            Some(time) => self.type_text(&time.format(&self.config.date_format)),
+            None => {
+                self.status_message = StatusMessage::from("Cannot read the clock.".to_owned());
+            }
+        }
+    }
+
+    fn toggle_whitespace(&mut self) {
+        self.show_whitespace = !self.show_whitespace;
+
+        let state = if self.show_whitespace {
+            "shown"
+        } else {
+            "hidden"
+        };
+
+        self.status_message = StatusMessage::from(format!("Whitespace is {state}."));
+    }
+
+    /// Puts the cursor on the 1-based `line` and `column` given on the
+    /// command line, within the document, with the line in view.
+    fn start_at(&mut self, line: usize, column: Option<usize>) {
+        self.cursor_position = Position {
+            x: column.unwrap_or(1).saturating_sub(1),
+            y: cmp::min(line, self.document.len()).saturating_sub(1),
+        };
+        self.move_cursor(Key::Null);
+        self.center_view();
+    }
+
+    /// Scrolls so that the cursor line sits in the middle of the view, or
+    /// as close to it as the top of the document allows.
+    fn center_view(&mut self) {
+        let height = self.view().height;
+        let line = self.visible_line(self.cursor_position.y);
+
+        self.offset.y = self.line_at(line.saturating_sub(height / 2));
+    }
+
+    /// Returns the tab width and whether to indent with spaces in
+    /// `document`, which depend on its file type unless the indentation
+    /// was detected in it.
+    fn indentation_of(&self, document: &Document) -> (usize, bool) {
+        let (tab_width, expand_tabs) = self.config.indentation(
+            &document.file_type(),
+            document.editor_config().indentation,
+            document.indentation_style(),
+        );
+
+        (tab_width, document.expand_tabs().unwrap_or(expand_tabs))
+    }
+
+    /// Returns whether saving ends the file in exactly one newline, as its
+    /// `.editorconfig` or else the config asks.
+    fn insert_final_newline(&self) -> bool {
+        self.document
+            .editor_config()
+            .insert_final_newline
+            .unwrap_or(self.config.insert_final_newline)
+    }
+
+    /// Returns how long the current message stays in the message bar, or
+    /// `None` if it stays until the next one.
+    fn message_duration(&self) -> Option<Duration> {
+        if self.status_message.help {
+            self.config.help_duration()
+        } else {
+            self.config.message_duration()
+        }
+    }
+
+    fn trim_on_save(&self) -> bool {
+        self.document
+            .editor_config()
+            .trim_trailing_whitespace
+            .unwrap_or(self.config.trim_trailing_whitespace)
+    }
+
+    fn tab_width(&self) -> usize {
+        self.indentation_of(&self.document).0
+    }
+
+    fn expand_tabs(&self) -> bool {
+        self.indentation_of(&self.document).1
+    }
+
+    /// Returns the display column of the cursor, which differs from its
+    /// grapheme index once the row contains wide characters or tabs.
+    fn cursor_column(&self) -> usize {
+        self.document.row(self.cursor_position.y).map_or(0, |row| {
+            row.width_to(self.cursor_position.x, self.tab_width())
+        })
+    }
+
+    /// Returns the number of screen rows showing the document, which excludes
+    /// the tab line shown while more than one buffer is open.
+    fn text_height(&self) -> usize {
+        let height = self.terminal.size().height as usize;
+
+        if self.buffers.len() > 1 {
+            height.saturating_sub(1)
+        } else {
+            height
+        }
+    }
+
+    fn draw_tab_line(&mut self) {
+        // Without a row to spare the tab line would cover the status bar.
+        if self.buffers.len() < 2 || self.terminal.size().height == 0 {
+            return;
+        }
+
+        let width = self.terminal.size().width as usize;
+        let mut line = String::new();
+        let mut len = 0;
+
+        for index in 0..self.buffers.len() {
+            let name = self.buffer_name(index);
+            let marker = if self.buffer_document(index).is_changed() {
+                "*"
+            } else {
+                ""
+            };
+            let label = format!(" {}:{name}{marker} ", index + 1);
+
+            len += label.chars().count();
+
+            if len > width {
+                break;
+            }
+
+            if index == self.active {
+                line.push_str(&format!("{}{label}{}", style::Invert, style::NoInvert));
+            } else {
+                line.push_str(&label);
+            }
+        }
+
+        self.draw_line(self.text_height(), line);
+    }
+
+    fn draw_status_bar(&mut self) {
+        if self.terminal.bar_rows() == 0 {
+            return;
+        }
+
+        let width = self.terminal.size().width as usize;
+        let marker = match (self.document.is_changed(), self.config.ascii_only) {
+            (false, _) => "",
+            (true, false) => " \u{25cf}",
+            (true, true) => " *",
+        };
+        let read_only = if self.document.read_only { " [RO]" } else { "" };
+        let follow = if self.follow { " [follow]" } else { "" };
+        let recording = self
+            .recording
+            .as_ref()
+            .map(|recording| format!(" [recording {}]", recording.register))
+            .unwrap_or_default();
+        let matches = match self.search_matches {
+            Some((_, MAX_MATCH_COUNT..)) => format!(" [{MAX_MATCH_COUNT}+ matches]"),
+            Some((Some(index), count)) => format!(" [match {index} of {count}]"),
+            Some((None, count)) => format!(" [{count} matches]"),
+            None => String::new(),
+        };
+        let details = format!(
+            "{marker}{read_only}{follow}{recording}{matches} - {} lines",
+            self.document.len()
+        );
+        let column = self.document.row(self.cursor_position.y).map_or(0, |row| {
+            row.width_to(self.cursor_position.x, self.tab_width())
+        });
+        let size = match self.selection() {
+            Some((from, to)) => {
+                format!(
+                    "{} selected",
+                    self.document.text(&from, &to).chars().count()
+                )
+            }
+            None => format!("{} bytes", self.document.byte_len()),
+        };
+        let pane = match &self.split {
+            Some(split) => format!("pane {}/2 | ", if split.focus_first { 1 } else { 2 }),
+            None => String::new(),
+        };
+        let bom = if self.document.has_bom() { " BOM" } else { "" };
+        let mode = if self.config.modal && !(self.mode == Mode::Insert && self.overwrite) {
+            self.mode.name()
+        } else if self.overwrite {
+            "OVR"
+        } else {
+            "INS"
+        };
+        let mut line_indicator = format!(
+            "{mode} | {pane}{} | {}{} | {}/{}, col {} | {size}",
+            self.document.file_type_badge(),
+            self.document.encoding(),
+            bom,
+            self.cursor_position.y.saturating_add(1),
+            self.document.len(),
+            column.saturating_add(1),
+        );
+        let room = width
+            .saturating_sub(text_width(&details) + text_width(&line_indicator) + 1)
+            .max(MIN_FILENAME_WIDTH);
+        let filename = self.document.filename.as_ref().map_or_else(
+            || "[No Name]".to_owned(),
+            |name| abbreviate_path(name, room, self.ellipsis()),
+        );
+        let mut status = format!("{filename}{details}");
+        let mut len = text_width(&status) + text_width(&line_indicator);
+        // The visual bell swaps the colors of the bar for a moment.
+        let rung = self.bell.is_some_and(|rung| rung.elapsed() < BELL_DURATION);
+        let (background, foreground) = if rung {
+            (self.theme.status_fg, self.theme.status_bg)
+        } else {
+            (self.theme.status_bg, self.theme.status_fg)
+        };
+
+        // The clock is the first to go when the bar is too narrow.
+        if let Some(time) = (self.now)().filter(|_| self.config.clock) {
+            let clock = format!(" | {}", time.clock());
+
+            if len + text_width(&clock) <= width {
+                len += text_width(&clock);
+                line_indicator.push_str(&clock);
+            }
+        }
+
+        status.push_str(&" ".repeat(width.saturating_sub(len)));
+        status = take_width(&format!("{status}{line_indicator}"), width).to_owned();
+
+        // The marker is colored separately, as long as it was not truncated.
+        if let Some(rest) = status.strip_prefix(&format!("{filename}{marker}")) {
+            if !marker.is_empty() {
+                status = format!(
+                    "{filename}{}{marker}{}{rest}",
+                    color::Fg(self.theme.modified),
+                    color::Fg(foreground),
+                );
+            }
+        }
+
+        // Without colors the bar is inverted instead, which the bell undoes.
+        if self.colorless() != rung {
+            status = format!("{}{status}{}", style::Invert, style::NoInvert);
+        }
+
+        let line = format!(
+            "{}{}{status}{}{}",
+            color::Bg(background),
+            color::Fg(foreground),
+            color::Fg(color::Reset),
+            color::Bg(color::Reset),
+        );
+
+        self.draw_line(self.terminal.size().height as usize, line);
+    }
+
+    /// Tells that a key did nothing, as at the edge of the document or when
+    /// a search finds no match, with the bells turned on in the config.
+    /// Also stops replaying macros, so that one repeated until it fails
+    /// does not go on.
+    fn ring_bell(&mut self) {
+        self.stop_replay();
+
+        if self.config.visual_bell {
+            self.bell = Some(Instant::now());
+        }
+
+        if self.config.audible_bell {
+            self.terminal.bell();
+        }
+    }
+
+    fn draw_message_bar(&mut self) {
+        // The status bar keeps the only row a terminal that small has.
+        if self.terminal.bar_rows() < 2 {
+            return;
+        }
+
+        let message = &self.status_message;
+        let mut text = String::new();
+
+        if self
+            .message_duration()
+            .is_none_or(|duration| message.time.elapsed() < duration)
+        {
+            text = fit_width(
+                &message.text,
+                self.terminal.size().width as usize,
+                self.ellipsis(),
+            );
+        }
+
+        self.draw_line(self.terminal.size().height as usize + 1, text);
+    }
+
+    fn prompt<C>(&mut self, prompt: &str, callback: C) -> Result<Option<String>, Box<dyn Err>>
+    where
+        C: FnMut(&mut Self, Key, &String),
+    {
+        self.prompt_with_history(prompt, &[], callback)
+    }
+
+    /// Works like `prompt`, with Up and Down recalling entries of `history`
+    /// into the prompt instead of reaching the callback as navigation.
+    fn prompt_with_history<C>(
+        &mut self,
+        prompt: &str,
+        history: &[String],
+        callback: C,
+    ) -> Result<Option<String>, Box<dyn Err>>
+    where
+        C: FnMut(&mut Self, Key, &String),
+    {
+        self.read_prompt(prompt, history, false, callback)
+    }
+
+    /// Asks for a file name, with Tab completing the path typed so far and
+    /// pressed again, or with Shift, cycling through the other completions.
+    fn prompt_path(&mut self, prompt: &str) -> Result<Option<String>, Box<dyn Err>> {
+        self.read_prompt(prompt, &[], true, |_, _, _| {})
+    }
+
+    fn read_prompt<C>(
+        &mut self,
+        prompt: &str,
+        history: &[String],
+        paths: bool,
+        mut callback: C,
+    ) -> Result<Option<String>, Box<dyn Err>>
+    where
+        C: FnMut(&mut Self, Key, &String),
+    {
+        let mut result = String::new();
+        let mut draft = String::new();
+        let mut recalled: Option<usize> = None;
+        let mut completions: Vec<String> = Vec::new();
+        let mut completion = 0;
+        let mut shown = mem::take(&mut self.status_message.text);
+
+        loop {
+            // A message left by the callback is shown after the input.
+            let hint = mem::take(&mut self.status_message.text);
+
+            shown = match hint {
+                hint if hint.is_empty() || hint == shown => format!("{prompt}{result}"),
+                hint => format!("{prompt}{result}  ({hint})"),
+            };
+            self.status_message = StatusMessage::from(shown.clone());
+            self.refresh_screen()?;
+
+            let mut key = match self.read_event()? {
+                Event::Key(key) => key,
+                // Pasted line breaks would end the input, so they are read
+                // as spaces.
+                Event::Paste(text) => {
+                    result.extend(
+                        text.chars()
+                            .map(|c| if c == '\n' { ' ' } else { c })
+                            .filter(|c| !c.is_control()),
+                    );
+                    Key::Null
+                }
+                Event::Resize => {
+                    self.clear_screen();
+                    continue;
+                }
+                Event::Modified(..) | Event::Mouse(_) => continue,
+            };
+
+            if !matches!(key, Key::Char('\t') | Key::BackTab) {
+                completions.clear();
+            }
+
+            match key {
+                Key::Char('\t') | Key::BackTab if paths => {
+                    // A single completion is completed further, into the
+                    // directory it names.
+                    if completions.len() < 2 {
+                        completions = finder::complete_path(&result);
+                        completion = 0;
+                    } else if key == Key::BackTab {
+                        completion = (completion + completions.len() - 1) % completions.len();
+                    } else {
+                        completion = (completion + 1) % completions.len();
+                    }
+
+                    match completions.get(completion) {
+                        Some(path) => {
+                            result.clone_from(path);
+
+                            if completions.len() > 1 {
+                                self.status_message.text =
+                                    format!("{}/{}", completion + 1, completions.len());
+                            }
+                        }
+                        None => self.status_message.text = "no match".to_owned(),
+                    }
+
+                    key = Key::Null;
+                }
+                Key::Backspace => {
+                    let last = result.grapheme_indices(true).next_back();
+
+                    result.truncate(last.map_or(0, |(start, _)| start));
+                }
+                Key::Char('\n') => break,
+                Key::Char(c) if !c.is_control() => result.push(c),
+                Key::Esc => {
+                    result.truncate(0);
+                    break;
+                }
+                Key::Up | Key::Down if !history.is_empty() => {
+                    if recalled.is_none() {
+                        draft.clone_from(&result);
+                    }
+
+                    recalled = match (key, recalled) {
+                        (Key::Up, None) => Some(history.len() - 1),
+                        (Key::Up, Some(index)) => Some(index.saturating_sub(1)),
+                        (_, Some(index)) if index + 1 < history.len() => Some(index + 1),
+                        _ => None,
+                    };
+                    result.clone_from(recalled.map_or(&draft, |index| &history[index]));
+                    key = Key::Null;
+                }
+                _ => (),
+            }
+            callback(self, key, &result);
+        }
+
+        self.status_message = StatusMessage::from(String::new());
+
+        if result.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(result))
+    }
+
+    fn command_line(&mut self) {
+        if let Some(command) = self.prompt(":", |_, _, _| {}).unwrap_or_default() {
+            self.execute_command(&command);
+        }
+    }
+
+    /// Runs a command from the command line: `w [file]`, `q`, `q!`, `wq`,
+    /// `x`, `e <file>`, `r [file]`, `extract [from,to] [file]`,
+    /// `set <option>`, `indent`, `retab [spaces|tabs]`, `uniq`, `revert`,
+    /// `add_word [word]`, `follow`, `strip_bom`, `encoding [name]`,
+    /// `reopen <encoding>`, a line number to jump to or a percentage of the
+    /// document, such as `50%`. The README tells what each one does.
+    fn execute_command(&mut self, command: &str) {
+        let command = command.trim();
+        let (name, argument) = command
+            .split_once(' ')
+            .map_or((command, ""), |(name, argument)| (name, argument.trim()));
+
+        match name {
+            "w" => {
+                if !argument.is_empty() {
+                    if !self.confirm_overwrite(argument) {
+                        self.status_message = StatusMessage::from("Save aborted.".to_owned());
+                        return;
+                    }
+
+                    self.rename(argument);
+                    self.write_buffer();
+                } else {
+                    self.save();
+                }
+            }
+            "q" | "wq" | "x" => {
+                if name != "q" {
+                    self.save();
+                }
+
+                if self.has_unsaved_changes() {
+                    self.status_message = StatusMessage::from(
+                        "There are unsaved changes, add ! to quit anyway.".to_owned(),
+                    );
+                } else {
+                    self.should_quit = true;
+                }
+            }
+            "q!" => self.should_quit = true,
+            "e" if argument.is_empty() => {
+                self.status_message = StatusMessage::from("No file name given.".to_owned());
+            }
+            "e" => self.open_file(argument),
+            "r" => self.insert_file(argument),
+            "extract" => self.extract(argument),
+            "set" => {
+                let clipboard = (self.config.system_clipboard, self.config.osc52);
+                let colors = self.config.color_support.clone();
+
+                if let Err(err) = self.config.set(argument) {
+                    self.status_message = StatusMessage::from(format!("{err}"));
+                }
+
+                if (self.config.system_clipboard, self.config.osc52) != clipboard {
+                    self.update_clipboard();
+                }
+
+                if self.config.color_support != colors {
+                    self.update_colors();
+                }
+
+                self.update_dictionary();
+
+                if !self.config.modal {
+                    self.leave_visual_mode();
+                    self.mode = Mode::Insert;
+                }
+            }
+            "indent" => self.detect_indentation(),
+            "uniq" => self.remove_duplicate_lines(),
+            "revert" => self.revert(),
+            "add_word" => self.add_word(argument),
+            "follow" => self.toggle_follow(),
+            "strip_bom" => {
+                self.status_message = StatusMessage::from(
+                    if self.document.read_only {
+                        "Buffer is read-only."
+                    } else if self.remove_bom() {
+                        "Removed the byte order mark."
+                    } else {
+                        "There is no byte order mark to remove."
+                    }
+                    .to_owned(),
+                );
+            }
+            "encoding" | "reopen" => self.change_encoding(argument, name == "reopen"),
+            "retab" => match argument {
+                "" => self.retab(self.expand_tabs()),
+                "spaces" => self.retab(true),
+                "tabs" => self.retab(false),
+                _ => {
+                    self.status_message =
+                        StatusMessage::from(format!("Cannot retab to {argument}."));
+                }
+            },
+            _ => match name.parse::<usize>() {
+                Ok(line) => self.goto_line(line),
+                Err(_) if name.ends_with('%') => match name[..name.len() - 1].parse::<i64>() {
+                    Ok(percent) => self.goto_percent(percent),
+                    Err(_) => {
+                        self.status_message =
+                            StatusMessage::from(format!("Not a percentage: {name}"));
+                    }
+                },
+                Err(_) => {
+                    self.status_message =
+                        StatusMessage::from(format!("Not an editor command: {command}"));
+                }
+            },
+        }
+    }
+
+    /// Removes consecutive duplicate lines from the selected lines, or from
+    /// the whole document, as a single undo step.
+    fn remove_duplicate_lines(&mut self) {
+        if self.document.read_only {
+            self.status_message = StatusMessage::from("Buffer is read-only.".to_owned());
+            return;
+        }
+
+        let lines = match (self.selected_lines(), self.selection()) {
+            (Some(lines), _) => *lines.start()..lines.end().saturating_add(1),
+            (None, Some((start, _))) => start.y..start.y.saturating_add(1),
+            (None, None) => 0..self.document.len(),
+        };
+
+        self.checkpoint();
+
+        let removed = self.document.dedup_rows(lines);
+
+        self.selection_anchor = None;
+        self.cursor_position.y -= removed
+            .iter()
+            .filter(|&&y| y <= self.cursor_position.y)
+            .count();
+        self.move_cursor(Key::Null);
+        self.checkpoint();
+        self.status_message = StatusMessage::from(match removed.len() {
+            0 => "No duplicate lines.".to_owned(),
+            1 => "Removed 1 duplicate line.".to_owned(),
+            count => format!("Removed {count} duplicate lines."),
+        });
+    }
+
+    /// Inserts the text of the file named `filename` at the cursor as a
+    /// single undo step, asking for the name when it is empty.
+    fn insert_file(&mut self, filename: &str) {
+        if self.document.read_only {
+            self.status_message = StatusMessage::from("Buffer is read-only.".to_owned());
+            return;
+        }
+
+        let filename = match filename {
+            "" => match self.prompt_path("Insert file: ").unwrap_or_default() {
+                Some(filename) => filename,
+                None => return,
+            },
+            filename => filename.to_owned(),
+        };
+        let text =
+            match fs::read(&filename).and_then(|bytes| Encoding::detect(&bytes).decode(&bytes)) {
+                Ok(text) => text.replace("\r\n", "\n"),
+                Err(err) => {
+                    self.status_message =
+                        StatusMessage::from(format!("Could not read {filename}: {err}"));
+                    return;
+                }
+            };
+
+        self.selection_anchor = None;
+        self.type_text(&text);
+        self.status_message = StatusMessage::from(match text.lines().count() {
+            1 => format!("Inserted 1 line from {filename}."),
+            count => format!("Inserted {count} lines from {filename}."),
+        });
+    }
+
+    /// Writes the selection, or the lines given as `from,to` before the file
+    /// name, to another file without changing the document.
+    fn extract(&mut self, argument: &str) {
+        let (lines, filename) = match argument.split_once(' ').unwrap_or((argument, "")) {
+            (range, filename) if range.contains(',') => (Some(range), filename.trim()),
+            _ => (None, argument),
+        };
+        let text = match lines {
+            Some(range) => {
+                let Some((from, to)) = range
+                    .split_once(',')
+                    .and_then(|(from, to)| Some((from.parse().ok()?, to.parse().ok()?)))
+                    .filter(|&(from, to): &(usize, usize)| {
+                        from > 0 && from <= to && to <= self.document.len()
+                    })
+                else {
+                    self.status_message = StatusMessage::from(format!("Invalid range {range}."));
+                    return;
+                };
+                let end = self.document.row(to - 1).map_or(0, Row::len);
+
+                self.document.text(
+                    &Position { x: 0, y: from - 1 },
+                    &Position { x: end, y: to - 1 },
+                ) + "\n"
+            }
+            None => match self.selected_text() {
+                Some(text) => text,
+                None => {
+                    self.status_message = StatusMessage::from("Nothing is selected.".to_owned());
+                    return;
+                }
+            },
+        };
+        let filename = match filename {
+            "" => match self.prompt_path("Write to: ").unwrap_or_default() {
+                Some(filename) => filename,
+                None => return,
+            },
+            filename => filename.to_owned(),
+        };
+
+        if self.document.filename.as_deref() == Some(filename.as_str())
+            || !self.confirm_overwrite(&filename)
+        {
+            self.status_message = StatusMessage::from("Write aborted.".to_owned());
+            return;
+        }
+
+        self.status_message =
+            StatusMessage::from(match self.document.write_text(&filename, &text) {
+                Ok(()) => match text.lines().count() {
+                    1 => format!("Wrote 1 line to {filename}."),
+                    count => format!("Wrote {count} lines to {filename}."),
+                },
+                Err(err) => format!("Error writing file: {err}."),
+            });
+    }
+
+    /// Makes Tab insert what the document is mostly indented with, in this
+    /// document only.
+    fn detect_indentation(&mut self) {
+        self.status_message = StatusMessage::from(match self.document.indents_with_tabs() {
+            Some(tabs) => {
+                self.document.set_expand_tabs(!tabs);
+
+                format!("Indented with {}.", if tabs { "tabs" } else { "spaces" })
+            }
+            None => "No indented lines.".to_owned(),
+        });
+    }
+
+    /// Converts the indentation of the whole document to spaces, or to tabs
+    /// when `expand` is not set, as a single undo step. The cursor stays on
+    /// the same text.
+    fn retab(&mut self, expand: bool) {
+        if self.document.read_only {
+            self.status_message = StatusMessage::from("Buffer is read-only.".to_owned());
+            return;
+        }
+
+        let row_len = |revise: &Self| {
+            revise
+                .document
+                .row(revise.cursor_position.y)
+                .map_or(0, Row::len)
+        };
+        let tail = row_len(self).saturating_sub(self.cursor_position.x);
+
+        self.checkpoint();
+
+        let changed = self.document.retab(self.tab_width(), expand);
+
+        self.cursor_position.x = row_len(self).saturating_sub(tail);
+        self.checkpoint();
+        self.status_message = StatusMessage::from(match (changed, expand) {
+            (false, _) => "Indentation is already converted.".to_owned(),
+            (true, true) => "Indentation converted to spaces.".to_owned(),
+            (true, false) => "Indentation converted to tabs.".to_owned(),
+        });
+    }
+
+    /// Removes the whitespace at the end of every line as a single undo
+    /// step, keeping the cursor within its line.
+    fn trim_trailing_whitespace(&mut self) {
+        self.checkpoint();
+
+        if self
+            .document
+            .trim_trailing_whitespace(self.config.trim_edited_lines_only)
+        {
+            self.move_cursor(Key::Null);
+        }
+
+        self.checkpoint();
+    }
+
+    /// Shows the encoding of the document, or changes the one it is saved in
+    /// to `argument`. With `reopen` the file is read again in that encoding
+    /// instead, as a single undo step.
+    fn change_encoding(&mut self, argument: &str, reopen: bool) {
+        if argument.is_empty() && !reopen {
+            self.status_message =
+                StatusMessage::from(format!("Encoding is {}.", self.document.encoding()));
+            return;
+        }
+
+        let Ok(encoding) = argument.parse::<Encoding>() else {
+            self.status_message = StatusMessage::from(format!("Unknown encoding {argument}."));
+            return;
+        };
+
+        if !reopen {
+            if self.document.read_only {
+                self.status_message = StatusMessage::from("Buffer is read-only.".to_owned());
+                return;
+            }
+
+            self.document.set_encoding(encoding);
+            self.status_message = StatusMessage::from(format!("Will save as {encoding}."));
+            return;
+        }
+
+        let name = self.buffer_name(self.active);
+
+        if self.document.filename.is_none() {
+            self.status_message = StatusMessage::from(format!("{name} has no file to reopen."));
+            return;
+        }
+
+        self.status_message = StatusMessage::from(match self.document.reopen_as(encoding) {
+            Ok(()) => format!("Reopened {name} as {encoding}, undo to get your changes back."),
+            Err(err) => format!("Cannot reopen {name} as {encoding}: {err}"),
+        });
+        self.cursor_position.y = cmp::min(self.cursor_position.y, self.document.len());
+        self.move_cursor(Key::Null);
+    }
+
+    /// Lets the user choose a file below the working directory by typing
+    /// part of its path.
+    fn find_file(&mut self) {
+        let (files, truncated) = finder::scan(Path::new("."));
+
+        if files.is_empty() {
+            self.status_message = StatusMessage::from("No files found.".to_owned());
+            return;
+        }
+
+        let title = if truncated {
+            format!("Find file in the first {} files", finder::MAX_FILES)
+        } else {
+            "Find file".to_owned()
+        };
+
+        if let Some(index) = self.pick_from(&title, files.clone(), Some(String::new())) {
+            self.open_file(&files[index]);
+        }
+    }
+
+    /// Searches the files below the working directory for a text and opens
+    /// the one chosen from the matching lines at the match.
+    fn find_in_files(&mut self) {
+        let history = std::mem::take(&mut self.search_history);
+        let query = self
+            .prompt_with_history("Search files: ", &history, |_, _, _| {})
+            .unwrap_or_default();
+
+        self.search_history = history;
+
+        let Some(query) = query else {
+            return;
+        };
+
+        if self.search_history.last() != Some(&query) {
+            self.search_history.push(query.clone());
+        }
+
+        let root = Path::new(".");
+        let (files, _) = finder::scan(root);
+        let (matches, truncated) = finder::grep(root, &files, &query);
+
+        if matches.is_empty() {
+            self.status_message = StatusMessage::from(format!("No files contain {query}."));
+            return;
+        }
+
+        let title = match (matches.len(), truncated) {
+            (count, true) => format!("First {count} lines containing {query}"),
+            (1, false) => format!("1 line containing {query}"),
+            (count, false) => format!("{count} lines containing {query}"),
+        };
+        let items = matches
+            .iter()
+            .map(|found| format!("{}:{}: {}", found.path, found.line, found.text.trim()))
+            .collect();
+        let Some(found) = self.pick(&title, items).map(|index| &matches[index]) else {
+            return;
+        };
+
+        self.open_file(&found.path);
+
+        if self.document.filename.as_deref() == Some(found.path.as_str()) {
+            self.jumps.push(self.cursor_position.clone());
+            self.start_at(found.line, Some(found.column + 1));
+        }
+    }
+
+    /// Opens the file named by a `path:line:column` reference under the
+    /// cursor, like the ones in compiler output, at that place.
+    fn goto_reference(&mut self) {
+        let reference = self
+            .document
+            .row(self.cursor_position.y)
+            .and_then(|row| file_reference(row.as_string(), self.cursor_position.x));
+        let Some(reference) = reference else {
+            self.status_message =
+                StatusMessage::from("No file reference under the cursor.".to_owned());
+            return;
+        };
+
+        if !Path::new(&reference.path).is_file() {
+            self.status_message =
+                StatusMessage::from(format!("{} does not exist.", reference.path));
+            return;
+        }
+
+        self.open_file(&reference.path);
+
+        if self.document.filename.as_deref() == Some(reference.path.as_str()) {
+            self.jumps.push(self.cursor_position.clone());
+            self.start_at(reference.line, reference.column);
+        }
+    }
+
+    /// Lets the user choose one of the files opened recently to open again.
+    fn open_recent(&mut self) {
+        if self.recent.files().is_empty() {
+            self.status_message = StatusMessage::from("No recent files.".to_owned());
+            return;
+        }
+
+        let files = self.recent.files().to_vec();
+        let Some(index) = self.pick("Recent files", files.clone()) else {
+            return;
+        };
+        let filename = &files[index];
+
+        if Path::new(filename).exists() {
+            self.open_file(filename);
+        } else {
+            self.recent.remove(filename).ok();
+            self.status_message = StatusMessage::from(format!("{filename} no longer exists."));
+        }
+    }
+
+    /// Puts `filename` first in the recent files. The list is a convenience,
+    /// so failing to save it goes unreported.
+    fn remember_file(&mut self, filename: &str) {
+        self.recent.add(filename).ok();
+    }
+
+    fn open(&mut self) {
+        if let Some(filename) = self.prompt_path("Open: ").unwrap_or_default() {
+            self.open_file(&filename);
+        }
+    }
+
+    /// Opens the file named `filename` in a new buffer, or switches to the
+    /// buffer already showing it.
+    fn open_file(&mut self, filename: &str) {
+        if Path::new(filename).is_dir() {
+            if let Some(filename) = self.browse(filename) {
+                self.open_file(&filename);
+            }
+
+            return;
+        }
+
+        let open = (0..self.buffers.len())
+            .find(|index| self.buffer_document(*index).filename.as_deref() == Some(filename));
+
+        if let Some(index) = open {
+            self.switch_buffer(index);
+            return;
+        }
+
+        match self.open_document(filename) {
+            Ok(document) => {
+                self.remember_file(filename);
+                self.buffers.push(Buffer {
+                    document,
+                    swap: Some(SwapFile::for_file(filename)),
+                    ..Buffer::default()
+                });
+                self.switch_buffer(self.buffers.len() - 1);
+
+                if let Err(err) = self.check_swap() {
+                    self.status_message = StatusMessage::from(format!("{err}"));
+                }
+            }
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("Could not open file {filename}: {err}"));
+            }
+        }
+    }
+
+    /// Lists the entries of `directory` to choose from, going into the
+    /// directories chosen and up with `../`. Returns the path of the file
+    /// chosen, or `None` if the list is left with Esc.
+    fn browse(&mut self, directory: &str) -> Option<String> {
+        let mut directory = PathBuf::from(directory);
+
+        loop {
+            let entries = match finder::list_directory(&directory) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    self.status_message = StatusMessage::from(format!(
+                        "Could not list {}: {err}",
+                        directory.display()
+                    ));
+                    return None;
+                }
+            };
+            let title = format!("Browse {}", directory.display());
+            let index = self.pick_from(&title, entries.clone(), Some(String::new()))?;
+            let entry = &entries[index];
+
+            if entry == "../" {
+                if matches!(
+                    directory.components().next_back(),
+                    Some(Component::Normal(_))
+                ) {
+                    directory.pop();
+                } else {
+                    directory.push("..");
+                }
+
+                if directory.as_os_str().is_empty() {
+                    directory.push(".");
+                }
+            } else if let Some(name) = entry.strip_suffix('/') {
+                directory.push(name);
+            } else {
+                let path = directory.join(entry);
+                let path = path.strip_prefix(".").unwrap_or(&path);
+
+                return Some(path.display().to_string());
+            }
+        }
+    }
+
+    /// Reads `filename`, showing in the message bar how far it got when the
+    /// file is large.
+    fn open_document(&mut self, filename: &str) -> Result<Document, IOError> {
+        let mut reported: Option<Instant> = None;
+        let mut large = false;
+        let mut document = Document::open_with_progress(filename, |read, size| {
+            large = size >= PROGRESS_SIZE;
+
+            if !large || reported.is_some_and(|time| time.elapsed() < PROGRESS_INTERVAL) {
+                return;
+            }
+
+            reported = Some(Instant::now());
+            self.status_message = StatusMessage::from(format!(
+                "Loading {filename}... {}%",
+                read.saturating_mul(100) / size
+            ));
+            self.draw_message_bar();
+            self.terminal.flush().ok();
+        })?;
+
+        if large {
+            self.status_message = StatusMessage::from(format!("Loaded {} lines.", document.len()));
+        }
+
+        self.fire(&HookEvent::Opened(filename));
+
+        if self.config.git_gutter {
+            document.load_committed();
+        }
+
+        document.load_editor_config();
+
+        Ok(document)
+    }
+
+    /// Parks the active buffer and makes the buffer at `index` the active one.
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.active || index >= self.buffers.len() {
+            return;
+        }
+
+        self.buffers[self.active] = Buffer {
+            document: mem::take(&mut self.document),
+            cursor_position: mem::take(&mut self.cursor_position),
+            offset: mem::take(&mut self.offset),
+            selection_anchor: self.selection_anchor.take(),
+            swap: self.swap.take(),
+            swap_pending: mem::take(&mut self.swap_pending),
+            marks: mem::take(&mut self.marks),
+            jumps: mem::take(&mut self.jumps),
+            folds: mem::take(&mut self.folds),
+        };
+
+        let buffer = mem::take(&mut self.buffers[index]);
+
+        self.document = buffer.document;
+        self.cursor_position = buffer.cursor_position;
+        self.offset = buffer.offset;
+        self.selection_anchor = buffer.selection_anchor;
+        self.swap = buffer.swap;
+        self.swap_pending = buffer.swap_pending;
+        self.marks = buffer.marks;
+        self.jumps = buffer.jumps;
+        self.folds = buffer.folds;
+        self.alternate = self.active;
+        self.active = index;
+    }
+
+    /// Asks a yes or no question in the message bar and waits for a single
+    /// key: `y` answers yes, `n` or `Esc` answers no, other keys are ignored.
+    fn confirm(&mut self, message: &str) -> bool {
+        self.status_message = StatusMessage::from(format!("{message} (y/n)"));
+
+        let answer = loop {
+            if self.refresh_screen().is_err() {
+                break false;
+            }
+
+            match self.read_key() {
+                Ok(Key::Char('y' | 'Y')) => break true,
+                Ok(Key::Char('n' | 'N') | Key::Esc) | Err(_) => break false,
+                Ok(_) => (),
+            }
+        };
+
+        self.status_message = StatusMessage::from(String::new());
+
+        answer
+    }
+
+    /// Asks before saving over an existing file other than the document's.
+    fn confirm_overwrite(&mut self, filename: &str) -> bool {
+        self.document.filename.as_deref() == Some(filename)
+            || !Path::new(filename).exists()
+            || self.confirm(&format!("Overwrite {filename}?"))
+    }
+
+    /// Asks for the letter naming a mark.
+    fn read_mark(&mut self, prompt: &str) -> Option<char> {
+        self.status_message = StatusMessage::from(prompt.to_owned());
+        self.refresh_screen().ok()?;
+
+        match self.read_key().ok()? {
+            Key::Char(c) if c.is_ascii_alphabetic() => Some(c),
+            Key::Esc => {
+                self.status_message = StatusMessage::from(String::new());
+                None
+            }
+            _ => {
+                self.status_message = StatusMessage::from("Marks are named by letters.".to_owned());
+                None
+            }
+        }
+    }
+
+    fn set_mark(&mut self) {
+        if let Some(name) = self.read_mark("Set mark: ") {
+            self.marks.insert(name, self.cursor_position.clone());
+            self.status_message = StatusMessage::from(format!("Mark '{name}' set."));
+        }
+    }
+
+    /// Starts recording the keys pressed into a register, or stops and
+    /// keeps them when already recording.
+    fn record_macro(&mut self) {
+        if let Some(mut recording) = self.recording.take() {
+            let register = recording.register;
+
+            recording.events.truncate(recording.command_start);
+            self.macros.insert(register, recording.events);
+            self.status_message = StatusMessage::from(format!("Recorded macro '{register}'."));
+        } else if let Some(register) = self.read_mark("Record macro into: ") {
+            self.recording = Some(Recording {
+                register,
+                events: Vec::new(),
+                command_start: 0,
+            });
+            self.status_message = StatusMessage::from(format!("Recording macro '{register}'."));
+        }
+    }
+
+    /// Replays the keys of a recorded macro `count` times, before the
+    /// ones of any macro it was replayed from. A macro replaying itself,
+    /// directly or through another one, stops instead of going on forever.
+    fn replay_macro(&mut self, count: usize) {
+        let Some(register) = self.read_mark("Replay macro: ") else {
+            return;
+        };
+
+        if self
+            .replaying
+            .iter()
+            .any(|&(replaying, _)| replaying == register)
+        {
+            self.stop_replay();
+            self.status_message =
+                StatusMessage::from(format!("Macro '{register}' cannot replay itself."));
+            return;
+        }
+
+        let Some(events) = self.macros.get(&register) else {
+            self.status_message = StatusMessage::from(format!("Macro '{register}' is empty."));
+            return;
+        };
+        let events: Vec<Event> = (0..count).flat_map(|_| events.iter().cloned()).collect();
+
+        self.status_message = StatusMessage::from(String::new());
+        self.replaying.push((register, events.len()));
+
+        for event in events.into_iter().rev() {
+            self.replay.push_front(event);
+        }
+    }
+
+    fn jump_to_mark(&mut self) {
+        let Some(name) = self.read_mark("Jump to mark: ") else {
+            return;
+        };
+
+        match self.marks.get(&name) {
+            Some(position) => {
+                self.jumps.push(self.cursor_position.clone());
+                self.cursor_position = Position {
+                    x: position.x,
+                    y: cmp::min(position.y, self.document.len().saturating_sub(1)),
+                };
+                self.move_cursor(Key::Null);
+                self.scroll();
+                self.status_message = StatusMessage::from(String::new());
+            }
+            None => {
+                self.status_message = StatusMessage::from(format!("Mark '{name}' is not set."));
+            }
+        }
+    }
+
+    fn walk_jumps(&mut self, position: Option<Position>, end: &str) {
+        match position {
+            Some(position) => {
+                self.cursor_position = position;
+                self.move_cursor(Key::Null);
+                self.scroll();
+            }
+            None => {
+                self.status_message =
+                    StatusMessage::from(format!("Already at the {end} position."));
+            }
+        }
+    }
+
+    /// Moves what points into the document along with its text after rows
+    /// were added or removed: the marks, the folds, and the cursor and the
+    /// top line of the other pane when it shows the same buffer. Folds that
+    /// rows were added to or removed from are unfolded.
+    fn follow_line_shifts(&mut self) {
+        for shift in self.document.take_line_shifts() {
+            for mark in self.marks.values_mut() {
+                mark.y = shift.line(mark.y);
+            }
+
+            shift_folds(&mut self.folds, &shift);
+
+            if let Some(split) = self
+                .split
+                .as_mut()
+                .filter(|split| split.buffer == self.active)
+            {
+                split.cursor_position.y = shift.line(split.cursor_position.y);
+                split.offset.y = shift.line(split.offset.y);
+                shift_folds(&mut split.folds, &shift);
+            }
+        }
+    }
+
+    fn visible_line(&self, y: usize) -> usize {
+        visible_line(&self.folds, y)
+    }
+
+    fn line_at(&self, visible: usize) -> usize {
+        line_at(&self.folds, visible)
+    }
+
+    fn previous_line(&self, y: usize) -> usize {
+        let y = y.saturating_sub(1);
+
+        self.folds
+            .iter()
+            .find(|(first, last)| *first < y && y <= *last)
+            .map_or(y, |(first, _)| *first)
+    }
+
+    fn next_line(&self, y: usize) -> usize {
+        self.folds
+            .iter()
+            .find(|(first, _)| *first == y)
+            .map_or(y, |(_, last)| *last)
+            .saturating_add(1)
+    }
+
+    /// Folds the region starting on the cursor line, or else the closest
+    /// one around it, or unfolds the cursor line if it is folded.
+    fn toggle_fold(&mut self) {
+        let y = self.cursor_position.y;
+
+        if let Some(index) = self.folds.iter().position(|(first, _)| *first == y) {
+            self.folds.remove(index);
+            return;
+        }
+
+        if self.document.is_lazy() {
+            self.status_message =
+                StatusMessage::from("The file is too large to be folded.".to_owned());
+            return;
+        }
+
+        let region = (0..=y).rev().find_map(|first| {
+            self.document
+                .fold_range(first)
+                .filter(|last| *last >= y)
+                .map(|last| (first, last))
+        });
+        let Some((first, last)) = region else {
+            self.status_message = StatusMessage::from("Nothing to fold here.".to_owned());
+            return;
+        };
+
+        self.folds
+            .retain(|(inner, _)| *inner < first || *inner > last);
+
+        let index = self.folds.partition_point(|(other, _)| *other < first);
+
+        self.folds.insert(index, (first, last));
+        self.cursor_position = Position { x: 0, y: first };
+    }
+
+    /// Folds every outermost region of the document.
+    fn fold_all(&mut self) {
+        if self.document.is_lazy() {
+            self.status_message =
+                StatusMessage::from("The file is too large to be folded.".to_owned());
+            return;
+        }
+
+        self.folds = self.document.fold_regions();
+
+        let line = self.cursor_position.y;
+
+        if let Some((first, _)) = self
+            .folds
+            .iter()
+            .find(|(first, last)| *first < line && line <= *last)
+        {
+            self.cursor_position = Position { x: 0, y: *first };
+        }
+
+        self.status_message = StatusMessage::from(format!("Folded {} regions.", self.folds.len()));
+    }
+
+    /// Draws a screen row past the end of the buffer `width` columns wide:
+    /// the `empty_line` placeholder, dimmed, and `text` centered if given.
+    fn empty_line(&self, text: Option<&str>, width: usize) -> String {
+        let marker: String = self.config.empty_line.chars().take(width).collect();
+        let text = text.map_or_else(String::new, |text| {
+            centered(text, width, marker.chars().count())
+        });
+
+        if marker.is_empty() {
+            return text;
+        }
+
+        format!(
+            "{}{marker}{}{text}",
+            color::Fg(self.theme.empty_line),
+            color::Fg(color::Reset)
+        )
+    }
+
+    /// Returns what is drawn after the first line of a fold: how many lines
+    /// it hides, cut to the room left in the view.
+    fn fold_marker(&self, folds: &[(usize, usize)], index: usize, room: usize) -> String {
+        let Some((first, last)) = folds.iter().find(|(first, _)| *first == index) else {
+            return String::new();
+        };
+        let symbol = if self.config.ascii_only {
+            "+"
+        } else {
+            "\u{2295}"
+        };
+        let marker = format!(" {symbol} {} lines", last - first);
+
+        format!(
+            "{}{}{}",
+            color::Fg(self.theme.line_number),
+            marker.chars().take(room).collect::<String>(),
+            color::Fg(color::Reset)
+        )
+    }
+
+    fn cycle_buffer(&mut self, direction: SearchDirection) {
+        let len = self.buffers.len();
+        let index = match direction {
+            SearchDirection::Forward => self.active + 1,
+            SearchDirection::Backward => self.active + len - 1,
+        };
+
+        self.switch_buffer(index % len);
+    }
+
+    fn buffer_document(&self, index: usize) -> &Document {
+        if index == self.active {
+            &self.document
+        } else {
+            &self.buffers[index].document
+        }
+    }
+
+    /// Returns the file name of the buffer at `index` without its directory.
+    fn buffer_name(&self, index: usize) -> String {
+        self.buffer_document(index)
+            .filename
+            .as_deref()
+            .map(Path::new)
+            .and_then(Path::file_name)
+            .map_or_else(
+                || "[No Name]".to_owned(),
+                |name| name.to_string_lossy().into_owned(),
+            )
+    }
+
+    fn has_unsaved_changes(&self) -> bool {
+        (0..self.buffers.len()).any(|index| self.buffer_document(index).is_changed())
+    }
+
+    /// Gives the document a new name, so that it is saved there from now on.
+    fn rename(&mut self, filename: &str) {
+        self.discard_swap();
+        self.swap = Some(SwapFile::for_file(filename));
+        self.document.filename = Some(filename.to_owned());
+        self.document.load_editor_config();
+    }
+
+    /// Moves the cursor to the start of the 1-based `line`.
+    fn goto_line(&mut self, line: usize) {
+        self.jumps.push(self.cursor_position.clone());
+        self.cursor_position = Position {
+            x: 0,
+            y: cmp::min(line, self.document.len()).saturating_sub(1),
+        };
+    }
+
+    /// Moves the cursor to the line `percent` of the way through the
+    /// document, as a percentage between 0 and 100, and centers it.
+    fn goto_percent(&mut self, percent: i64) {
+        let percent = usize::try_from(percent.clamp(0, 100)).unwrap_or_default();
+        let line = self.document.len().saturating_mul(percent).div_ceil(100);
+
+        self.goto_line(line);
+        self.scroll();
+        self.center_view();
+    }
+
+    /// Describes the first unbalanced bracket in code whose brackets should
+    /// come in pairs.
+    fn bracket_warning(&mut self) -> Option<String> {
+        if !self.document.balanced_brackets() {
+            return None;
+        }
+
+        self.document
+            .highlight(&self.highlight_term(), self.dictionary.as_ref(), None)
+            .ok()?;
+
+        let (position, bracket) = self.document.unbalanced_bracket()?;
+
+        Some(format!(
+            "Unbalanced {bracket} on line {}.",
+            position.y.saturating_add(1)
+        ))
+    }
+
+    fn save(&mut self) {
+        if self.document.read_only {
+            self.status_message = StatusMessage::from("Buffer is read-only.".to_owned());
+            self.ring_bell();
+            return;
+        }
+
+        self.write_buffer();
+    }
+
+    /// Saves the buffer even when it is read-only, as `:w <file>` does once
+    /// it renamed it.
+    fn write_buffer(&mut self) {
+        if self.document.is_lazy() {
+            self.status_message =
+                StatusMessage::from(format!("Error writing file: {}.", SaveError::TooLarge));
+            return;
+        }
+
+        if self.document.filename.is_none() {
+            let new_name = self
+                .prompt_path("Save as: ")
+                .unwrap_or_default()
+                .filter(|name| self.confirm_overwrite(name));
+
+            if new_name.is_none() {
+                self.status_message = StatusMessage::from("Save aborted.".to_owned());
+                return;
+            }
+            self.swap = new_name.as_deref().map(SwapFile::for_file);
+            self.document.filename = new_name;
+            self.document.load_editor_config();
+        }
+
+        if self.document.changed_on_disk() && !self.overwrite_changed_file() {
+            return;
+        }
+
+        let filename = self.document.filename.clone().unwrap_or_default();
+
+        self.fire(&HookEvent::BeforeSave(&filename));
+
+        if self.config.strip_bom {
+            self.remove_bom();
+        }
+
+        if self.trim_on_save() {
+            self.trim_trailing_whitespace();
+        }
+
+        let warning = self.format_on_save();
+
+        let backup = match self.document.backup() {
+            Ok(backup) => backup,
+            // The backup is written next to the file, which is likely
+            // not writable either.
+            Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+                self.save_elevated(&filename, warning);
+                return;
+            }
+            Err(err) => {
+                self.status_message =
+                    StatusMessage::from(format!("Backup failed, file not saved: {err}"));
+                return;
+            }
+        };
+
+        let mut saved = self.document.save(self.insert_final_newline());
+
+        if let Err(SaveError::MissingDirectory(directory)) = &saved {
+            if !self.confirm(&format!("Directory {directory} does not exist, create it?")) {
+                self.status_message = StatusMessage::from("Save aborted.".to_owned());
+                return;
+            }
+
+            saved = std::fs::create_dir_all(directory)
+                .map_err(|err| SaveError::from_io(err, directory))
+                .and_then(|()| self.document.save(self.insert_final_newline()));
+        }
+
+        match saved {
+            Ok(()) => {
+                let message = match (backup, warning) {
+                    (_, Some(warning)) => format!("File saved unformatted, {warning}"),
+                    (Some(backup), None) => {
+                        format!("File saved successfully, backup written to {backup}.")
+                    }
+                    (None, None) => "File saved successfully.".to_owned(),
+                };
+
+                self.finish_save(&filename, message);
+            }
+            Err(SaveError::PermissionDenied(_)) => self.save_elevated(&filename, warning),
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("Error writing file: {err}."));
+            }
+        }
+    }
+
+    fn finish_save(&mut self, filename: &str, message: String) {
+        self.discard_swap();
+        self.reload_committed();
+        self.status_message = StatusMessage::from(message);
+        self.fire(&HookEvent::AfterSave(filename));
+
+        if let Some(warning) = self.bracket_warning() {
+            self.status_message.text = format!("{} {warning}", self.status_message.text);
+        }
+    }
+
+    /// Offers to write a file the user may not write with `sudo`: the text
+    /// goes to a private temporary file that `sudo -n cp` copies over it.
+    /// Unless `sudo` remembers the user, the password is checked first with
+    /// `sudo -S -v`, so nothing is written unless `sudo` lets the user in.
+    fn save_elevated(&mut self, filename: &str, warning: Option<String>) {
+        let elevate = self.elevate.clone();
+
+        if !self.confirm(&format!(
+            "Permission denied, save {filename} with {elevate}?"
+        )) {
+            self.status_message = StatusMessage::from("Save aborted.".to_owned());
+            return;
+        }
+
+        let bytes = match self.document.to_bytes(self.insert_final_newline()) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("Cannot save: {err}"));
+                return;
+            }
+        };
+
+        if !run_with_input(Command::new(&elevate).args(["-n", "true"]), &[])
+            .is_ok_and(|status| status.success())
+        {
+            let Some(password) = self.prompt_secret(&format!("Password for {elevate}: ")) else {
+                self.status_message = StatusMessage::from("Save aborted.".to_owned());
+                return;
+            };
+            let validated = run_with_input(
+                Command::new(&elevate).args(["-S", "-p", "", "-v"]),
+                format!("{password}\n").as_bytes(),
+            );
+
+            if !validated.is_ok_and(|status| status.success()) {
+                self.status_message =
+                    StatusMessage::from(format!("{elevate} refused, file not saved."));
+                return;
+            }
+        }
+
+        let copy = match write_private_copy(&bytes) {
+            Ok(copy) => copy,
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("Cannot save: {err}"));
+                return;
+            }
+        };
+        let copied = run_with_input(
+            Command::new(&elevate)
+                .args(["-n", "cp", "--"])
+                .arg(&copy)
+                .arg(filename),
+            &[],
+        );
+
+        fs::remove_file(&copy).ok();
+
+        match copied {
+            Ok(status) if status.success() => {
+                self.document.mark_saved(self.insert_final_newline());
+
+                let message = match warning {
+                    Some(warning) => format!("File saved unformatted with {elevate}, {warning}"),
+                    None => format!("File saved with {elevate}."),
+                };
+
+                self.finish_save(filename, message);
+            }
+            Ok(status) => {
+                self.status_message =
+                    StatusMessage::from(format!("{elevate} cp failed with {status}."));
+            }
+            Err(err) => {
+                self.status_message = StatusMessage::from(format!("Cannot run {elevate}: {err}"));
+            }
+        }
+    }
+
+    /// Asks for text that is not shown, such as a password, drawing a `*`
+    /// for each character typed.
+    fn prompt_secret(&mut self, prompt: &str) -> Option<String> {
+        let mut secret = String::new();
+
+        let entered = loop {
+            self.status_message =
+                StatusMessage::from(format!("{prompt}{}", "*".repeat(secret.chars().count())));
+
+            if self.refresh_screen().is_err() {
+                break false;
+            }
+
+            match self.terminal.read_key() {
+                Ok(Key::Char('\n')) => break true,
+                Ok(Key::Char(c)) if !c.is_control() => secret.push(c),
+                Ok(Key::Backspace) => {
+                    secret.pop();
+                }
+                Ok(Key::Esc) | Err(_) => break false,
+                Ok(_) => (),
+            }
+        };
+
+        self.status_message = StatusMessage::from(String::new());
+
+        entered.then_some(secret)
+    }
+
+    /// Runs the document through the formatter configured for its file
+    /// type, returning why it failed if it did.
+    fn format_on_save(&mut self) -> Option<String> {
+        let filename = self.document.filename.as_deref()?;
+        let command = self.config.formatter(filename)?.to_owned();
+
+        if self.document.is_lazy() {
+            return None;
+        }
+
+        self.selection_anchor = None;
+        self.filter_through_command(&command).err()
+    }
+
+    /// Asks what to do with a file something else modified since it was
+    /// read, returning whether to save over it.
+    fn overwrite_changed_file(&mut self) -> bool {
+        let question = format!(
+            "{} changed on disk. (O)verwrite, (R)eload or (C)ancel: ",
+            self.buffer_name(self.active)
+        );
+        let answer = self.prompt(&question, |_, _, _| {}).unwrap_or_default();
+
+        match answer.map(|answer| answer.to_ascii_lowercase()).as_deref() {
+            Some("o") => return true,
+            Some("r") => self.reload(),
+            _ => self.status_message = StatusMessage::from("Save aborted.".to_owned()),
+        }
+
+        false
+    }
+
+    /// Asks before dropping the changes made since the buffer was last read
+    /// or saved.
+    fn revert(&mut self) {
+        let name = self.buffer_name(self.active);
+
+        if self.document.read_only {
+            self.status_message = StatusMessage::from("Buffer is read-only.".to_owned());
+            return;
+        }
+
+        if !self.document.is_changed() {
+            self.status_message = StatusMessage::from(format!("{name} has no changes to revert."));
+            return;
+        }
+
+        if !self.confirm(&format!("Revert {name} to the last save?")) {
+            self.status_message = StatusMessage::from("Revert aborted.".to_owned());
+            return;
+        }
+
+        self.status_message = StatusMessage::from(match self.document.revert() {
+            Ok(()) => format!("Reverted {name}, undo to get your changes back."),
+            Err(err) => format!("Cannot revert {name}: {err}"),
+        });
+        self.selection_anchor = None;
+        self.cursor_position.y = cmp::min(self.cursor_position.y, self.document.len());
+        self.move_cursor(Key::Null);
+    }
+
+    fn reload(&mut self) {
+        let name = self.buffer_name(self.active);
+
+        self.status_message = StatusMessage::from(match self.document.reload() {
+            Ok(()) => format!("Reloaded {name}, undo to get your changes back."),
+            Err(err) => format!("Cannot reload {name}: {err}"),
+        });
+        self.cursor_position.y = cmp::min(self.cursor_position.y, self.document.len());
+        self.move_cursor(Key::Null);
+    }
+
+    /// Tells once about each change made to the file by something else, or
+    /// with `auto_reload` or while following reads the file again when
+    /// there are no unsaved changes to lose.
+    fn warn_disk_change(&mut self) {
+        if !self.document.take_disk_change() {
+            return;
+        }
+
+        if (self.config.auto_reload || self.follow) && !self.document.is_changed() {
+            let name = self.buffer_name(self.active);
+
+            if let Err(err) = self.document.reload() {
+                self.status_message = StatusMessage::from(format!("Cannot reload {name}: {err}"));
+                return;
+            }
+
+            self.reload_committed();
+
+            if self.follow {
+                self.cursor_position = self.document_end();
+            } else {
+                self.cursor_position.y = cmp::min(self.cursor_position.y, self.document.len());
+            }
+
+            self.move_cursor(Key::Null);
+        } else {
+            self.status_message = StatusMessage::from(format!(
+                "WARNING! {} changed on disk since it was read.",
+                self.buffer_name(self.active)
+            ));
+        }
+    }
+
+    /// Starts or stops keeping the cursor on the last line, going there
+    /// when starting.
+    fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+
+        if self.follow {
+            self.cursor_position = self.document_end();
+            self.scroll();
+        }
+
+        self.status_message = StatusMessage::from(
+            if self.follow {
+                "Following the end of the file."
+            } else {
+                "Stopped following the end of the file."
+            }
+            .to_owned(),
+        );
+    }
+
+    fn filter(&mut self) {
+        let target = if self.selection().is_some() {
+            "selection"
+        } else {
+            "document"
+        };
+        let command = self
+            .prompt(&format!("Filter {target} through: "), |_, _, _| {})
+            .unwrap_or_default()
+            .filter(|command| !command.trim().is_empty());
+
+        if let Some(command) = command {
+            if let Err(err) = self.filter_through_command(&command) {
+                self.status_message = StatusMessage::from(err);
+            }
+        }
+    }
+
+    fn search(&mut self) {
+        let old_position = self.cursor_position.clone();
+        let mut direction = SearchDirection::Forward;
+        let mut hint = None;
+        let history = std::mem::take(&mut self.search_history);
+        let query = self
+            .prompt_with_history(
+                "Search (ESC to cancel, Arrows to navigate, Up/Down for history): ",
+                &history,
+                |revise, key, query| {
+                    let before = revise.cursor_position.clone();
+
+                    match key {
+                        Key::Right | Key::Down => {
+                            direction = SearchDirection::Forward;
+
+                            // Past the whole match the cursor is on, so that
+                            // the next one does not overlap it.
+                            let Position { x, y } = revise.cursor_position;
+                            let on_match = revise.document.row(y).is_some_and(|row| {
+                                row.find(query, x, SearchDirection::Forward) == Some(x)
+                            });
+
+                            if on_match && !query.is_empty() {
+                                revise.cursor_position.x = x + query.graphemes(true).count();
+                            } else {
+                                revise.move_cursor(Key::Right);
+                            }
+                        }
+                        Key::Left | Key::Up => direction = SearchDirection::Backward,
+                        // The query changed: look for its first match from
+                        // where the search started, so it does not drift.
+                        _ => {
+                            direction = SearchDirection::Forward;
+                            revise.cursor_position = old_position.clone();
+                        }
+                    }
+
+                    hint = match revise
+                        .document
+                        .find(query, &revise.cursor_position, direction)
+                    {
+                        Ok(Some(found)) => {
+                            revise.count_matches(query, &found.position);
+                            revise.cursor_position = found.position;
+                            revise.scroll();
+
+                            match direction {
+                                _ if !found.wrapped => None,
+                                SearchDirection::Forward => {
+                                    Some("search hit BOTTOM, continuing at TOP".to_owned())
+                                }
+                                SearchDirection::Backward => {
+                                    Some("search hit TOP, continuing at BOTTOM".to_owned())
+                                }
+                            }
+                        }
+                        Err(err) => {
+                            revise.search_matches = None;
+
+                            Some(format!("Cannot read the file: {err}"))
+                        }
+                        Ok(None) => {
+                            revise.search_matches = None;
+
+                            if matches!(key, Key::Right | Key::Down) {
+                                revise.cursor_position = before;
+                            }
+
+                            if !query.is_empty() {
+                                revise.ring_bell();
+                            }
+
+                            (!query.is_empty()).then(|| format!("Pattern not found: {query}"))
+                        }
+                    };
+
+                    if let Some(hint) = &hint {
+                        revise.status_message = StatusMessage::from(hint.clone());
+                    }
+
+                    revise.highlighted_word = Some(query.to_owned());
+                },
+            )
+            .unwrap_or_default();
+
+        self.search_history = history;
+
+        match query {
+            Some(query) => {
+                if self.cursor_position != old_position {
+                    self.jumps.push(old_position);
+                }
+
+                if let Some(hint) = hint {
+                    self.status_message = StatusMessage::from(hint);
+                }
+
+                if self.search_history.last() != Some(&query) {
+                    self.search_history.push(query);
+                }
+            }
+            None => {
+                self.cursor_position = old_position;
+                self.scroll();
+            }
+        }
+
+        self.highlighted_word = None;
+        self.search_matches = None;
+    }
+
+    /// Counts the matches of `query` for the status bar, with the one at
+    /// `at`. Files too large to read at once are not searched through.
+    fn count_matches(&mut self, query: &str, at: &Position) {
+        self.search_matches = (!self.document.is_lazy()).then(|| {
+            let matches = self.document.find_all(query, MAX_MATCH_COUNT);
+
+            (
+                matches.binary_search(at).ok().map(|index| index + 1),
+                matches.len(),
+            )
+        });
+    }
+
+    /// Comments out the current line, or every line of a multi-line
+    /// selection at their common indentation, skipping blank lines. Lines
+    /// that are all commented already are uncommented instead.
+    fn toggle_comment(&mut self) {
+        let Some(prefix) = self.document.comment_prefix() else {
+            self.status_message = StatusMessage::from(format!(
+                "No comment syntax for {}.",
+                self.document.file_type()
+            ));
+            return;
+        };
+        let y = self.cursor_position.y;
+        let selected = self.selected_lines();
+        let lines: Vec<(usize, usize, String)> = selected
+            .clone()
+            .unwrap_or(y..=y)
+            .filter_map(|y| {
+                let row = self.document.row(y)?;
+
+                Some((
+                    y,
+                    row.indentation(),
+                    row.as_string().trim_start().to_owned(),
+                ))
+            })
+            .filter(|(_, _, text)| selected.is_none() || !text.is_empty())
+            .collect();
+        let Some(column) = lines.iter().map(|(_, indentation, _)| *indentation).min() else {
+            return;
+        };
+
+        if lines.iter().all(|(_, _, text)| text.starts_with(prefix)) {
+            for (y, indentation, text) in lines {
+                let at = Position { x: indentation, y };
+                let mut removed = prefix.graphemes(true).count();
+
+                if text[prefix.len()..].starts_with(' ') {
+                    removed += 1;
+                }
+
+                for _ in 0..removed {
+                    self.delete_at(&at);
+                }
+
+                self.adjust_columns(y, |x| {
+                    if x > indentation {
+                        x.saturating_sub(removed).max(indentation)
+                    } else {
+                        x
+                    }
+                });
+            }
+        } else {
+            let inserted = format!("{prefix} ");
+            let len = inserted.graphemes(true).count();
+
+            for (y, _, _) in lines {
+                let at = Position { x: column, y };
+
+                for c in inserted.chars().rev() {
+                    self.insert_at(&at, c);
+                }
+
+                self.adjust_columns(y, |x| if x >= column { x + len } else { x });
+            }
+        }
+    }
+
+    fn indent_unit(&self) -> String {
+        if self.expand_tabs() {
+            " ".repeat(self.tab_width())
+        } else {
+            "\t".to_owned()
+        }
+    }
+
+    /// Returns the lines covered by a selection spanning several lines. A
+    /// selection ending at the start of a line leaves that line out.
+    fn selected_lines(&self) -> Option<RangeInclusive<usize>> {
+        let (start, end) = self.selection()?;
+
+        if start.y == end.y {
+            return None;
+        }
+
+        let last = if end.x == 0 { end.y - 1 } else { end.y };
+
+        Some(start.y..=last)
+    }
+
+    /// Moves the cursor and the selection anchor on line `y` to the column
+    /// returned by `adjust`.
+    fn adjust_columns(&mut self, y: usize, adjust: impl Fn(usize) -> usize) {
+        for position in [
+            self.selection_anchor.as_mut(),
+            Some(&mut self.cursor_position),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if position.y == y {
+                position.x = adjust(position.x);
+            }
+        }
+    }
+
+    /// Indents every line of a multi-line selection by one unit, or removes
+    /// up to one unit of leading whitespace from each when `dedent` is set,
+    /// keeping the selection. Returns `false` if there is no such selection.
+    fn indent_selection(&mut self, dedent: bool) -> bool {
+        let Some(lines) = self.selected_lines() else {
+            return false;
+        };
+        let unit = self.indent_unit();
+
+        for y in lines {
+            let Some(row) = self.document.row(y) else {
+                break;
+            };
+            let at = Position { x: 0, y };
+            let shift: isize = if dedent {
+                let text = row.as_string();
+                let removed = match text.strip_prefix('\t') {
+                    Some(_) => 1,
+                    None => text
+                        .chars()
+                        .take_while(|c| *c == ' ')
+                        .take(self.tab_width())
+                        .count(),
+                };
+
+                for _ in 0..removed {
+                    self.delete_at(&at);
+                }
+
+                -(removed as isize)
+            } else if row.is_empty() {
+                0
+            } else {
+                for c in unit.chars().rev() {
+                    self.insert_at(&at, c);
+                }
+
+                unit.len() as isize
+            };
+
+            self.adjust_columns(y, |x| x.saturating_add_signed(shift));
+        }
+
+        true
+    }
+
+    fn match_bracket(&mut self) {
+        match self.find_bracket_match(usize::MAX) {
+            Some((_, Some(position))) => {
+                self.cursor_position = position;
+                self.scroll();
+            }
+            Some((_, None)) => {
+                self.status_message = StatusMessage::from("No matching bracket found.".to_owned());
+                self.ring_bell();
+            }
+            None => {
+                self.status_message = StatusMessage::from("Cursor is not on a bracket.".to_owned())
+            }
+        }
+    }
+
+    /// Selects the next larger unit of text around the selection, or the
+    /// cursor: a word, the contents of a string or brackets, the string or
+    /// brackets, the whole lines and the whole document.
+    fn expand_selection(&mut self) {
+        let selection = self
+            .selection()
+            .unwrap_or_else(|| (self.cursor_position.clone(), self.cursor_position.clone()));
+
+        if self.expansions.last().map(|expansion| &expansion.selection) != self.selection().as_ref()
+        {
+            self.expansions.clear();
+        }
+
+        // Without the word under the cursor marked, which hides strings.
+        self.document
+            .highlight(&None, self.dictionary.as_ref(), None)
+            .ok();
+
+        let Some((start, end)) = self.document.enclosing_unit(&selection.0, &selection.1) else {
+            self.status_message = StatusMessage::from("Nothing larger to select.".to_owned());
+            return;
+        };
+
+        self.expansions.push(Expansion {
+            anchor: self.selection_anchor.clone(),
+            cursor: self.cursor_position.clone(),
+            selection: (start.clone(), end.clone()),
+        });
+        self.selection_anchor = Some(start);
+        self.cursor_position = end;
+        self.scroll();
+    }
+
+    /// Goes back to the selection the last expansion started from.
+    fn shrink_selection(&mut self) {
+        let expanded = self
+            .expansions
+            .last()
+            .is_some_and(|expansion| Some(&expansion.selection) == self.selection().as_ref());
+
+        if !expanded {
+            self.expansions.clear();
+            self.status_message = StatusMessage::from("Selection was not expanded.".to_owned());
+            return;
+        }
+
+        if let Some(expansion) = self.expansions.pop() {
+            self.selection_anchor = expansion.anchor;
+            self.cursor_position = expansion.cursor;
+            self.scroll();
+        }
+    }
+
+    /// Returns the bracket under the cursor and its match, if any, looking
+    /// over at most `rows` rows for it. Gives `None` when that is not enough
+    /// to tell whether the bracket is matched.
+    fn find_bracket_match(&self, rows: usize) -> Option<(Position, Option<Position>)> {
+        self.document
+            .row(self.cursor_position.y)
+            .and_then(|row| row.grapheme(self.cursor_position.x))
+            .and_then(bracket_pair)?;
+
+        let matching = self
+            .document
+            .matching_bracket_within(&self.cursor_position, rows)?;
+
+        Some((self.cursor_position.clone(), matching))
+    }
+
+    fn insert_at(&mut self, at: &Position, c: char) {
+        if let Err(err) = self.document.insert(at, c) {
+            self.status_message = StatusMessage::from(format!("Failed to insert content: {err}"));
+        }
+    }
+
+    fn delete_at(&mut self, at: &Position) {
+        if let Err(err) = self.document.delete(at) {
+            self.status_message = StatusMessage::from(format!("Failed to remove content: {err}"));
+        }
+    }
+
+    /// Closes the pending undo step, remembering the selection with the
+    /// cursor position.
+    fn checkpoint(&mut self) {
+        self.document
+            .checkpoint_selection(&self.cursor_position, self.selection_anchor.as_ref());
+    }
+
+    fn undo(&mut self) {
+        self.checkpoint();
+
+        match self.document.undo(&self.cursor_position) {
+            Some((position, anchor)) => {
+                self.cursor_position = position;
+                self.selection_anchor = anchor;
+                self.move_cursor(Key::Null);
+            }
+            None => self.status_message = StatusMessage::from("Nothing to undo.".to_owned()),
+        }
+    }
+
+    fn redo(&mut self) {
+        self.checkpoint();
+
+        match self.document.redo(&self.cursor_position) {
+            Some((position, anchor)) => {
+                self.cursor_position = position;
+                self.selection_anchor = anchor;
+                self.move_cursor(Key::Null);
+            }
+            None => self.status_message = StatusMessage::from("Nothing to redo.".to_owned()),
+        }
+    }
+
+    /// Copies the selection, or the cursor line without one. A selection of
+    /// the whole buffer is copied as the file is saved, final line break
+    /// included.
+    fn copy_content(&mut self) -> Result<(), Box<dyn Err>> {
+        if let Some((start, end)) = self.selection() {
+            let last = self.document.len().saturating_sub(1);
+            let whole = start == Position::default()
+                && (end.y > last
+                    || (end.y == last && end.x >= self.document.row(last).map_or(0, Row::len)));
+            let text = if whole {
+                self.document.to_string()
+            } else {
+                self.document.text(&start, &end)
+            };
+
+            return self.set_clipboard(text, false);
+        }
+
+        let row = self.document.row(self.cursor_position.y);
+
+        match row {
+            Some(v) => self.set_clipboard(format!("{}\n", v.as_string()), true),
+            None => Err(Box::new(CopyError)),
+        }
+    }
+
+    /// Copies `text` into the register, and into the system clipboard or
+    /// through the terminal with OSC 52. `lines` tells whether it holds
+    /// whole lines.
+    fn set_clipboard(&mut self, text: String, lines: bool) -> Result<(), Box<dyn Err>> {
+        if self.config.osc52() {
+            self.terminal.copy_to_terminal(&text);
+        }
+
+        if lines {
+            self.clipboard.set_lines(text)
+        } else {
+            self.clipboard.set_contents(text)
+        }
+    }
+
+    /// Uses the system clipboard as configured, unless copying through the
+    /// terminal instead.
+    fn update_clipboard(&mut self) {
+        self.clipboard
+            .use_system(self.config.system_clipboard && !self.config.osc52());
+    }
+
+    /// Names the file being edited in the title of the terminal window,
+    /// when it changed since it was last set.
+    fn update_title(&mut self) {
+        if !self.config.title {
+            return;
+        }
+
+        let name = self
+            .document
+            .filename
+            .as_deref()
+            .map_or("[No Name]", |filename| {
+                Path::new(filename)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or(filename)
+            });
+        // Control characters in a file name would end the escape sequence.
+        let title: String = format!("{name} - Revise")
+            .chars()
+            .filter(|c| !c.is_control())
+            .collect();
+
+        if self.title.as_ref() != Some(&title) {
+            self.terminal.set_title(&title);
+            self.title = Some(title);
+        }
+    }
+
+    /// Draws with the colors `color_support` allows, redrawing the screen
+    /// in them.
+    fn update_colors(&mut self) {
+        self.terminal.set_color_support(self.config.color_support());
+        self.clear_screen();
+    }
+
+    /// Loads the dictionary when spell checking is turned on and drops it
+    /// when it is turned off. Spell checking is turned off again if there
+    /// is no word list to check against.
+    fn update_dictionary(&mut self) {
+        if !self.config.spell_check {
+            self.dictionary = None;
+        } else if self.dictionary.is_none() {
+            match Dictionary::load() {
+                Ok(dictionary) => self.dictionary = Some(dictionary),
+                Err(err) => {
+                    self.config.spell_check = false;
+                    self.status_message =
+                        StatusMessage::from(format!("Cannot check spelling: {err}."));
+                }
+            }
+        }
+    }
+
+    /// Adds `word`, or the word under the cursor if empty, to the personal
+    /// dictionary so that it is no longer marked as misspelled.
+    fn add_word(&mut self, word: &str) {
+        let word = match word {
+            "" => self.word_under_cursor().map(str::to_owned),
+            _ => Some(word.to_owned()),
+        };
+        let Some(word) = word else {
+            self.status_message = StatusMessage::from("No word to add.".to_owned());
+
+            return;
+        };
+        let Some(dictionary) = &mut self.dictionary else {
+            self.status_message = StatusMessage::from(
+                "Spell checking is off, turn it on with set spell_check.".to_owned(),
+            );
+
+            return;
+        };
+
+        self.status_message = StatusMessage::from(match dictionary.add(&word) {
+            Ok(()) => format!("Added {word} to the dictionary."),
+            Err(err) => format!("Cannot add {word} to the dictionary: {err}."),
+        });
+        self.document.recheck_spelling();
+
+        for buffer in &mut self.buffers {
+            buffer.document.recheck_spelling();
+        }
+    }
+
+    /// Tells that copied text stays within the editor when the system
+    /// clipboard is wanted but cannot be reached, e.g. over SSH.
+    fn report_local_clipboard(&mut self) {
+        if self.config.system_clipboard && !self.config.osc52() && !self.clipboard.has_system() {
+            self.status_message = StatusMessage::from(
+                "No system clipboard available, copied within the editor only.".to_owned(),
+            );
+        }
+    }
+
+    /// Copies `count` lines from the cursor line on as whole lines.
+    fn yank_lines(&mut self, count: usize) {
+        let y = self.cursor_position.y;
+        let lines: String = (y..y.saturating_add(count))
+            .filter_map(|y| self.document.row(y))
+            .map(|row| format!("{}\n", row.as_string()))
+            .collect();
+
+        if let Err(err) = self.set_clipboard(lines, true) {
+            self.status_message = StatusMessage::from(format!("{err}"));
+        }
+    }
+
+    fn cut_content(&mut self) -> Result<(), Box<dyn Err>> {
+        self.copy_content()?;
+
+        if let Some((start, end)) = self.selection() {
+            self.document.delete_range(&start, &end);
+            self.cursor_position = start;
+        } else {
+            self.document.remove_row(self.cursor_position.y);
+            self.move_cursor(Key::Null);
+        }
+
+        self.selection_anchor = None;
+
+        Ok(())
+    }
+
+    /// Inserts the clipboard at the cursor or, if it holds whole lines,
+    /// above the cursor line, or below it when `below` is set.
+    fn paste(&mut self, below: bool) {
+        let content = self.paste_content();
+        let lines = self.clipboard.is_lines(&content);
+
+        self.insert_clipboard(&content, lines, below);
+    }
+
+    /// Inserts `content` as `paste` does, as whole lines if `lines` is set.
+    fn insert_clipboard(&mut self, content: &str, lines: bool, below: bool) {
+        let at = match (lines, below) {
+            (true, false) => Position {
+                x: 0,
+                y: self.cursor_position.y,
+            },
+            (true, true) => Position {
+                x: 0,
+                y: cmp::min(self.cursor_position.y + 1, self.document.len()),
+            },
+            (false, _) => self.cursor_position.clone(),
+        };
+
+        for c in content.chars().rev() {
+            if let Err(err) = self.document.insert(&at, c) {
+                self.status_message =
+                    StatusMessage::from(format!("Failed to paste content: {err}"));
+            }
+        }
+
+        if lines {
+            self.cursor_position = if below {
+                at
+            } else {
+                Position {
+                    x: self.cursor_position.x,
+                    y: at.y + content.matches('\n').count(),
+                }
+            };
+            self.move_cursor(Key::Null);
+        }
+    }
+
+    /// Pastes like `paste`, shifting the lines pasted to the indentation of
+    /// the cursor line while keeping their indentation relative to each
+    /// other. A line pasted after text on the cursor line is left as it is.
+    fn paste_indented(&mut self) {
+        let content = self.paste_content();
+        let lines = self.clipboard.is_lines(&content);
+
+        if !content.contains('\n') {
+            self.insert_clipboard(&content, lines, false);
+            return;
+        }
+
+        let Position { x, y } = self.cursor_position;
+        let (indentation, after_text) = self.document.row(y).map_or_else(Default::default, |row| {
+            let string = row.as_string();
+            let text = string.trim_start_matches([' ', '\t']);
+
+            (
+                string[..string.len() - text.len()].to_owned(),
+                !row.slice(0, x).trim().is_empty(),
+            )
+        });
+        let mut reindented = reindent(&content, &indentation, self.tab_width(), self.expand_tabs());
+
+        // The first line goes at the cursor, which the indentation is before.
+        if !lines {
+            let (_, rest) = reindented.split_once('\n').unwrap_or_default();
+
+            reindented = if after_text {
+                format!("{}\n{rest}", content.split('\n').next().unwrap_or_default())
+            } else {
+                reindented
+                    .strip_prefix(indentation.as_str())
+                    .unwrap_or(&reindented)
+                    .to_owned()
+            };
+        }
+
+        self.insert_clipboard(&reindented, lines, false);
+    }
+
+    fn paste_content(&mut self) -> String {
+        let mut content = self.clipboard.get_contents().replace("\r\n", "\n");
+
+        if content.is_empty() {
+            content = String::from(" ");
+            self.cursor_position.y = self.cursor_position.y.saturating_add(1);
+        }
+
+        content
+    }
+}
+
+/// Runs `command` quietly, writing `input` to it. A command that stops
+/// reading early is killed rather than left running.
+fn run_with_input(command: &mut Command, input: &[u8]) -> Result<ExitStatus, IOError> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(err) = stdin.write_all(input) {
+            child.kill().ok();
+            child.wait().ok();
+            return Err(err);
+        }
+    }
+
+    child.wait()
+}
+
+/// Writes `bytes` to a new file in the temporary directory only the user
+/// can read, for a save with `ELEVATE` to copy over the real one.
+fn write_private_copy(bytes: &[u8]) -> Result<PathBuf, IOError> {
+    let (path, mut file) = temp::create_private("revise-save")?;
+
+    if let Err(err) = file.write_all(bytes) {
+        fs::remove_file(&path).ok();
+        return Err(err);
+    }
+
+    Ok(path)
+}
+
+/// Runs `command` with the shell on `input` and returns what it printed,
+/// or the first line of its error output if it failed.
+fn run_filter(command: &str, input: String) -> Result<String, String> {
+    let cannot_run = |err: IOError| format!("Cannot run {command}: {err}");
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(cannot_run)?;
+    let stdin = child.stdin.take();
+    // Written from another thread, so that a command printing a lot before
+    // it has read all of its input does not block on a full pipe.
+    let writer = thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            stdin.write_all(input.as_bytes()).ok();
+        }
+    });
+    let output = child.wait_with_output().map_err(cannot_run)?;
+
+    writer.join().ok();
+
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+
+        return Err(match errors.lines().find(|line| !line.trim().is_empty()) {
+            Some(error) => format!("{command} failed: {error}"),
+            None => format!("{command} failed with {}", output.status),
+        });
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| format!("{command} printed invalid UTF-8"))
+}
+
+/// A reference to a place in a file, as compilers print them.
+#[derive(Debug, PartialEq, Eq)]
+struct FileReference {
+    path: String,
+    line: usize,
+    column: Option<usize>,
+}
+
+/// Parses `path:line` or `path:line:column` from the word of `line` at
+/// the grapheme index `at`, or from the first word that is one when the
+/// word there is not.
+fn file_reference(line: &str, at: usize) -> Option<FileReference> {
+    let mut words: Vec<(usize, String)> = Vec::new();
+    let mut previous_blank = true;
+
+    for (index, grapheme) in line.graphemes(true).enumerate() {
+        let blank = grapheme.chars().all(char::is_whitespace);
+
+        if !blank && previous_blank {
+            words.push((index, String::new()));
+        }
+
+        if let Some((_, word)) = words.last_mut().filter(|_| !blank) {
+            word.push_str(grapheme);
+        }
+
+        previous_blank = blank;
+    }
+
+    let parse = |word: &str| {
+        let word = word
+            .trim_matches(|c: char| "\"'`()[]{}<>,;".contains(c))
+            .trim_end_matches([':', '.']);
+        let mut parts = word.splitn(3, ':');
+        let path = parts.next().filter(|path| !path.is_empty())?;
+        let line = parts.next()?.parse().ok().filter(|line| *line > 0)?;
+        let column = parts.next().and_then(|column| column.parse().ok());
+
+        Some(FileReference {
+            path: path.to_owned(),
+            line,
+            column,
+        })
+    };
+
+    words
+        .iter()
+        .find(|(start, word)| (*start..=start + word.graphemes(true).count()).contains(&at))
+        .and_then(|(_, word)| parse(word))
+        .or_else(|| words.iter().find_map(|(_, word)| parse(word)))
+}
+
+/// Returns the index of line `y` among the lines not hidden by `folds`.
+fn visible_line(folds: &[(usize, usize)], y: usize) -> usize {
+    let hidden: usize = folds
+        .iter()
+        .map(|(first, last)| cmp::min(last.saturating_add(1), y).saturating_sub(first + 1))
+        .sum();
+
+    y - hidden
+}
+
+/// Returns the line shown at index `visible` among the lines not hidden
+/// by `folds`.
+fn line_at(folds: &[(usize, usize)], visible: usize) -> usize {
+    let mut y = visible;
+
+    for (first, last) in folds {
+        if *first >= y {
+            break;
+        }
+
+        y += last - first;
+    }
+
+    y
+}
+
+/// Moves `folds` along with the text after `shift`, unfolding the ones
+/// that rows were added to or removed from.
+fn shift_folds(folds: &mut Vec<(usize, usize)>, shift: &LineShift) {
+    folds.retain(|(first, last)| shift.at > *last || shift.at + shift.removed <= *first);
+
+    for (first, last) in folds {
+        *first = shift.line(*first);
+        *last = shift.line(*last);
+    }
+}
+
+/// Draws `text` in the middle of an empty line `width` columns wide, after
+/// a placeholder `marker` columns wide, cut at the right edge when it does
+/// not fit.
+fn centered(text: &str, width: usize, marker: usize) -> String {
+    let padding = width.saturating_sub(text_width(text)) / 2;
+    let spaces = " ".repeat(padding.saturating_sub(marker));
+
+    take_width(&format!("{spaces}{text}"), width.saturating_sub(marker)).to_owned()
+}
+
+/// Shortens `text` to `width` columns for a single line. The longest
+/// path in it loses its start first, since its end names the file, and
+/// what is still too long is cut at the end. What was left out is marked
+/// with `ellipsis`.
+fn fit_width(text: &str, width: usize, ellipsis: &str) -> String {
+    let len = text_width(text);
+
+    if len <= width {
+        return text.to_owned();
+    }
+
+    let excess = len - width + text_width(ellipsis);
+    let path = text
+        .split_whitespace()
+        .filter(|word| word.contains('/'))
+        .max_by_key(|word| text_width(word))
+        .filter(|path| text_width(path) > excess);
+
+    if let Some((start, path)) = path.and_then(|path| Some((text.find(path)?, path))) {
+        let kept = skip_width(path, excess);
+
+        return format!(
+            "{}{ellipsis}{kept}{}",
+            &text[..start],
+            &text[start + path.len()..]
+        );
+    }
+
+    let kept = take_width(text, width.saturating_sub(text_width(ellipsis)));
+
+    take_width(&format!("{kept}{ellipsis}"), width).to_owned()
+}
+
+/// Shortens `path` to `width` characters by leaving out the directories
+/// between its first one and the file name, e.g. `src/…/application.rs`,
+/// keeping as many of the last ones as fit. The first directory goes next
+/// and the start of the file name last.
+fn abbreviate_path(path: &str, width: usize, ellipsis: &str) -> String {
+    if text_width(path) <= width {
+        return path.to_owned();
+    }
+
+    let Some((directory, name)) = path.rsplit_once('/') else {
+        let skip = (text_width(path) + text_width(ellipsis)).saturating_sub(width);
+
+        return take_width(&format!("{ellipsis}{}", skip_width(path, skip)), width).to_owned();
+    };
+    let root = usize::from(directory.starts_with('/'));
+    let head = match directory[root..].find('/') {
+        Some(end) => &directory[..=root + end],
+        None => "",
+    };
+
+    for (index, _) in directory[head.len()..].match_indices('/') {
+        let tail = &path[head.len() + index + 1..];
+
+        if text_width(head) + text_width(ellipsis) + 1 + text_width(tail) <= width {
+            return format!("{head}{ellipsis}/{tail}");
+        }
+    }
+
+    if text_width(head) + text_width(ellipsis) + 1 + text_width(name) <= width {
+        format!("{head}{ellipsis}/{name}")
+    } else if text_width(ellipsis) + 1 + text_width(name) <= width {
+        format!("{ellipsis}/{name}")
+    } else {
+        abbreviate_path(name, width, ellipsis)
+    }
+}
+
+/// Leaves the screen clean with the cursor visible however the editor
+/// exits. After a panic the hook installed by `Terminal` has already
+/// done so, and clearing would hide its message.
+impl<B: Backend> Drop for Revise<B> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+
+        self.terminal.reset_fg_color();
+        self.terminal.reset_bg_color();
+        self.terminal.clear_screen();
+        self.terminal.cursor_position(&Position::default());
+        self.terminal.cursor_show();
+        self.terminal.flush().ok();
+    }
+}
+
+#[cfg(test)]
+mod application_tests {
+    use super::*;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    fn editor(text: &str) -> Revise<TestBackend> {
+        let document = Document::from_reader(text.as_bytes()).unwrap();
+
+        Revise::from_document(
+            document,
+            Size {
+                width: 80,
+                height: 22,
+            },
+        )
+    }
+
+    fn fixed_time() -> Option<LocalTime> {
+        Some(LocalTime {
+            year: 2024,
+            month: 3,
+            day: 9,
+            hour: 7,
+            minute: 5,
+            second: 30,
+            offset: 0,
+        })
+    }
+
+    #[test]
+    fn test_insert_text() {
+        let mut revise = editor("fn main() {}\n");
+
+        revise.move_cursor(Key::End);
+        revise.move_cursor(Key::Left);
+        revise.insert_text("\n    todo!();\n");
+
+        assert_eq!(revise.current_line(), Some("}"));
+        assert_eq!(revise.cursor_position(), &Position { x: 0, y: 2 });
+        assert_eq!(
+            revise.document().row(1).map(Row::as_string),
+            Some(&"    todo!();".to_owned())
+        );
+
+        revise.undo();
+
+        assert_eq!(revise.current_line(), Some("fn main() {}"));
+
+        let mut revise = editor("ab\n");
+
+        revise.move_cursor(Key::End);
+        revise.insert_text("e\u{301}x");
+
+        assert_eq!(revise.current_line(), Some("abe\u{301}x"));
+        assert_eq!(revise.cursor_position(), &Position { x: 4, y: 0 });
+
+        revise.cursor_position = Position { x: 1, y: 0 };
+        revise.insert_text("o\u{308}y");
+
+        assert_eq!(revise.current_line(), Some("ao\u{308}ybe\u{301}x"));
+        assert_eq!(revise.cursor_position(), &Position { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn test_delete_range() {
+        let mut revise = editor("first\nsecond\nthird\n");
+
+        revise.delete_range(&Position { x: 3, y: 1 }, &Position { x: 2, y: 0 });
+
+        assert_eq!(revise.current_line(), Some("fiond"));
+        assert_eq!(revise.cursor_position(), &Position { x: 2, y: 0 });
+        assert_eq!(revise.document().len(), 2);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut revise = editor("");
+
+        revise.backend_mut().push_text("hello");
+        revise.backend_mut().push_event(Event::Key(Key::Ctrl('q')));
+        revise.backend_mut().push_event(Event::Key(Key::Ctrl('q')));
+
+        assert!(revise.run().is_ok());
+        assert_eq!(revise.current_line(), Some("hello"));
+        assert!(revise.backend().output().contains("unsaved changes"));
+    }
+
+    #[test]
+    fn test_run_defers_refresh() {
+        let mut revise = editor("one\ntwo\n");
+
+        revise.backend_mut().set_typeahead(true);
+
+        for key in [Key::Down, Key::Right, Key::Right, Key::Ctrl('q')] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        assert!(revise.run().is_ok());
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 1 });
+        // The first frame and the one clearing the screen on quitting.
+        assert_eq!(revise.backend().output().matches("\x1b[?25l").count(), 2);
+    }
+
+    #[test]
+    fn test_deferred_refresh_matches() {
+        let frame = |typeahead: bool| {
+            let mut revise = editor("one\ntwo\n");
+
+            revise.backend_mut().set_typeahead(typeahead);
+            revise.backend_mut().push_text("abc");
+
+            for key in [Key::Down, Key::Backspace, Key::Ctrl('q'), Key::Ctrl('q')] {
+                revise.backend_mut().push_event(Event::Key(key));
+            }
+
+            assert!(revise.run().is_ok());
+
+            revise.should_quit = false;
+            revise.backend_mut().clear_output();
+            revise.refresh_screen().unwrap();
+
+            (
+                revise.backend().output().to_owned(),
+                revise.document.contents(),
+            )
+        };
+
+        assert_eq!(frame(true), frame(false));
+    }
+
+    #[test]
+    fn test_scroll() {
+        let text: String = (0..50).map(|index| format!("line {index}\n")).collect();
+        let mut revise = editor(&text);
+
+        revise.config.highlight_symbol = false;
+        revise.config.scrolloff = 0;
+        revise.backend_mut().push_event(Event::Key(Key::PageDown));
+        revise.backend_mut().push_event(Event::Key(Key::PageDown));
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.offset.y, 23);
+        assert!(revise.backend().output().contains("line 44"));
+        assert!(!revise.backend().output().contains("line 22\r"));
+    }
+
+    #[test]
+    fn test_scrolloff() {
+        let mut revise = editor(&"line\n".repeat(50));
+        let height = revise.view().height;
+
+        revise.cursor_position = Position {
+            x: 0,
+            y: height - 3,
+        };
+        revise.scroll();
+
+        assert_eq!(revise.offset.y, 1);
+
+        revise.cursor_position = Position { x: 0, y: 3 };
+        revise.scroll();
+
+        assert_eq!(revise.offset.y, 0);
+
+        revise.cursor_position = Position { x: 0, y: 50 };
+        revise.scroll();
+
+        assert_eq!(revise.offset.y, 51 - height);
+
+        revise.config.scrolloff = 100;
+        revise.cursor_position = Position { x: 0, y: 30 };
+        revise.scroll();
+
+        assert_eq!(revise.offset.y, 30 - (height - 1) / 2);
+    }
+
+    #[test]
+    fn test_wide_cursor() {
+        let mut revise = editor("ab漢字c\nabcdef\n");
+
+        for _ in 0..3 {
+            revise.move_cursor(Key::Right);
+        }
+
+        assert_eq!(revise.cursor_column(), 4);
+
+        revise.move_cursor(Key::Down);
+
+        assert_eq!(revise.cursor_position().x, 4);
+
+        revise.move_cursor(Key::Left);
+        revise.move_cursor(Key::Up);
+
+        assert_eq!(revise.cursor_position().x, 2);
+        assert_eq!(revise.cursor_column(), 2);
+
+        revise.move_cursor(Key::Right);
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .ends_with("\u{1b}[1;5H\u{1b}[?25h"));
+    }
+
+    #[test]
+    fn test_redraw_changed_rows() {
+        let mut revise = editor("first\nsecond\n");
+
+        revise.config.highlight_symbol = false;
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("first"));
+
+        revise.backend_mut().clear_output();
+        revise.move_cursor(Key::Right);
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains("first"));
+        assert!(!revise.backend().output().contains('~'));
+
+        revise.backend_mut().clear_output();
+        revise.insert_text("!");
+        revise.refresh_screen().unwrap();
+
+        let output = revise.backend().output();
+
+        assert!(output.contains("f!irst"));
+        assert!(output.contains("\u{25cf}"));
+        assert!(!output.contains("second"));
+
+        revise.backend_mut().clear_output();
+        revise.clear_screen();
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("second"));
+    }
+
+    #[test]
+    fn test_status_bar() {
+        let mut revise = editor("\tab\nsecond\n");
+
+        revise.move_cursor(Key::End);
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("1/2, col 7 | 11 bytes"));
+
+        revise.selection_anchor = Some(Position::default());
+        revise.move_cursor(Key::Down);
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains("2/2, col 7 | 10 selected"));
+    }
+
+    #[test]
+    fn test_status_badge() {
+        let path = env::temp_dir().join("revise_status_badge.txt");
+        let mut revise = editor("fn main() {}\n");
+
+        revise.document.filename = Some(path.display().to_string());
+        revise.insert_text("x");
+        revise.config.ascii_only = true;
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains(&format!("{} *", color::Fg(revise.theme.modified))));
+        assert!(revise.backend().output().contains("TXT | UTF-8 | 1/1"));
+
+        revise.document.filename = Some(path.with_extension("rs").display().to_string());
+        revise.save();
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("RS | UTF-8 | 1/1"));
+        std::fs::remove_file(path.with_extension("rs")).ok();
+        std::fs::remove_file(path.with_extension("rs.bak")).ok();
+    }
+
+    #[test]
+    fn test_word_count() {
+        let mut revise = editor("one two\n\nthree  four five\n");
+
+        revise.backend_mut().push_event(Event::Key(Key::Ctrl('k')));
+        revise.backend_mut().push_event(Event::Key(Key::Char('w')));
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(
+            revise.status_message.text,
+            "Document: 3 lines, 5 words, 23 characters"
+        );
+        assert_eq!(revise.current_line(), Some("one two"));
+
+        revise.selection_anchor = Some(Position { x: 4, y: 0 });
+        revise.cursor_position = Position { x: 5, y: 2 };
+        revise.word_count();
+
+        assert_eq!(
+            revise.status_message.text,
+            "Selection: 3 lines, 2 words, 8 characters"
+        );
+    }
+
+    #[test]
+    fn test_auto_pairs() {
+        let mut revise = editor("let x = ;\n");
+
+        revise.cursor_position = Position { x: 8, y: 0 };
+
+        for c in "foo(\"a".chars() {
+            revise.backend_mut().push_event(Event::Key(Key::Char(c)));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.current_line(), Some("let x = foo(\"a\");"));
+
+        for key in [Key::Char('"'), Key::Char(')')] {
+            revise.backend_mut().push_event(Event::Key(key));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.current_line(), Some("let x = foo(\"a\");"));
+        assert_eq!(revise.cursor_position(), &Position { x: 16, y: 0 });
+
+        revise.config.auto_pairs = false;
+        revise.process_key(Key::Char('['));
+        revise.config.auto_pairs = true;
+        revise.process_key(Key::Char('['));
+        revise.process_key(Key::Backspace);
+
+        assert_eq!(revise.current_line(), Some("let x = foo(\"a\")[;"));
+
+        revise.process_key(Key::Backspace);
+        revise.process_key(Key::Char('\''));
+
+        assert_eq!(revise.current_line(), Some("let x = foo(\"a\")'';"));
+
+        revise.cursor_position = Position { x: 11, y: 0 };
+        revise.process_key(Key::Char('\''));
+
+        assert_eq!(revise.current_line(), Some("let x = foo'(\"a\")'';"));
+    }
+
+    #[test]
+    fn test_incremental_search() {
+        let mut revise = editor("ab abc\n");
+
+        revise.backend_mut().push_text("abc");
+        revise.backend_mut().push_event(Event::Key(Key::Backspace));
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 0 });
+
+        revise.backend_mut().push_text("ab");
+        revise.backend_mut().push_event(Event::Key(Key::Right));
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn test_search_wraps_around() {
+        let mut revise = editor("one\ntwo\n");
+
+        revise.cursor_position = Position { x: 0, y: 1 };
+        revise.backend_mut().push_text("one\n");
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 0 });
+        assert_eq!(
+            revise.status_message.text,
+            "search hit BOTTOM, continuing at TOP"
+        );
+
+        revise.backend_mut().push_text("three\n");
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 0 });
+        assert_eq!(revise.status_message.text, "Pattern not found: three");
+    }
+
+    #[test]
+    fn test_search_steps_past_match() {
+        let mut revise = editor("aaa aaa\nan aaa\n");
+
+        revise.backend_mut().push_text("aa");
+        revise.backend_mut().push_event(Event::Key(Key::Right));
+        revise.backend_mut().push_event(Event::Key(Key::Right));
+        revise.backend_mut().push_text("\n");
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position { x: 3, y: 1 });
+
+        revise.cursor_position = Position::default();
+        revise.backend_mut().push_text("aaa aaa a");
+        revise.backend_mut().push_event(Event::Key(Key::Right));
+        revise.backend_mut().push_text("\n");
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position::default());
+        assert_eq!(revise.status_message.text, "Pattern not found: aaa aaa a");
+    }
+
+    #[test]
+    fn test_repeat_count() {
+        let text: String = (0..10).map(|index| format!("line {index}\n")).collect();
+        let mut revise = editor(&text);
+
+        for event in [
+            Event::Key(Key::Alt('3')),
+            Event::Key(Key::Down),
+            Event::Key(Key::Alt('1')),
+            Event::Key(Key::Alt('2')),
+            Event::Key(Key::Char('x')),
+            Event::Key(Key::Alt('2')),
+            Event::Key(Key::Esc),
+            Event::Key(Key::Down),
+        ] {
+            revise.backend_mut().push_event(event);
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.cursor_position().y, 4);
+        assert_eq!(
+            revise.document().row(3).map(Row::as_string),
+            Some(&format!("{}line 3", "x".repeat(12)))
+        );
+    }
+
+    #[test]
+    fn test_modal_editing() {
+        let mut revise = editor("one\ntwo\nthree\nfour\n");
+
+        revise.config.modal = true;
+        revise.mode = Mode::Normal;
+
+        for c in "jxdd".chars() {
+            revise.backend_mut().push_event(Event::Key(Key::Char(c)));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.document().len(), 3);
+        assert_eq!(revise.current_line(), Some("three"));
+
+        for key in [
+            Key::Char('a'),
+            Key::Char('!'),
+            Key::Esc,
+            Key::Char('2'),
+            Key::Char('k'),
+        ] {
+            revise.backend_mut().push_event(Event::Key(key));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(
+            revise.document().row(1).map(Row::as_string),
+            Some(&"t!hree".to_owned())
+        );
+        assert_eq!(revise.cursor_position(), &Position { x: 2, y: 0 });
+        assert_eq!(revise.mode, Mode::Normal);
+
+        for c in "vld".chars() {
+            revise.backend_mut().push_event(Event::Key(Key::Char(c)));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.current_line(), Some("on"));
+        assert_eq!(revise.mode, Mode::Normal);
+
+        for event in [
+            Event::Key(Key::Char('v')),
+            Event::Key(Key::Char('l')),
+            Event::Key(Key::Ctrl('l')),
+        ] {
+            revise.backend_mut().push_event(event);
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.selection(), None);
+        assert_eq!(revise.mode, Mode::Normal);
+
+        revise.draw_status_bar();
+
+        assert!(revise.backend().output().contains("NORMAL | "));
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut revise = editor("one\ntwo\nthree\n");
+
+        revise.cursor_position = Position { x: 2, y: 2 };
+
+        for event in [
+            Event::Key(Key::Ctrl('k')),
+            Event::Key(Key::Char('m')),
+            Event::Key(Key::Char('a')),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+        revise.cursor_position = Position::default();
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.marks.get(&'a'), Some(&Position { x: 2, y: 3 }));
+
+        for event in [
+            Event::Key(Key::Ctrl('k')),
+            Event::Key(Key::Char('\'')),
+            Event::Key(Key::Char('a')),
+            Event::Key(Key::Ctrl('k')),
+            Event::Key(Key::Char('\'')),
+            Event::Key(Key::Char('b')),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 3 });
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.status_message.text, "Mark 'b' is not set.");
+    }
+
+    #[test]
+    fn test_jump_list() {
+        let mut revise = editor("one\ntwo\nthree\n");
+
+        revise.cursor_position = Position { x: 2, y: 0 };
+        revise.goto_line(3);
+
+        for event in [
+            Event::Modified(Key::Left, Modifiers::ALT),
+            Event::Modified(Key::Left, Modifiers::ALT),
+            Event::Modified(Key::Right, Modifiers::ALT),
+            Event::Modified(Key::Right, Modifiers::ALT),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 0 });
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(
+            revise.status_message.text,
+            "Already at the oldest position."
+        );
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 2 });
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(
+            revise.status_message.text,
+            "Already at the newest position."
+        );
+    }
+
+    #[test]
+    fn test_overwrite() {
+        let mut revise = editor("abc\n");
+
+        revise.config.highlight_symbol = false;
+
+        for key in [
+            Key::Insert,
+            Key::Char('x'),
+            Key::Char('y'),
+            Key::Char('z'),
+            Key::Char('!'),
+        ] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        for _ in 0..5 {
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.current_line(), Some("xyz!"));
+        assert_eq!(revise.cursor_position, Position { x: 4, y: 0 });
+
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("OVR | TXT"));
+
+        revise.undo();
+
+        assert_eq!(revise.current_line(), Some("abc"));
+
+        revise.process_key(Key::Insert);
+        revise.process_key(Key::Char('-'));
+
+        assert_eq!(revise.current_line(), Some("-abc"));
+    }
+
+    #[test]
+    fn test_multiple_cursors() {
+        let mut revise = editor("ab\ncd\nef\n");
+        let below = Event::Modified(
+            Key::Down,
+            Modifiers {
+                ctrl: true,
+                alt: true,
+                shift: false,
+            },
+        );
+
+        revise.cursor_position = Position { x: 1, y: 0 };
+
+        for event in [
+            below.clone(),
+            below.clone(),
+            below,
+            Event::Key(Key::Char('X')),
+            Event::Key(Key::Char('\n')),
+            Event::Key(Key::Backspace),
+            Event::Key(Key::Left),
+            Event::Key(Key::Delete),
+            Event::Key(Key::Esc),
+            Event::Key(Key::Char('!')),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        for _ in 0..3 {
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.status_message.text, "3 cursors.");
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "aXb\ncXd\neXf\n");
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 0 });
+        assert_eq!(
+            revise.cursors,
+            vec![
+                Cursor {
+                    position: Position { x: 2, y: 1 },
+                    anchor: None,
+                },
+                Cursor {
+                    position: Position { x: 2, y: 2 },
+                    anchor: None,
+                },
+            ]
+        );
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "aX\nb\ncX\nd\neX\nf\n");
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 1 });
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "aXb\ncXd\neXf\n");
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 0 });
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "ab\ncd\nef\n");
+        assert_eq!(revise.cursors.len(), 2);
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert!(revise.cursors.is_empty());
+        assert_eq!(revise.document.contents(), "a!b\ncd\nef\n");
+
+        revise.undo();
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "aXb\ncXd\neXf\n");
+    }
+
+    #[test]
+    fn test_block_selection() {
+        let mut revise = editor("abcd\nab\nabcdef\n");
+        let extend = |key| Event::Modified(key, Modifiers::ALT_SHIFT);
+
+        revise.cursor_position = Position { x: 1, y: 0 };
+
+        for event in [
+            extend(Key::Down),
+            extend(Key::Down),
+            extend(Key::Right),
+            extend(Key::Right),
+            Event::Key(Key::Char('X')),
+            Event::Key(Key::Char('Y')),
+            Event::Key(Key::Backspace),
+            Event::Key(Key::Delete),
+            Event::Key(Key::Esc),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        for _ in 0..5 {
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.document.contents(), "aXd\nab\naXdef\n");
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 2 });
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "aXd\nab\naXdef\n");
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "aX\nab\naXef\n");
+
+        revise.process_keypress().unwrap();
+
+        assert!(revise.block.is_none());
+
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "aXd\nab\naXdef\n");
+
+        revise.config.pad_block_selection = true;
+        revise.cursor_position = Position { x: 3, y: 0 };
+
+        for event in [extend(Key::Down), Event::Key(Key::Char('|'))] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "aXd|\nab |\naXdef\n");
+    }
+
+    #[test]
+    fn test_add_next_occurrence() {
+        let mut revise = editor("foo bar\nfoo\nbaz foo\n");
+
+        revise.cursor_position = Position { x: 1, y: 0 };
+
+        for _ in 0..4 {
+            revise.backend_mut().push_event(Event::Key(Key::Alt('n')));
+        }
+
+        revise.backend_mut().push_event(Event::Key(Key::Char('X')));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.selected_text().as_deref(), Some("foo"));
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.status_message.text, "3 cursors.");
+        assert_eq!(revise.cursor_position, Position { x: 7, y: 2 });
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.status_message.text, "No more matches.");
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "X bar\nX\nbaz X\n");
+        assert_eq!(revise.cursor_position, Position { x: 5, y: 2 });
+        assert_eq!(revise.cursors.len(), 2);
+
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "foo bar\nfoo\nbaz foo\n");
+    }
+
+    #[test]
+    fn test_paste_event() {
+        let mut revise = editor("x\n");
+
+        revise
+            .backend_mut()
+            .push_event(Event::Paste("if (a) {\n    b\n}".to_owned()));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(
+            revise.document.row(0).map(|row| row.as_string().as_str()),
+            Some("if (a) {")
+        );
+        assert_eq!(
+            revise.document.row(1).map(|row| row.as_string().as_str()),
+            Some("    b")
+        );
+        assert_eq!(revise.current_line(), Some("}x"));
+        assert_eq!(revise.cursor_position, Position { x: 1, y: 2 });
+
+        revise.undo();
+
+        assert_eq!(revise.current_line(), Some("x"));
+        assert_eq!(revise.document.len(), 1);
+
+        revise.selection_anchor = Some(Position { x: 1, y: 0 });
+        revise
+            .backend_mut()
+            .push_event(Event::Paste("yz".to_owned()));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.current_line(), Some("yz"));
+        assert_eq!(revise.selection(), None);
+
+        revise.undo();
+
+        assert_eq!(revise.current_line(), Some("x"));
+
+        let mut revise = editor("ab\n");
+
+        revise.cursor_position = Position { x: 1, y: 0 };
+        revise
+            .backend_mut()
+            .push_event(Event::Paste("e\u{301}x".to_owned()));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.current_line(), Some("ae\u{301}xb"));
+        assert_eq!(revise.cursor_position, Position { x: 3, y: 0 });
+    }
+
+    #[test]
+    fn test_read_only() {
+        let mut revise = editor("abc\n");
+
+        revise.config.highlight_symbol = false;
+        revise.document.read_only = true;
+
+        for event in [
+            Event::Key(Key::Char('x')),
+            Event::Key(Key::Char('\n')),
+            Event::Key(Key::Delete),
+            Event::Paste("pasted".to_owned()),
+            Event::Modified(Key::Down, Modifiers::ALT),
+            Event::Key(Key::Right),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        for _ in 0..6 {
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.current_line(), Some("abc"));
+        assert_eq!(revise.document.len(), 1);
+        assert_eq!(revise.cursor_position, Position { x: 1, y: 0 });
+        assert!(!revise.document.is_changed());
+
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("[RO]"));
+
+        let path = env::temp_dir().join("revise_test_save_read_only.txt");
+        let copy = env::temp_dir().join("revise_test_save_read_only_copy.txt");
+
+        std::fs::write(&path, "abc  \n").unwrap();
+        revise.config.trim_trailing_whitespace = true;
+        revise.document.filename = Some(path.display().to_string());
+        revise.backend_mut().clear_output();
+        revise.save();
+
+        assert_eq!(revise.status_message.text, "Buffer is read-only.");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "abc  \n");
+
+        revise.execute_command("wq");
+
+        assert_eq!(revise.status_message.text, "Buffer is read-only.");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "abc  \n");
+
+        revise.execute_command(&format!("w {}", copy.display()));
+
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "abc\n");
+
+        revise.document.read_only = false;
+        revise.backend_mut().push_event(Event::Key(Key::Char('x')));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.current_line(), Some("axbc"));
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&copy).ok();
+        std::fs::remove_file(copy.with_extension("txt.bak")).ok();
+    }
+
+    #[test]
+    fn test_filter_through_command() {
+        let mut revise = editor("pear\napple\nfig\n");
+
+        revise.cursor_position = Position { x: 2, y: 1 };
+
+        assert!(revise.filter_through_command("sort").is_ok());
+        assert_eq!(revise.document.contents(), "apple\nfig\npear\n");
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 1 });
+
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "pear\napple\nfig\n");
+
+        revise.selection_anchor = Some(Position { x: 1, y: 0 });
+        revise.cursor_position = Position { x: 3, y: 0 };
+
+        assert!(revise.filter_through_command("tr a-z A-Z").is_ok());
+        assert_eq!(revise.current_line(), Some("pEAr"));
+        assert_eq!(
+            revise.filter_through_command("echo broken >&2; exit 3"),
+            Err("echo broken >&2; exit 3 failed: broken".to_owned())
+        );
+        assert_eq!(revise.current_line(), Some("pEAr"));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut revise = editor("let answer = 42;\nlast\n");
+
+        revise.cursor_position = Position { x: 10, y: 0 };
+
+        assert_eq!(revise.word_under_cursor(), Some("answer"));
+        assert_eq!(revise.selected_text(), None);
+
+        revise.selection_anchor = Some(Position { x: 4, y: 0 });
+        revise.cursor_position = Position { x: 2, y: 1 };
+
+        assert_eq!(revise.selected_text().as_deref(), Some("answer = 42;\nla"));
+        assert_eq!(revise.cursor_position(), &Position { x: 2, y: 1 });
+        assert_eq!(revise.document().contents(), "let answer = 42;\nlast\n");
+
+        revise.cursor_position = Position { x: 11, y: 0 };
+
+        assert_eq!(revise.word_under_cursor(), None);
+    }
+
+    #[test]
+    fn test_desired_column() {
+        let mut revise = editor("a long line\nab\n\nanother line\n");
+
+        revise.cursor_position = Position { x: 7, y: 0 };
+        revise.move_cursor(Key::Down);
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 1 });
+
+        revise.move_cursor(Key::Down);
+        revise.move_cursor(Key::Down);
+
+        assert_eq!(revise.cursor_position, Position { x: 7, y: 3 });
+
+        revise.move_cursor(Key::Left);
+        revise.move_cursor_times(Key::Up, 3);
+
+        assert_eq!(revise.cursor_position, Position { x: 6, y: 0 });
+
+        revise.cursor_position = Position { x: 2, y: 1 };
+        revise.move_cursor(Key::Up);
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 0 });
+    }
+
+    #[test]
+    fn test_smart_home() {
+        let mut revise = editor("    let x = 1;\n  \n");
+
+        revise.cursor_position = Position { x: 9, y: 0 };
+        revise.move_cursor(Key::Home);
+
+        assert_eq!(revise.cursor_position, Position { x: 4, y: 0 });
+
+        revise.move_cursor(Key::Home);
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 0 });
+
+        revise.move_cursor(Key::Home);
+
+        assert_eq!(revise.cursor_position, Position { x: 4, y: 0 });
+
+        revise.cursor_position = Position { x: 2, y: 1 };
+        revise.move_cursor(Key::Home);
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 1 });
+    }
+
+    #[test]
+    fn test_document_start_and_end() {
+        let mut revise = editor("one\ntwo\nthree\n");
+
+        revise.cursor_position = Position { x: 1, y: 1 };
+
+        for event in [
+            Event::Modified(Key::End, Modifiers::CTRL),
+            Event::Modified(Key::Home, Modifiers::CTRL),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 5, y: 2 });
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position::default());
+    }
+
+    #[test]
+    fn test_modified_movement() {
+        let mut revise = editor("one\ntwo\nthree\n");
+        let ctrl_shift = Modifiers {
+            shift: true,
+            ctrl: true,
+            ..Modifiers::default()
+        };
+
+        for event in [
+            Event::Modified(Key::Down, Modifiers::CTRL),
+            Event::Modified(Key::Right, ctrl_shift),
+            Event::Modified(Key::End, ctrl_shift),
+            Event::Modified(Key::Left, Modifiers::CTRL),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 1 });
+        assert_eq!(revise.selection_anchor, None);
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 5, y: 2 });
+        assert_eq!(revise.selection_anchor, Some(Position { x: 0, y: 1 }));
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 4, y: 2 });
+        assert_eq!(revise.selection_anchor, None);
+    }
+
+    #[test]
+    fn test_center_view() {
+        let mut revise = editor(&"line\n".repeat(100));
+
+        revise.cursor_position = Position { x: 0, y: 60 };
+        revise.scroll();
+        revise.perform(Action::CenterView);
+
+        assert_eq!(revise.offset.y, 60 - revise.view().height / 2);
+
+        revise.cursor_position = Position { x: 0, y: 3 };
+        revise.scroll();
+        revise.perform(Action::CenterView);
+
+        assert_eq!(revise.offset.y, 0);
+    }
+
+    #[test]
+    fn test_folds() {
+        let mut revise = editor("fn main() {\n    a();\n    b();\n}\nfn other() {\n    c();\n}\n");
+
+        revise.cursor_position = Position { x: 0, y: 2 };
+        revise.perform(Action::ToggleFold);
+
+        assert_eq!(revise.folds, vec![(0, 3)]);
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 0 });
+
+        revise.move_cursor(Key::Down);
+
+        assert_eq!(revise.cursor_position.y, 4);
+        assert_eq!(revise.visible_line(4), 1);
+
+        revise.move_cursor(Key::Up);
+
+        assert_eq!(revise.cursor_position.y, 0);
+
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("\u{2295} 3 lines"));
+        assert_eq!(revise.line_at(2), 5);
+
+        revise.perform(Action::FoldAll);
+
+        assert_eq!(revise.folds, vec![(0, 3), (4, 6)]);
+        assert_eq!(revise.status_message.text, "Folded 2 regions.");
+
+        revise.goto_line(6);
+        revise.scroll();
+
+        assert_eq!(revise.folds, vec![(0, 3)]);
+
+        revise.cursor_position = Position { x: 11, y: 0 };
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.process_keypress().unwrap();
+
+        assert!(revise.folds.is_empty());
+
+        revise.perform(Action::FoldAll);
+        revise.perform(Action::UnfoldAll);
+
+        assert!(revise.folds.is_empty());
+
+        revise.perform(Action::FoldAll);
+        revise.toggle_split(SplitDirection::Stacked);
+        revise.switch_pane();
+        revise.perform(Action::UnfoldAll);
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.folds.is_empty());
+        assert_eq!(
+            revise.split.as_ref().map(|split| split.folds.clone()),
+            Some(vec![(0, 4), (5, 7)])
+        );
+        assert!(revise.backend().output().contains("\u{2295} 4 lines"));
+    }
+
+    #[test]
+    fn test_start_at() {
+        let mut revise = editor(&"line\n".repeat(100));
+
+        revise.start_at(60, Some(3));
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 59 });
+        assert_eq!(revise.offset.y, 59 - revise.view().height / 2);
+
+        revise.start_at(500, Some(80));
+
+        assert_eq!(revise.cursor_position, Position { x: 4, y: 99 });
+
+        revise.start_at(0, None);
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn test_toggle_comment() {
+        let path = env::temp_dir().join("revise_toggle_comment.rs");
+
+        std::fs::write(&path, "fn main() {\n    one();\n\n  // two();\n}\n").unwrap();
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(
+            document,
+            Size {
+                width: 80,
+                height: 22,
+            },
+        );
+        let lines = |revise: &Revise<TestBackend>| {
+            (1..4)
+                .filter_map(|y| revise.document().row(y).map(|row| row.as_string().clone()))
+                .collect::<Vec<_>>()
+        };
+
+        std::fs::remove_file(&path).ok();
+        revise.selection_anchor = Some(Position { x: 4, y: 1 });
+        revise.cursor_position = Position { x: 11, y: 3 };
+        revise.perform(Action::ToggleComment);
+
+        assert_eq!(lines(&revise), ["  //   one();", "", "  // // two();"]);
+        assert_eq!(
+            revise.selection(),
+            Some((Position { x: 7, y: 1 }, Position { x: 14, y: 3 }))
+        );
+
+        revise.perform(Action::ToggleComment);
+
+        assert_eq!(lines(&revise), ["    one();", "", "  // two();"]);
+
+        revise.selection_anchor = None;
+        revise.cursor_position = Position { x: 0, y: 3 };
+        revise.perform(Action::ToggleComment);
+
+        assert_eq!(revise.current_line(), Some("  two();"));
+    }
+
+    #[test]
+    fn test_indent_selection() {
+        let mut revise = editor("one\n  two\n\nthree\nfour\n");
+        let lines = |revise: &Revise<TestBackend>| {
+            (0..5)
+                .filter_map(|y| revise.document().row(y).map(|row| row.as_string().clone()))
+                .collect::<Vec<_>>()
+        };
+
+        revise.selection_anchor = Some(Position { x: 1, y: 0 });
+        revise.cursor_position = Position { x: 0, y: 4 };
+
+        for key in [Key::Char('\t'), Key::BackTab, Key::BackTab] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(lines(&revise), ["\tone", "\t  two", "", "\tthree", "four"]);
+        assert_eq!(
+            revise.selection(),
+            Some((Position { x: 2, y: 0 }, Position { x: 0, y: 4 }))
+        );
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(lines(&revise), ["one", "two", "", "three", "four"]);
+        assert_eq!(revise.selection_anchor, Some(Position { x: 1, y: 0 }));
+
+        revise.undo();
+
+        assert_eq!(lines(&revise), ["one", "  two", "", "three", "four"]);
+    }
+
+    #[test]
+    fn test_highlight_term() {
+        let mut revise = editor("let count = 1;\ncount += 2;\n");
+
+        revise.cursor_position = Position { x: 9, y: 0 };
+
+        assert_eq!(
+            revise.highlight_term(),
+            Some(highlight::Term::Symbol("count".to_owned()))
+        );
+
+        revise.cursor_position = Position { x: 12, y: 0 };
+
+        assert_eq!(revise.highlight_term(), None);
+
+        revise.highlighted_word = Some("un".to_owned());
+
+        assert_eq!(
+            revise.highlight_term(),
+            Some(highlight::Term::Search("un".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_search_history() {
+        let mut revise = editor("alpha beta\ngamma beta\n");
+
+        for query in ["beta", "beta", "gamma"] {
+            revise.backend_mut().push_text(query);
+            revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+            revise.search();
+        }
+
+        assert_eq!(revise.search_history, ["beta", "gamma"]);
+
+        revise.cursor_position = Position::default();
+        revise.backend_mut().push_event(Event::Key(Key::Up));
+        revise.backend_mut().push_event(Event::Key(Key::Up));
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position { x: 6, y: 0 });
+        assert_eq!(revise.search_history, ["beta", "gamma", "beta"]);
+    }
+
+    #[test]
+    fn test_execute_command() {
+        let path = env::temp_dir().join("revise_execute_command.txt");
+        let mut revise = editor("one\ntwo\nthree\n");
+
+        revise.execute_command("3");
+
+        assert_eq!(revise.current_line(), Some("three"));
+
+        revise.execute_command("set number");
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains(&format!("2 {}two", color::Fg(color::Reset))));
+
+        revise.execute_command("frobnicate");
+
+        assert_eq!(
+            revise.status_message.text,
+            "Not an editor command: frobnicate"
+        );
+
+        revise.insert_text("!");
+        revise.execute_command("q");
+
+        assert!(!revise.should_quit);
+
+        revise.execute_command(&format!("w {}", path.display()));
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "one\ntwo\n!three\n"
+        );
+
+        revise.execute_command("e Cargo.toml");
+        revise.execute_command("2");
+
+        assert_eq!(revise.current_line(), Some("name = \"revise\""));
+
+        revise.execute_command("q");
+
+        assert!(revise.should_quit);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_buffers() {
+        let mut revise = editor("scratch\n");
+
+        revise.move_cursor(Key::End);
+        revise.open_file("Cargo.toml");
+        revise.move_cursor(Key::Down);
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.current_line(), Some("name = \"revise\""));
+        assert!(revise.backend().output().contains(&format!(
+            " 1:[No Name] {} 2:Cargo.toml {}",
+            style::Invert,
+            style::NoInvert
+        )));
+
+        revise
+            .backend_mut()
+            .push_event(Event::Modified(Key::PageUp, Modifiers::CTRL));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.current_line(), Some("scratch"));
+        assert_eq!(revise.cursor_position().x, 7);
+
+        revise.insert_text("!");
+        revise.open_file("Cargo.toml");
+
+        assert_eq!(revise.active, 1);
+        assert_eq!(revise.buffers.len(), 2);
+        assert!(revise.has_unsaved_changes());
+        assert_eq!(revise.cursor_position().y, 1);
+
+        revise.open_file("README.md");
+
+        for active in [1, 2, 1] {
+            revise.backend_mut().push_event(Event::Key(Key::Ctrl('6')));
+            revise.process_keypress().unwrap();
+
+            assert_eq!(revise.active, active);
+        }
+
+        let mut single = editor("alone\n");
+
+        single.backend_mut().push_event(Event::Key(Key::Ctrl('6')));
+        single.process_keypress().unwrap();
+
+        assert_eq!(single.active, 0);
+        assert_eq!(single.current_line(), Some("alone"));
+    }
+
+    #[test]
+    fn test_escape() {
+        let mut revise = editor("one two\n");
+
+        revise.highlighted_word = Some("two".to_owned());
+        revise.selection_anchor = Some(Position { x: 3, y: 0 });
+
+        for event in [Event::Key(Key::Alt('3')), Event::Key(Key::Esc)] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.status_message.text, "Repeat: 3");
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.status_message.text, "");
+        assert_eq!(revise.count, None);
+        assert_eq!(revise.highlighted_word, None);
+        assert_eq!(revise.selection(), None);
+        assert!(!revise.should_quit);
+    }
+
+    #[test]
+    fn test_help() {
+        let mut revise = editor("text\n");
+
+        for event in [
+            Event::Key(Key::Ctrl('k')),
+            Event::Key(Key::Char('?')),
+            Event::Key(Key::Down),
+            Event::Key(Key::Char('x')),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert!(revise.backend().output().contains("  Ctrl-K ?        help"));
+        assert_eq!(revise.help, None);
+        assert_eq!(revise.current_line(), Some("text"));
+
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(
+            revise.screen[0],
+            revise.render_row(revise.document.row(0).unwrap(), 0)
+        );
+    }
+
+    #[test]
+    fn test_yank_lines() {
+        let mut revise = editor("one\ntwo\nthree\n");
+
+        revise.cursor_position = Position { x: 1, y: 1 };
+        revise.perform(Action::Copy);
+        revise.perform(Action::Paste);
+
+        assert_eq!(
+            revise.document().row(1).map(Row::as_string),
+            Some(&"two".to_owned())
+        );
+        assert_eq!(revise.current_line(), Some("two"));
+        assert_eq!(revise.cursor_position, Position { x: 1, y: 2 });
+        assert_eq!(revise.document().len(), 4);
+
+        revise.config.modal = true;
+        revise.mode = Mode::Normal;
+        revise.cursor_position = Position::default();
+
+        for c in "2ddp".chars() {
+            revise.backend_mut().push_event(Event::Key(Key::Char(c)));
+            revise.process_keypress().unwrap();
+        }
+
+        let lines: Vec<&String> = (0..4)
+            .filter_map(|y| revise.document().row(y))
+            .map(Row::as_string)
+            .collect();
+
+        assert_eq!(lines, ["two", "one", "two", "three"]);
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 1 });
+
+        for c in "yyP".chars() {
+            revise.backend_mut().push_event(Event::Key(Key::Char(c)));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.document().len(), 5);
+        assert_eq!(revise.current_line(), Some("one"));
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 2 });
+    }
+
+    #[test]
+    fn test_local_clipboard() {
+        let mut revise = editor("one\n");
+
+        revise.config.osc52 = Some(false);
+        revise.perform(Action::Copy);
+
+        assert_eq!(
+            revise.status_message.text,
+            "No system clipboard available, copied within the editor only."
+        );
+
+        revise.status_message = StatusMessage::from(String::new());
+        revise.execute_command("set nosystem_clipboard");
+        revise.perform(Action::Cut);
+        revise.perform(Action::Paste);
+
+        assert_eq!(revise.status_message.text, "");
+        assert_eq!(
+            revise.document().row(0).map(Row::as_string),
+            Some(&"one".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_osc52() {
+        let mut revise = editor("one\ntwo\n");
+
+        revise.execute_command("set osc52");
+        revise.selection_anchor = Some(Position { x: 1, y: 0 });
+        revise.cursor_position = Position { x: 2, y: 1 };
+        revise.perform(Action::Copy);
+
+        assert!(revise.backend().output().contains("\x1b]52;c;bmUKdHc=\x07"));
+        assert_eq!(revise.status_message.text, "");
+        assert_eq!(revise.clipboard.get_contents(), "ne\ntw");
+    }
+
+    #[test]
+    fn test_confirm() {
+        let path = env::temp_dir().join("revise_confirm.txt");
+        let mut revise = editor("new\n");
+
+        std::fs::write(&path, "old\n").unwrap();
+
+        for key in [Key::Char('x'), Key::Char('y'), Key::Esc, Key::Char('n')] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        assert!(revise.confirm("Really?"));
+        assert!(revise.backend().output().contains("Really? (y/n)"));
+        assert!(!revise.confirm("Really?"));
+
+        revise.execute_command(&format!("w {}", path.display()));
+
+        assert_eq!(revise.status_message.text, "Save aborted.");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_remove_duplicate_lines() {
+        let mut revise = editor("a\na\nb\nb\nc\nc\nc\n");
+
+        revise.selection_anchor = Some(Position { x: 0, y: 1 });
+        revise.cursor_position = Position { x: 0, y: 4 };
+        revise.execute_command("uniq");
+
+        assert_eq!(revise.document.contents(), "a\na\nb\nc\nc\nc\n");
+        assert_eq!(revise.status_message.text, "Removed 1 duplicate line.");
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 3 });
+
+        revise.execute_command("uniq");
+
+        assert_eq!(revise.document.contents(), "a\nb\nc\n");
+        assert_eq!(revise.status_message.text, "Removed 3 duplicate lines.");
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 2 });
+
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "a\na\nb\nc\nc\nc\n");
+    }
+
+    #[test]
+    fn test_retab() {
+        let mut revise = editor("if x {\n\ty = 1;\n\tif y {\n\t\tz();\n\t}\n}\n");
+
+        revise.config.tab_width = 2;
+        revise.cursor_position = Position { x: 3, y: 3 };
+        revise.execute_command("indent");
+
+        assert_eq!(revise.status_message.text, "Indented with tabs.");
+        assert!(!revise.expand_tabs());
+        assert_eq!(revise.config.overrides.expand_tabs, None);
+
+        revise.execute_command("retab spaces");
+
+        assert_eq!(revise.current_line(), Some("    z();"));
+        assert_eq!(revise.cursor_position, Position { x: 5, y: 3 });
+        assert!(revise.document.is_changed());
+
+        revise.execute_command("indent");
+
+        assert!(revise.expand_tabs());
+
+        revise.undo();
+
+        assert_eq!(
+            revise.document.contents(),
+            "if x {\n\ty = 1;\n\tif y {\n\t\tz();\n\t}\n}\n"
+        );
+
+        revise.execute_command("retab tabs");
+
+        assert_eq!(
+            revise.status_message.text,
+            "Indentation is already converted."
+        );
+    }
+
+    #[test]
+    fn test_format_on_save() {
+        let path = env::temp_dir().join("revise_format_on_save.txt");
+
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(document, Size::default());
+
+        revise
+            .config
+            .formatters
+            .insert("txt".to_owned(), "tr a-z A-Z".to_owned());
+        revise.cursor_position = Position { x: 2, y: 1 };
+        revise.insert_text("o");
+        revise.save();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ONE\nTWOO\n");
+        assert_eq!(revise.cursor_position, Position { x: 3, y: 1 });
+        assert!(revise
+            .status_message
+            .text
+            .starts_with("File saved successfully"));
+
+        revise
+            .config
+            .formatters
+            .insert("txt".to_owned(), "echo broken >&2; exit 1".to_owned());
+        revise.insert_text("s");
+        revise.save();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ONE\nTWOsO\n");
+        assert_eq!(
+            revise.status_message.text,
+            "File saved unformatted, echo broken >&2; exit 1 failed: broken"
+        );
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(path.with_extension("txt.bak")).ok();
+    }
+
+    #[test]
+    fn test_auto_save() {
+        let path = env::temp_dir().join("revise_auto_save.txt");
+        let mut revise = editor("text\n");
+        let due = Instant::now() - Duration::from_secs(2);
+
+        revise.config.autosave_interval = 1;
+        revise.document.filename = Some(
+            env::temp_dir()
+                .join("revise_auto_save_missing")
+                .join("file.txt")
+                .display()
+                .to_string(),
+        );
+        revise.insert_text("more ");
+        revise.last_keypress = due;
+        revise.autosave_attempt = due;
+        revise.process_idle();
+
+        assert!(revise.status_message.text.starts_with("Auto-save failed"));
+        assert!(revise.poll_timeout() > Duration::from_millis(500));
+
+        std::fs::write(&path, "text\n").unwrap();
+        revise.document.filename = Some(path.display().to_string());
+        revise.document.read_only = true;
+        revise.autosave_attempt = due;
+        revise.process_idle();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "text\n");
+
+        revise.document.read_only = false;
+        revise.process_idle();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "more text\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_changed_on_disk() {
+        let path = env::temp_dir().join("revise_changed_on_disk.txt");
+        let write = |text: &str, seconds| {
+            std::fs::write(&path, text).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+        };
+
+        write("old\n", 1_000);
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(document, Size::default());
+
+        revise.insert_text("mine ");
+        write("theirs\n", 2_000);
+        revise.warn_disk_change();
+
+        assert!(revise.status_message.text.contains("changed on disk"));
+
+        for key in [Key::Char('c'), Key::Char('\n')] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        revise.save();
+
+        assert_eq!(revise.status_message.text, "Save aborted.");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "theirs\n");
+
+        for key in [Key::Char('r'), Key::Char('\n')] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        revise.save();
+
+        assert_eq!(revise.current_line(), Some("theirs"));
+        assert!(!revise.document.is_changed());
+
+        revise.cursor_position = Position::default();
+        revise.insert_text("mine ");
+        write("again\n", 3_000);
+
+        for key in [Key::Char('o'), Key::Char('\n')] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        revise.save();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "mine theirs\n");
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(path.with_extension("txt.bak")).ok();
+    }
+
+    #[test]
+    fn test_lazy_rows() {
+        let path = env::temp_dir().join("revise_lazy_rows.txt");
+        let contents: String = (1..=200).map(|n| format!("line {n}\n")).collect();
+
+        std::fs::write(&path, contents).unwrap();
+
+        let document = Document::open_lazy(&path.display().to_string()).unwrap();
+        let size = Size {
+            width: 80,
+            height: 22,
+        };
+        let mut revise = Revise::from_document(document, size);
+
+        revise.cursor_position = Position { x: 0, y: 150 };
+        revise.scroll();
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(
+            revise.document.row(150).map(|row| row.as_string().as_str()),
+            Some("line 151")
+        );
+        assert_eq!(revise.document.row(0).map(Row::len), Some(0));
+
+        let copy = env::temp_dir().join("revise_lazy_rows_copy.txt");
+
+        std::fs::write(&copy, "").unwrap();
+        revise.backend_mut().push_event(Event::Key(Key::Char('y')));
+        revise.execute_command(&format!("w {}", copy.display()));
+
+        assert_eq!(
+            revise.status_message.text,
+            "Error writing file: the file is too large to be saved."
+        );
+        assert!(!copy.with_extension("txt.bak").exists());
+        std::fs::remove_file(&copy).ok();
+
+        revise.perform(Action::ToggleReadOnly);
+
+        assert!(revise.document.read_only);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_loading_progress() {
+        let path = env::temp_dir().join("revise_loading_progress.txt");
+        let filename = path.display().to_string();
+        let line = "0123456789abcdef\n";
+        let lines = PROGRESS_SIZE as usize / line.len() + 1;
+
+        std::fs::write(&path, line.repeat(lines)).unwrap();
+
+        let mut revise = editor("");
+
+        revise.open_file(&filename);
+
+        assert!(revise.backend().output().contains("Loading"));
+        assert_eq!(revise.status_message.text, format!("Loaded {lines} lines."));
+        assert_eq!(revise.document.len(), lines);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_quit() {
+        let mut revise = editor("scratch\n");
+
+        revise.open_file("Cargo.toml");
+        revise.quit().unwrap();
+
+        assert!(revise.should_quit);
+
+        revise.should_quit = false;
+        revise.insert_text("!");
+        revise.cycle_buffer(SearchDirection::Backward);
+        revise.insert_text("!");
+
+        for event in [
+            Event::Key(Key::Ctrl('q')),
+            Event::Resize,
+            Event::Key(Key::Right),
+            Event::Key(Key::Ctrl('q')),
+            Event::Key(Key::Ctrl('q')),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(
+            revise.status_message.text,
+            "WARNING! There are unsaved changes in [No Name], Cargo.toml. Press Ctrl-Q 1 more time to quit."
+        );
+
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert!(!revise.should_quit);
+
+        revise.process_keypress().unwrap();
+
+        assert!(revise.should_quit);
+    }
+
+    #[test]
+    fn test_split() {
+        let text: String = (0..30).map(|index| format!("line {index}\n")).collect();
+        let mut revise = editor(&text);
+
+        revise.config.highlight_symbol = false;
+        revise.config.scrolloff = 0;
+        revise.clear_screen();
+        revise.toggle_split(SplitDirection::SideBySide);
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.view().width, 39);
+        assert!(revise.backend().output().contains(&format!(
+            "{}\u{2502}{}line 0",
+            cursor::Goto(40, 1),
+            cursor::Goto(41, 1)
+        )));
+        assert!(revise.backend().output().contains("pane 1/2"));
+
+        revise.toggle_split(SplitDirection::Stacked);
+        revise.backend_mut().push_event(Event::Key(Key::Ctrl('w')));
+        revise.process_keypress().unwrap();
+        revise.move_cursor(Key::PageDown);
+        revise.scroll();
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.view().y, 11);
+        assert_eq!(revise.cursor_position().y, 11);
+        assert_eq!(revise.offset.y, 1);
+        assert!(revise.backend().output().contains("pane 2/2"));
+
+        revise
+            .backend_mut()
+            .push_event(Event::Mouse(MouseEvent::Press(MouseButton::Left, 1, 3)));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position().y, 2);
+        assert!(revise.split.as_ref().is_some_and(|split| split.focus_first));
+
+        revise.toggle_split(SplitDirection::Stacked);
+
+        assert!(revise.split.is_none());
+        assert_eq!(revise.view().height, 22);
+    }
+
+    #[test]
+    fn test_whitespace_warnings() {
+        let mut revise = editor("clean\ntrailing \n");
+
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains('\u{258c}'));
+
+        revise.config.whitespace_warnings = true;
+        revise.config.line_numbers = true;
+        revise.refresh_screen().unwrap();
+
+        let output = revise.backend().output();
+
+        assert_eq!(revise.gutter_width(), 3);
+        assert_eq!(output.matches('\u{258c}').count(), 1);
+        assert!(output.contains(&format!(
+            "\u{258c}{}{}2 ",
+            color::Fg(color::Reset),
+            color::Fg(revise.theme.line_number)
+        )));
+    }
+
+    #[test]
+    fn test_poll_timeout() {
+        let mut revise = editor("text\n");
+
+        revise.status_message = StatusMessage::from(String::new());
+
+        assert_eq!(revise.poll_timeout(), TICK_INTERVAL);
+
+        revise.status_message = StatusMessage {
+            text: "Saved.".to_owned(),
+            time: Instant::now() - Duration::from_millis(4800),
+            help: false,
+        };
+
+        assert!(revise.poll_timeout() <= Duration::from_millis(200));
+
+        revise.status_message.time = Instant::now() - Duration::from_secs(5);
+
+        assert_eq!(revise.poll_timeout(), TICK_INTERVAL);
+
+        revise.config.message_duration = 0;
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("Saved."));
+        assert_eq!(revise.poll_timeout(), TICK_INTERVAL);
+
+        revise.config.message_duration = 5;
+        revise.config.help_duration = 30;
+        revise.status_message = StatusMessage::help("HELP: Ctrl-Q = quit".to_owned());
+        revise.status_message.time = Instant::now() - Duration::from_secs(10);
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("HELP: Ctrl-Q = quit"));
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut revise = editor("text\n");
+
+        revise.config.clock = true;
+        revise.now = fixed_time;
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains(" | 07:05"));
+
+        let document = Document::from_reader("text\n".as_bytes()).unwrap();
+        let mut revise = Revise::from_document(
+            document,
+            Size {
+                width: 64,
+                height: 22,
+            },
+        );
+
+        revise.config.clock = true;
+        revise.now = fixed_time;
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains(" | 07:05"));
+        assert!(revise.backend().output().contains("5 bytes"));
+    }
+
+    #[test]
+    fn test_open_recent() {
+        let first = env::temp_dir().join("revise_test_recent_first.txt");
+        let second = env::temp_dir().join("revise_test_recent_second.txt");
+        let mut revise = editor("");
+
+        std::fs::write(&first, "first\n").unwrap();
+        std::fs::write(&second, "second\n").unwrap();
+
+        revise.perform(Action::RecentFiles);
+
+        assert_eq!(revise.status_message.text, "No recent files.");
+
+        revise.remember_file(&first.to_string_lossy());
+        revise.remember_file(&second.to_string_lossy());
+        revise.remember_file(&first.to_string_lossy());
+
+        assert_eq!(revise.recent.files().len(), 2);
+
+        revise.backend_mut().push_event(Event::Key(Key::Down));
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.perform(Action::RecentFiles);
+
+        assert_eq!(revise.document.contents(), "second\n");
+        assert!(revise.picker.is_none());
+
+        std::fs::remove_file(&first).ok();
+        revise.backend_mut().push_event(Event::Key(Key::Down));
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.perform(Action::RecentFiles);
+
+        assert!(revise.status_message.text.ends_with("no longer exists."));
+        assert_eq!(revise.recent.files().len(), 1);
+
+        revise.backend_mut().push_event(Event::Key(Key::Esc));
+        revise.perform(Action::RecentFiles);
+
+        assert_eq!(revise.document.contents(), "second\n");
+
+        std::fs::remove_file(&second).ok();
+    }
+
+    #[test]
+    fn test_find_file() {
+        let mut revise = editor("");
+
+        revise.backend_mut().push_text("srcfinder");
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.perform(Action::FindFile);
+
+        assert_eq!(revise.document.filename.as_deref(), Some("src/finder.rs"));
+
+        revise.backend_mut().push_text("no such file, surely");
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.perform(Action::FindFile);
+
+        assert_eq!(revise.document.filename.as_deref(), Some("src/finder.rs"));
+        assert!(revise.picker.is_none());
+    }
+
+    #[test]
+    fn test_find_in_files() {
+        let mut revise = editor("");
+
+        revise.backend_mut().push_text("fn find_in_files(");
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.perform(Action::FindInFiles);
+
+        assert_eq!(
+            revise.document.filename.as_deref(),
+            Some("src/application.rs")
+        );
+        assert!(revise
+            .current_line()
+            .is_some_and(|line| line.trim_start().starts_with("fn find_in_files(")));
+        assert_eq!(revise.search_history, ["fn find_in_files("]);
+
+        revise.backend_mut().push_text("no\u{1}such\u{1}text");
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.perform(Action::FindInFiles);
+
+        assert!(revise.status_message.text.starts_with("No files contain"));
+    }
+
+    #[test]
+    fn test_pick_wide_items() {
+        let mut revise = editor("");
+
+        revise.backend_mut().push_event(Event::Key(Key::Esc));
+        revise.pick("Files", vec!["字".to_owned(), "漢".repeat(50)]);
+
+        let output = revise.backend().output();
+
+        assert!(output.contains(&format!("字{}{}", " ".repeat(78), style::NoInvert)));
+        assert!(output.contains(&"漢".repeat(40)));
+        assert!(!output.contains(&"漢".repeat(41)));
+    }
+
+    #[test]
+    fn test_pick_control_characters() {
+        let mut revise = editor("");
+
+        revise.backend_mut().push_event(Event::Key(Key::Esc));
+
+        assert_eq!(
+            revise.pick("Lines", vec!["a\u{1b}]2;x\u{7}\tb".to_owned()]),
+            None
+        );
+        assert!(revise.backend().output().contains("a^[]2;x^G b"));
+        assert!(!revise.backend().output().contains("\u{1b}]2;x"));
+    }
+
+    #[test]
+    fn test_file_reference() {
+        let reference = |path: &str, line, column| {
+            Some(FileReference {
+                path: path.to_owned(),
+                line,
+                column,
+            })
+        };
+
+        assert_eq!(
+            file_reference("  --> src/row.rs:471:12", 0),
+            reference("src/row.rs", 471, Some(12))
+        );
+        assert_eq!(
+            file_reference("see (main.rs:3), then a.rs:9:", 24),
+            reference("a.rs", 9, None)
+        );
+        assert_eq!(
+            file_reference("see (main.rs:3), then a.rs:9:", 1),
+            reference("main.rs", 3, None)
+        );
+        assert_eq!(file_reference("no reference: here", 3), None);
+        assert_eq!(file_reference("file.rs:0", 0), None);
+    }
+
+    #[test]
+    fn test_goto_reference() {
+        let mut revise = editor("error at src/finder.rs:5:5\nmissing.rs:2\n");
+
+        revise.cursor_position = Position { x: 0, y: 1 };
+        revise.perform(Action::GotoReference);
+
+        assert_eq!(revise.status_message.text, "missing.rs does not exist.");
+
+        revise.cursor_position = Position { x: 12, y: 0 };
+        revise.perform(Action::GotoReference);
+
+        assert_eq!(revise.document.filename.as_deref(), Some("src/finder.rs"));
+        assert_eq!(revise.cursor_position, Position { x: 4, y: 4 });
+    }
+
+    #[test]
+    fn test_hooks() {
+        let path = env::temp_dir().join("revise_test_hooks.txt");
+        let filename = path.display().to_string();
+
+        std::fs::write(&path, "text\n").unwrap();
+
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let log = std::rc::Rc::clone(&events);
+        let mut revise = Revise::from_document(Document::open(&filename).unwrap(), Size::default());
+
+        revise.add_hook(move |event| {
+            log.borrow_mut().push(match event {
+                HookEvent::Opened(_) => "opened".to_owned(),
+                HookEvent::BeforeSave(_) => "before save".to_owned(),
+                HookEvent::AfterSave(_) => "after save".to_owned(),
+                HookEvent::CursorMoved(position) => format!("moved to {}", position.x),
+                HookEvent::TextChanged => "changed".to_owned(),
+            });
+            Ok(())
+        });
+        revise.backend_mut().push_text("a");
+        revise.process_keypress().unwrap();
+        revise.backend_mut().push_event(Event::Key(Key::End));
+        revise.process_keypress().unwrap();
+        revise.save();
+
+        assert_eq!(
+            *events.borrow(),
+            [
+                "changed",
+                "moved to 1",
+                "moved to 5",
+                "before save",
+                "after save"
+            ]
+        );
+        assert!(revise
+            .status_message
+            .text
+            .starts_with("File saved successfully"));
+
+        events.borrow_mut().clear();
+        revise.insert_text("xy");
+        revise.delete_range(&Position { x: 0, y: 0 }, &Position { x: 2, y: 0 });
+
+        assert_eq!(
+            *events.borrow(),
+            ["changed", "moved to 7", "changed", "moved to 0"]
+        );
+
+        events.borrow_mut().clear();
+        revise.replace_range(&Position { x: 0, y: 0 }, &Position { x: 2, y: 0 }, "AB");
+
+        assert!(revise.filter_through_command("tr a-z A-Z").is_ok());
+        assert_eq!(revise.document.contents(), "ABTXY\n");
+        assert_eq!(*events.borrow(), ["changed", "moved to 2", "changed"]);
+
+        revise.add_hook(|event| match event {
+            HookEvent::TextChanged => Err("read-only, sorry".into()),
+            _ => Ok(()),
+        });
+        revise.backend_mut().push_text("b");
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.status_message.text, "Hook failed: read-only, sorry");
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_bracket_warning() {
+        let path = env::temp_dir().join("revise_test_bracket_warning.rs");
+        let filename = path.display().to_string();
+
+        std::fs::write(&path, "fn main() {\n    run();\n").unwrap();
+
+        let mut revise = Revise::from_document(Document::open(&filename).unwrap(), Size::default());
+
+        revise.save();
+
+        assert!(revise
+            .status_message
+            .text
+            .ends_with("Unbalanced { on line 1."));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn main() {\n    run();\n"
+        );
+
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_encoding() {
+        let path = env::temp_dir().join("revise_test_encoding.txt");
+        let filename = path.display().to_string();
+
+        std::fs::write(&path, b"caf\xe9\n").unwrap();
+
+        let mut revise = Revise::from_document(Document::open(&filename).unwrap(), Size::default());
+
+        assert_eq!(revise.document.encoding(), Encoding::Latin1);
+        assert_eq!(revise.document.row(0).unwrap().as_string(), "café");
+
+        revise.execute_command("reopen utf-8");
+
+        assert!(revise.status_message.text.starts_with("Cannot reopen"));
+        assert_eq!(revise.document.encoding(), Encoding::Latin1);
+
+        revise.execute_command("encoding ebcdic");
+
+        assert_eq!(revise.status_message.text, "Unknown encoding ebcdic.");
+
+        revise.execute_command("encoding utf-16le");
+        revise.save();
+
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"\xff\xfec\0a\0f\0\xe9\0\n\0"
+        );
+
+        revise.execute_command("reopen latin1");
+
+        assert_eq!(revise.document.encoding(), Encoding::Latin1);
+        assert!(revise
+            .document
+            .row(0)
+            .unwrap()
+            .as_string()
+            .starts_with("\u{ff}\u{fe}c\0"));
+
+        revise.execute_command("reopen utf-16");
+        revise.execute_command("encoding latin1");
+        revise.save();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"caf\xe9\n");
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(format!("{filename}.bak")).ok();
+    }
+
+    #[test]
+    fn test_toggle_whitespace() {
+        let mut revise = editor("\tlet x = 1;\n");
+
+        revise.perform(Action::ToggleWhitespace);
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.status_message.text, "Whitespace is shown.");
+        assert!(revise.backend().output().contains("\u{2192}   let\u{b7}x"));
+
+        revise.perform(Action::ToggleWhitespace);
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.status_message.text, "Whitespace is hidden.");
+        assert!(revise.backend().output().contains(" let x"));
+    }
+
+    #[test]
+    fn test_undo_selection() {
+        let mut revise = editor("one\ntwo\nthree\n");
+        let anchor = Position { x: 1, y: 0 };
+
+        revise.selection_anchor = Some(anchor.clone());
+        revise.cursor_position = Position { x: 2, y: 1 };
+
+        for key in [Key::Ctrl('x'), Key::Ctrl('z')] {
+            revise.backend_mut().push_event(Event::Key(key));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.document.contents(), "one\ntwo\nthree\n");
+        assert_eq!(revise.selection_anchor, Some(anchor));
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 1 });
+
+        revise.backend_mut().push_event(Event::Key(Key::Ctrl('y')));
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "oo\nthree\n");
+        assert_eq!(revise.selection_anchor, None);
+        assert_eq!(revise.cursor_position, Position { x: 1, y: 0 });
+    }
+
+    #[test]
+    fn test_insert_date() {
+        let mut revise = editor("Notes\n");
+
+        revise.config.date_format = String::from("[%Y]");
+        revise.cursor_position.x = 5;
+        revise.now = fixed_time;
+        revise.perform(Action::InsertDate);
+
+        assert_eq!(revise.document.contents(), "Notes[2024]\n");
+        assert_eq!(revise.cursor_position.x, 11);
+
+        revise.document.read_only = true;
+        revise.backend_mut().push_event(Event::Key(Key::Ctrl('k')));
+        revise.backend_mut().push_event(Event::Key(Key::Char('d')));
+        revise.process_keypress().unwrap();
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.document.contents(), "Notes[2024]\n");
+        assert_eq!(revise.status_message.text, "Buffer is read-only.");
+    }
+
+    #[test]
+    fn test_change_case() {
+        let mut revise = editor("let max_size = straße;\nnext\n");
+
+        revise.cursor_position.x = 6;
+        revise.perform(Action::UpperCase);
+
+        assert_eq!(revise.document.contents(), "let MAX_SIZE = straße;\nnext\n");
+        assert_eq!(revise.cursor_position.x, 6);
+
+        revise.selection_anchor = Some(Position { x: 15, y: 0 });
+        revise.cursor_position = Position { x: 2, y: 1 };
+        revise.perform(Action::UpperCase);
+
+        assert_eq!(
+            revise.document.contents(),
+            "let MAX_SIZE = STRASSE;\nNExt\n"
+        );
+        assert_eq!(revise.selected_text().as_deref(), Some("STRASSE;\nNE"));
+
+        revise.perform(Action::ToggleCase);
+
+        assert_eq!(
+            revise.document.contents(),
+            "let MAX_SIZE = strasse;\nnext\n"
+        );
+
+        revise.selection_anchor = None;
+        revise.cursor_position = Position { x: 4, y: 0 };
+        revise.perform(Action::TitleCase);
+
+        assert_eq!(
+            revise.document.contents(),
+            "let Max_Size = strasse;\nnext\n"
+        );
+        assert_eq!(revise.status_message.text, "Changed to title case.");
+
+        revise.undo();
+
+        assert_eq!(
+            revise.document.contents(),
+            "let MAX_SIZE = strasse;\nnext\n"
+        );
+
+        revise.cursor_position = Position { x: 13, y: 0 };
+        revise.perform(Action::LowerCase);
+
+        assert_eq!(
+            revise.status_message.text,
+            "No selection or word to change."
+        );
+    }
+
+    #[test]
+    fn test_add_to_number() {
+        let mut revise = editor("width = 9;\nnone\n");
+
+        for key in [Key::Alt('a'), Key::Alt('1'), Key::Alt('2'), Key::Alt('x')] {
+            revise.backend_mut().push_event(Event::Key(key));
+            revise.process_keypress().unwrap();
+        }
+
+        assert_eq!(revise.document.contents(), "width = -2;\nnone\n");
+        assert_eq!(revise.cursor_position.x, 9);
+
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "width = 10;\nnone\n");
+
+        revise.cursor_position = Position { x: 0, y: 1 };
+        revise.perform(Action::Increment);
+
+        assert_eq!(revise.status_message.text, "No number to change.");
+    }
+
+    #[test]
+    fn test_scrollbar() {
+        let text: String = (0..100).map(|line| format!("{line:0>90}\n")).collect();
+        let mut revise = editor(&text);
+        let symbol = |revise: &Revise<TestBackend>, y: u16, symbol: &str| {
+            revise.backend().output().contains(&format!(
+                "{}{}{symbol}",
+                cursor::Goto(80, y),
+                color::Fg(revise.theme.line_number)
+            ))
+        };
+
+        revise.config.scrollbar = true;
+        revise.refresh_screen().unwrap();
+
+        assert!(symbol(&revise, 1, "\u{2588}"));
+        assert!(symbol(&revise, 5, "\u{2502}"));
+        assert!(revise
+            .backend()
+            .output()
+            .contains(&format!("{:0>79}\u{1b}", 0)));
+
+        revise.offset.y = 78;
+        revise.refresh_screen().unwrap();
+
+        assert!(symbol(&revise, 22, "\u{2588}"));
+        assert!(symbol(&revise, 19, "\u{2588}"));
+        assert!(symbol(&revise, 18, "\u{2502}"));
+    }
+
+    #[test]
+    fn test_indent_guides() {
+        let mut revise = editor("fn main() {\n        run();\n}\n");
+        let guide = format!("{}\u{2502}", color::Fg(revise.theme.indent_guide));
+
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains(&guide));
+
+        revise.execute_command("set indent_guides");
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.backend().output().matches(&guide).count(), 2);
+    }
+
+    #[test]
+    fn test_fit_width() {
+        let message = "Could not open file /home/user/projects/revise/src/main.rs: denied";
+
+        assert_eq!(fit_width("short", 10, "\u{2026}"), "short");
+        assert_eq!(
+            fit_width(message, 50, "\u{2026}"),
+            "Could not open file \u{2026}ts/revise/src/main.rs: denied"
+        );
+        assert_eq!(
+            fit_width(message, 30, "..."),
+            "Could not open file /home/u..."
+        );
+        assert_eq!(fit_width("a rather long message", 10, "..."), "a rathe...");
+        assert_eq!(fit_width("漢字漢字漢字", 7, "..."), "漢字...");
+
+        let mut revise = Revise::from_document(
+            Document::default(),
+            Size {
+                width: 50,
+                height: 22,
+            },
+        );
+
+        revise.status_message = StatusMessage::from(message.to_owned());
+        revise.document.filename = Some("/home/user/projects/revise/src/main.rs".to_owned());
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains("revise/src/main.rs: denied"));
+        assert!(revise
+            .backend()
+            .output()
+            .contains("/home/\u{2026}/main.rs - 0 lines"));
+    }
+
+    #[test]
+    fn test_welcome() {
+        let mut revise = editor("");
+
+        revise.refresh_screen().unwrap();
+
+        let output = revise.backend().output();
+
+        assert!(output.contains(&format!("Revise | v{VERSION}")));
+        assert!(output.contains("Ctrl-O     open a file"));
+        assert!(!output.contains("Recent files"));
+
+        revise.remember_file("/tmp/revise_test_welcome.txt");
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains("/tmp/revise_test_welcome.txt"));
+        assert!(revise.backend().output().contains("reopen a recent file"));
+
+        let mut revise = editor("");
+
+        revise.execute_command("set nowelcome");
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains("Revise | v"));
+        assert_eq!(centered("Revise", 4, 1), "Rev");
+        assert_eq!(centered("Revise", 0, 1), "");
+        assert_eq!(centered("ab", 8, 1), "  ab");
+        assert_eq!(centered("ab", 8, 0), "   ab");
+        assert_eq!(centered("漢字", 8, 1), " 漢字");
+        assert_eq!(centered("漢字", 4, 1), "漢");
+    }
+
+    #[test]
+    fn test_tiny_terminal() {
+        let mut revise = editor("");
+
+        for direction in [Action::SplitStacked, Action::SplitSideBySide] {
+            revise.perform(direction);
+
+            for (width, rows) in [(0, 0), (1, 1), (5, 1), (20, 2), (20, 3), (0, 4)] {
+                revise.backend_mut().resize(width, rows);
+                revise.process_keypress().unwrap();
+                revise.refresh_screen().unwrap();
+            }
+
+            revise.perform(direction);
+        }
+
+        revise.perform(Action::Help);
+        revise.refresh_screen().unwrap();
+        revise.perform(Action::Help);
+
+        revise.backend_mut().resize(30, 1);
+        revise.process_keypress().unwrap();
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        let output = revise.backend().output().to_owned();
+
+        assert!(output.contains("[No Name]"));
+        assert!(!output.contains("Revise | v"));
+        assert!(!output.contains(&format!("{}", cursor::Goto(1, 2))));
+
+        revise.backend_mut().resize(80, 24);
+        revise.process_keypress().unwrap();
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("Revise | v"));
+    }
+
+    #[test]
+    fn test_abbreviate_path() {
+        let ellipsis = "\u{2026}";
+
+        assert_eq!(abbreviate_path("src/main.rs", 20, ellipsis), "src/main.rs");
+        assert_eq!(
+            abbreviate_path("src/editor/application.rs", 22, "..."),
+            "src/.../application.rs"
+        );
+        assert_eq!(
+            abbreviate_path("/home/user/projects/revise/src/main.rs", 28, ellipsis),
+            "/home/\u{2026}/revise/src/main.rs"
+        );
+        assert_eq!(
+            abbreviate_path("/home/user/projects/revise/src/main.rs", 16, ellipsis),
+            "/home/\u{2026}/main.rs"
+        );
+        assert_eq!(
+            abbreviate_path("src/editor/application.rs", 16, ellipsis),
+            "\u{2026}/application.rs"
+        );
+        assert_eq!(
+            abbreviate_path("src/editor/application.rs", 8, ellipsis),
+            "\u{2026}tion.rs"
+        );
+        assert_eq!(abbreviate_path("application.rs", 2, "..."), "..");
+        assert_eq!(
+            abbreviate_path("文書/メモ帳.txt", 12, ellipsis),
+            "\u{2026}/メモ帳.txt"
+        );
+        assert_eq!(abbreviate_path("メモ帳.txt", 7, ellipsis), "\u{2026}帳.txt");
+    }
+
+    #[test]
+    fn test_git_gutter() {
+        let root = env::temp_dir().join("revise_test_git_gutter");
+        let path = root.join("notes.txt");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args(args)
+                .output()
+                .map(|output| output.status.success())
+        };
+
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+
+        if !matches!(git(&["init", "-q"]), Ok(true)) {
+            return;
+        }
+
+        git(&["add", "notes.txt"]).unwrap();
+        git(&[
+            "-c",
+            "user.name=Revise",
+            "-c",
+            "user.email=revise@example.com",
+            "commit",
+            "-qm",
+            "notes",
+        ])
+        .unwrap();
+
+        let mut revise = editor("");
+
+        revise.config.git_gutter = true;
+        revise.open_file(&path.display().to_string());
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.document.is_committed());
+        assert_eq!(revise.gutter_width(), 1);
+        assert_eq!(revise.document.change(0), None);
+
+        revise.insert_text("new\n");
+        revise.cursor_position = Position { x: 0, y: 2 };
+        revise.insert_text("2");
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.document.change(0), Some(Change::Added));
+        assert_eq!(revise.document.change(2), Some(Change::Modified));
+        assert!(revise
+            .backend()
+            .output()
+            .contains(&format!("{}+", color::Fg(revise.theme.added))));
+
+        revise.save();
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.document.change(0), Some(Change::Added));
+
+        let outside = env::temp_dir().join("revise_test_git_gutter.txt");
+        let mut revise = editor("");
+
+        revise.config.git_gutter = true;
+        std::fs::write(&outside, "one\n").unwrap();
+        revise.open_file(&outside.display().to_string());
+
+        assert!(!revise.document.is_committed());
+        assert_eq!(revise.gutter_width(), 0);
+        std::fs::remove_dir_all(root).ok();
+        std::fs::remove_file(outside).ok();
+    }
+
+    #[test]
+    fn test_revert() {
+        let mut revise = editor("one\ntwo\nthree\n");
+
+        revise.perform(Action::Revert);
+
+        assert!(revise
+            .status_message
+            .text
+            .ends_with("has no changes to revert."));
+
+        revise.cursor_position = Position { x: 5, y: 2 };
+        revise.insert_text(" four\nfive");
+        revise.backend_mut().push_event(Event::Key(Key::Char('n')));
+        revise.perform(Action::Revert);
+
+        assert_eq!(revise.status_message.text, "Revert aborted.");
+        assert!(revise.document.is_changed());
+
+        revise.document.read_only = true;
+        revise.perform(Action::Revert);
+
+        assert_eq!(revise.status_message.text, "Buffer is read-only.");
+        assert!(revise.document.is_changed());
+
+        revise.document.read_only = false;
+
+        revise.backend_mut().push_event(Event::Key(Key::Char('y')));
+        revise.perform(Action::Revert);
+
+        assert_eq!(revise.document.contents(), "one\ntwo\nthree\n");
+        assert!(!revise.document.is_changed());
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 3 });
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let root = env::temp_dir().join("revise_test_complete_prompt");
+        let base = root.display().to_string();
+
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("src/make.rs"), "").unwrap();
+
+        let mut revise = editor("");
+
+        revise.backend_mut().push_text(&format!("{base}/s\tma\t\t"));
+        revise.backend_mut().push_event(Event::Key(Key::BackTab));
+        revise.backend_mut().push_text("\n");
+        revise.perform(Action::Open);
+
+        assert_eq!(
+            revise.document.filename,
+            Some(format!("{base}/src/main.rs"))
+        );
+        assert_eq!(revise.document.contents(), "fn main() {}\n");
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_prompt_text() {
+        let mut revise = editor("");
+
+        revise.backend_mut().push_text("cafe\u{301}");
+        revise.backend_mut().push_event(Event::Key(Key::Backspace));
+        revise
+            .backend_mut()
+            .push_event(Event::Paste("\u{e9} au\nlait".to_owned()));
+        revise.backend_mut().push_text("\n");
+
+        assert_eq!(
+            revise.prompt("Find: ", |_, _, _| {}).unwrap(),
+            Some("caf\u{e9} au lait".to_owned())
+        );
+
+        revise.backend_mut().push_text("\u{e9}");
+        revise.backend_mut().push_event(Event::Key(Key::Backspace));
+        revise.backend_mut().push_event(Event::Key(Key::Backspace));
+        revise.backend_mut().push_text("\n");
+
+        assert_eq!(revise.prompt("Find: ", |_, _, _| {}).unwrap(), None);
+    }
+
+    #[test]
+    fn test_follow_line_shifts() {
+        let text: String = (1..=6).map(|n| format!("line {n}\n")).collect();
+        let mut revise = editor(&text);
+        let press = |revise: &mut Revise<TestBackend>, key| {
+            revise.backend_mut().push_event(Event::Key(key));
+            revise.process_keypress().unwrap();
+        };
+
+        revise.perform(Action::SplitStacked);
+        revise.split.as_mut().unwrap().cursor_position = Position { x: 2, y: 4 };
+        revise.marks.insert('a', Position { x: 0, y: 3 });
+        revise.cursor_position = Position { x: 0, y: 1 };
+        press(&mut revise, Key::Ctrl('x'));
+
+        let other = revise.split.as_ref().unwrap().cursor_position.clone();
+
+        assert_eq!(other, Position { x: 2, y: 3 });
+        assert_eq!(revise.document.row(other.y).unwrap().as_string(), "line 5");
+        assert_eq!(revise.marks[&'a'].y, 2);
+
+        revise.cursor_position = Position { x: 0, y: 0 };
+        press(&mut revise, Key::Char('\n'));
+
+        assert_eq!(revise.split.as_ref().unwrap().cursor_position.y, 4);
+        assert_eq!(revise.marks[&'a'].y, 3);
+
+        press(&mut revise, Key::Ctrl('z'));
+        press(&mut revise, Key::Ctrl('z'));
+
+        assert_eq!(revise.split.as_ref().unwrap().cursor_position.y, 4);
+        assert_eq!(revise.marks[&'a'].y, 3);
+        assert_eq!(revise.document.row(4).unwrap().as_string(), "line 5");
+    }
+
+    #[test]
+    fn test_spell_check() {
+        let path = env::temp_dir().join("revise_spell_check.rs");
+
+        std::fs::write(&path, "// teh end\nlet teh = 1;\n").unwrap();
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(
+            document,
+            Size {
+                width: 80,
+                height: 22,
+            },
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        revise.config.spell_check = true;
+        revise.dictionary = Some(Dictionary::from_words("the\nend\nlet\n", None));
+        revise.refresh_screen().unwrap();
+
+        let misspelled = format!("{}teh{}", style::Underline, style::NoUnderline);
+
+        assert_eq!(revise.backend().output().matches(&misspelled).count(), 1);
+
+        revise.cursor_position = Position { x: 4, y: 0 };
+        revise.perform(Action::AddWord);
+
+        assert_eq!(revise.status_message.text, "Added teh to the dictionary.");
+
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains(&misspelled));
+
+        revise.execute_command("set nospell_check");
+
+        assert!(revise.dictionary.is_none());
+
+        revise.perform(Action::AddWord);
+
+        assert_eq!(
+            revise.status_message.text,
+            "Spell checking is off, turn it on with set spell_check."
+        );
+    }
+
+    #[test]
+    fn test_strip_bom() {
+        let mut revise = editor("\u{feff}one\ntwo\n");
+
+        revise.cursor_position = Position { x: 2, y: 0 };
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("UTF-8 BOM"));
+        assert!(revise.backend().output().contains("<feff>"));
+
+        revise.execute_command("strip_bom");
+
+        assert_eq!(revise.status_message.text, "Removed the byte order mark.");
+        assert_eq!(revise.document.contents(), "one\ntwo\n");
+        assert_eq!(revise.cursor_position, Position { x: 1, y: 0 });
+
+        revise.execute_command("strip_bom");
+
+        assert_eq!(
+            revise.status_message.text,
+            "There is no byte order mark to remove."
+        );
+
+        revise.perform(Action::Undo);
+
+        assert_eq!(revise.document.contents(), "\u{feff}one\ntwo\n");
+
+        let path = env::temp_dir().join("revise_strip_bom.txt");
+
+        std::fs::write(&path, "\u{feff}text\n").unwrap();
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(document, Size::default());
+
+        revise.config.strip_bom = true;
+        revise.save();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "text\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_scroll_half_page() {
+        let text: String = (1..=100).map(|line| format!("line {line}\n")).collect();
+        let mut revise = editor(&text);
+
+        let half = revise.view().height / 2;
+
+        revise.cursor_position = Position { x: 2, y: 5 };
+
+        for event in [
+            Event::Key(Key::Alt('d')),
+            Event::Key(Key::Alt('u')),
+            Event::Key(Key::Alt('u')),
+        ] {
+            revise.backend_mut().push_event(event);
+        }
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 5 + half });
+        assert_eq!(revise.offset.y, half);
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 5 });
+        assert_eq!(revise.offset.y, 0);
+
+        revise.process_keypress().unwrap();
+
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 0 });
+        assert_eq!(revise.offset.y, 0);
+
+        revise.config.scroll_amount = 3;
+        let last_top = 101 - revise.view().height;
+
+        revise.cursor_position = Position { x: 0, y: 95 };
+        revise.offset.y = last_top - 1;
+        revise.perform(Action::HalfPageDown);
+
+        assert_eq!(revise.cursor_position.y, 98);
+        assert_eq!(revise.offset.y, last_top);
+
+        revise.perform(Action::HalfPageDown);
+
+        assert_eq!(revise.cursor_position.y, 100);
+        assert_eq!(revise.offset.y, last_top);
+    }
+
+    #[test]
+    fn test_copy_whole_buffer() {
+        let mut revise = editor("one\r\ntwo\r\n");
+
+        revise.perform(Action::SelectAll);
+        revise.perform(Action::Copy);
+
+        assert_eq!(revise.clipboard.get_contents(), "one\r\ntwo\r\n");
+
+        revise.selection_anchor = None;
+        revise.cursor_position = Position { x: 0, y: 2 };
+        revise.perform(Action::Paste);
+
+        assert_eq!(revise.document.contents(), "one\r\ntwo\r\none\r\ntwo\r\n");
+    }
+
+    #[test]
+    fn test_browse() {
+        let root = env::temp_dir().join("revise_test_browse");
+        let dir = root.display().to_string();
+
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("a.txt"), "first\n").unwrap();
+        std::fs::write(root.join("sub").join("b.txt"), "second\n").unwrap();
+
+        let mut revise = editor("");
+
+        revise.start_directory = Some(dir.clone());
+
+        for text in ["sub", "..", "a.t"] {
+            revise.backend_mut().push_text(text);
+            revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        }
+
+        revise.backend_mut().push_event(Event::Key(Key::Ctrl('q')));
+
+        assert!(revise.run().is_ok());
+        assert_eq!(
+            revise.document.filename,
+            Some(root.join("a.txt").display().to_string())
+        );
+        assert_eq!(revise.document.contents(), "first\n");
+
+        revise.backend_mut().push_text("sub");
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.backend_mut().push_text("b");
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.execute_command(&format!("e {dir}"));
+
+        assert_eq!(
+            revise.document.filename,
+            Some(root.join("sub").join("b.txt").display().to_string())
+        );
+        assert_eq!(revise.buffers.len(), 2);
+
+        revise.backend_mut().push_event(Event::Key(Key::Esc));
+        revise.execute_command(&format!("e {dir}"));
+
+        assert_eq!(revise.buffers.len(), 2);
+        assert!(revise.picker.is_none());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_auto_reload() {
+        let path = env::temp_dir().join("revise_auto_reload.log");
+        let write = |text: &str, seconds| {
+            std::fs::write(&path, text).unwrap();
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+        };
+
+        write("one\n", 1_000);
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(
+            document,
+            Size {
+                width: 80,
+                height: 22,
+            },
+        );
+
+        revise.config.auto_reload = true;
+        write("one\ntwo\n", 2_000);
+        revise.warn_disk_change();
+
+        assert_eq!(revise.document.contents(), "one\ntwo\n");
+        assert_eq!(revise.cursor_position, Position::default());
+
+        revise.perform(Action::ToggleFollow);
+        write("one\ntwo\nthree\n", 3_000);
+        revise.warn_disk_change();
+
+        assert_eq!(revise.current_line(), Some("three"));
+        revise.refresh_screen().unwrap();
+        assert!(revise.backend().output().contains("[follow]"));
+
+        revise.insert_text("mine ");
+        write("four\n", 4_000);
+        revise.warn_disk_change();
+
+        assert!(revise.status_message.text.contains("changed on disk"));
+        assert!(revise.document.contents().contains("mine"));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_paste_indented() {
+        let mut revise = editor("fn main() {\n    \n}\n");
+
+        revise.config.expand_tabs = true;
+        revise.cursor_position = Position { x: 4, y: 1 };
+        revise
+            .clipboard
+            .set_contents("if x {\n\ty();\n}".to_owned())
+            .unwrap();
+        revise.perform(Action::PasteIndented);
+
+        assert_eq!(
+            revise.document.contents(),
+            "fn main() {\n    if x {\n        y();\n    }\n}\n"
+        );
+
+        revise.perform(Action::Undo);
+
+        assert_eq!(revise.document.contents(), "fn main() {\n    \n}\n");
+
+        revise.cursor_position = Position { x: 0, y: 1 };
+        revise
+            .clipboard
+            .set_lines("        a();\n          b();\n".to_owned())
+            .unwrap();
+        revise.perform(Action::PasteIndented);
+
+        assert_eq!(
+            revise.document.contents(),
+            "fn main() {\n    a();\n      b();\n    \n}\n"
+        );
+    }
+
+    #[test]
+    fn test_report_cursor() {
+        let path = env::temp_dir().join("revise_test_report_cursor");
+        let mut revise = editor("one\ntwo\n");
+
+        revise.cursor_position = Position { x: 2, y: 1 };
+        revise.print_cursor = Some(path.display().to_string());
+
+        assert!(revise.report_cursor().is_ok());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "2:3\n");
+        assert!(editor("").report_cursor().is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_expand_selection() {
+        let path = env::temp_dir().join("revise_test_expand_selection.rs");
+
+        std::fs::write(
+            &path,
+            "// (not code)\nfn main() {\n    call(\"hello world\", [a, b]);\n}\n",
+        )
+        .unwrap();
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(
+            document,
+            Size {
+                width: 80,
+                height: 22,
+            },
+        );
+        let selected = |revise: &Revise<TestBackend>| {
+            let (start, end) = revise.selection().unwrap();
+
+            revise.document.text(&start, &end)
+        };
+
+        revise.cursor_position = Position { x: 12, y: 2 };
+
+        let expected = [
+            "hello",
+            "hello world",
+            "\"hello world\"",
+            "\"hello world\", [a, b]",
+            "(\"hello world\", [a, b])",
+            "    call(\"hello world\", [a, b]);\n",
+            "\n    call(\"hello world\", [a, b]);\n",
+            "{\n    call(\"hello world\", [a, b]);\n}",
+            "fn main() {\n    call(\"hello world\", [a, b]);\n}",
+            "// (not code)\nfn main() {\n    call(\"hello world\", [a, b]);\n}",
+        ];
+
+        for text in expected {
+            revise.perform(Action::ExpandSelection);
+
+            assert_eq!(selected(&revise), text);
+        }
+
+        revise.perform(Action::ExpandSelection);
+
+        assert_eq!(revise.status_message.text, "Nothing larger to select.");
+
+        for text in expected.iter().rev().skip(1) {
+            revise.perform(Action::ShrinkSelection);
+
+            assert_eq!(selected(&revise), *text);
+        }
+
+        revise.perform(Action::ShrinkSelection);
+
+        assert_eq!(revise.selection(), None);
+        assert_eq!(revise.cursor_position, Position { x: 12, y: 2 });
+
+        revise.perform(Action::ShrinkSelection);
+
+        assert_eq!(revise.status_message.text, "Selection was not expanded.");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_elevated() {
+        let mut revise = editor("text\n");
+
+        for key in [
+            Key::Char('p'),
+            Key::Char('w'),
+            Key::Backspace,
+            Key::Char('x'),
+            Key::Char('\n'),
+        ] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        assert_eq!(revise.prompt_secret("Password: "), Some("px".to_owned()));
+        assert!(revise.backend().output().contains("Password: **"));
+        assert!(!revise.backend().output().contains("px"));
+
+        revise.backend_mut().push_event(Event::Key(Key::Esc));
+
+        assert_eq!(revise.prompt_secret("Password: "), None);
+
+        revise.backend_mut().push_event(Event::Key(Key::Char('n')));
+        revise.save_elevated("/etc/hosts", None);
+
+        assert_eq!(revise.status_message.text, "Save aborted.");
+
+        let root = env::temp_dir().join("revise_test_save_elevated");
+        let elevate = root.join("elevate");
+        let target = root.join("target.txt");
+        let filename = target.display().to_string();
+
+        std::fs::remove_dir_all(&root).ok();
+        std::fs::create_dir_all(&root).unwrap();
+        // Stands in for `sudo`, taking "secret" for the password.
+        std::fs::write(
+            &elevate,
+            format!(
+                "#!/bin/sh\n\
+                 case $1 in\n\
+                 -n) shift; [ -e '{0}/token' ] || exit 1; exec \"$@\";;\n\
+                 -S) read password; [ \"$password\" = secret ] && touch '{0}/token';;\n\
+                 esac\n",
+                root.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&elevate, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(&target, "old\n").unwrap();
+        revise.elevate = elevate.display().to_string();
+
+        revise.backend_mut().push_event(Event::Key(Key::Char('y')));
+        revise.backend_mut().push_text("wrong\n");
+        revise.save_elevated(&filename, None);
+
+        assert_eq!(
+            revise.status_message.text,
+            format!("{} refused, file not saved.", revise.elevate)
+        );
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "old\n");
+
+        revise.backend_mut().push_event(Event::Key(Key::Char('y')));
+        revise.backend_mut().push_text("secret\n");
+        revise.save_elevated(&filename, None);
+
+        assert_eq!(
+            revise.status_message.text,
+            format!("File saved with {}.", revise.elevate)
+        );
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "text\n");
+        assert!(!revise.document.is_changed());
+
+        revise.insert_text("more ");
+        revise.backend_mut().push_event(Event::Key(Key::Char('y')));
+        revise.save_elevated(&filename, None);
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "more text\n");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_save_creates_directory() {
+        let directory = env::temp_dir().join("revise_test_save_creates_directory");
+        let path = directory.join("sub").join("new.txt");
+        let mut revise = editor("text\n");
+
+        std::fs::remove_dir_all(&directory).ok();
+        revise.document.filename = Some(path.display().to_string());
+        revise.backend_mut().push_event(Event::Key(Key::Char('n')));
+        revise.save();
+
+        assert_eq!(revise.status_message.text, "Save aborted.");
+        assert!(!path.exists());
+
+        revise.backend_mut().push_event(Event::Key(Key::Char('y')));
+        revise.save();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "text\n");
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_smart_backspace() {
+        let mut revise = editor("          x\n");
+
+        revise.config.expand_tabs = true;
+        revise.cursor_position = Position { x: 10, y: 0 };
+        revise.process_key(Key::Backspace);
+
+        assert_eq!(revise.current_line(), Some("        x"));
+
+        revise.process_key(Key::Backspace);
+
+        assert_eq!(revise.current_line(), Some("    x"));
+        assert_eq!(revise.cursor_position, Position { x: 4, y: 0 });
+
+        revise.cursor_position = Position { x: 5, y: 0 };
+        revise.process_key(Key::Backspace);
+
+        assert_eq!(revise.current_line(), Some("    "));
+
+        revise.config.smart_backspace = false;
+        revise.process_key(Key::Backspace);
+
+        assert_eq!(revise.current_line(), Some("   "));
+
+        revise.config.smart_backspace = true;
+        revise.config.expand_tabs = false;
+        revise.process_key(Key::Backspace);
+
+        assert_eq!(revise.current_line(), Some("  "));
+    }
+
+    #[test]
+    fn test_bell() {
+        let mut revise = editor("text\n");
+
+        revise.process_key(Key::Left);
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.bell.is_none());
+        assert!(!revise.backend().output().contains('\x07'));
+
+        revise.config.visual_bell = true;
+        revise.config.audible_bell = true;
+        revise.process_key(Key::Up);
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.bell.is_some());
+        assert!(revise.poll_timeout() <= BELL_DURATION);
+        assert!(revise.backend().output().contains(&format!(
+            "{}{}",
+            color::Bg(revise.theme.status_fg),
+            color::Fg(revise.theme.status_bg)
+        )));
+
+        revise.bell = None;
+        revise.process_key(Key::Right);
+
+        assert!(revise.bell.is_none());
+        assert!(!revise.backend().output().contains('\x07'));
+
+        revise.config.visual_bell = false;
+        revise.process_key(Key::Up);
+
+        assert!(revise.bell.is_none());
+        assert!(revise.backend().output().contains('\x07'));
+    }
+
+    #[test]
+    fn test_macro() {
+        let mut revise = editor("one\ntwo\nthree\nfour");
+
+        revise.cursor_position = Position { x: 0, y: 3 };
+        let keys = |revise: &mut Revise<TestBackend>, events: &[Event]| {
+            for event in events {
+                revise.backend_mut().push_event(event.clone());
+            }
+
+            while !revise.replay.is_empty() || revise.backend_mut().has_pending_input() {
+                revise.process_keypress().unwrap();
+            }
+        };
+
+        revise.backend_mut().set_typeahead(true);
+        keys(
+            &mut revise,
+            &[
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('q')),
+                Event::Key(Key::Char('a')),
+            ],
+        );
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("[recording a]"));
+
+        keys(
+            &mut revise,
+            &[
+                Event::Key(Key::Home),
+                Event::Key(Key::Char('-')),
+                Event::Key(Key::Up),
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('q')),
+            ],
+        );
+
+        assert!(revise.recording.is_none());
+        assert_eq!(revise.macros.get(&'a').map(Vec::len), Some(3));
+
+        keys(
+            &mut revise,
+            &[
+                Event::Key(Key::Alt('9')),
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('@')),
+                Event::Key(Key::Char('a')),
+            ],
+        );
+
+        // Up on the first line fails, which ends the replay.
+        assert_eq!(revise.document.contents(), "-one\n-two\n-three\n-four");
+        assert!(revise.replay.is_empty());
+
+        keys(
+            &mut revise,
+            &[
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('@')),
+                Event::Key(Key::Char('b')),
+            ],
+        );
+
+        assert_eq!(revise.status_message.text, "Macro 'b' is empty.");
+    }
+
+    #[test]
+    fn test_macro_replaying_itself() {
+        let mut revise = editor("one\n");
+        let keys = |revise: &mut Revise<TestBackend>, events: &[Event]| {
+            for event in events {
+                revise.backend_mut().push_event(event.clone());
+            }
+
+            while !revise.replay.is_empty()
+                || !revise.typed_ahead.is_empty()
+                || revise.backend_mut().has_pending_input()
+            {
+                revise.process_keypress().unwrap();
+            }
+        };
+
+        revise.backend_mut().set_typeahead(true);
+        keys(
+            &mut revise,
+            &[
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('q')),
+                Event::Key(Key::Char('a')),
+                Event::Key(Key::Char('-')),
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('@')),
+                Event::Key(Key::Char('a')),
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('q')),
+            ],
+        );
+        revise.document = Document::from_reader("one\n".as_bytes()).unwrap();
+        revise.cursor_position = Position::default();
+        keys(
+            &mut revise,
+            &[
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('@')),
+                Event::Key(Key::Char('a')),
+            ],
+        );
+
+        assert_eq!(revise.current_line(), Some("-one"));
+        assert_eq!(
+            revise.status_message.text,
+            "Macro 'a' cannot replay itself."
+        );
+        assert!(revise.replay.is_empty());
+
+        revise.macros.insert('x', vec![Event::Key(Key::Char('x'))]);
+        keys(
+            &mut revise,
+            &[
+                Event::Key(Key::Alt('9')),
+                Event::Key(Key::Alt('9')),
+                Event::Key(Key::Ctrl('k')),
+                Event::Key(Key::Char('@')),
+                Event::Key(Key::Char('x')),
+                Event::Key(Key::Char('y')),
+            ],
+        );
+
+        assert_eq!(
+            revise.current_line(),
+            Some(format!("-{}yone", "x".repeat(99)).as_str())
+        );
+
+        revise.cursor_position = Position::default();
+
+        for key in [
+            Key::Alt('9'),
+            Key::Ctrl('k'),
+            Key::Char('@'),
+            Key::Char('x'),
+        ] {
+            revise.backend_mut().push_event(Event::Key(key));
+        }
+
+        while revise.replay.is_empty() {
+            revise.process_keypress().unwrap();
+        }
+
+        revise.backend_mut().push_event(Event::Key(Key::Esc));
+        revise.process_keypress().unwrap();
+
+        assert!(revise.replay.is_empty());
+        assert_eq!(revise.status_message.text, "Macro interrupted.");
+    }
+
+    #[test]
+    fn test_file_type_indentation() {
+        let directory = env::temp_dir().join("revise_test_file_type_indentation");
+        let open = |name: &str| {
+            let path = directory.join(name);
+
+            std::fs::write(&path, "\tx\n").unwrap();
+
+            let document = Document::open(&path.display().to_string()).unwrap();
+
+            Revise::from_document(
+                document,
+                Size {
+                    width: 80,
+                    height: 22,
+                },
+            )
+        };
+
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut make = open("Makefile");
+
+        make.move_cursor(Key::Right);
+        make.refresh_screen().unwrap();
+
+        assert!(make.backend().output().contains("1/1, col 9"));
+
+        make.cursor_position = Position::default();
+        make.process_key(Key::Char('\t'));
+
+        assert_eq!(make.document.row(0).unwrap().as_string(), "\t\tx");
+
+        let mut rust = open("main.rs");
+
+        rust.cursor_position = Position { x: 1, y: 0 };
+        rust.process_key(Key::Char('\t'));
+
+        assert_eq!(rust.document.row(0).unwrap().as_string(), "\t    x");
+
+        rust.execute_command("set tab_width=2");
+
+        assert_eq!(rust.tab_width(), 2);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_goto_percent() {
+        let text: String = (1..=200).map(|line| format!("{line}\n")).collect();
+        let mut revise = editor(&text);
+
+        revise.execute_command("50%");
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 99 });
+        assert_eq!(revise.offset.y, 99 - revise.view().height / 2);
+
+        revise.execute_command("150%");
+
+        assert_eq!(revise.cursor_position.y, 199);
+
+        revise.execute_command("-5%");
+
+        assert_eq!(revise.cursor_position.y, 0);
+
+        revise.execute_command("half%");
+
+        assert_eq!(revise.status_message.text, "Not a percentage: half%");
+    }
+
+    #[test]
+    fn test_close_block_comment() {
+        let path = env::temp_dir().join("revise_test_close_block_comment.rs");
+
+        std::fs::write(&path, "fn f() {}\n").unwrap();
+
+        let document = Document::open(&path.display().to_string()).unwrap();
+        let mut revise = Revise::from_document(
+            document,
+            Size {
+                width: 80,
+                height: 22,
+            },
+        );
+
+        revise.refresh_screen().unwrap();
+        revise.cursor_position = Position { x: 0, y: 0 };
+        revise.process_key(Key::Char('/'));
+        revise.process_key(Key::Char('*'));
+
+        assert_eq!(revise.current_line(), Some("/**/fn f() {}"));
+        assert_eq!(revise.cursor_position, Position { x: 2, y: 0 });
+
+        revise.process_key(Key::Char('*'));
+        revise.refresh_screen().unwrap();
+        revise.process_key(Key::Char('\n'));
+
+        assert_eq!(revise.document.contents(), "/**\n * \n */fn f() {}\n");
+        assert_eq!(revise.cursor_position, Position { x: 3, y: 1 });
+
+        revise.refresh_screen().unwrap();
+        revise.process_key(Key::Char('/'));
+        revise.process_key(Key::Char('*'));
+
+        assert_eq!(revise.current_line(), Some(" * /*"));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_empty_line() {
+        let mut revise = editor("text");
+        let dimmed = format!(
+            "{}~{}",
+            color::Fg(revise.theme.empty_line),
+            color::Fg(color::Reset)
+        );
+
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains(&dimmed));
+
+        revise.execute_command("set empty_line=.");
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.screen[1].ends_with(".\u{1b}[39m"));
+
+        revise.execute_command("set empty_line=");
+        revise.refresh_screen().unwrap();
+
+        assert_eq!(revise.screen[1], "");
+    }
+
+    #[test]
+    fn test_color_support() {
+        let mut revise = editor("fn main() {}");
+
+        revise.refresh_screen().unwrap();
+
+        assert!(revise.backend().output().contains("\x1b[38;2;"));
+
+        revise.execute_command("set color_support=256");
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains("\x1b[38;2;"));
+        assert!(revise.backend().output().contains("\x1b[38;5;"));
+        assert!(revise.backend().output().contains("main() {}"));
+
+        revise.execute_command("set color_support=none");
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains("\x1b[38;"));
+        assert!(!revise.backend().output().contains("\x1b[48;"));
+        assert!(revise.backend().output().contains("main() {}"));
+        assert!(revise
+            .backend()
+            .output()
+            .contains(&format!("{}[No Name]", style::Invert)));
+
+        revise.highlighted_word = Some("main".to_owned());
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains(&format!("{}main", style::Invert)));
+
+        revise.config.visual_bell = true;
+        revise.ring_bell();
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise
+            .backend()
+            .output()
+            .contains(&format!("{}[No Name]", style::Invert)));
+    }
+
+    #[test]
+    fn test_title() {
+        let mut revise = editor("text");
+
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains("\x1b]2;[No Name] - Revise\x1b\\"));
+
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains("\x1b]2;"));
+
+        revise.document.filename = Some("src/main\x1b.rs".to_owned());
+        revise.refresh_screen().unwrap();
+
+        assert!(revise
+            .backend()
+            .output()
+            .contains("\x1b]2;main.rs - Revise\x1b\\"));
+
+        revise.execute_command("set notitle");
+        revise.document.filename = None;
+        revise.backend_mut().clear_output();
+        revise.refresh_screen().unwrap();
+
+        assert!(!revise.backend().output().contains("\x1b]2;"));
+    }
+
+    #[test]
+    fn test_insert_file() {
+        let path = env::temp_dir().join("revise_test_insert_file.txt");
+        let mut revise = editor("start end\nlast");
+
+        std::fs::write(&path, "one\r\ntwo\n").unwrap();
+        revise.cursor_position = Position { x: 6, y: 0 };
+        revise.execute_command(&format!("r {}", path.display()));
+
+        assert_eq!(revise.document.contents(), "start one\ntwo\nend\nlast");
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 2 });
+        assert_eq!(
+            revise.status_message.text,
+            format!("Inserted 2 lines from {}.", path.display())
+        );
+
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "start end\nlast");
+
+        std::fs::remove_file(&path).unwrap();
+        revise.execute_command(&format!("r {}", path.display()));
+
+        assert_eq!(revise.document.contents(), "start end\nlast");
+        assert!(revise.status_message.text.starts_with("Could not read"));
+    }
+
+    #[test]
+    fn test_extract() {
+        let path = env::temp_dir().join("revise_test_extract.txt");
+        let mut revise = editor("one\ntwo\nthree");
+
+        revise.execute_command(&format!("extract 2,3 {}", path.display()));
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "two\nthree\n");
+        assert_eq!(
+            revise.status_message.text,
+            format!("Wrote 2 lines to {}.", path.display())
+        );
+
+        revise.selection_anchor = Some(Position { x: 1, y: 0 });
+        revise.cursor_position = Position { x: 2, y: 1 };
+        std::fs::remove_file(&path).unwrap();
+        revise.execute_command(&format!("extract {}", path.display()));
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ne\ntw");
+        assert_eq!(revise.document.contents(), "one\ntwo\nthree");
+        assert!(!revise.document.is_changed());
+
+        revise.execute_command(&format!("extract 3,4 {}", path.display()));
+
+        assert_eq!(revise.status_message.text, "Invalid range 3,4.");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_editor_config() {
+        let dir = env::temp_dir().join("revise_editor_config");
+        let path = dir.join("notes.txt");
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(".editorconfig"),
+            "root = true\n[*.txt]\nindent_style = space\nindent_size = 2\nend_of_line = crlf\n\
+             trim_trailing_whitespace = true\n",
+        )
+        .unwrap();
+        std::fs::write(&path, "one  \ntwo\t").unwrap();
+
+        let mut revise = editor("");
+
+        revise.execute_command(&format!("e {}", path.display()));
+
+        assert_eq!((revise.tab_width(), revise.expand_tabs()), (2, true));
+
+        revise.cursor_position = Position { x: 5, y: 0 };
+        revise.save();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\r\ntwo\r\n");
+        assert_eq!(revise.cursor_position, Position { x: 3, y: 0 });
+
+        revise.undo();
+
+        assert_eq!(revise.document.contents(), "one  \r\ntwo\t\r\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_matches() {
+        let mut revise = editor("one two\none one\n");
+
+        revise.backend_mut().push_text("one");
+        revise.backend_mut().push_event(Event::Key(Key::Right));
+        revise.backend_mut().push_event(Event::Key(Key::Char('\n')));
+        revise.search();
+
+        assert_eq!(revise.cursor_position, Position { x: 0, y: 1 });
+        assert!(revise.backend().output().contains("[match 1 of 3]"));
+        assert!(revise.backend().output().contains("[match 2 of 3]"));
+        assert_eq!(revise.search_matches, None);
+
+        revise.count_matches("one", &Position { x: 1, y: 0 });
+
+        assert_eq!(revise.search_matches, Some((None, 3)));
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = env::temp_dir().join("revise_test_diff_old.txt");
+        let new = env::temp_dir().join("revise_test_diff_new.txt");
+        let mut old_text: String = (0..30).map(|index| format!("line {index}\n")).collect();
+        let new_text = old_text.replace("line 1\n", "line one\nextra\n");
+
+        old_text.push_str("last\n");
+        std::fs::write(&old, &old_text).unwrap();
+        std::fs::write(&new, new_text).unwrap();
+
+        let mut revise = editor("");
+
+        revise.config.scrolloff = 0;
+        revise.start_diff(&old.display().to_string(), &new.display().to_string());
+
+        assert!(revise.document.read_only);
+        assert!(revise.buffers[1].document.read_only);
+        assert_eq!(
+            revise.status_message.text,
+            "1 changed, 1 added and 1 removed lines."
+        );
+        assert_eq!(revise.split.as_ref().map(|split| split.buffer), Some(1));
+
+        revise.refresh_screen().unwrap();
+
+        let output = revise.backend().output();
+
+        assert!(output.contains(&color::Bg(revise.theme.diff_changed).to_string()));
+        assert!(output.contains(&color::Bg(revise.theme.diff_added).to_string()));
+
+        revise.offset.y = 5;
+        revise.cursor_position.y = 5;
+        revise.refresh_screen().unwrap();
+
+        let split = revise.split.as_ref().unwrap();
+
+        assert_eq!(split.offset.y, 6);
+        assert_eq!(split.cursor_position.y, 6);
+
+        std::fs::remove_file(old).unwrap();
+        std::fs::remove_file(new).unwrap();
+    }
 }
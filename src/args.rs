@@ -0,0 +1,204 @@
+use thiserror::Error;
+
+#[derive(Default, Debug, PartialEq)]
+pub struct Arguments {
+    pub filename: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub expand_tabs: Option<bool>,
+    pub readonly: bool,
+    /// Where to write the position of the cursor on exit, `-` for the
+    /// standard output.
+    pub print_cursor: Option<String>,
+    /// The old and the new file to compare side by side.
+    pub diff: Option<(String, String)>,
+}
+
+#[derive(Debug, Error)]
+pub enum ArgumentError {
+    #[error("missing value for {0}")]
+    MissingValue(String),
+    #[error("invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+    #[error("unknown option: {0}")]
+    UnknownOption(String),
+}
+
+impl Arguments {
+    /// Parses command-line arguments, excluding the program name.
+    ///
+    /// # Errors
+    ///
+    /// Will return `ArgumentError` if an option is unknown or has an invalid value
+    pub fn parse<I>(args: I) -> Result<Self, ArgumentError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut arguments = Self::default();
+        let mut args = args.into_iter();
+        let mut start_line = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tab-width" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| ArgumentError::MissingValue(arg.clone()))?;
+                    let width = value
+                        .parse()
+                        .map_err(|_| ArgumentError::InvalidValue(arg.clone(), value))?;
+
+                    arguments.tab_width = Some(width);
+                }
+                "--expand-tabs" => arguments.expand_tabs = Some(true),
+                "--no-expand-tabs" => arguments.expand_tabs = Some(false),
+                "--readonly" => arguments.readonly = true,
+                "--print-cursor-on-exit" => arguments.print_cursor = Some("-".to_owned()),
+                "--diff" => {
+                    let mut file = || {
+                        args.next()
+                            .ok_or_else(|| ArgumentError::MissingValue(arg.clone()))
+                    };
+                    let old = file()?;
+
+                    arguments.diff = Some((old, file()?));
+                }
+                "--print-cursor-to" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| ArgumentError::MissingValue(arg.clone()))?;
+
+                    arguments.print_cursor = Some(path);
+                }
+                option if option.starts_with("--") => {
+                    return Err(ArgumentError::UnknownOption(arg));
+                }
+                option if option.starts_with('+') => {
+                    let line = option[1..]
+                        .parse()
+                        .map_err(|_| ArgumentError::InvalidValue("+".to_owned(), arg.clone()))?;
+
+                    start_line = Some(line);
+                }
+                _ => {
+                    let (filename, line, column) = split_position(&arg);
+
+                    arguments.filename = Some(filename.to_owned());
+                    arguments.line = line;
+                    arguments.column = column;
+                }
+            }
+        }
+
+        // `+N` wins over a line given after the file name.
+        if start_line.is_some() {
+            arguments.line = start_line;
+            arguments.column = None;
+        }
+
+        Ok(arguments)
+    }
+}
+
+/// Splits a `file:line` or `file:line:column` argument, as printed by
+/// compilers and grep, into its parts.
+fn split_position(arg: &str) -> (&str, Option<usize>, Option<usize>) {
+    let number = |part: &str| part.parse::<usize>().ok();
+    let Some((rest, last)) = arg.rsplit_once(':') else {
+        return (arg, None, None);
+    };
+    let Some(last) = number(last).filter(|_| !rest.is_empty()) else {
+        return (arg, None, None);
+    };
+
+    match rest.rsplit_once(':') {
+        Some((filename, line)) if !filename.is_empty() => match number(line) {
+            Some(line) => (filename, Some(line), Some(last)),
+            None => (rest, Some(last), None),
+        },
+        _ => (rest, Some(last), None),
+    }
+}
+
+#[cfg(test)]
+mod args_tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Arguments, ArgumentError> {
+        Arguments::parse(args.iter().map(|arg| (*arg).to_owned()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let arguments = parse(&["--tab-width", "2", "main.rs", "--no-expand-tabs"]).unwrap();
+
+        assert_eq!(
+            arguments,
+            Arguments {
+                filename: Some("main.rs".to_owned()),
+                line: None,
+                column: None,
+                tab_width: Some(2),
+                expand_tabs: Some(false),
+                readonly: false,
+                print_cursor: None,
+                diff: None,
+            }
+        );
+        assert!(parse(&["--readonly", "main.rs"]).unwrap().readonly);
+        assert_eq!(
+            parse(&["--print-cursor-on-exit"]).unwrap().print_cursor,
+            Some("-".to_owned())
+        );
+        assert_eq!(
+            parse(&["--print-cursor-to", "/tmp/pos"])
+                .unwrap()
+                .print_cursor,
+            Some("/tmp/pos".to_owned())
+        );
+        assert!(parse(&["--print-cursor-to"]).is_err());
+        assert_eq!(
+            parse(&["--diff", "a.txt", "b.txt"]).unwrap().diff,
+            Some(("a.txt".to_owned(), "b.txt".to_owned()))
+        );
+        assert!(parse(&["--diff", "a.txt"]).is_err());
+        assert!(parse(&["--tab-width"]).is_err());
+        assert!(parse(&["--tab-width", "two"]).is_err());
+        assert!(parse(&["--unknown"]).is_err());
+    }
+
+    #[test]
+    fn test_split_position() {
+        assert_eq!(split_position("main.rs:42"), ("main.rs", Some(42), None));
+        assert_eq!(
+            split_position("src/main.rs:42:7"),
+            ("src/main.rs", Some(42), Some(7))
+        );
+        assert_eq!(split_position("a:b:3"), ("a:b", Some(3), None));
+        assert_eq!(split_position("main.rs:"), ("main.rs:", None, None));
+        assert_eq!(split_position(":42"), (":42", None, None));
+        assert_eq!(split_position("main.rs"), ("main.rs", None, None));
+
+        let arguments = parse(&["notes.txt:3:1"]).unwrap();
+
+        assert_eq!(arguments.filename.as_deref(), Some("notes.txt"));
+        assert_eq!((arguments.line, arguments.column), (Some(3), Some(1)));
+    }
+
+    #[test]
+    fn test_start_line() {
+        let arguments = parse(&["+42", "main.rs"]).unwrap();
+
+        assert_eq!(arguments.filename.as_deref(), Some("main.rs"));
+        assert_eq!(arguments.line, Some(42));
+
+        let arguments = parse(&["+7", "main.rs:3:2"]).unwrap();
+
+        assert_eq!((arguments.line, arguments.column), (Some(7), None));
+        assert!(matches!(
+            parse(&["+x", "main.rs"]),
+            Err(ArgumentError::InvalidValue(_, _))
+        ));
+    }
+}
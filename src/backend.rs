@@ -0,0 +1,255 @@
+use crate::clipboard;
+use crate::palette::ColorSupport;
+use crate::Event;
+use crate::Position;
+use crate::Size;
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::time::Duration;
+use termion::color::{self, Rgb};
+use termion::event::Key;
+use termion::{clear, cursor};
+
+/// The terminal I/O the editor draws to and reads input from.
+pub trait Backend {
+    /// Returns the size of the text area, excluding the status and message bars.
+    fn size(&self) -> &Size;
+
+    /// Returns how many rows are left below the text area for the status
+    /// and message bars, which is fewer than two only in a terminal one
+    /// or two rows high.
+    fn bar_rows(&self) -> usize {
+        2
+    }
+
+    fn clear_screen(&mut self);
+
+    fn clear_current_line(&mut self);
+
+    fn cursor_position(&mut self, position: &Position);
+
+    fn cursor_hide(&mut self);
+
+    fn cursor_show(&mut self);
+
+    fn set_bg_color(&mut self, color: Rgb);
+
+    fn reset_bg_color(&mut self);
+
+    fn set_fg_color(&mut self, color: Rgb);
+
+    fn reset_fg_color(&mut self);
+
+    fn print(&mut self, text: &str);
+
+    /// Sets how many colors the backend can draw with, the theme's own
+    /// colors being brought down to the nearest ones it has. Backends that
+    /// only ever draw in true color can keep this default, which does
+    /// nothing.
+    fn set_color_support(&mut self, _colors: ColorSupport) {}
+
+    /// Sets the title of the terminal window or tab.
+    fn set_title(&mut self, title: &str) {
+        self.print(&format!("\x1b]2;{title}\x1b\\"));
+    }
+
+    /// Rings the terminal bell, which some terminals flash instead.
+    fn bell(&mut self) {
+        self.print("\x07");
+    }
+
+    /// Asks the terminal to put `text` on its own clipboard.
+    fn copy_to_terminal(&mut self, text: &str) {
+        self.print(&clipboard::osc52(text));
+    }
+
+    /// # Errors
+    ///
+    /// Will return an error if not
+    /// all bytes could be written due to I/O errors
+    /// or EOF being reached.
+    fn flush(&mut self) -> Result<(), Error>;
+
+    /// Waits for the next input event.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if it fails to read event
+    fn read_event(&mut self) -> Result<Event, Error>;
+
+    /// Like `read_event`, but gives up and returns `None` once `timeout`
+    /// has passed without input.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if it fails to read event
+    fn read_event_timeout(&mut self, timeout: Duration) -> Result<Option<Event>, Error>;
+
+    /// Returns whether input has come in that has not been read yet.
+    fn has_pending_input(&mut self) -> bool {
+        false
+    }
+
+    /// # Errors
+    ///
+    /// Will return an error if it fails to read key
+    fn read_key(&mut self) -> Result<Key, Error> {
+        loop {
+            if let Event::Key(key) = self.read_event()? {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+/// An in-memory backend that records everything drawn to it and replays
+/// scripted events, for running the editor without a TTY.
+pub struct TestBackend {
+    size: Size,
+    bar_rows: usize,
+    events: VecDeque<Event>,
+    typeahead: bool,
+    colors: ColorSupport,
+    output: String,
+}
+
+impl Default for TestBackend {
+    fn default() -> Self {
+        Self::new(Size::default())
+    }
+}
+
+impl TestBackend {
+    pub fn new(size: Size) -> Self {
+        Self {
+            size,
+            bar_rows: 2,
+            events: VecDeque::new(),
+            typeahead: false,
+            colors: ColorSupport::TrueColor,
+            output: String::new(),
+        }
+    }
+
+    /// Changes the size to that of a terminal `rows` high and queues the
+    /// resize event.
+    pub fn resize(&mut self, width: u16, rows: u16) {
+        self.size = Size {
+            width,
+            height: rows.saturating_sub(2),
+        };
+        self.bar_rows = cmp::min(usize::from(rows), 2);
+        self.events.push_back(Event::Resize);
+    }
+
+    /// Queues `event` to be read after the ones already scripted.
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    /// Queues a key press for each character of `text`.
+    pub fn push_text(&mut self, text: &str) {
+        let keys = text.chars().map(|c| Event::Key(Key::Char(c)));
+
+        self.events.extend(keys);
+    }
+
+    /// Makes the scripted events count as pending input, as keys typed
+    /// ahead do in a terminal. Off by default, so that every event is
+    /// followed by a refresh.
+    pub fn set_typeahead(&mut self, typeahead: bool) {
+        self.typeahead = typeahead;
+    }
+
+    /// Returns everything drawn so far, escape sequences included.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    pub fn clear_output(&mut self) {
+        self.output.clear();
+    }
+
+    fn write(&mut self, value: impl std::fmt::Display) {
+        self.print(&value.to_string());
+    }
+}
+
+impl Backend for TestBackend {
+    fn size(&self) -> &Size {
+        &self.size
+    }
+
+    fn bar_rows(&self) -> usize {
+        self.bar_rows
+    }
+
+    fn clear_screen(&mut self) {
+        self.write(clear::All);
+    }
+
+    fn clear_current_line(&mut self) {
+        self.write(clear::CurrentLine);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn cursor_position(&mut self, position: &Position) {
+        self.write(cursor::Goto(
+            position.x.saturating_add(1) as u16,
+            position.y.saturating_add(1) as u16,
+        ));
+    }
+
+    fn cursor_hide(&mut self) {
+        self.write(cursor::Hide);
+    }
+
+    fn cursor_show(&mut self) {
+        self.write(cursor::Show);
+    }
+
+    fn set_bg_color(&mut self, color: Rgb) {
+        self.write(color::Bg(color));
+    }
+
+    fn reset_bg_color(&mut self) {
+        self.write(color::Bg(color::Reset));
+    }
+
+    fn set_fg_color(&mut self, color: Rgb) {
+        self.write(color::Fg(color));
+    }
+
+    fn reset_fg_color(&mut self) {
+        self.write(color::Fg(color::Reset));
+    }
+
+    fn print(&mut self, text: &str) {
+        self.output.push_str(&self.colors.downgrade(text));
+    }
+
+    fn set_color_support(&mut self, colors: ColorSupport) {
+        self.colors = colors;
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Returns the next scripted event, failing once the script is exhausted.
+    fn read_event(&mut self) -> Result<Event, Error> {
+        self.events
+            .pop_front()
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "no more scripted events"))
+    }
+
+    fn read_event_timeout(&mut self, _timeout: Duration) -> Result<Option<Event>, Error> {
+        self.read_event().map(Some)
+    }
+
+    fn has_pending_input(&mut self) -> bool {
+        self.typeahead && !self.events.is_empty()
+    }
+}
@@ -0,0 +1,73 @@
+//! Changing the case of text, with the full Unicode mappings: `ß` becomes
+//! `SS` in upper case and `İ` becomes `i̇` in lower case.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    /// The first letter of every word in upper case, the others in lower case.
+    Title,
+    /// Upper case letters in lower case and the other way around.
+    Toggle,
+}
+
+impl Case {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Self::Upper => text.to_uppercase(),
+            Self::Lower => text.to_lowercase(),
+            Self::Title => {
+                let mut result = String::with_capacity(text.len());
+                let mut in_word = false;
+
+                for c in text.chars() {
+                    if in_word {
+                        result.extend(c.to_lowercase());
+                    } else {
+                        result.extend(c.to_uppercase());
+                    }
+
+                    in_word = c.is_alphanumeric() || c == '\'';
+                }
+
+                result
+            }
+            Self::Toggle => text
+                .chars()
+                .flat_map(|c| -> Box<dyn Iterator<Item = char>> {
+                    if c.is_uppercase() {
+                        Box::new(c.to_lowercase())
+                    } else {
+                        Box::new(c.to_uppercase())
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Upper => "upper case",
+            Self::Lower => "lower case",
+            Self::Title => "title case",
+            Self::Toggle => "toggled case",
+        }
+    }
+}
+
+#[cfg(test)]
+mod case_tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(Case::Upper.apply("straße"), "STRASSE");
+        assert_eq!(Case::Lower.apply("İSTANBUL"), "i\u{307}stanbul");
+        assert_eq!(Case::Lower.apply("ΟΔΟΣ"), "οδος");
+        assert_eq!(
+            Case::Title.apply("hello wORLD, it's me"),
+            "Hello World, It's Me"
+        );
+        assert_eq!(Case::Toggle.apply("MAX_len ß"), "max_LEN SS");
+    }
+}
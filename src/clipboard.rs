@@ -0,0 +1,161 @@
+use cli_clipboard::{ClipboardContext, ClipboardProvider};
+use std::error::Error;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A register private to the editor, mirrored to the system clipboard when
+/// there is one, e.g. unless running headless.
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<ClipboardContext>,
+    local: String,
+    /// Whether `local` holds whole lines, which are pasted above the cursor
+    /// line rather than at the cursor.
+    linewise: bool,
+}
+
+impl Clipboard {
+    /// Starts or stops mirroring the register to the system clipboard.
+    pub fn use_system(&mut self, enabled: bool) {
+        if !enabled {
+            self.system = None;
+        } else if self.system.is_none() {
+            self.system = ClipboardContext::new().ok();
+        }
+    }
+
+    pub fn has_system(&self) -> bool {
+        self.system.is_some()
+    }
+
+    /// Reads the system clipboard, or the register if the system clipboard
+    /// is unavailable or cannot be read.
+    pub fn get_contents(&mut self) -> String {
+        match &mut self.system {
+            Some(context) => context
+                .get_contents()
+                .unwrap_or_else(|_| self.local.clone()),
+            None => self.local.clone(),
+        }
+    }
+
+    /// Stores `contents` in the register and the system clipboard.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the system clipboard cannot be written, the
+    /// register is set anyway
+    pub fn set_contents(&mut self, contents: String) -> Result<(), Box<dyn Error>> {
+        self.linewise = false;
+        self.store(contents)
+    }
+
+    /// Works like `set_contents` for whole `lines`, each ending with a
+    /// newline.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the system clipboard cannot be written
+    pub fn set_lines(&mut self, lines: String) -> Result<(), Box<dyn Error>> {
+        self.linewise = true;
+        self.store(lines)
+    }
+
+    /// Returns `true` if `contents` read back from the clipboard are the
+    /// lines last given to `set_lines`, not something copied since.
+    pub fn is_lines(&self, contents: &str) -> bool {
+        self.linewise && self.local == contents
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Error` if the system clipboard cannot be cleared
+    pub fn clear(&mut self) -> Result<(), Box<dyn Error>> {
+        self.local.clear();
+        self.linewise = false;
+
+        match &mut self.system {
+            Some(context) => context.clear(),
+            None => Ok(()),
+        }
+    }
+
+    fn store(&mut self, contents: String) -> Result<(), Box<dyn Error>> {
+        self.local.clone_from(&contents);
+
+        match &mut self.system {
+            Some(context) => context.set_contents(contents),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the OSC 52 escape sequence asking the terminal to put `text` on
+/// the clipboard of the machine it runs on, which works over SSH.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod clipboard_tests {
+    use super::*;
+
+    #[test]
+    fn test_local() {
+        let mut clipboard = Clipboard::default();
+
+        clipboard.set_contents("copied".to_owned()).unwrap();
+        assert_eq!(clipboard.get_contents(), "copied");
+        assert!(!clipboard.has_system());
+
+        clipboard.clear().unwrap();
+        assert_eq!(clipboard.get_contents(), "");
+    }
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(osc52("héllo\n"), "\x1b]52;c;aMOpbGxvCg==\x07");
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut clipboard = Clipboard::default();
+
+        clipboard.set_lines("one\ntwo\n".to_owned()).unwrap();
+
+        assert!(clipboard.is_lines("one\ntwo\n"));
+        assert!(!clipboard.is_lines("one\n"));
+
+        clipboard.set_contents("one\ntwo\n".to_owned()).unwrap();
+
+        assert!(!clipboard.is_lines("one\ntwo\n"));
+    }
+}
@@ -0,0 +1,122 @@
+//! The wall-clock time in the local timezone, which the C library knows
+//! from the system settings and `TZ`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// The offset from UTC, in seconds east of it.
+    pub offset: i64,
+}
+
+impl LocalTime {
+    /// Returns the current local time, or `None` if the system clock or
+    /// timezone cannot be read.
+    // `tm_gmtoff` is a C `long`, which is only 32 bits wide on some targets.
+    #[allow(clippy::useless_conversion)]
+    pub fn now() -> Option<Self> {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        let time = libc::time_t::try_from(seconds).ok()?;
+        // SAFETY: `tm` is plain data for which all zeroes is a valid value,
+        // and `localtime_r` only writes to it.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+        // SAFETY: both pointers are valid for the duration of the call.
+        let converted = unsafe { !libc::localtime_r(&time, &mut tm).is_null() };
+
+        converted.then(|| Self {
+            year: tm.tm_year + 1900,
+            month: u32::try_from(tm.tm_mon + 1).unwrap_or(1),
+            day: u32::try_from(tm.tm_mday).unwrap_or(1),
+            hour: u32::try_from(tm.tm_hour).unwrap_or(0),
+            minute: u32::try_from(tm.tm_min).unwrap_or(0),
+            second: u32::try_from(tm.tm_sec).unwrap_or(0),
+            offset: i64::from(tm.tm_gmtoff),
+        })
+    }
+
+    /// Formats the time as `HH:MM`.
+    pub fn clock(&self) -> String {
+        format!("{:02}:{:02}", self.hour, self.minute)
+    }
+
+    /// Formats the time the way `strftime` does, with `%Y`, `%m`, `%d`,
+    /// `%H`, `%M`, `%S`, `%z` and `%%`. Other characters, including unknown
+    /// conversions, are kept as they are.
+    pub fn format(&self, format: &str) -> String {
+        let mut result = String::new();
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('Y') => result.push_str(&format!("{:04}", self.year)),
+                Some('m') => result.push_str(&format!("{:02}", self.month)),
+                Some('d') => result.push_str(&format!("{:02}", self.day)),
+                Some('H') => result.push_str(&format!("{:02}", self.hour)),
+                Some('M') => result.push_str(&format!("{:02}", self.minute)),
+                Some('S') => result.push_str(&format!("{:02}", self.second)),
+                Some('z') => {
+                    let sign = if self.offset < 0 { '-' } else { '+' };
+                    let minutes = self.offset.abs() / 60;
+
+                    result.push_str(&format!("{sign}{:02}{:02}", minutes / 60, minutes % 60));
+                }
+                Some('%') => result.push('%'),
+                Some(other) => {
+                    result.push('%');
+                    result.push(other);
+                }
+                None => result.push('%'),
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::*;
+
+    #[test]
+    fn test_now() {
+        let time = LocalTime::now().unwrap();
+
+        assert!((1..=12).contains(&time.month));
+        assert!((1..=31).contains(&time.day));
+        assert!(time.hour < 24 && time.minute < 60 && time.second < 61);
+        assert!(time.year >= 2024);
+    }
+
+    #[test]
+    fn test_clock() {
+        let time = LocalTime {
+            year: 2024,
+            month: 3,
+            day: 9,
+            hour: 7,
+            minute: 5,
+            second: 30,
+            offset: -16200,
+        };
+
+        assert_eq!(time.clock(), "07:05");
+        assert_eq!(time.format("%Y-%m-%d"), "2024-03-09");
+        assert_eq!(
+            time.format("%Y-%m-%dT%H:%M:%S%z"),
+            "2024-03-09T07:05:30-0430"
+        );
+        assert_eq!(time.format("100%% %q %"), "100% %q %");
+    }
+}
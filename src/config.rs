@@ -0,0 +1,625 @@
+use crate::args::Arguments;
+use crate::history::UndoLimit;
+use crate::palette::ColorSupport;
+use crate::theme::{Theme, ThemeError};
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs::read_to_string;
+use std::io::{Error as IOError, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+pub const MIN_TAB_WIDTH: usize = 1;
+pub const MAX_TAB_WIDTH: usize = 16;
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub tab_width: usize,
+    pub expand_tabs: bool,
+    pub autosave_interval: u64,
+    pub message_duration: u64,
+    /// Whether `help_text` is shown in the message bar at startup, for
+    /// `help_duration` seconds.
+    pub show_help: bool,
+    pub help_duration: u64,
+    pub ascii_only: bool,
+    pub line_numbers: bool,
+    pub auto_pairs: bool,
+    pub insert_final_newline: bool,
+    pub trim_trailing_whitespace: bool,
+    pub trim_edited_lines_only: bool,
+    pub highlight_symbol: bool,
+    pub modal: bool,
+    pub scrolloff: usize,
+    pub scroll_amount: usize,
+    pub system_clipboard: bool,
+    pub osc52: Option<bool>,
+    pub pad_block_selection: bool,
+    pub whitespace_warnings: bool,
+    pub clock: bool,
+    pub colorcolumn: usize,
+    pub cursor_line: bool,
+    pub cursor_column: bool,
+    pub scrollbar: bool,
+    pub indent_guides: bool,
+    pub welcome: bool,
+    pub git_gutter: bool,
+    pub spell_check: bool,
+    pub strip_bom: bool,
+    pub auto_reload: bool,
+    pub smart_backspace: bool,
+    pub visual_bell: bool,
+    pub audible_bell: bool,
+    pub rainbow_brackets: bool,
+    pub title: bool,
+    /// The number of undo steps kept, or 0 for no limit.
+    pub undo_levels: usize,
+    /// The approximate memory the undo steps take in megabytes, or 0 for no
+    /// limit.
+    pub undo_memory: usize,
+    /// Writes undo steps past the limits to a temporary file instead of
+    /// dropping them.
+    pub undo_spill: bool,
+    pub date_format: String,
+    pub help_text: String,
+    /// What is drawn on screen rows past the end of the buffer, at most one
+    /// character.
+    pub empty_line: String,
+    pub theme: String,
+    /// `auto`, `truecolor`, `256`, `16` or `none`.
+    pub color_support: String,
+    pub colors: HashMap<String, String>,
+    pub keys: HashMap<String, String>,
+    pub formatters: HashMap<String, String>,
+    pub file_types: HashMap<String, Indentation>,
+    /// The indentation set on the command line or with `:set`, which
+    /// applies to files of every type.
+    #[serde(skip)]
+    pub overrides: Indentation,
+}
+
+/// The indentation for files of one type, from a `[file_types.<name>]`
+/// table. Settings left out fall back to the style of the language and
+/// then to `tab_width` and `expand_tabs`.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Indentation {
+    pub tab_width: Option<usize>,
+    pub expand_tabs: Option<bool>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("cannot read config file: {0}")]
+    Read(#[from] IOError),
+    #[error("invalid config file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("tab_width must be between {MIN_TAB_WIDTH} and {MAX_TAB_WIDTH}, got {0}")]
+    TabWidth(usize),
+    #[error("invalid theme: {0}")]
+    Theme(#[from] ThemeError),
+    #[error("unknown option: {0}")]
+    UnknownOption(String),
+    #[error("invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            expand_tabs: false,
+            autosave_interval: 0,
+            message_duration: 5,
+            show_help: true,
+            help_duration: 5,
+            ascii_only: false,
+            line_numbers: false,
+            auto_pairs: true,
+            insert_final_newline: true,
+            trim_trailing_whitespace: false,
+            trim_edited_lines_only: false,
+            highlight_symbol: true,
+            modal: false,
+            scrolloff: 3,
+            scroll_amount: 0,
+            system_clipboard: true,
+            osc52: None,
+            pad_block_selection: false,
+            whitespace_warnings: false,
+            clock: false,
+            colorcolumn: 0,
+            cursor_line: false,
+            cursor_column: false,
+            scrollbar: false,
+            indent_guides: false,
+            welcome: true,
+            git_gutter: false,
+            spell_check: false,
+            strip_bom: false,
+            auto_reload: false,
+            smart_backspace: true,
+            visual_bell: false,
+            audible_bell: false,
+            rainbow_brackets: false,
+            title: true,
+            undo_levels: 1000,
+            undo_memory: 64,
+            undo_spill: false,
+            date_format: String::from("%Y-%m-%d"),
+            help_text: String::from(
+                "HELP: Ctrl-F = find | Ctrl-S = save | Ctrl-Z = undo | Ctrl-Q = quit | Ctrl-K ? = keys",
+            ),
+            empty_line: String::from("~"),
+            theme: String::from("dark"),
+            color_support: String::from("auto"),
+            colors: HashMap::new(),
+            keys: HashMap::new(),
+            formatters: HashMap::new(),
+            file_types: HashMap::new(),
+            overrides: Indentation::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns `$XDG_CONFIG_HOME/revise/config.toml`, falling back to
+    /// `~/.config/revise/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(base.join("revise").join("config.toml"))
+    }
+
+    /// Loads the user configuration, using the defaults if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Will return `ConfigError` if the file cannot be read or parsed,
+    /// or if a value is out of range
+    pub fn load() -> Result<Self, ConfigError> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        match read_to_string(path) {
+            Ok(contents) => Self::parse(&contents),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(ConfigError::Read(err)),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Will return `ConfigError` if the contents are not valid TOML
+    /// or if a value is out of range
+    pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(contents)?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Overrides configured values with the ones given on the command line.
+    ///
+    /// # Errors
+    ///
+    /// Will return `ConfigError` if an overridden value is out of range,
+    /// keeping the configured one for it
+    pub fn apply(&mut self, arguments: &Arguments) -> Result<(), ConfigError> {
+        if let Some(expand_tabs) = arguments.expand_tabs {
+            self.expand_tabs = expand_tabs;
+            self.overrides.expand_tabs = Some(expand_tabs);
+        }
+
+        if let Some(tab_width) = arguments.tab_width {
+            if !(MIN_TAB_WIDTH..=MAX_TAB_WIDTH).contains(&tab_width) {
+                return Err(ConfigError::TabWidth(tab_width));
+            }
+
+            self.tab_width = tab_width;
+            self.overrides.tab_width = Some(tab_width);
+        }
+
+        Ok(())
+    }
+
+    /// Changes a setting from a `:set` command: `name=value`, `name` to turn
+    /// a flag on or `noname` to turn it off. `number` is accepted for
+    /// `line_numbers`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `ConfigError` if the option is unknown or its value is
+    /// invalid, leaving the setting unchanged
+    pub fn set(&mut self, option: &str) -> Result<(), ConfigError> {
+        let (name, value) = option.split_once('=').unwrap_or((option, ""));
+        let invalid = || ConfigError::InvalidValue(name.to_owned(), value.to_owned());
+        let (name, flag) = match name.strip_prefix("no") {
+            Some(flag) if value.is_empty() => (flag, false),
+            _ => (name, true),
+        };
+        let flag = || match value {
+            "" => Ok(flag),
+            _ => value.parse().map_err(|_| invalid()),
+        };
+
+        match name {
+            "tab_width" => {
+                let tab_width = value.parse().map_err(|_| invalid())?;
+
+                if !(MIN_TAB_WIDTH..=MAX_TAB_WIDTH).contains(&tab_width) {
+                    return Err(ConfigError::TabWidth(tab_width));
+                }
+
+                self.tab_width = tab_width;
+                self.overrides.tab_width = Some(tab_width);
+            }
+            "autosave_interval" => self.autosave_interval = value.parse().map_err(|_| invalid())?,
+            "message_duration" => self.message_duration = value.parse().map_err(|_| invalid())?,
+            "help_duration" => self.help_duration = value.parse().map_err(|_| invalid())?,
+            "scrolloff" => self.scrolloff = value.parse().map_err(|_| invalid())?,
+            "scroll_amount" => self.scroll_amount = value.parse().map_err(|_| invalid())?,
+            "colorcolumn" => self.colorcolumn = value.parse().map_err(|_| invalid())?,
+            "undo_levels" => self.undo_levels = value.parse().map_err(|_| invalid())?,
+            "undo_memory" => self.undo_memory = value.parse().map_err(|_| invalid())?,
+            "date_format" => self.date_format = value.to_owned(),
+            "help_text" => self.help_text = value.to_owned(),
+            "empty_line" if value.chars().count() <= 1 => self.empty_line = value.to_owned(),
+            "empty_line" => return Err(invalid()),
+            "color_support" if is_color_support(value) => self.color_support = value.to_owned(),
+            "color_support" => return Err(invalid()),
+            "expand_tabs" => {
+                self.expand_tabs = flag()?;
+                self.overrides.expand_tabs = Some(self.expand_tabs);
+            }
+            "ascii_only" => self.ascii_only = flag()?,
+            "line_numbers" | "number" => self.line_numbers = flag()?,
+            "auto_pairs" => self.auto_pairs = flag()?,
+            "insert_final_newline" => self.insert_final_newline = flag()?,
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = flag()?,
+            "trim_edited_lines_only" => self.trim_edited_lines_only = flag()?,
+            "highlight_symbol" => self.highlight_symbol = flag()?,
+            "modal" => self.modal = flag()?,
+            "system_clipboard" => self.system_clipboard = flag()?,
+            "osc52" => self.osc52 = Some(flag()?),
+            "pad_block_selection" => self.pad_block_selection = flag()?,
+            "whitespace_warnings" => self.whitespace_warnings = flag()?,
+            "clock" => self.clock = flag()?,
+            "scrollbar" => self.scrollbar = flag()?,
+            "cursor_line" => self.cursor_line = flag()?,
+            "cursor_column" => self.cursor_column = flag()?,
+            "indent_guides" => self.indent_guides = flag()?,
+            "welcome" => self.welcome = flag()?,
+            "show_help" => self.show_help = flag()?,
+            "git_gutter" => self.git_gutter = flag()?,
+            "spell_check" => self.spell_check = flag()?,
+            "strip_bom" => self.strip_bom = flag()?,
+            "auto_reload" => self.auto_reload = flag()?,
+            "smart_backspace" => self.smart_backspace = flag()?,
+            "visual_bell" => self.visual_bell = flag()?,
+            "audible_bell" => self.audible_bell = flag()?,
+            "rainbow_brackets" => self.rainbow_brackets = flag()?,
+            "title" => self.title = flag()?,
+            "undo_spill" => self.undo_spill = flag()?,
+            _ => return Err(ConfigError::UnknownOption(option.to_owned())),
+        }
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Will return `ConfigError` if the theme or one of its colors is unknown
+    pub fn theme(&self) -> Result<Theme, ConfigError> {
+        Ok(Theme::load(&self.theme, &self.colors)?)
+    }
+
+    /// Returns how long to wait after the last keypress before saving,
+    /// or `None` if auto-save is disabled.
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        (self.autosave_interval > 0).then(|| Duration::from_secs(self.autosave_interval))
+    }
+
+    /// Returns how long messages stay in the message bar, or `None` if
+    /// they stay until the next one.
+    pub fn message_duration(&self) -> Option<Duration> {
+        (self.message_duration > 0).then(|| Duration::from_secs(self.message_duration))
+    }
+
+    /// Returns how long the help stays in the message bar at startup, or
+    /// `None` if it stays until the next message.
+    pub fn help_duration(&self) -> Option<Duration> {
+        (self.help_duration > 0).then(|| Duration::from_secs(self.help_duration))
+    }
+
+    /// Returns how much undo history documents keep in memory.
+    pub fn undo_limit(&self) -> UndoLimit {
+        UndoLimit {
+            steps: self.undo_levels,
+            bytes: self.undo_memory.saturating_mul(1024 * 1024),
+            spill: self.undo_spill,
+        }
+    }
+
+    /// Returns the command that formats files with the extension of
+    /// `filename` before they are saved.
+    pub fn formatter(&self, filename: &str) -> Option<&str> {
+        let extension = Path::new(filename).extension()?.to_str()?;
+
+        self.formatters.get(extension).map(String::as_str)
+    }
+
+    /// Returns the tab width and whether to indent with spaces in files of
+    /// `file_type`: as set on the command line or with `:set`, by the
+    /// `project` in `.editorconfig`, in its `[file_types]` table, in the
+    /// `style` of the language, or else as configured for all files.
+    pub fn indentation(
+        &self,
+        file_type: &str,
+        project: Indentation,
+        style: Option<(usize, bool)>,
+    ) -> (usize, bool) {
+        let configured = self
+            .file_types
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(file_type))
+            .map(|(_, indentation)| *indentation)
+            .unwrap_or_default();
+        let tab_width = self
+            .overrides
+            .tab_width
+            .or(project.tab_width)
+            .or(configured.tab_width)
+            .or(style.map(|(tab_width, _)| tab_width))
+            .unwrap_or(self.tab_width);
+        let expand_tabs = self
+            .overrides
+            .expand_tabs
+            .or(project.expand_tabs)
+            .or(configured.expand_tabs)
+            .or(style.map(|(_, expand_tabs)| expand_tabs))
+            .unwrap_or(self.expand_tabs);
+
+        (tab_width, expand_tabs)
+    }
+
+    /// Returns the colors to draw with: as configured, or else as the
+    /// terminal and `NO_COLOR` tell.
+    pub fn color_support(&self) -> ColorSupport {
+        ColorSupport::from_name(&self.color_support).unwrap_or_else(ColorSupport::detect)
+    }
+
+    /// Returns whether to copy through the terminal with OSC 52, which
+    /// unless configured is done in SSH sessions.
+    pub fn osc52(&self) -> bool {
+        self.osc52.unwrap_or_else(|| {
+            env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some()
+        })
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(tab_width) = [self.tab_width]
+            .into_iter()
+            .chain(
+                self.file_types
+                    .values()
+                    .filter_map(|file_type| file_type.tab_width),
+            )
+            .find(|tab_width| !(MIN_TAB_WIDTH..=MAX_TAB_WIDTH).contains(tab_width))
+        {
+            return Err(ConfigError::TabWidth(tab_width));
+        }
+
+        if self.empty_line.chars().count() > 1 {
+            return Err(ConfigError::InvalidValue(
+                "empty_line".to_owned(),
+                self.empty_line.clone(),
+            ));
+        }
+
+        if !is_color_support(&self.color_support) {
+            return Err(ConfigError::InvalidValue(
+                "color_support".to_owned(),
+                self.color_support.clone(),
+            ));
+        }
+
+        self.theme()?;
+
+        Ok(())
+    }
+}
+
+fn is_color_support(value: &str) -> bool {
+    value == "auto" || ColorSupport::from_name(value).is_some()
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(
+            Config::parse("tab_width = 2\nexpand_tabs = true").unwrap(),
+            Config {
+                tab_width: 2,
+                expand_tabs: true,
+                ..Config::default()
+            }
+        );
+        assert_eq!(
+            Config::parse("autosave_interval = 30")
+                .unwrap()
+                .autosave_interval(),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(Config::default().autosave_interval(), None);
+        assert_eq!(
+            Config::default().message_duration(),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            Config::parse("message_duration = 0")
+                .unwrap()
+                .message_duration(),
+            None
+        );
+        assert_eq!(
+            Config::parse("help_duration = 0").unwrap().help_duration(),
+            None
+        );
+        assert!(matches!(
+            Config::parse("[colors]\nstring = \"ultraviolet\""),
+            Err(ConfigError::Theme(ThemeError::InvalidColor(_, _)))
+        ));
+        assert!(matches!(
+            Config::parse("tab_width = 32"),
+            Err(ConfigError::TabWidth(32))
+        ));
+        assert!(matches!(
+            Config::parse("tab_width = "),
+            Err(ConfigError::Parse(_))
+        ));
+
+        let config = Config::parse("[formatters]\nrs = \"rustfmt\"").unwrap();
+
+        assert_eq!(config.formatter("src/main.rs"), Some("rustfmt"));
+        assert_eq!(config.formatter("main.go"), None);
+        assert_eq!(config.formatter("Makefile"), None);
+    }
+
+    #[test]
+    fn test_set() {
+        let mut config = Config::default();
+
+        assert!(config.set("number").is_ok());
+        assert!(config.set("tab_width=2").is_ok());
+        assert!(config.set("noexpand_tabs").is_ok());
+        assert!(config.line_numbers);
+        assert_eq!(config.tab_width, 2);
+        assert!(!config.expand_tabs);
+        assert!(matches!(
+            config.set("tab_width=99"),
+            Err(ConfigError::TabWidth(99))
+        ));
+        assert!(matches!(
+            config.set("ascii_only=maybe"),
+            Err(ConfigError::InvalidValue(_, _))
+        ));
+        assert!(matches!(
+            config.set("colour"),
+            Err(ConfigError::UnknownOption(_))
+        ));
+        assert_eq!(config.tab_width, 2);
+
+        assert!(config.set("nonumber").is_ok());
+        assert!(!config.line_numbers);
+
+        assert!(config.set("empty_line=").is_ok());
+        assert_eq!(config.empty_line, "");
+        assert!(matches!(
+            config.set("empty_line=~~"),
+            Err(ConfigError::InvalidValue(_, _))
+        ));
+        assert!(matches!(
+            Config::parse("empty_line = \"--\""),
+            Err(ConfigError::InvalidValue(_, _))
+        ));
+
+        assert!(config.set("undo_levels=0").is_ok());
+        assert!(config.set("undo_spill").is_ok());
+        assert_eq!(
+            config.undo_limit(),
+            UndoLimit {
+                steps: 0,
+                bytes: 64 * 1024 * 1024,
+                spill: true
+            }
+        );
+
+        assert!(config.set("color_support=256").is_ok());
+        assert_eq!(config.color_support(), ColorSupport::Ansi256);
+        assert!(matches!(
+            config.set("color_support=88"),
+            Err(ConfigError::InvalidValue(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_indentation() {
+        let mut config = Config::parse(
+            "tab_width = 2\n[file_types.makefile]\ntab_width = 4\n[file_types.go]\nexpand_tabs = true",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.indentation("Rust", Indentation::default(), Some((4, true))),
+            (4, true)
+        );
+        assert_eq!(
+            config.indentation("Makefile", Indentation::default(), Some((8, false))),
+            (4, false)
+        );
+        assert_eq!(
+            config.indentation("Go", Indentation::default(), None),
+            (2, true)
+        );
+        assert_eq!(
+            config.indentation("No filetype", Indentation::default(), None),
+            (2, false)
+        );
+        assert!(matches!(
+            Config::parse("[file_types.rust]\ntab_width = 0"),
+            Err(ConfigError::TabWidth(0))
+        ));
+
+        let project = Indentation {
+            tab_width: None,
+            expand_tabs: Some(true),
+        };
+
+        assert_eq!(
+            config.indentation("Makefile", project, Some((8, false))),
+            (4, true)
+        );
+
+        config.set("tab_width=3").unwrap();
+
+        assert_eq!(
+            config.indentation("Makefile", Indentation::default(), Some((8, false))),
+            (3, false)
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut config = Config::default();
+        let arguments = Arguments {
+            tab_width: Some(8),
+            ..Arguments::default()
+        };
+
+        assert!(config.apply(&arguments).is_ok());
+        assert_eq!(config.tab_width, 8);
+        assert!(config
+            .apply(&Arguments {
+                tab_width: Some(0),
+                expand_tabs: Some(true),
+                ..Arguments::default()
+            })
+            .is_err());
+        assert_eq!(config.tab_width, 8);
+        assert!(config.expand_tabs);
+    }
+}
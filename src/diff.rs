@@ -0,0 +1,232 @@
+//! Comparing two files line by line for `--diff`, which shows them side by
+//! side with the lines that differ tinted.
+
+use crate::git;
+
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineChange {
+    /// Only in the new file.
+    Added,
+    /// Only in the old file.
+    Removed,
+    /// Facing a different line on the other side.
+    Changed,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LineDiff {
+    /// How each line of the old and the new file differs, if it does.
+    pub old: Vec<Option<LineChange>>,
+    pub new: Vec<Option<LineChange>>,
+    /// The lines the files have in common, as pairs of their indices in
+    /// the old and the new file, in order.
+    matches: Vec<(usize, usize)>,
+    /// Whether the files differ by too much to match every common line, so
+    /// only the lines found once in each file were.
+    pub approximate: bool,
+}
+
+impl LineDiff {
+    pub fn new(old: &[String], new: &[&str]) -> Self {
+        let mut approximate = false;
+        let matches = git::common_lines(old, new, |old, new| {
+            if old.len().saturating_mul(new.len()) > git::MAX_DIFF_CELLS {
+                approximate = true;
+                matching_unique_lines(old, new)
+            } else {
+                git::matching_lines(old, new)
+            }
+        });
+        let mut diff = Self {
+            old: vec![None; old.len()],
+            new: vec![None; new.len()],
+            matches: Vec::new(),
+            approximate,
+        };
+
+        // Between two common lines, as many lines as both sides have are
+        // taken for changed, and the rest for added or removed.
+        for (old_lines, new_lines) in git::runs(&matches, old.len(), new.len()) {
+            let changed = old_lines.len().min(new_lines.len());
+
+            for (k, change) in diff.old[old_lines].iter_mut().enumerate() {
+                *change = Some(if k < changed {
+                    LineChange::Changed
+                } else {
+                    LineChange::Removed
+                });
+            }
+
+            for (k, change) in diff.new[new_lines].iter_mut().enumerate() {
+                *change = Some(if k < changed {
+                    LineChange::Changed
+                } else {
+                    LineChange::Added
+                });
+            }
+        }
+
+        diff.matches = matches;
+        diff
+    }
+
+    /// Returns the line of the other file facing `line` of the old file, or
+    /// of the new one when `from_old` is false, to scroll both together.
+    pub fn facing(&self, line: usize, from_old: bool) -> usize {
+        // Each pair as the line on the side of `line` and the one facing it.
+        let oriented =
+            |&(old, new): &(usize, usize)| if from_old { (old, new) } else { (new, old) };
+        let other_len = if from_old {
+            self.new.len()
+        } else {
+            self.old.len()
+        };
+        let next = self
+            .matches
+            .partition_point(|pair| oriented(pair).0 <= line);
+        let facing = match next.checked_sub(1) {
+            Some(previous) => {
+                let (side, other) = oriented(&self.matches[previous]);
+
+                other + (line - side)
+            }
+            None => line,
+        };
+        // Past the lines of a change that the other side has fewer of, the
+        // other side waits at its next common line.
+        let limit = self
+            .matches
+            .get(next)
+            .map_or(other_len.saturating_sub(1), |pair| oriented(pair).1);
+
+        facing.min(limit)
+    }
+
+    /// Returns how many lines were changed, added and removed.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let count = |lines: &[Option<LineChange>], kind| {
+            lines.iter().filter(|change| **change == Some(kind)).count()
+        };
+
+        (
+            count(&self.new, LineChange::Changed),
+            count(&self.new, LineChange::Added),
+            count(&self.old, LineChange::Removed),
+        )
+    }
+}
+
+/// Matches the lines found exactly once in both `old` and `new`, as long
+/// as they come in the same order, which takes linear time however large
+/// the files are.
+fn matching_unique_lines(old: &[String], new: &[&str]) -> Vec<(usize, usize)> {
+    // How often each line is in the old and the new file, and where it
+    // last was in the old one.
+    let mut lines: HashMap<&str, (usize, usize, usize)> = HashMap::new();
+
+    for (index, line) in old.iter().enumerate() {
+        let (in_old, _, at) = lines.entry(line.as_str()).or_default();
+
+        *in_old += 1;
+        *at = index;
+    }
+
+    for line in new {
+        if let Some((_, in_new, _)) = lines.get_mut(line) {
+            *in_new += 1;
+        }
+    }
+
+    let mut matches: Vec<(usize, usize)> = Vec::new();
+
+    for (index, line) in new.iter().enumerate() {
+        if let Some(&(1, 1, at)) = lines.get(line) {
+            if matches.last().is_none_or(|&(previous, _)| previous < at) {
+                matches.push((at, index));
+            }
+        }
+    }
+
+    matches
+}
+
+#[cfg(test)]
+mod diff_tests {
+    use super::*;
+
+    fn diff(old: &[&str], new: &[&str]) -> LineDiff {
+        let old: Vec<String> = old.iter().map(|line| (*line).to_owned()).collect();
+
+        LineDiff::new(&old, new)
+    }
+
+    #[test]
+    fn test_new() {
+        let diff = diff(&["a", "b", "c", "d", "e"], &["a", "B", "c", "x", "y", "e"]);
+
+        assert_eq!(
+            diff.old,
+            vec![
+                None,
+                Some(LineChange::Changed),
+                None,
+                Some(LineChange::Changed),
+                None
+            ]
+        );
+        assert_eq!(
+            diff.new,
+            vec![
+                None,
+                Some(LineChange::Changed),
+                None,
+                Some(LineChange::Changed),
+                Some(LineChange::Added),
+                None
+            ]
+        );
+        assert_eq!(diff.counts(), (2, 1, 0));
+        assert_eq!(
+            self::diff(&["a", "b"], &["b"]).old,
+            vec![Some(LineChange::Removed), None]
+        );
+    }
+
+    #[test]
+    fn test_facing() {
+        let diff = diff(&["a", "b", "c", "d"], &["a", "x", "y", "z", "c", "d"]);
+
+        assert_eq!(diff.facing(0, true), 0);
+        assert_eq!(diff.facing(2, true), 4);
+        assert_eq!(diff.facing(3, true), 5);
+        assert_eq!(diff.facing(2, false), 2);
+        assert_eq!(diff.facing(3, false), 2);
+        assert_eq!(diff.facing(5, false), 3);
+        assert_eq!(diff.facing(9, false), 3);
+    }
+
+    #[test]
+    fn test_approximate() {
+        let old: Vec<String> = (0..1100).map(|line| line.to_string()).collect();
+        let new: Vec<&str> = old
+            .iter()
+            .enumerate()
+            .map(|(line, text)| {
+                if line % 2 == 0 {
+                    text.as_str()
+                } else {
+                    "other"
+                }
+            })
+            .collect();
+        let diff = LineDiff::new(&old, &new);
+
+        assert!(diff.approximate);
+        assert_eq!(diff.new[1], Some(LineChange::Changed));
+        assert_eq!(diff.new[2], None);
+        assert_eq!(diff.counts(), (550, 0, 0));
+        assert!(!self::diff(&["a"], &["b"]).approximate);
+    }
+}
@@ -1,20 +1,207 @@
-use crate::highlight::HighlightError;
+use crate::editorconfig::EditorConfig;
+use crate::encoding::Encoding;
+use crate::gap_buffer::GapBuffer;
+use crate::git::{self, Change};
+use crate::highlight;
+use crate::history::{Edit, History, UndoLimit};
+use crate::line_index::{Chunk, LineIndex};
 use crate::row::RowError;
+use crate::spelling::Dictionary;
 use crate::FileType;
 use crate::Position;
 use crate::Row;
 use crate::SearchDirection;
 
+use std::cmp;
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs::{read_to_string, File};
-use std::io::{Error as IOError, Write};
+use std::ffi::CString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Error as IOError, ErrorKind, Read, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+use std::time::SystemTime;
+use thiserror::Error;
+
+/// Files larger than this are read as their rows come into view.
+const LARGE_FILE_SIZE: u64 = 64 * 1024 * 1024;
 
 #[derive(Default)]
 pub struct Document {
-    rows: Vec<Row>,
+    rows: GapBuffer<Row>,
     pub filename: Option<String>,
+    pub read_only: bool,
+    /// Whether highlighting colors brackets by their nesting depth.
+    pub rainbow_brackets: bool,
     changed: bool,
+    /// Counts the changes to the text, including undoing and redoing them.
+    edits: usize,
     file_type: FileType,
+    history: History,
+    transaction: Option<Transaction>,
+    checkpoint: Position,
+    checkpoint_anchor: Option<Position>,
+    backed_up: bool,
+    missing_final_newline: bool,
+    /// Whether lines end in `\r\n`, as found at the end of the first line.
+    crlf: bool,
+    encoding: Encoding,
+    /// The file name and modification time when the file was last read
+    /// or written, and the later modification time already reported.
+    on_disk: Option<(String, SystemTime)>,
+    reported: Option<SystemTime>,
+    /// Where the rows not read yet are, for a large file opened lazily.
+    lines: Option<LineIndex>,
+    /// The lines of the file as committed to Git, and how each row differs
+    /// from them as of the edit count they were compared at.
+    committed: Option<Vec<String>>,
+    editor_config: EditorConfig,
+    /// Whether to indent with spaces, as found in the text on request.
+    expand_tabs: Option<bool>,
+    changes: Vec<Option<Change>>,
+    changes_edits: Option<usize>,
+    /// The text as it was last read or saved, to revert to.
+    saved: String,
+    /// Where rows were added or removed since the last `take_line_shifts`,
+    /// and the rows the latest change is replacing, with where they start
+    /// whether each was edited and how many rows follow them.
+    shifts: Vec<LineShift>,
+    pending_shift: Option<(usize, Vec<String>, Vec<bool>, usize)>,
+}
+
+/// Rows `at..at + removed` of the document were replaced by `inserted`
+/// rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineShift {
+    pub at: usize,
+    pub removed: usize,
+    pub inserted: usize,
+}
+
+impl LineShift {
+    /// Returns where line `y` went: lines below the change move along with
+    /// it and lines that were replaced stay within the rows replacing them,
+    /// or go to the row after them if none did.
+    pub fn line(self, y: usize) -> usize {
+        if y < self.at {
+            y
+        } else if y >= self.at + self.removed {
+            y - self.removed + self.inserted
+        } else {
+            cmp::min(y, self.at + self.inserted.saturating_sub(1))
+        }
+    }
+
+    /// Returns the change from `old` rows at `at` to `new` ones, leaving out
+    /// the rows they start and end with alike, or `None` if as many rows
+    /// are left as there were.
+    fn between(at: usize, old: &[String], new: &[&str]) -> Option<Self> {
+        let (prefix, suffix) = common_ends(old, new);
+        let removed = old.len() - prefix - suffix;
+        let inserted = new.len() - prefix - suffix;
+
+        (removed != inserted).then_some(Self {
+            at: at + prefix,
+            removed,
+            inserted,
+        })
+    }
+}
+
+/// Returns how many rows `old` and `new` start and end with alike.
+fn common_ends(old: &[String], new: &[&str]) -> (usize, usize) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == *b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == *b)
+        .count();
+
+    (prefix, suffix)
+}
+
+/// Line, word and character counts of a part of the document.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Stats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+}
+
+/// Where a search matched, and whether it went past an end of the document
+/// to get there.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    pub position: Position,
+    pub wrapped: bool,
+}
+
+/// Why a document could not be saved.
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("permission denied, cannot write {0}")]
+    PermissionDenied(String),
+    #[error("directory {0} does not exist")]
+    MissingDirectory(String),
+    #[error("no space left on the disk")]
+    DiskFull,
+    #[error("the file system is read-only")]
+    ReadOnlyFileSystem,
+    #[error("the file is too large to be saved")]
+    TooLarge,
+    #[error("{0}")]
+    Io(#[from] IOError),
+}
+
+impl SaveError {
+    /// Tells what went wrong writing to `filename` with `err`.
+    pub fn from_io(err: IOError, filename: &str) -> Self {
+        match err.kind() {
+            ErrorKind::PermissionDenied => Self::PermissionDenied(filename.to_owned()),
+            ErrorKind::NotFound => {
+                let directory = Path::new(filename)
+                    .parent()
+                    .filter(|parent| !parent.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+
+                if directory.exists() {
+                    Self::Io(err)
+                } else {
+                    Self::MissingDirectory(directory.display().to_string())
+                }
+            }
+            ErrorKind::StorageFull => Self::DiskFull,
+            ErrorKind::ReadOnlyFilesystem => Self::ReadOnlyFileSystem,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+/// Tells `report` how many bytes were read from `reader` so far.
+struct Progress<R, F> {
+    reader: R,
+    read: u64,
+    report: F,
+}
+
+impl<R: Read, F: FnMut(u64)> Read for Progress<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IOError> {
+        let read = self.reader.read(buf)?;
+
+        self.read += read as u64;
+        (self.report)(self.read);
+
+        Ok(read)
+    }
+}
+
+struct Transaction {
+    at: usize,
+    tail: usize,
+    removed: Vec<String>,
 }
 
 impl Document {
@@ -22,32 +209,239 @@ impl Document {
     ///
     /// Will return `Error` if it fails read filename
     pub fn open(filename: &str) -> Result<Self, IOError> {
-        let contents = read_to_string(filename)?;
-        let file_type = FileType::new().from(filename);
-        let mut rows = Vec::new();
+        Self::open_with_progress(filename, |_, _| {})
+    }
+
+    /// Opens `filename` like `open`, calling `progress` with the number of
+    /// bytes read so far and the size of the file as it is read.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if it fails read filename
+    pub fn open_with_progress<F>(filename: &str, progress: F) -> Result<Self, IOError>
+    where
+        F: FnMut(u64, u64),
+    {
+        let lazy = fs::metadata(filename)?.len() > LARGE_FILE_SIZE;
+
+        Self::read_file(filename, lazy, progress)
+    }
+
+    /// Opens a file read-only, reading only its line breaks until rows are
+    /// loaded with `load_rows`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if it fails read filename
+    pub fn open_lazy(filename: &str) -> Result<Self, IOError> {
+        Self::read_file(filename, true, |_, _| {})
+    }
+
+    fn read_file<F>(filename: &str, lazy: bool, mut progress: F) -> Result<Self, IOError>
+    where
+        F: FnMut(u64, u64),
+    {
+        let file = File::open(filename)?;
+        let metadata = file.metadata()?;
+        let mut report = |read| progress(read, metadata.len());
+
+        report(0);
+
+        let mut document = if lazy {
+            let lines = LineIndex::new(file, &mut report)?;
+
+            Self {
+                missing_final_newline: lines.missing_final_newline()?,
+                lines: Some(lines),
+                read_only: true,
+                ..Self::default()
+            }
+        } else {
+            let mut bytes = Vec::new();
+
+            Progress {
+                reader: file,
+                read: 0,
+                report: &mut report,
+            }
+            .read_to_end(&mut bytes)?;
+
+            let encoding = Encoding::detect(&bytes);
+
+            Self {
+                encoding,
+                ..Self::from_text(&encoding.decode(&bytes)?)
+            }
+        };
+
+        document.file_type = document.detect_file_type(filename);
+        document.filename = Some(filename.to_owned());
+        document.read_only |= metadata.permissions().readonly() || !is_writable(filename);
+        document.remember_disk_state();
+
+        Ok(document)
+    }
+
+    /// Returns whether the file was too large to read at once, in which
+    /// case it cannot be edited.
+    pub fn is_lazy(&self) -> bool {
+        self.lines.is_some()
+    }
+
+    /// Reads the version of the file committed to Git again, to compare the
+    /// rows with. Files outside a repository are not compared.
+    pub fn load_committed(&mut self) {
+        self.committed = self.filename.as_deref().and_then(git::committed);
+        self.changes.clear();
+        self.changes_edits = None;
+    }
 
-        for value in contents.lines() {
-            rows.push(Row::from(value));
+    /// Reads the settings `.editorconfig` files give the file, which from
+    /// then on is saved with the line breaks they ask for.
+    pub fn load_editor_config(&mut self) {
+        self.editor_config = self
+            .filename
+            .as_deref()
+            .map(EditorConfig::for_file)
+            .unwrap_or_default();
+
+        if let Some(crlf) = self.editor_config.crlf {
+            self.crlf = crlf;
         }
+    }
 
-        Ok(Self {
-            rows,
-            filename: Some(filename.to_owned()),
-            changed: false,
-            file_type,
-        })
+    pub fn editor_config(&self) -> &EditorConfig {
+        &self.editor_config
+    }
+
+    /// Returns whether to indent this document with spaces, if it was set
+    /// with `set_expand_tabs`.
+    pub fn expand_tabs(&self) -> Option<bool> {
+        self.expand_tabs
+    }
+
+    /// Makes this document indent with spaces, or with tabs, whatever the
+    /// configuration says.
+    pub fn set_expand_tabs(&mut self, expand_tabs: bool) {
+        self.expand_tabs = Some(expand_tabs);
+    }
+
+    /// Compares the rows with the committed version again if the text
+    /// changed since they last were. Large files opened lazily are not.
+    pub fn update_changes(&mut self) {
+        let Some(committed) = self.committed.as_ref().filter(|_| !self.is_lazy()) else {
+            return;
+        };
+
+        if self.changes_edits == Some(self.edits) {
+            return;
+        }
+
+        let lines: Vec<&str> = self
+            .rows
+            .iter()
+            .map(|row| row.as_string().as_str())
+            .collect();
+
+        self.changes = git::diff(committed, &lines);
+        self.changes_edits = Some(self.edits);
+    }
+
+    /// Returns whether the rows are compared with a committed version.
+    pub fn is_committed(&self) -> bool {
+        self.committed.is_some()
+    }
+
+    /// Returns how the row at `index` differs from the committed version,
+    /// as of the last `update_changes`.
+    pub fn change(&self, index: usize) -> Option<Change> {
+        self.changes.get(index).copied().flatten()
+    }
+
+    /// Reads the rows in `ranges` of a file opened lazily, dropping rows
+    /// outside them once too many are kept.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read
+    pub fn load_rows(&mut self, ranges: &[Range<usize>]) -> Result<(), IOError> {
+        match &mut self.lines {
+            Some(lines) => lines.load(ranges),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads an unnamed document, e.g. from piped standard input.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if it fails to read or the content is not UTF-8
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, IOError> {
+        let mut contents = String::new();
+
+        reader.read_to_string(&mut contents)?;
+
+        Ok(Self::from_text(&contents))
+    }
+
+    fn from_text(contents: &str) -> Self {
+        Self {
+            rows: contents.lines().map(Row::from).collect(),
+            missing_final_newline: !contents.is_empty() && !contents.ends_with('\n'),
+            crlf: contents
+                .find('\n')
+                .is_some_and(|end| contents[..end].ends_with('\r')),
+            saved: contents.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the encoding the file was read in and is saved in.
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Returns whether the text starts with a byte order mark, which UTF-8
+    /// text does not need and which many tools do not expect.
+    pub fn has_bom(&self) -> bool {
+        self.row(0).and_then(|row| row.grapheme(0)) == Some("\u{feff}")
+    }
+
+    /// Removes the byte order mark the text starts with, returning whether
+    /// there was one.
+    pub fn strip_bom(&mut self) -> bool {
+        self.has_bom() && self.delete(&Position::default()).is_ok()
+    }
+
+    /// Makes the next save write the file in `encoding`.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if encoding != self.encoding {
+            self.encoding = encoding;
+            self.changed = true;
+        }
     }
 
     pub fn row(&self, index: usize) -> Option<&Row> {
-        self.rows.get(index)
+        match &self.lines {
+            Some(lines) => lines.row(index),
+            None => self.rows.get(index),
+        }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.rows.is_empty()
+        self.len() == 0
     }
 
     pub fn len(&self) -> usize {
-        self.rows.len()
+        self.lines.as_ref().map_or(self.rows.len(), LineIndex::len)
+    }
+
+    /// Returns the size of the document as it would be saved, in bytes.
+    pub fn byte_len(&self) -> usize {
+        match &self.lines {
+            Some(lines) => usize::try_from(lines.byte_len()).unwrap_or(usize::MAX),
+            None => self.rows.iter().map(|row| row.as_bytes().len() + 1).sum(),
+        }
     }
 
     pub fn insert(&mut self, at: &Position, c: char) -> Result<(), Box<dyn Error>> {
@@ -58,15 +452,17 @@ impl Document {
         self.changed = true;
 
         if c == '\n' {
-            match self.insert_newline(at) {
+            match self.split_row(at) {
                 Ok(_) => (),
                 Err(err) => return Err(err),
             }
         } else if at.y == self.rows.len() {
+            self.touch(at.y, at.y);
             let mut row = Row::default();
             row.insert(0, c);
             self.rows.push(row);
         } else {
+            self.touch(at.y, at.y.saturating_add(1));
             let row = self.rows.get_mut(at.y);
 
             match row {
@@ -75,8 +471,6 @@ impl Document {
             }
         }
 
-        self.unhighlight_rows(at.y);
-
         Ok(())
     }
 
@@ -91,11 +485,12 @@ impl Document {
         }
 
         self.changed = true;
-        let row = self.rows.get_mut(at.y);
+        let row_len = self.rows.get(at.y).map(Row::len);
 
-        match row {
-            Some(r) => {
-                if at.x == r.len() && at.y < len - 1 {
+        match row_len {
+            Some(row_len) => {
+                if at.x == row_len && at.y < len - 1 {
+                    self.touch(at.y, at.y.saturating_add(2));
                     let next_row = self.rows.remove(at.y + 1);
                     let row = self.rows.get_mut(at.y);
 
@@ -104,6 +499,7 @@ impl Document {
                         None => return Err(Box::new(RowError::DeletionError(at.x, at.y))),
                     }
                 } else {
+                    self.touch(at.y, at.y.saturating_add(1));
                     let row = self.rows.get_mut(at.y);
 
                     match row {
@@ -112,159 +508,2445 @@ impl Document {
                     }
                 }
 
-                self.unhighlight_rows(at.y);
-
                 Ok(())
             }
             None => Err(Box::new(RowError::DeletionError(at.x, at.y))),
         }
     }
 
-    /// # Errors
-    ///
-    /// Will return `Error` if it fails to create a file to save
-    pub fn save(&mut self) -> Result<(), IOError> {
-        if let Some(filename) = &self.filename {
-            let mut file = File::create(filename)?;
-            self.file_type = FileType::new().from(filename);
+    /// Returns the text between `start` and `end`, joining rows with `\n`.
+    pub fn text(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
 
-            for row in &mut self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n")?;
+        for y in start.y..=end.y {
+            let Some(row) = self.row(y) else {
+                break;
+            };
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+
+            if y > start.y {
+                text.push('\n');
             }
 
-            self.changed = false;
+            text.push_str(&row.substring(from, to));
         }
 
-        Ok(())
+        text
     }
 
-    pub fn is_changed(&self) -> bool {
-        self.changed
+    /// Returns the whole text, as `to_string` does.
+    pub fn contents(&self) -> String {
+        self.to_string()
     }
 
-    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
-        if at.y >= self.rows.len() {
-            return None;
+    /// Counts the lines, whitespace-delimited words and characters between
+    /// `start` and `end`. Line breaks are not counted as characters.
+    pub fn stats(&self, start: &Position, end: &Position) -> Stats {
+        let mut stats = Stats {
+            lines: 0,
+            words: 0,
+            chars: 0,
+        };
+
+        for y in start.y..=end.y {
+            let Some(row) = self.row(y) else {
+                break;
+            };
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+            let line = row.substring(from, to);
+
+            stats.lines += 1;
+            stats.words += line.split_whitespace().count();
+            stats.chars += line.chars().count();
         }
 
-        let mut position = Position { x: at.x, y: at.y };
-        let start = if direction == SearchDirection::Forward {
-            at.y
-        } else {
-            0
-        };
-        let end = if direction == SearchDirection::Forward {
-            self.rows.len()
+        stats
+    }
+
+    /// Inserts `text` at `at`, splitting the row at its line breaks, and
+    /// returns the position right after it.
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Position {
+        if at.y > self.rows.len() || text.is_empty() {
+            return at.clone();
+        }
+
+        let (head, tail) = self.rows.get(at.y).map_or_else(Default::default, |row| {
+            (row.substring(0, at.x), row.substring(at.x, row.len()))
+        });
+        let mut lines: Vec<String> = text.split('\n').map(str::to_owned).collect();
+        let last = lines.len() - 1;
+
+        lines[0].insert_str(0, &head);
+
+        let x = Row::from(lines[last].as_str()).len();
+
+        lines[last].push_str(&tail);
+        self.touch(at.y, at.y.saturating_add(1));
+
+        let end = cmp::min(at.y + 1, self.rows.len());
+
+        self.rows
+            .splice(at.y..end, lines.iter().map(|line| Row::from(line.as_str())));
+        self.changed = true;
+
+        Position { x, y: at.y + last }
+    }
+
+    /// Removes the text between `start` and `end`, merging the rows they lie on.
+    pub fn delete_range(&mut self, start: &Position, end: &Position) {
+        if start.y >= self.rows.len() || start >= end {
+            return;
+        }
+
+        let last = cmp::min(end.y, self.rows.len().saturating_sub(1));
+
+        self.touch(start.y, last.saturating_add(1));
+
+        let head = self.rows[start.y].substring(0, start.x);
+        let tail = if end.y == last {
+            let row = &self.rows[last];
+            row.substring(end.x, row.len())
         } else {
-            at.y.saturating_add(1)
+            String::new()
         };
 
-        for _ in start..end {
-            if let Some(row) = self.rows.get(position.y) {
-                if let Some(x) = row.find(query, position.x, direction) {
-                    position.x = x;
+        self.rows.splice(
+            start.y..last + 1,
+            [Row::from(format!("{head}{tail}").as_str())],
+        );
+        self.changed = true;
+    }
 
-                    return Some(position);
-                }
+    /// Replaces the text between `start` and `end` with what `f` makes of
+    /// it, line by line, and returns where the replaced text ends.
+    pub fn map_range(
+        &mut self,
+        start: &Position,
+        end: &Position,
+        f: impl Fn(&str) -> String,
+    ) -> Position {
+        let mut last = start.clone();
 
-                if direction == SearchDirection::Forward {
-                    position.y = position.y.saturating_add(1);
-                    position.x = 0;
-                } else {
-                    position.y = position.y.saturating_sub(1);
-                    match self.rows.get(position.y) {
-                        Some(row) => position.x = row.len(),
-                        None => return None,
-                    }
-                }
+        if start.y >= self.rows.len() || start >= end {
+            return last;
+        }
+
+        let last_row = cmp::min(end.y, self.rows.len().saturating_sub(1));
+
+        for y in start.y..=last_row {
+            let row = &self.rows[y];
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y {
+                end.x.min(row.len())
             } else {
-                return None;
+                row.len()
+            };
+            let mapped = f(&row.substring(from, to));
+            let text = format!(
+                "{}{mapped}{}",
+                row.substring(0, from),
+                row.substring(to, row.len())
+            );
+
+            last = Position {
+                x: from + Row::from(mapped.as_str()).len(),
+                y,
+            };
+
+            if text != *row.as_string() {
+                self.touch(y, y.saturating_add(1));
+                self.rows[y] = Row::from(text.as_str());
+                self.changed = true;
             }
         }
 
-        None
+        last
     }
 
-    pub fn highlight(
-        &mut self,
-        word: &Option<String>,
-        until: Option<usize>,
-    ) -> Result<(), Box<dyn Error>> {
-        let mut start_with_comment = false;
-        let len = self.rows.len();
-        let until = if let Some(until) = until {
-            if until.saturating_add(1) < len {
-                until.saturating_add(1)
-            } else {
-                len
-            }
-        } else {
-            len
-        };
-        let rows = self.rows.get_mut(..until);
+    /// Adds `delta` to the integer at or after `at` on its line and returns
+    /// the index of its last digit, or `None` if there is no number.
+    pub fn add_to_number(&mut self, at: &Position, delta: i128) -> Option<usize> {
+        let row = self.rows.get(at.y)?;
+        let (range, number) = row.add_to_number(at.x, delta)?;
+        let text = format!(
+            "{}{number}{}",
+            row.substring(0, range.start),
+            row.substring(range.end, row.len())
+        );
 
-        match rows {
-            Some(r) => {
-                for row in r {
-                    start_with_comment =
-                        row.highlight(self.file_type.highlight_options(), word, start_with_comment);
-                }
-            }
-            None => return Err(Box::new(HighlightError)),
+        self.touch(at.y, at.y.saturating_add(1));
+        self.rows[at.y] = Row::from(text.as_str());
+        self.changed = true;
+
+        Some(range.start + number.len() - 1)
+    }
+
+    /// Removes the row at `index` and returns it.
+    pub fn remove_row(&mut self, index: usize) -> Option<Row> {
+        if index >= self.rows.len() {
+            return None;
         }
 
-        Ok(())
+        self.touch(index, index.saturating_add(1));
+        self.changed = true;
+
+        Some(self.rows.remove(index))
     }
 
-    pub fn file_type(&self) -> String {
-        self.file_type.name()
+    /// Inserts a copy of the row at `index` directly below it.
+    pub fn duplicate_row(&mut self, index: usize) {
+        let copy = match self.rows.get(index) {
+            Some(row) => Row::from(row.as_string().as_str()),
+            None => return,
+        };
+
+        self.touch(index.saturating_add(1), index.saturating_add(1));
+        self.rows.insert(index.saturating_add(1), copy);
+        self.changed = true;
     }
 
-    fn insert_newline(&mut self, at: &Position) -> Result<(), Box<dyn Error>> {
-        if at.y > self.rows.len() {
-            return Ok(());
+    /// Removes the row at `index`, doing nothing if it is out of range.
+    pub fn delete_row(&mut self, index: usize) {
+        if index >= self.rows.len() {
+            return;
         }
 
-        if at.y == self.rows.len() {
-            self.rows.push(Row::default());
-            return Ok(());
+        self.touch(index, index.saturating_add(1));
+        self.rows.remove(index);
+        self.changed = true;
+    }
+
+    /// Exchanges the rows at `a` and `b`, doing nothing if either is out of range.
+    pub fn swap_rows(&mut self, a: usize, b: usize) {
+        if a >= self.rows.len() || b >= self.rows.len() || a == b {
+            return;
         }
 
-        let current_row = self.rows.get_mut(at.y);
+        let (first, last) = (cmp::min(a, b), cmp::max(a, b));
 
-        match current_row {
-            Some(row) => {
-                let new_row = row.split(at.x);
-                self.rows.insert(at.y + 1, new_row);
+        self.touch(first, last.saturating_add(1));
+        self.rows.swap(a, b);
+        self.changed = true;
+    }
 
-                Ok(())
+    /// Returns the last line of the region starting on line `y` that can be
+    /// folded: up to the bracket closing one opened on the line, or else
+    /// over the lines below it that are indented deeper.
+    pub fn fold_range(&self, y: usize) -> Option<usize> {
+        let row = self.rows.get(y)?;
+        let closing = (0..row.len()).rev().find_map(|x| {
+            let (_, _, direction) = bracket_pair(row.grapheme(x)?)?;
+
+            if direction != SearchDirection::Forward || !row.is_code(x) {
+                return None;
+            }
+
+            self.matching_bracket(&Position { x, y })
+                .filter(|closing| closing.y > y)
+        });
+
+        closing.map_or_else(|| self.indented_range(y), |closing| Some(closing.y))
+    }
+
+    /// Returns every outermost region `fold_range` finds, going through the
+    /// document once.
+    pub fn fold_regions(&self) -> Vec<(usize, usize)> {
+        // The line closing the last bracket opened on each line that is
+        // closed on a later one, and the brackets still open by kind.
+        let mut closing: Vec<Option<(usize, usize)>> = vec![None; self.rows.len()];
+        let mut open: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+
+        for (y, row) in self.rows.iter().enumerate() {
+            for x in (0..row.len()).filter(|&x| row.is_code(x)) {
+                let Some((opening, _, direction)) = row.grapheme(x).and_then(bracket_pair) else {
+                    continue;
+                };
+                let stack = open.entry(opening).or_default();
+
+                if direction == SearchDirection::Forward {
+                    stack.push((x, y));
+                } else if let Some((from, line)) = stack.pop() {
+                    if line < y && closing[line].is_none_or(|(other, _)| other < from) {
+                        closing[line] = Some((from, y));
+                    }
+                }
+            }
+        }
+
+        let mut regions = Vec::new();
+        let mut y = 0;
+
+        while y < self.rows.len() {
+            match closing[y].map_or_else(|| self.indented_range(y), |(_, last)| Some(last)) {
+                Some(last) => {
+                    regions.push((y, last));
+                    y = last.saturating_add(1);
+                }
+                None => y = y.saturating_add(1),
             }
-            None => return Err(Box::new(RowError::InsertionError(at.x, at.y))),
         }
+
+        regions
     }
 
-    fn unhighlight_rows(&mut self, start: usize) {
-        let start = start.saturating_sub(1);
+    /// Returns the last of the lines below `y` that are indented deeper
+    /// than it.
+    fn indented_range(&self, y: usize) -> Option<usize> {
+        let row = self.rows.get(y)?;
 
-        for row in self.rows.iter_mut().skip(start) {
-            row.is_highlighted = false;
+        if row.as_string().trim().is_empty() {
+            return None;
         }
+
+        let indentation = row.indentation();
+        let is_blank = |index: usize| self.rows[index].as_string().trim().is_empty();
+
+        (y.saturating_add(1)..self.rows.len())
+            .take_while(|&index| is_blank(index) || self.rows[index].indentation() > indentation)
+            .filter(|&index| !is_blank(index))
+            .last()
     }
-}
 
-#[cfg(test)]
-mod document_tests {
-    use super::*;
+    /// Removes the rows in `range` that repeat the row right above them, as
+    /// `uniq` does, returning the indices they had.
+    pub fn dedup_rows(&mut self, range: Range<usize>) -> Vec<usize> {
+        let end = cmp::min(range.end, self.rows.len());
+        let removed: Vec<usize> = (range.start.saturating_add(1)..end)
+            .filter(|&index| self.rows[index].as_string() == self.rows[index - 1].as_string())
+            .collect();
 
-    #[test]
-    fn test_save() {
-        let mut new_doc = Document {
-            filename: Some("test.txt".to_owned()),
-            ..Document::default()
-        };
-        let save_res = new_doc.save();
+        for &index in removed.iter().rev() {
+            self.touch(index, index.saturating_add(1));
+            self.rows.remove(index);
+        }
 
-        assert_eq!(save_res.ok(), Some(()),);
+        self.changed |= !removed.is_empty();
+
+        removed
+    }
+
+    /// Returns whether indented rows mostly start with a tab rather than a
+    /// space, or `None` if no row is indented.
+    pub fn indents_with_tabs(&self) -> Option<bool> {
+        let (tabs, spaces) = self.rows.iter().fold((0, 0), |(tabs, spaces), row| {
+            match row.as_string().chars().next() {
+                Some('\t') => (tabs + 1, spaces),
+                Some(' ') => (tabs, spaces + 1),
+                _ => (tabs, spaces),
+            }
+        });
+
+        (tabs + spaces > 0).then_some(tabs > spaces)
+    }
+
+    /// Rewrites the leading tabs and spaces of every row as spaces when
+    /// `expand` is set, or else as tabs followed by the spaces left over,
+    /// keeping the width they take with tabs of `tab_width` columns.
+    /// Returns whether a row changed.
+    pub fn retab(&mut self, tab_width: usize, expand: bool) -> bool {
+        let mut changed = false;
+
+        for index in 0..self.rows.len() {
+            let string = self.rows[index].as_string();
+            let text = string.trim_start_matches([' ', '\t']);
+            let leading = &string[..string.len() - text.len()];
+            let indentation = indentation(indentation_width(leading, tab_width), tab_width, expand);
+
+            if leading == indentation {
+                continue;
+            }
+
+            let row = Row::from(format!("{indentation}{text}").as_str());
+
+            self.touch(index, index.saturating_add(1));
+            self.rows[index] = row;
+            changed = true;
+        }
+
+        self.changed |= changed;
+
+        changed
+    }
+
+    /// Removes the spaces and tabs at the end of every row, or with
+    /// `edited_only` of the rows changed since the file was opened,
+    /// returning whether there were any.
+    pub fn trim_trailing_whitespace(&mut self, edited_only: bool) -> bool {
+        let mut changed = false;
+
+        self.resolve_shift();
+
+        for index in 0..self.rows.len() {
+            if edited_only && !self.rows[index].is_edited() {
+                continue;
+            }
+
+            let string = self.rows[index].as_string();
+            let trimmed = string.trim_end_matches([' ', '\t']);
+
+            if trimmed.len() == string.len() {
+                continue;
+            }
+
+            let row = Row::from(trimmed);
+
+            self.touch(index, index.saturating_add(1));
+            self.rows[index] = row;
+            changed = true;
+        }
+
+        self.changed |= changed;
+
+        changed
+    }
+
+    /// Appends the row below `index` to it, collapsing the whitespace around
+    /// the join into a single space. Returns the column of the join point.
+    pub fn join_line(&mut self, index: usize) -> Option<usize> {
+        if index.saturating_add(1) >= self.rows.len() {
+            return None;
+        }
+
+        self.touch(index, index.saturating_add(2));
+
+        let next_row = self.rows.remove(index.saturating_add(1));
+        let row = self.rows.get_mut(index)?;
+        let left = row.as_string().trim_end();
+        let right = next_row.as_string().trim_start();
+        let joined = if left.is_empty() || right.is_empty() {
+            format!("{left}{right}")
+        } else {
+            format!("{left} {right}")
+        };
+        let column = Row::from(left).len();
+
+        *row = Row::from(joined.as_str());
+        self.changed = true;
+
+        Some(column)
+    }
+
+    /// Closes the pending undo step and starts a new one at `cursor`.
+    pub fn checkpoint(&mut self, cursor: &Position) {
+        self.checkpoint_selection(cursor, None);
+    }
+
+    /// Closes the pending undo step and starts a new one at `cursor`, with
+    /// the text from `anchor` selected, so that undoing and redoing the
+    /// steps restore the selection.
+    pub fn checkpoint_selection(&mut self, cursor: &Position, anchor: Option<&Position>) {
+        if let Some(transaction) = self.transaction.take() {
+            let end = self.rows.len().saturating_sub(transaction.tail);
+            let inserted: Vec<String> = self
+                .rows
+                .range(transaction.at..end)
+                .map(|row| row.as_string().clone())
+                .collect();
+
+            if inserted != transaction.removed {
+                self.history.push(Edit {
+                    at: transaction.at,
+                    removed: transaction.removed,
+                    inserted,
+                    cursor_before: self.checkpoint.clone(),
+                    cursor_after: cursor.clone(),
+                    anchor_before: self.checkpoint_anchor.take(),
+                    anchor_after: anchor.cloned(),
+                });
+            }
+        }
+
+        self.checkpoint = cursor.clone();
+        self.checkpoint_anchor = anchor.cloned();
+    }
+
+    /// Limits how much undo history is kept in memory, from the next step on.
+    pub fn set_undo_limit(&mut self, limit: UndoLimit) {
+        self.history.limit = limit;
+    }
+
+    /// Reverts the last undo step and returns the cursor position it started
+    /// from, with the selection anchor then.
+    pub fn undo(&mut self, cursor: &Position) -> Option<(Position, Option<Position>)> {
+        self.checkpoint(cursor);
+
+        self.resolve_shift();
+
+        let edit = self.history.undo()?;
+        let removed: Vec<&str> = edit.removed.iter().map(String::as_str).collect();
+
+        self.shifts
+            .extend(LineShift::between(edit.at, &edit.inserted, &removed));
+
+        let end = edit.at.saturating_add(edit.inserted.len());
+        let rows = edit.removed.iter().map(|row| Row::from(row.as_str()));
+        let at = edit.at;
+        let len = edit.removed.len();
+        let old = edit.inserted.clone();
+        let edited: Vec<bool> = self.rows.range(at..end).map(Row::is_edited).collect();
+        let position = edit.cursor_before.clone();
+        let anchor = edit.anchor_before.clone();
+
+        self.rows.splice(at..end, rows);
+        self.carry_edited(at, at + len, &old, &edited);
+        self.changed = true;
+        self.edits = self.edits.wrapping_add(1);
+        self.checkpoint = position.clone();
+        self.checkpoint_anchor = anchor.clone();
+
+        Some((position, anchor))
+    }
+
+    /// Reapplies the last undone step and returns the cursor position it
+    /// ended at, with the selection anchor then.
+    pub fn redo(&mut self, cursor: &Position) -> Option<(Position, Option<Position>)> {
+        self.checkpoint(cursor);
+
+        self.resolve_shift();
+
+        let edit = self.history.redo()?;
+        let inserted: Vec<&str> = edit.inserted.iter().map(String::as_str).collect();
+
+        self.shifts
+            .extend(LineShift::between(edit.at, &edit.removed, &inserted));
+
+        let end = edit.at.saturating_add(edit.removed.len());
+        let rows = edit.inserted.iter().map(|row| Row::from(row.as_str()));
+        let at = edit.at;
+        let len = edit.inserted.len();
+        let old = edit.removed.clone();
+        let edited: Vec<bool> = self.rows.range(at..end).map(Row::is_edited).collect();
+        let position = edit.cursor_after.clone();
+        let anchor = edit.anchor_after.clone();
+
+        self.rows.splice(at..end, rows);
+        self.carry_edited(at, at + len, &old, &edited);
+        self.changed = true;
+        self.edits = self.edits.wrapping_add(1);
+        self.checkpoint = position.clone();
+        self.checkpoint_anchor = anchor.clone();
+
+        Some((position, anchor))
+    }
+
+    /// Writes the document to its file. With `insert_final_newline` the file
+    /// ends in exactly one newline, otherwise it keeps the ending it was read
+    /// with.
+    ///
+    /// # Errors
+    ///
+    /// Will return `SaveError` telling why the file could not be written
+    pub fn save(&mut self, insert_final_newline: bool) -> Result<(), SaveError> {
+        if let Some(filename) = self.filename.clone() {
+            // Encoded before the file is created, so that text which cannot
+            // be written in the encoding leaves the file as it was.
+            let bytes = self.to_bytes(insert_final_newline)?;
+            let mut file =
+                File::create(&filename).map_err(|err| SaveError::from_io(err, &filename))?;
+
+            file.write_all(&bytes)
+                .map_err(|err| SaveError::from_io(err, &filename))?;
+            drop(file);
+            self.mark_saved(insert_final_newline);
+        }
+
+        Ok(())
+    }
+
+    /// Writes `text` to the file named `filename` with the document's line
+    /// breaks and encoding, leaving the document as it is.
+    ///
+    /// # Errors
+    ///
+    /// Will return `SaveError` telling why the file could not be written
+    pub fn write_text(&self, filename: &str, text: &str) -> Result<(), SaveError> {
+        let text = if self.crlf {
+            text.replace('\n', "\r\n")
+        } else {
+            text.to_owned()
+        };
+        let bytes = match self.encoding {
+            Encoding::Utf8 => text.into_bytes(),
+            encoding => encoding.encode(&text)?,
+        };
+
+        fs::write(filename, bytes).map_err(|err| SaveError::from_io(err, filename))
+    }
+
+    /// Returns what `save` writes to the file, in the document's encoding.
+    ///
+    /// # Errors
+    ///
+    /// Will return `SaveError` if the file was opened too large to be saved
+    /// or the text cannot be written in the encoding
+    pub fn to_bytes(&self, insert_final_newline: bool) -> Result<Vec<u8>, SaveError> {
+        if self.is_lazy() {
+            return Err(SaveError::TooLarge);
+        }
+
+        let mut bytes = Vec::new();
+
+        self.write_to(&mut bytes, insert_final_newline)?;
+
+        if self.encoding != Encoding::Utf8 {
+            bytes = self.encoding.encode(&String::from_utf8_lossy(&bytes))?;
+        }
+
+        Ok(bytes)
+    }
+
+    /// Takes the document for saved once `to_bytes` was written to its file,
+    /// by `save` or by a command run to write it.
+    pub fn mark_saved(&mut self, insert_final_newline: bool) {
+        let Some(filename) = &self.filename else {
+            return;
+        };
+        let file_type = self.detect_file_type(filename);
+
+        if file_type.name() != self.file_type.name() {
+            self.file_type = file_type;
+            self.unhighlight_rows();
+        }
+
+        self.changed = false;
+        self.remember_disk_state();
+
+        if insert_final_newline {
+            self.missing_final_newline = false;
+        }
+
+        self.saved = self.contents();
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Error` if it fails to write to `writer`
+    pub fn write_to<W: Write>(
+        &self,
+        writer: &mut W,
+        insert_final_newline: bool,
+    ) -> Result<(), IOError> {
+        for (line, ending) in self.lines_with_endings(insert_final_newline) {
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(ending.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns each row with the line break written after it: `\r\n` or
+    /// `\n` as the file had, and none after the last row of a file that did
+    /// not end in one. With `insert_final_newline` the trailing empty rows
+    /// are left out and the last row always ends in a line break.
+    fn lines_with_endings(
+        &self,
+        insert_final_newline: bool,
+    ) -> impl Iterator<Item = (&str, &'static str)> {
+        let len = if insert_final_newline {
+            (0..self.rows.len())
+                .rev()
+                .find(|&index| !self.rows[index].is_empty())
+                .map_or(0, |last| last + 1)
+        } else {
+            self.rows.len()
+        };
+        let ending = if self.crlf { "\r\n" } else { "\n" };
+
+        self.rows
+            .iter()
+            .take(len)
+            .enumerate()
+            .map(move |(index, row)| {
+                let ending =
+                    if index + 1 < len || insert_final_newline || !self.missing_final_newline {
+                        ending
+                    } else {
+                        ""
+                    };
+
+                (row.as_string().as_str(), ending)
+            })
+    }
+
+    /// Replaces every row with the contents of `reader` as a single undo step.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if it fails to read or the content is not UTF-8
+    pub fn restore<R: Read>(&mut self, reader: R) -> Result<(), IOError> {
+        let restored = Self::from_reader(reader)?;
+
+        self.touch(0, self.rows.len());
+        self.rows = restored.rows;
+        self.lines = None;
+        self.missing_final_newline = restored.missing_final_newline;
+        self.crlf = restored.crlf;
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Reads the file again, as a single undo step, dropping the unsaved
+    /// changes.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read
+    pub fn reload(&mut self) -> Result<(), IOError> {
+        let Some(filename) = self.filename.clone() else {
+            return Ok(());
+        };
+
+        if self.is_lazy() {
+            *self = Self::open_lazy(&filename)?;
+            return Ok(());
+        }
+
+        self.reopen_as(self.encoding)
+    }
+
+    /// Reads the file again in `encoding`, as a single undo step, dropping
+    /// the unsaved changes.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read or is not valid in
+    /// `encoding`
+    pub fn reopen_as(&mut self, encoding: Encoding) -> Result<(), IOError> {
+        let Some(filename) = self.filename.clone() else {
+            return Ok(());
+        };
+
+        if self.is_lazy() {
+            return Err(IOError::new(
+                ErrorKind::Unsupported,
+                "the file is too large to be converted",
+            ));
+        }
+
+        let contents = encoding.decode(&fs::read(filename)?)?;
+
+        self.restore(contents.as_bytes())?;
+        self.encoding = encoding;
+        self.changed = false;
+        self.saved = contents;
+        self.remember_disk_state();
+
+        Ok(())
+    }
+
+    /// Goes back to the text as it was when the file was last read or
+    /// saved, as a single undo step, without reading the file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file was opened too large to be edited
+    pub fn revert(&mut self) -> Result<(), IOError> {
+        if self.is_lazy() {
+            return Err(IOError::new(
+                ErrorKind::Unsupported,
+                "the file is too large to be reverted",
+            ));
+        }
+
+        self.restore(self.saved.clone().as_bytes())?;
+        self.changed = false;
+
+        Ok(())
+    }
+
+    /// Returns whether something else modified the file since it was last
+    /// read or written.
+    pub fn changed_on_disk(&self) -> bool {
+        self.disk_change().is_some()
+    }
+
+    /// Returns whether the file changed on disk since this was last asked,
+    /// so that each change is reported once.
+    pub fn take_disk_change(&mut self) -> bool {
+        let change = self.disk_change();
+
+        if change.is_none() || change == self.reported {
+            return false;
+        }
+
+        self.reported = change;
+
+        true
+    }
+
+    fn disk_change(&self) -> Option<SystemTime> {
+        let (filename, seen) = self.on_disk.as_ref()?;
+
+        if self.filename.as_ref() != Some(filename) {
+            return None;
+        }
+
+        modified_time(filename).filter(|modified| modified != seen)
+    }
+
+    fn remember_disk_state(&mut self) {
+        self.on_disk = self
+            .filename
+            .as_ref()
+            .and_then(|filename| Some((filename.clone(), modified_time(filename)?)));
+        self.reported = None;
+    }
+
+    /// Copies the file on disk to `<name>.bak` the first time it is about
+    /// to be overwritten in this session and returns the backup path.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the original exists but cannot be copied
+    pub fn backup(&mut self) -> Result<Option<String>, IOError> {
+        let Some(filename) = self.filename.as_ref().filter(|_| !self.backed_up) else {
+            return Ok(None);
+        };
+        let backup = format!("{filename}.bak");
+
+        match fs::copy(filename, &backup) {
+            Ok(_) => {
+                self.backed_up = true;
+
+                Ok(Some(backup))
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns a count that changes whenever the text does, to be compared
+    /// with an earlier one.
+    pub fn edits(&self) -> usize {
+        self.edits
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Looks for `query` from `at` on, continuing from the other end of the
+    /// document when it reaches the bottom, or the top searching backward.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the rows of a lazily opened file cannot be read
+    pub fn find(
+        &self,
+        query: &str,
+        at: &Position,
+        direction: SearchDirection,
+    ) -> Result<Option<SearchMatch>, IOError> {
+        let len = self.len();
+        let mut chunk = Chunk::default();
+
+        if at.y >= len {
+            return Ok(None);
+        }
+
+        // The last step comes back to the starting row, for the part of it
+        // on the other side of `at`.
+        for step in 0..=len {
+            let y = match direction {
+                SearchDirection::Forward => (at.y + step) % len,
+                SearchDirection::Backward => (at.y + len - step % len) % len,
+            };
+            let wrapped = step > 0
+                && match direction {
+                    SearchDirection::Forward => y <= at.y,
+                    SearchDirection::Backward => y >= at.y,
+                };
+            let row = match &self.lines {
+                Some(lines) => lines.search_row(y, &mut chunk)?,
+                None => &self.rows[y],
+            };
+            let x = match direction {
+                _ if step == 0 => at.x,
+                SearchDirection::Forward => 0,
+                SearchDirection::Backward => row.len(),
+            };
+
+            if let Some(x) = row.find(query, x, direction) {
+                return Ok(Some(SearchMatch {
+                    position: Position { x, y },
+                    wrapped,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns where `query` matches from the top of the document down,
+    /// without overlapping matches, stopping after `limit` of them. The
+    /// rows of a lazily opened file are not searched.
+    pub fn find_all(&self, query: &str, limit: usize) -> Vec<Position> {
+        let mut matches = Vec::new();
+
+        if query.is_empty() {
+            return matches;
+        }
+
+        let len = Row::from(query).len();
+
+        for (y, row) in self.rows.iter().enumerate() {
+            let mut x = 0;
+
+            while let Some(found) = row
+                .find(query, x, SearchDirection::Forward)
+                .filter(|_| matches.len() < limit)
+            {
+                matches.push(Position { x: found, y });
+                x = found + len;
+            }
+
+            if matches.len() >= limit {
+                break;
+            }
+        }
+
+        matches
+    }
+
+    /// Returns the position of the bracket matching the one at `at`, skipping
+    /// brackets inside strings and comments.
+    pub fn matching_bracket(&self, at: &Position) -> Option<Position> {
+        self.matching_bracket_within(at, usize::MAX).flatten()
+    }
+
+    /// Looks for the bracket matching the one at `at` like
+    /// `matching_bracket`, over at most `rows` rows past it. Returns `None`
+    /// if it gives up before finding the match or an end of the document,
+    /// and `Some(None)` if the bracket has no match.
+    pub fn matching_bracket_within(&self, at: &Position, rows: usize) -> Option<Option<Position>> {
+        let Some((open, close, direction)) = self
+            .rows
+            .get(at.y)
+            .and_then(|row| row.grapheme(at.x))
+            .and_then(bracket_pair)
+        else {
+            return Some(None);
+        };
+        let row = &self.rows[at.y];
+        let forward = direction == SearchDirection::Forward;
+        let (same, other) = if forward {
+            (open, close)
+        } else {
+            (close, open)
+        };
+        let code_only = row.is_code(at.x);
+        let mut depth = 0_usize;
+        let mut y = at.y;
+
+        loop {
+            let Some(row) = self.rows.get(y) else {
+                return Some(None);
+            };
+
+            if y.abs_diff(at.y) > rows {
+                return None;
+            }
+
+            let mut columns = if forward {
+                let start = if y == at.y { at.x } else { 0 };
+
+                start..row.len()
+            } else {
+                let end = if y == at.y {
+                    at.x.saturating_add(1)
+                } else {
+                    row.len()
+                };
+
+                0..cmp::min(end, row.len())
+            };
+
+            // Graphemes are looked up one at a time, as the bracket is
+            // usually close by even on long rows.
+            while let Some(x) = if forward {
+                columns.next()
+            } else {
+                columns.next_back()
+            } {
+                if code_only && !row.is_code(x) {
+                    continue;
+                }
+
+                let grapheme = row.grapheme(x);
+
+                if grapheme == Some(same) {
+                    depth += 1;
+                } else if grapheme == Some(other) {
+                    depth = depth.saturating_sub(1);
+
+                    if depth == 0 {
+                        return Some(Some(Position { x, y }));
+                    }
+                }
+            }
+
+            y = match y.checked_add_signed(if forward { 1 } else { -1 }) {
+                Some(y) => y,
+                None => return Some(None),
+            };
+        }
+    }
+
+    /// Returns the smallest unit of text around `start..end` and larger than
+    /// it: the word there, the contents of the string or brackets around
+    /// it, the string or brackets themselves, the whole lines and at last
+    /// the whole document. Rows have to be highlighted to tell strings and
+    /// comments apart.
+    pub fn enclosing_unit(&self, start: &Position, end: &Position) -> Option<(Position, Position)> {
+        let row = self.rows.get(start.y)?;
+        let at = |x| Position { x, y: start.y };
+        let mut units = Vec::new();
+
+        if start.y == end.y {
+            let is_word = |x: usize| {
+                row.grapheme(x).is_some_and(|grapheme| {
+                    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+                })
+            };
+
+            // A run of graphemes all `inside`, taking in the range, which an
+            // empty one at its end is part of.
+            let run = |inside: &dyn Fn(usize) -> bool| {
+                if !(start.x..end.x).all(inside) {
+                    return None;
+                }
+
+                let mut from = start.x;
+                let mut to = end.x;
+
+                while from > 0 && inside(from - 1) {
+                    from -= 1;
+                }
+
+                while inside(to) {
+                    to += 1;
+                }
+
+                (from < to).then_some((from, to))
+            };
+
+            if let Some((from, to)) = run(&is_word) {
+                units.push((at(from), at(to)));
+            }
+
+            if let Some((from, to)) = run(&|x| row.is_literal(x)) {
+                units.push((at(from), at(to)));
+
+                if to - from >= 2 {
+                    units.push((at(from + 1), at(to - 1)));
+                }
+            }
+        }
+
+        let mut outside = start.clone();
+
+        while let Some((open, close)) = self.enclosing_brackets(&outside) {
+            if close >= *end {
+                let after = |position: &Position| Position {
+                    x: position.x.saturating_add(1),
+                    y: position.y,
+                };
+
+                units.push((after(&open), close.clone()));
+                units.push((open, after(&close)));
+
+                break;
+            }
+
+            outside = open;
+        }
+
+        let last = if end.x == 0 && end.y > start.y {
+            end.y - 1
+        } else {
+            end.y
+        };
+        let line_end = |y: usize| match self.rows.get(y.saturating_add(1)) {
+            Some(_) => Position {
+                x: 0,
+                y: y.saturating_add(1),
+            },
+            None => Position {
+                x: self.rows.get(y).map_or(0, Row::len),
+                y,
+            },
+        };
+
+        units.push((Position { x: 0, y: start.y }, line_end(last)));
+        units.push((
+            Position::default(),
+            line_end(self.rows.len().saturating_sub(1)),
+        ));
+
+        units
+            .into_iter()
+            .filter(|(from, to)| from <= start && to >= end && (from, to) != (start, end))
+            .min_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)))
+    }
+
+    /// Returns the nearest opening bracket before `at` that is not closed
+    /// before it, and the bracket closing it, skipping those in strings and
+    /// comments.
+    fn enclosing_brackets(&self, at: &Position) -> Option<(Position, Position)> {
+        let mut closed = 0_usize;
+        let mut y = at.y;
+
+        loop {
+            let row = self.rows.get(y)?;
+            let end = if y == at.y {
+                cmp::min(at.x, row.len())
+            } else {
+                row.len()
+            };
+
+            for x in (0..end).rev() {
+                let Some((_, _, direction)) = row.grapheme(x).and_then(bracket_pair) else {
+                    continue;
+                };
+
+                if !row.is_code(x) {
+                    continue;
+                }
+
+                if direction == SearchDirection::Backward {
+                    closed += 1;
+                } else if closed > 0 {
+                    closed -= 1;
+                } else {
+                    let open = Position { x, y };
+                    let close = self.matching_bracket(&open)?;
+
+                    return Some((open, close));
+                }
+            }
+
+            y = y.checked_sub(1)?;
+        }
+    }
+
+    /// Highlights the rows up to `until`, marking the words `dictionary`
+    /// does not know when given.
+    pub fn highlight(
+        &mut self,
+        term: &Option<highlight::Term>,
+        dictionary: Option<&Dictionary>,
+        until: Option<usize>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut state = highlight::State::Normal;
+        let len = self.len();
+        let until = if let Some(until) = until {
+            if until.saturating_add(1) < len {
+                until.saturating_add(1)
+            } else {
+                len
+            }
+        } else {
+            len
+        };
+        let options = self.file_type.highlight_options();
+        let mut depth = 0;
+        // Only the rows loaded from a large file are highlighted.
+        let rows: Box<dyn Iterator<Item = &mut Row>> = match &mut self.lines {
+            Some(lines) => Box::new(lines.loaded_rows_mut(until)),
+            None => Box::new(self.rows.iter_mut().take(until)),
+        };
+
+        for row in rows {
+            state = row.highlight(options, term, state);
+
+            if self.rainbow_brackets {
+                depth = row.color_brackets(depth);
+            } else {
+                row.clear_bracket_colors();
+            }
+
+            match dictionary {
+                Some(dictionary) => row.check_spelling(dictionary, options.is_prose()),
+                None => row.clear_spelling(),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn file_type(&self) -> String {
+        self.file_type.name()
+    }
+
+    /// Returns the delimiters of block comments in the file type.
+    pub fn block_comment(&self) -> Option<(&'static str, &'static str)> {
+        self.file_type.highlight_options().multiline_comment()
+    }
+
+    /// Returns the whitespace before the aligned `*` that a line breaking
+    /// `row` at `x` starts with, when that is inside a `/* */` comment
+    /// on a line that opens it or already starts with `*`. Rows have to be
+    /// highlighted to tell comments apart.
+    fn comment_continuation(&self, row: &Row, x: usize) -> Option<String> {
+        let before = row.slice(0, x);
+        let text = before.trim_start();
+        let lead = &before[..before.len() - text.len()];
+        let in_comment = x
+            .checked_sub(1)
+            .is_some_and(|x| !row.is_code(x) && !row.is_literal(x));
+
+        if self.block_comment() != Some(("/*", "*/")) || !in_comment || text.ends_with("*/") {
+            return None;
+        }
+
+        if text.starts_with("/*") {
+            Some(format!("{lead} "))
+        } else if text.starts_with('*') {
+            Some(lead.to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the first bracket outside strings and comments that is not
+    /// matched: a closing one without an opening one of its kind before
+    /// it, or else the first opening one never closed. Rows have to be
+    /// highlighted to tell code apart.
+    pub fn unbalanced_bracket(&self) -> Option<(Position, &'static str)> {
+        let mut open: Vec<(Position, &'static str)> = Vec::new();
+
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, grapheme) in row.graphemes().enumerate() {
+                let Some((opening, closing, direction)) = bracket_pair(grapheme) else {
+                    continue;
+                };
+
+                if !row.is_code(x) {
+                    continue;
+                }
+
+                if direction == SearchDirection::Forward {
+                    open.push((Position { x, y }, opening));
+                } else if open.last().map(|(_, last)| *last) == Some(opening) {
+                    open.pop();
+                } else {
+                    return Some((Position { x, y }, closing));
+                }
+            }
+        }
+
+        open.into_iter().next()
+    }
+
+    pub fn balanced_brackets(&self) -> bool {
+        self.file_type.balanced_brackets()
+    }
+
+    /// Returns the tab width and whether to indent with spaces in the
+    /// usual style of the file type, if it has one.
+    pub fn indentation_style(&self) -> Option<(usize, bool)> {
+        self.file_type.indentation()
+    }
+
+    pub fn file_type_badge(&self) -> &'static str {
+        self.file_type.badge()
+    }
+
+    pub fn comment_prefix(&self) -> Option<&'static str> {
+        self.file_type.comment_prefix()
+    }
+
+    /// Breaks the line at `at` the way pressing Enter does: the new line
+    /// keeps the indentation of the current one, and breaking between a pair
+    /// of brackets opens an empty line indented by `indent_unit`, with the
+    /// closing bracket moved below it. Returns the new cursor position.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if it fails to get the row to split
+    pub fn insert_newline(
+        &mut self,
+        at: &Position,
+        indent_unit: &str,
+    ) -> Result<Position, Box<dyn Error>> {
+        let Some(row) = self.rows.get(at.y) else {
+            self.insert(at, '\n')?;
+
+            return Ok(Position {
+                x: 0,
+                y: at.y.saturating_add(1),
+            });
+        };
+        let indentation = cmp::min(row.indentation(), at.x);
+        let indent = row.substring(0, indentation);
+        let star = self.comment_continuation(row, at.x);
+        let is_block = matches!(
+            (
+                at.x.checked_sub(1).and_then(|x| row.grapheme(x)),
+                row.grapheme(at.x)
+            ),
+            (Some("("), Some(")")) | (Some("["), Some("]")) | (Some("{"), Some("}"))
+        );
+
+        self.changed = true;
+        self.touch(at.y, at.y.saturating_add(1));
+
+        let rest = self.rows[at.y].split(at.x);
+        let rest = if at.x > indentation {
+            rest.as_string().trim_start()
+        } else {
+            rest.as_string()
+        };
+        let below = Row::from(format!("{indent}{rest}").as_str());
+
+        if let Some(star) = star {
+            // The end of the comment goes on a line of its own, aligned.
+            let (inner, below) = match rest.strip_prefix("*/") {
+                Some(_) => (format!("{star}* "), Some(format!("{star}{rest}"))),
+                None => (format!("{star}* {rest}"), None),
+            };
+            let x = star.chars().count() + 2;
+
+            self.rows.insert(at.y + 1, Row::from(inner.as_str()));
+
+            if let Some(below) = below {
+                self.rows.insert(at.y + 2, Row::from(below.as_str()));
+            }
+
+            Ok(Position { x, y: at.y + 1 })
+        } else if is_block {
+            let inner = Row::from(format!("{indent}{indent_unit}").as_str());
+            let x = inner.len();
+
+            self.rows.insert(at.y + 1, inner);
+            self.rows.insert(at.y + 2, below);
+
+            Ok(Position { x, y: at.y + 1 })
+        } else {
+            self.rows.insert(at.y + 1, below);
+
+            Ok(Position {
+                x: indentation,
+                y: at.y + 1,
+            })
+        }
+    }
+
+    /// Detects the file type from `filename`, falling back to a `#!` line
+    /// at the top of the document.
+    fn detect_file_type(&self, filename: &str) -> FileType {
+        let file_type = FileType::new().from(filename);
+
+        if file_type.is_known() {
+            return file_type;
+        }
+
+        self.rows
+            .get(0)
+            .and_then(|row| file_type.from_shebang(row.as_string()))
+            .unwrap_or(file_type)
+    }
+
+    fn split_row(&mut self, at: &Position) -> Result<(), Box<dyn Error>> {
+        if at.y > self.rows.len() {
+            return Ok(());
+        }
+
+        if at.y == self.rows.len() {
+            self.touch(at.y, at.y);
+            self.rows.push(Row::default());
+            return Ok(());
+        }
+
+        self.touch(at.y, at.y.saturating_add(1));
+        let current_row = self.rows.get_mut(at.y);
+
+        match current_row {
+            Some(row) => {
+                let new_row = row.split(at.x);
+                self.rows.insert(at.y + 1, new_row);
+
+                Ok(())
+            }
+            None => Err(Box::new(RowError::InsertionError(at.x, at.y))),
+        }
+    }
+
+    /// Returns where rows were added or removed since the last call, in
+    /// the order it happened.
+    pub fn take_line_shifts(&mut self) -> Vec<LineShift> {
+        self.resolve_shift();
+        mem::take(&mut self.shifts)
+    }
+
+    /// Records how the rows the last change was about to replace were
+    /// replaced, once it is done.
+    fn resolve_shift(&mut self) {
+        let Some((at, old, edited, tail)) = self.pending_shift.take() else {
+            return;
+        };
+        let end = self.rows.len().saturating_sub(tail).max(at);
+        let new: Vec<&str> = self
+            .rows
+            .range(at..end)
+            .map(|row| row.as_string().as_str())
+            .collect();
+
+        self.shifts.extend(LineShift::between(at, &old, &new));
+        self.carry_edited(at, end, &old, &edited);
+    }
+
+    /// Marks rows `at..end`, which replaced `old` ones, as edited. Rows they
+    /// start and end with alike were only moved, and keep whether the rows
+    /// they replace were `edited`.
+    fn carry_edited(&mut self, at: usize, end: usize, old: &[String], edited: &[bool]) {
+        let new: Vec<&str> = self
+            .rows
+            .range(at..end)
+            .map(|row| row.as_string().as_str())
+            .collect();
+        let (prefix, suffix) = common_ends(old, &new);
+        let flags: Vec<bool> = (0..new.len())
+            .map(|index| match index {
+                _ if index < prefix => edited[index],
+                _ if index >= new.len() - suffix => edited[old.len() - (new.len() - index)],
+                _ => true,
+            })
+            .collect();
+
+        for (index, flag) in flags.into_iter().enumerate() {
+            self.rows[at + index].set_edited(flag);
+        }
+    }
+
+    /// Records the rows in `start..end` as about to change, so the pending
+    /// undo step covers them.
+    fn touch(&mut self, start: usize, end: usize) {
+        self.resolve_shift();
+
+        let len = self.rows.len();
+
+        self.edits = self.edits.wrapping_add(1);
+        let end = cmp::min(end, len);
+        let start = cmp::min(start, end);
+
+        self.pending_shift = Some((
+            start,
+            self.rows
+                .range(start..end)
+                .map(|row| row.as_string().clone())
+                .collect(),
+            self.rows.range(start..end).map(Row::is_edited).collect(),
+            len - end,
+        ));
+
+        match &mut self.transaction {
+            Some(transaction) => {
+                if start < transaction.at {
+                    let rows = self
+                        .rows
+                        .range(start..transaction.at)
+                        .map(|row| row.as_string().clone());
+
+                    transaction.removed.splice(0..0, rows);
+                    transaction.at = start;
+                }
+
+                if len - end < transaction.tail {
+                    let rows = self
+                        .rows
+                        .range(len - transaction.tail..end)
+                        .map(|row| row.as_string().clone());
+
+                    transaction.removed.extend(rows);
+                    transaction.tail = len - end;
+                }
+            }
+            None => {
+                self.transaction = Some(Transaction {
+                    at: start,
+                    tail: len - end,
+                    removed: self
+                        .rows
+                        .range(start..end)
+                        .map(|row| row.as_string().clone())
+                        .collect(),
+                });
+            }
+        }
+    }
+
+    /// Checks the spelling of every row again, e.g. after a word was added
+    /// to the dictionary.
+    pub fn recheck_spelling(&mut self) {
+        for row in &mut self.rows {
+            row.recheck_spelling();
+        }
+    }
+
+    fn unhighlight_rows(&mut self) {
+        for row in &mut self.rows {
+            row.unhighlight();
+        }
+    }
+}
+
+/// Writes the whole text the way it is saved without `insert_final_newline`,
+/// with the line breaks and the ending of the file it was read from.
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (line, ending) in self.lines_with_endings(false) {
+            f.write_str(line)?;
+            f.write_str(ending)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Shifts the lines of `text` so that the least indented of those with
+/// text starts with `indentation`, keeping how much further the others are
+/// indented with spaces when `expand` is set, or else with tabs of
+/// `tab_width` columns. Blank lines are emptied.
+pub fn reindent(text: &str, indentation: &str, tab_width: usize, expand: bool) -> String {
+    let split = |line: &str| {
+        let rest = line.trim_start_matches([' ', '\t']);
+
+        (
+            indentation_width(&line[..line.len() - rest.len()], tab_width),
+            rest.to_owned(),
+        )
+    };
+    let least = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| split(line).0)
+        .min()
+        .unwrap_or_default();
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| {
+            let (width, rest) = split(line);
+
+            if rest.trim().is_empty() {
+                String::new()
+            } else {
+                let relative = self::indentation(width - least, tab_width, expand);
+
+                format!("{indentation}{relative}{rest}")
+            }
+        })
+        .collect();
+
+    lines.join("\n")
+}
+
+/// Returns how many columns the tabs and spaces of `leading` take, with
+/// tabs of `tab_width` columns.
+fn indentation_width(leading: &str, tab_width: usize) -> usize {
+    leading.chars().fold(0, |width, c| match c {
+        '\t' => width - width % tab_width + tab_width,
+        _ => width + 1,
+    })
+}
+
+/// Returns spaces taking `width` columns, or when `expand` is not set tabs
+/// of `tab_width` columns followed by the spaces left over.
+fn indentation(width: usize, tab_width: usize, expand: bool) -> String {
+    if expand {
+        " ".repeat(width)
+    } else {
+        "\t".repeat(width / tab_width) + &" ".repeat(width % tab_width)
+    }
+}
+
+/// Returns whether this user may write to `filename`, which the permission
+/// bits alone do not tell for files owned by someone else.
+fn is_writable(filename: &str) -> bool {
+    let Ok(path) = CString::new(filename) else {
+        return true;
+    };
+
+    // SAFETY: `path` is a valid C string for the duration of the call.
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
+
+fn modified_time(filename: &str) -> Option<SystemTime> {
+    fs::metadata(filename)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Returns the opening and closing brackets for `grapheme` and the direction
+/// in which its match lies.
+pub fn bracket_pair(grapheme: &str) -> Option<(&'static str, &'static str, SearchDirection)> {
+    match grapheme {
+        "(" => Some(("(", ")", SearchDirection::Forward)),
+        "[" => Some(("[", "]", SearchDirection::Forward)),
+        "{" => Some(("{", "}", SearchDirection::Forward)),
+        ")" => Some(("(", ")", SearchDirection::Backward)),
+        "]" => Some(("[", "]", SearchDirection::Backward)),
+        "}" => Some(("{", "}", SearchDirection::Backward)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod document_tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_save() {
+        let filename = env::temp_dir().join("revise_test_save.txt");
+        let mut new_doc = Document {
+            filename: Some(filename.display().to_string()),
+            ..Document::default()
+        };
+        let save_res = new_doc.save(true);
+
+        assert_eq!(save_res.ok(), Some(()),);
+
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_save_error() {
+        let directory = env::temp_dir().join("revise_test_save_error");
+        let filename = directory.join("file.txt").display().to_string();
+        let mut doc = Document {
+            filename: Some(filename.clone()),
+            ..Document::default()
+        };
+
+        fs::remove_dir_all(&directory).ok();
+
+        assert!(matches!(
+            doc.save(true),
+            Err(SaveError::MissingDirectory(missing)) if missing == directory.display().to_string()
+        ));
+        assert!(matches!(
+            SaveError::from_io(IOError::from(ErrorKind::PermissionDenied), &filename),
+            SaveError::PermissionDenied(name) if name == filename
+        ));
+        assert!(matches!(
+            SaveError::from_io(IOError::from(ErrorKind::StorageFull), &filename),
+            SaveError::DiskFull
+        ));
+        assert!(matches!(
+            SaveError::from_io(IOError::from(ErrorKind::NotFound), "file.txt"),
+            SaveError::Io(_)
+        ));
+    }
+
+    #[test]
+    fn test_contents() {
+        let text = "first\n\tsecond\n";
+
+        assert_eq!(
+            Document::from_reader(text.as_bytes()).unwrap().contents(),
+            text
+        );
+        assert_eq!(
+            Document::from_reader("no newline".as_bytes())
+                .unwrap()
+                .contents(),
+            "no newline"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for text in [
+            "one\ntwo\n",
+            "one\ntwo",
+            "one\r\ntwo\r\n",
+            "one\r\n\r\ntwo",
+            "",
+            "\n",
+        ] {
+            let path = env::temp_dir().join("revise_round_trip.txt");
+
+            fs::write(&path, text).unwrap();
+
+            let mut document = Document::open(&path.display().to_string()).unwrap();
+
+            assert_eq!(document.to_string(), text);
+
+            document.save(false).unwrap();
+
+            assert_eq!(fs::read_to_string(&path).unwrap(), text);
+            fs::remove_file(&path).unwrap();
+        }
+
+        let mut document = Document::from_reader("a\r\nb".as_bytes()).unwrap();
+
+        document.insert(&Position { x: 1, y: 1 }, '\n').unwrap();
+
+        assert_eq!(document.to_string(), "a\r\nb\r\n");
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let doc = Document::from_reader("first\nsecond\n".as_bytes()).unwrap();
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(rows, vec!["first", "second"]);
+        assert_eq!(doc.filename, None);
+        assert!(!doc.is_changed());
+    }
+
+    #[test]
+    fn test_final_newline() {
+        let write = |doc: &Document, insert_final_newline| {
+            let mut bytes = Vec::new();
+
+            doc.write_to(&mut bytes, insert_final_newline).unwrap();
+
+            String::from_utf8(bytes).unwrap()
+        };
+        let doc = Document::from_reader("first\nsecond".as_bytes()).unwrap();
+
+        assert_eq!(write(&doc, false), "first\nsecond");
+        assert_eq!(write(&doc, true), "first\nsecond\n");
+
+        let doc = Document::from_reader("first\n\n\n".as_bytes()).unwrap();
+
+        assert_eq!(write(&doc, false), "first\n\n\n");
+        assert_eq!(write(&doc, true), "first\n");
+        assert_eq!(write(&Document::default(), true), "");
+    }
+
+    #[test]
+    fn test_backup() {
+        let filename = env::temp_dir().join("revise_test_backup.txt");
+        let filename = filename.to_string_lossy().into_owned();
+        let mut doc = Document {
+            filename: Some(filename.clone()),
+            ..Document::default()
+        };
+
+        fs::remove_file(&filename).ok();
+        assert_eq!(doc.backup().ok(), Some(None));
+
+        fs::write(&filename, "original\n").unwrap();
+
+        let backup = doc.backup().unwrap().unwrap();
+
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "original\n");
+        assert_eq!(doc.backup().ok(), Some(None));
+
+        fs::remove_file(&filename).ok();
+        fs::remove_file(&backup).ok();
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let filename = env::temp_dir().join("revise_test_read_only.txt");
+        let filename = filename.to_string_lossy().into_owned();
+
+        fs::write(&filename, "text\n").unwrap();
+        assert!(!Document::open(&filename).unwrap().read_only);
+
+        let mut permissions = fs::metadata(&filename).unwrap().permissions();
+
+        permissions.set_readonly(true);
+        fs::set_permissions(&filename, permissions).unwrap();
+        assert!(Document::open(&filename).unwrap().read_only);
+
+        // Others may write to it, but its owner may not, and root may write
+        // to anything.
+        fs::set_permissions(&filename, fs::Permissions::from_mode(0o466)).unwrap();
+
+        // SAFETY: `geteuid` has no preconditions.
+        if unsafe { libc::geteuid() } != 0 {
+            assert!(Document::open(&filename).unwrap().read_only);
+        }
+
+        fs::remove_file(&filename).ok();
+    }
+
+    #[test]
+    fn test_changed_on_disk() {
+        let filename = env::temp_dir().join("revise_test_changed_on_disk.txt");
+        let filename = filename.to_string_lossy().into_owned();
+        let touch = |seconds| {
+            let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(seconds);
+
+            File::options()
+                .write(true)
+                .open(&filename)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        };
+
+        fs::write(&filename, "original\n").unwrap();
+        touch(1_000);
+
+        let mut doc = Document::open(&filename).unwrap();
+
+        assert!(!doc.changed_on_disk());
+
+        fs::write(&filename, "external\n").unwrap();
+        touch(2_000);
+
+        assert!(doc.changed_on_disk());
+        assert!(doc.take_disk_change());
+        assert!(!doc.take_disk_change());
+
+        doc.reload().unwrap();
+
+        assert_eq!(doc.rows[0].as_string(), "external");
+        assert!(!doc.changed_on_disk());
+        assert!(!doc.is_changed());
+
+        touch(3_000);
+        doc.save(true).unwrap();
+
+        assert!(!doc.changed_on_disk());
+
+        fs::remove_file(&filename).ok();
+    }
+
+    #[test]
+    fn test_open_lazy() {
+        let filename = env::temp_dir().join("revise_test_open_lazy.txt");
+        let filename = filename.to_string_lossy().into_owned();
+        let contents: String = (1..=100).map(|n| format!("line {n}\n")).collect();
+
+        fs::write(&filename, contents).unwrap();
+
+        let mut doc = Document::open_lazy(&filename).unwrap();
+
+        assert!(doc.is_lazy() && doc.read_only);
+        assert_eq!(doc.len(), 100);
+        assert!(doc.rows.is_empty());
+        assert_eq!(doc.row(10).map(Row::len), Some(0));
+
+        let visible = 10..12;
+
+        doc.load_rows(&[visible]).unwrap();
+
+        assert_eq!(doc.row(10).map(Row::as_string), Some(&"line 11".to_owned()));
+        assert_eq!(doc.row(12).map(Row::len), Some(0));
+        assert!(doc.row(100).is_none());
+        assert_eq!(
+            doc.find("line 90", &Position::default(), SearchDirection::Forward)
+                .unwrap()
+                .map(|found| found.position),
+            Some(Position { x: 0, y: 89 })
+        );
+        assert_eq!(
+            doc.find(
+                "line 5",
+                &Position { x: 0, y: 10 },
+                SearchDirection::Backward
+            )
+            .unwrap()
+            .map(|found| found.position),
+            Some(Position { x: 0, y: 4 })
+        );
+        assert_eq!(doc.row(89).map(Row::len), Some(0));
+        assert!(doc.save(true).is_err());
+        assert_eq!(fs::read_to_string(&filename).unwrap().lines().count(), 100);
+
+        fs::remove_file(&filename).ok();
+    }
+
+    #[test]
+    fn test_open_with_progress() {
+        let filename = env::temp_dir().join("revise_test_progress.txt");
+        let filename = filename.to_string_lossy().into_owned();
+        let mut reports = Vec::new();
+
+        fs::write(&filename, "first\nsecond\n").unwrap();
+
+        let doc = Document::open_with_progress(&filename, |read, size| reports.push((read, size)))
+            .unwrap();
+
+        assert_eq!(doc.len(), 2);
+        assert_eq!(reports.first(), Some(&(0, 13)));
+        assert_eq!(reports.last(), Some(&(13, 13)));
+
+        fs::remove_file(&filename).ok();
+    }
+
+    #[test]
+    fn test_duplicate_row() {
+        let mut doc = Document {
+            rows: vec![Row::from("first"), Row::from(""), Row::from("last")].into(),
+            ..Document::default()
+        };
+
+        doc.duplicate_row(1);
+        doc.duplicate_row(3);
+        doc.duplicate_row(5);
+
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(rows, vec!["first", "", "", "last", "last"]);
+        assert!(doc.is_changed());
+    }
+
+    #[test]
+    fn test_swap_rows() {
+        let mut doc = Document {
+            rows: vec![Row::from("a"), Row::from("b")].into(),
+            ..Document::default()
+        };
+
+        doc.swap_rows(0, 1);
+        doc.swap_rows(1, 2);
+
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(rows, vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_fold_range() {
+        let doc = Document {
+            rows: vec![
+                Row::from("fn main() {"),
+                Row::from("    let x = (1,"),
+                Row::from("        2);"),
+                Row::from("}"),
+                Row::from("def f():"),
+                Row::from("    return 1"),
+                Row::from(""),
+                Row::from("    pass"),
+                Row::from("x = 1"),
+            ]
+            .into(),
+            ..Document::default()
+        };
+
+        assert_eq!(doc.fold_range(0), Some(3));
+        assert_eq!(doc.fold_range(1), Some(2));
+        assert_eq!(doc.fold_range(2), None);
+        assert_eq!(doc.fold_range(4), Some(7));
+        assert_eq!(doc.fold_range(6), None);
+        assert_eq!(doc.fold_range(8), None);
+        assert_eq!(doc.fold_regions(), vec![(0, 3), (4, 7)]);
+    }
+
+    #[test]
+    fn test_dedup_rows() {
+        let mut doc = Document {
+            rows: vec![
+                Row::from("a"),
+                Row::from("a"),
+                Row::from("b"),
+                Row::from("b"),
+                Row::from("b"),
+                Row::from("a"),
+                Row::from("a"),
+            ]
+            .into(),
+            ..Document::default()
+        };
+
+        assert_eq!(doc.dedup_rows(1..5), vec![3, 4]);
+
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(rows, vec!["a", "a", "b", "a", "a"]);
+        assert!(doc.is_changed());
+        assert_eq!(doc.dedup_rows(0..9), vec![1, 4]);
+
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(rows, vec!["a", "b", "a"]);
+        assert!(doc.dedup_rows(0..3).is_empty());
+    }
+
+    #[test]
+    fn test_retab() {
+        let mut doc = Document {
+            rows: vec![
+                Row::from("fn main() {"),
+                Row::from("\tlet x;"),
+                Row::from("  \t  x += 1;"),
+                Row::from("    }"),
+            ]
+            .into(),
+            ..Document::default()
+        };
+
+        assert_eq!(doc.indents_with_tabs(), Some(false));
+        assert!(doc.retab(4, false));
+
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(rows, vec!["fn main() {", "\tlet x;", "\t  x += 1;", "\t}"]);
+        assert_eq!(doc.indents_with_tabs(), Some(true));
+        assert!(doc.is_changed());
+        assert!(!doc.retab(4, false));
+        assert!(doc.retab(4, true));
+
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(
+            rows,
+            vec!["fn main() {", "    let x;", "      x += 1;", "    }"]
+        );
+        assert_eq!(Document::default().indents_with_tabs(), None);
+    }
+
+    #[test]
+    fn test_reindent() {
+        let text = "\tif x {\n  \n\t    y();\n\t}\n";
+
+        assert_eq!(
+            reindent(text, "  ", 4, true),
+            "  if x {\n\n      y();\n  }\n"
+        );
+        assert_eq!(
+            reindent("    a(\n        b,\n  c)", "", 4, false),
+            "  a(\n\t  b,\nc)"
+        );
+        assert_eq!(reindent("one", "\t", 4, false), "\tone");
+    }
+
+    #[test]
+    fn test_text() {
+        let doc = Document {
+            rows: vec![Row::from("first"), Row::from("second"), Row::from("third")].into(),
+            ..Document::default()
+        };
+
+        assert_eq!(
+            doc.text(&Position { x: 2, y: 0 }, &Position { x: 3, y: 2 }),
+            "rst\nsecond\nthi"
+        );
+        assert_eq!(
+            doc.text(&Position { x: 1, y: 1 }, &Position { x: 4, y: 1 }),
+            "eco"
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let doc = Document {
+            rows: vec![
+                Row::from("  two   words "),
+                Row::from(""),
+                Row::from("\tthree more\twords"),
+            ]
+            .into(),
+            ..Document::default()
+        };
+
+        assert_eq!(
+            doc.stats(&Position::default(), &Position { x: 17, y: 2 }),
+            Stats {
+                lines: 3,
+                words: 5,
+                chars: 31,
+            }
+        );
+        assert_eq!(
+            doc.stats(&Position { x: 3, y: 0 }, &Position { x: 7, y: 0 })
+                .words,
+            1
+        );
+    }
+
+    #[test]
+    fn test_delete_range() {
+        let mut doc = Document {
+            rows: vec![Row::from("first"), Row::from("second"), Row::from("third")].into(),
+            ..Document::default()
+        };
+
+        doc.checkpoint(&Position::default());
+        doc.delete_range(&Position { x: 2, y: 0 }, &Position { x: 3, y: 2 });
+
+        assert_eq!(doc.len(), 1);
+        assert_eq!(doc.row(0).map(Row::as_string), Some(&"fird".to_owned()));
+
+        doc.undo(&Position::default());
+
+        assert_eq!(doc.len(), 3);
+        assert_eq!(doc.row(1).map(Row::as_string), Some(&"second".to_owned()));
+    }
+
+    #[test]
+    fn test_insert_str() {
+        let mut doc = Document {
+            rows: vec![Row::from("first"), Row::from("last")].into(),
+            ..Document::default()
+        };
+
+        assert_eq!(
+            doc.insert_str(&Position { x: 2, y: 0 }, "e\u{301}\nnew\nx"),
+            Position { x: 1, y: 2 }
+        );
+        assert_eq!(doc.len(), 4);
+        assert_eq!(
+            doc.row(0).map(Row::as_string),
+            Some(&"fie\u{301}".to_owned())
+        );
+        assert_eq!(doc.row(1).map(Row::as_string), Some(&"new".to_owned()));
+        assert_eq!(doc.row(2).map(Row::as_string), Some(&"xrst".to_owned()));
+        assert_eq!(
+            doc.insert_str(&Position { x: 0, y: 4 }, "end"),
+            Position { x: 3, y: 4 }
+        );
+        assert_eq!(doc.row(4).map(Row::as_string), Some(&"end".to_owned()));
+        assert!(doc.is_changed());
+    }
+
+    #[test]
+    fn test_highlight_propagation() {
+        let mut doc = Document {
+            rows: vec![
+                Row::from("let a = 1;"),
+                Row::from("let b = 2;"),
+                Row::from("c();"),
+            ]
+            .into(),
+            file_type: FileType::new().from("test.rs"),
+            ..Document::default()
+        };
+
+        doc.highlight(&None, None, None).unwrap();
+        assert!(doc.rows[2].is_code(0));
+
+        doc.insert(&Position { x: 0, y: 0 }, '*').unwrap();
+        doc.insert(&Position { x: 0, y: 0 }, '/').unwrap();
+        doc.highlight(&None, None, None).unwrap();
+        assert!(!doc.rows[2].is_code(0));
+
+        doc.delete(&Position { x: 1, y: 0 }).unwrap();
+        doc.highlight(&None, None, None).unwrap();
+        assert!(doc.rows[2].is_code(0));
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let doc = Document {
+            rows: vec![
+                Row::from("fn a() {"),
+                Row::from("    (b[0]);"),
+                Row::from("}"),
+            ]
+            .into(),
+            ..Document::default()
+        };
+
+        assert_eq!(
+            doc.matching_bracket(&Position { x: 7, y: 0 }),
+            Some(Position { x: 0, y: 2 })
+        );
+        assert_eq!(
+            doc.matching_bracket(&Position { x: 0, y: 2 }),
+            Some(Position { x: 7, y: 0 })
+        );
+        assert_eq!(
+            doc.matching_bracket(&Position { x: 9, y: 1 }),
+            Some(Position { x: 4, y: 1 })
+        );
+        assert_eq!(doc.matching_bracket(&Position { x: 1, y: 0 }), None);
+        assert_eq!(
+            doc.matching_bracket_within(&Position { x: 0, y: 2 }, 1),
+            None
+        );
+        assert_eq!(
+            doc.matching_bracket_within(&Position { x: 0, y: 2 }, 2),
+            Some(Some(Position { x: 7, y: 0 }))
+        );
+    }
+
+    #[test]
+    fn test_unbalanced_bracket() {
+        let document = |text: &str| {
+            let mut document = Document::from_reader(text.as_bytes()).unwrap();
+
+            document.file_type = FileType::new().from("main.rs");
+            document.highlight(&None, None, None).unwrap();
+            document
+        };
+
+        assert_eq!(
+            document("fn a() {\n    b(\"(\"); // }\n}\n").unbalanced_bracket(),
+            None
+        );
+        assert_eq!(
+            document("fn a() {\n    b(c]);\n}\n").unbalanced_bracket(),
+            Some((Position { x: 7, y: 1 }, "]"))
+        );
+        assert_eq!(
+            document("fn a() {\n    if b {\n}\n").unbalanced_bracket(),
+            Some((Position { x: 7, y: 0 }, "{"))
+        );
+    }
+
+    #[test]
+    fn test_join_line() {
+        let mut doc = Document {
+            rows: vec![Row::from("let x =  "), Row::from("    42;"), Row::from("")].into(),
+            ..Document::default()
+        };
+
+        assert_eq!(doc.join_line(0), Some(7));
+        assert_eq!(
+            doc.row(0).map(Row::as_string),
+            Some(&"let x = 42;".to_owned())
+        );
+        assert_eq!(doc.join_line(0), Some(11));
+        assert_eq!(doc.join_line(0), None);
+        assert_eq!(doc.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_newline() {
+        let mut doc = Document::from_reader("    if x {}".as_bytes()).unwrap();
+
+        assert_eq!(
+            doc.insert_newline(&Position { x: 10, y: 0 }, "\t").ok(),
+            Some(Position { x: 5, y: 1 })
+        );
+
+        let rows: Vec<&String> = doc.rows.iter().map(Row::as_string).collect();
+
+        assert_eq!(rows, vec!["    if x {", "    \t", "    }"]);
+        assert_eq!(
+            doc.insert_newline(&Position { x: 4, y: 0 }, "\t").ok(),
+            Some(Position { x: 4, y: 1 })
+        );
+        assert_eq!(
+            doc.row(1).map(Row::as_string),
+            Some(&"    if x {".to_owned())
+        );
+        assert_eq!(
+            doc.insert_newline(&Position { x: 0, y: 4 }, "\t").ok(),
+            Some(Position { x: 0, y: 5 })
+        );
+        assert_eq!(doc.len(), 5);
+    }
+
+    #[test]
+    fn test_insert_newline_in_comment() {
+        let mut doc = Document {
+            rows: vec![Row::from("    /***/"), Row::from("let x = 1; /* y")].into(),
+            file_type: FileType::new().from("test.rs"),
+            ..Document::default()
+        };
+        let rows = |doc: &Document| -> Vec<String> {
+            doc.rows.iter().map(Row::as_string).cloned().collect()
+        };
+
+        doc.highlight(&None, None, None).unwrap();
+
+        assert_eq!(
+            doc.insert_newline(&Position { x: 7, y: 0 }, "\t").ok(),
+            Some(Position { x: 7, y: 1 })
+        );
+        assert_eq!(
+            rows(&doc),
+            vec!["    /**", "     * ", "     */", "let x = 1; /* y"]
+        );
+
+        doc.highlight(&None, None, None).unwrap();
+
+        assert_eq!(
+            doc.insert_newline(&Position { x: 7, y: 1 }, "\t").ok(),
+            Some(Position { x: 7, y: 2 })
+        );
+        assert_eq!(doc.row(2).map(Row::as_string), Some(&"     * ".to_owned()));
+
+        doc.highlight(&None, None, None).unwrap();
+
+        assert_eq!(
+            doc.insert_newline(&Position { x: 8, y: 4 }, "\t").ok(),
+            Some(Position { x: 0, y: 5 })
+        );
+        assert_eq!(doc.row(5).map(Row::as_string), Some(&"1; /* y".to_owned()));
+    }
+
+    #[test]
+    fn test_find() {
+        let doc = Document::from_reader("one\ntwo one\nthree\n".as_bytes()).unwrap();
+        let find = |x, y, direction| doc.find("one", &Position { x, y }, direction).unwrap();
+
+        assert_eq!(
+            find(1, 0, SearchDirection::Forward),
+            Some(SearchMatch {
+                position: Position { x: 4, y: 1 },
+                wrapped: false
+            })
+        );
+        assert_eq!(
+            find(0, 2, SearchDirection::Forward),
+            Some(SearchMatch {
+                position: Position { x: 0, y: 0 },
+                wrapped: true
+            })
+        );
+        assert_eq!(
+            find(4, 1, SearchDirection::Backward),
+            Some(SearchMatch {
+                position: Position { x: 0, y: 0 },
+                wrapped: false
+            })
+        );
+        assert_eq!(
+            find(0, 0, SearchDirection::Backward),
+            Some(SearchMatch {
+                position: Position { x: 4, y: 1 },
+                wrapped: true
+            })
+        );
+        assert_eq!(
+            doc.find("four", &Position::default(), SearchDirection::Forward)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_all() {
+        let doc = Document::from_reader("one\ntwo one\naaaa\n".as_bytes()).unwrap();
+
+        assert_eq!(
+            doc.find_all("one", 10),
+            vec![Position { x: 0, y: 0 }, Position { x: 4, y: 1 }]
+        );
+        assert_eq!(
+            doc.find_all("aa", 10),
+            vec![Position { x: 0, y: 2 }, Position { x: 2, y: 2 }]
+        );
+        assert_eq!(doc.find_all("o", 2).len(), 2);
+        assert!(doc.find_all("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut doc = Document {
+            rows: vec![Row::from("ab")].into(),
+            ..Document::default()
+        };
+        let origin = Position { x: 1, y: 0 };
+
+        doc.checkpoint(&origin);
+        doc.duplicate_row(0);
+        doc.checkpoint(&Position { x: 1, y: 0 });
+        doc.insert(&Position { x: 1, y: 1 }, '\n').unwrap();
+
+        assert_eq!(doc.len(), 3);
+        assert_eq!(
+            doc.undo(&Position { x: 0, y: 2 }).map(|(p, _)| p.y),
+            Some(0)
+        );
+        assert_eq!(doc.len(), 2);
+        assert_eq!(doc.undo(&Position::default()).map(|(p, _)| p.x), Some(1));
+        assert_eq!(doc.len(), 1);
+        assert!(doc.undo(&Position::default()).is_none());
+        assert!(doc.redo(&Position::default()).is_some());
+        assert_eq!(doc.len(), 2);
+        assert_eq!(doc.row(1).map(Row::as_string), Some(&"ab".to_owned()));
+    }
+
+    #[test]
+    fn test_revert() {
+        let path = env::temp_dir().join("revise_test_revert.txt");
+        let filename = path.display().to_string();
+
+        fs::write(&path, "one\ntwo\n").unwrap();
+
+        let mut document = Document::open(&filename).unwrap();
+
+        document.insert(&Position { x: 3, y: 0 }, '!').unwrap();
+        document.revert().unwrap();
+
+        assert_eq!(document.contents(), "one\ntwo\n");
+        assert!(!document.is_changed());
+
+        document.insert(&Position { x: 0, y: 2 }, 'x').unwrap();
+        document.save(true).unwrap();
+        document
+            .insert_newline(&Position { x: 0, y: 0 }, "")
+            .unwrap();
+        document.checkpoint(&Position::default());
+        document.revert().unwrap();
+
+        assert_eq!(document.contents(), "one\ntwo\nx\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\nx\n");
+
+        document.undo(&Position::default());
+
+        assert_eq!(document.contents(), "\none\ntwo\nx\n");
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_line_shifts() {
+        let mut doc = Document::from_reader("a\nb\nc\nd\n".as_bytes()).unwrap();
+
+        doc.delete_row(1);
+        doc.checkpoint(&Position::default());
+        doc.insert_newline(&Position { x: 0, y: 0 }, "").unwrap();
+        doc.checkpoint(&Position::default());
+
+        assert_eq!(
+            doc.take_line_shifts(),
+            vec![
+                LineShift {
+                    at: 1,
+                    removed: 1,
+                    inserted: 0
+                },
+                LineShift {
+                    at: 0,
+                    removed: 0,
+                    inserted: 1
+                }
+            ]
+        );
+        assert!(doc.take_line_shifts().is_empty());
+
+        doc.undo(&Position::default());
+
+        assert_eq!(
+            doc.take_line_shifts(),
+            vec![LineShift {
+                at: 0,
+                removed: 1,
+                inserted: 0
+            }]
+        );
+
+        let removal = LineShift {
+            at: 2,
+            removed: 3,
+            inserted: 1,
+        };
+
+        assert_eq!([1, 2, 3, 4, 5].map(|y| removal.line(y)), [1, 2, 2, 2, 3]);
+    }
+
+    #[test]
+    fn test_trim_edited_lines() {
+        let mut doc = Document::from_reader("one \ntwo \nthree \n".as_bytes()).unwrap();
+
+        doc.insert(&Position { x: 0, y: 1 }, 'a').unwrap();
+        doc.insert_newline(&Position { x: 0, y: 2 }, "").unwrap();
+
+        assert!(doc.trim_trailing_whitespace(true));
+        assert_eq!(doc.contents(), "one \natwo\n\nthree \n");
+
+        doc.undo(&Position::default());
+        doc.undo(&Position::default());
+
+        assert!(doc.trim_trailing_whitespace(true));
+        assert_eq!(doc.contents(), "one \ntwo\nthree \n");
+        assert!(doc.trim_trailing_whitespace(false));
+        assert_eq!(doc.contents(), "one\ntwo\nthree\n");
     }
 }
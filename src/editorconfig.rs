@@ -0,0 +1,261 @@
+//! Reading the settings `.editorconfig` files give a file, so that it is
+//! edited the way its project asks: <https://editorconfig.org>.
+
+use crate::config::{Indentation, MAX_TAB_WIDTH, MIN_TAB_WIDTH};
+use crate::glob::glob_match;
+
+use std::fs;
+use std::path::{self, Path};
+
+const EDITORCONFIG: &str = ".editorconfig";
+
+/// The settings for one file. Those left out fall back to the config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EditorConfig {
+    pub indentation: Indentation,
+    /// Whether lines end in `\r\n` rather than `\n`.
+    pub crlf: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+}
+
+/// The `indent_size` and `tab_width` values read so far. They are resolved
+/// once every file is read, since `indent_size = tab` takes the `tab_width`
+/// a file further out may set.
+#[derive(Default)]
+struct Widths {
+    indent_size: Option<String>,
+    tab_width: Option<String>,
+}
+
+impl Widths {
+    /// Returns the indent size, falling back to `tab_width` when it is
+    /// `tab` or unset.
+    fn resolve(&self) -> Option<usize> {
+        let width = |value: &str| {
+            value
+                .parse()
+                .ok()
+                .filter(|size| (MIN_TAB_WIDTH..=MAX_TAB_WIDTH).contains(size))
+        };
+        let tab_width = self.tab_width.as_deref().and_then(width);
+
+        match self.indent_size.as_deref() {
+            None | Some("tab") => tab_width,
+            Some(size) => width(size),
+        }
+    }
+}
+
+impl EditorConfig {
+    /// Reads the `.editorconfig` files in the directory of `filename` and
+    /// the ones above it, up to one with `root = true`. Settings from files
+    /// closer to it win, as do later sections within a file.
+    pub fn for_file(filename: &str) -> Self {
+        let mut config = Self::default();
+        let mut widths = Widths::default();
+        let Ok(path) = path::absolute(filename) else {
+            return config;
+        };
+        let mut files = Vec::new();
+
+        for directory in path.ancestors().skip(1) {
+            let Ok(contents) = fs::read_to_string(directory.join(EDITORCONFIG)) else {
+                continue;
+            };
+            let root = is_root(&contents);
+
+            files.push((directory, contents));
+
+            if root {
+                break;
+            }
+        }
+
+        for (directory, contents) in files.iter().rev() {
+            if let Some(relative) = relative_path(&path, directory) {
+                config.apply(contents, &relative, &mut widths);
+            }
+        }
+
+        config.indentation.tab_width = widths.resolve();
+        config
+    }
+
+    /// Applies the sections of `contents` matching the file at `path`,
+    /// relative to the directory of the `.editorconfig`.
+    fn apply(&mut self, contents: &str, path: &str, widths: &mut Widths) {
+        let mut matches = false;
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some(section) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                matches = section_matches(section, path);
+            } else if let Some((key, value)) = line.split_once('=').filter(|_| matches) {
+                self.set(
+                    &key.trim().to_lowercase(),
+                    value.trim().to_lowercase(),
+                    widths,
+                );
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: String, widths: &mut Widths) {
+        let flag = match value.as_str() {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+
+        match key {
+            "indent_style" => {
+                self.indentation.expand_tabs = match value.as_str() {
+                    "space" => Some(true),
+                    "tab" => Some(false),
+                    _ => None,
+                };
+            }
+            "indent_size" => widths.indent_size = Some(value),
+            "tab_width" => widths.tab_width = Some(value),
+            "end_of_line" => {
+                self.crlf = match value.as_str() {
+                    "crlf" => Some(true),
+                    "lf" => Some(false),
+                    _ => None,
+                };
+            }
+            "insert_final_newline" => self.insert_final_newline = flag,
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = flag,
+            _ => (),
+        }
+    }
+}
+
+/// Returns whether `root = true` is set before the first section.
+fn is_root(contents: &str) -> bool {
+    contents
+        .lines()
+        .map(str::trim)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .any(|(key, value)| {
+            key.trim().eq_ignore_ascii_case("root") && value.trim().eq_ignore_ascii_case("true")
+        })
+}
+
+fn relative_path(path: &Path, directory: &Path) -> Option<String> {
+    let relative = path.strip_prefix(directory).ok()?;
+    let components: Vec<_> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+
+    Some(components.join("/"))
+}
+
+/// Returns whether the glob of a section header matches `path`. Globs
+/// without a `/` match the name of the file in any directory.
+fn section_matches(section: &str, path: &str) -> bool {
+    let (pattern, path) = if section.contains('/') {
+        (section.trim_start_matches('/'), path)
+    } else {
+        (section, path.rsplit('/').next().unwrap_or(path))
+    };
+
+    glob_match(pattern, path)
+}
+
+#[cfg(test)]
+mod editorconfig_tests {
+    use super::*;
+
+    #[test]
+    fn test_section_matches() {
+        assert!(section_matches("*", "src/main.rs"));
+        assert!(section_matches("*.rs", "src/main.rs"));
+        assert!(!section_matches("*.rs", "src/main.rsx"));
+        assert!(section_matches("*.{js,ts}", "web/app.ts"));
+        assert!(section_matches("Makefile", "Makefile"));
+        assert!(section_matches("src/*.rs", "src/main.rs"));
+        assert!(!section_matches("src/*.rs", "src/bin/main.rs"));
+        assert!(section_matches("/src/**.rs", "src/bin/main.rs"));
+        assert!(section_matches("file?.[ch]", "file1.h"));
+        assert!(!section_matches("file?.[!ch]", "file1.h"));
+        assert!(section_matches("[a-c]*.md", "b.md"));
+    }
+
+    #[test]
+    fn test_for_file() {
+        let root = std::env::temp_dir().join(format!("revise-editorconfig-{}", std::process::id()));
+        let project = root.join("project");
+        let nested = project.join("src");
+
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(
+            root.join(EDITORCONFIG),
+            "[*]\nindent_style = tab\n\n[*.go]\ntab_width = 8\n",
+        )
+        .unwrap();
+        fs::write(
+            project.join(EDITORCONFIG),
+            "root = true\n\n[*]\nindent_style = space\nindent_size = 4\nend_of_line = lf\n\n\
+             [*.rs]\ntrim_trailing_whitespace = true\n\n[*.go]\nindent_size = tab\ntab_width = 8\n",
+        )
+        .unwrap();
+        fs::write(
+            nested.join(EDITORCONFIG),
+            "# closer\n[main.rs]\nindent_size = 2\nend_of_line = CRLF\ninsert_final_newline = false\n\n\
+             [*.md]\ntab_width = 3\n\n[*.go]\nindent_size = tab\n",
+        )
+        .unwrap();
+
+        let file = |name: &str| EditorConfig::for_file(&nested.join(name).display().to_string());
+
+        assert_eq!(
+            file("main.rs"),
+            EditorConfig {
+                indentation: Indentation {
+                    tab_width: Some(2),
+                    expand_tabs: Some(true),
+                },
+                crlf: Some(true),
+                insert_final_newline: Some(false),
+                trim_trailing_whitespace: Some(true),
+            }
+        );
+        assert_eq!(
+            file("notes.txt"),
+            EditorConfig {
+                indentation: Indentation {
+                    tab_width: Some(4),
+                    expand_tabs: Some(true),
+                },
+                crlf: Some(false),
+                ..EditorConfig::default()
+            }
+        );
+        assert_eq!(file("main.go").indentation.tab_width, Some(8));
+        assert_eq!(file("notes.md").indentation.tab_width, Some(4));
+        assert_eq!(
+            EditorConfig::for_file(&root.join("other.go").display().to_string())
+                .indentation
+                .tab_width,
+            Some(8)
+        );
+        assert_eq!(
+            EditorConfig::for_file(&root.join("other.txt").display().to_string())
+                .indentation
+                .expand_tabs,
+            Some(false)
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+//! The character encodings files can be read and written in. Text is kept
+//! as UTF-8 in memory and converted when reading and writing the file.
+
+use std::fmt;
+use std::io::{Error as IOError, ErrorKind};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO 8859-1, where every byte is the character with the same code.
+    Latin1,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Latin1 => "Latin-1",
+        }
+    }
+
+    /// Guesses the encoding of `bytes`: UTF-16 when they start with its
+    /// byte order mark, UTF-8 when they are valid UTF-8 and Latin-1, which
+    /// any bytes are, otherwise.
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes {
+            [0xff, 0xfe, ..] => Self::Utf16Le,
+            [0xfe, 0xff, ..] => Self::Utf16Be,
+            _ if std::str::from_utf8(bytes).is_ok() => Self::Utf8,
+            _ => Self::Latin1,
+        }
+    }
+
+    /// Converts `bytes` to text, dropping a UTF-16 byte order mark.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the bytes are not valid in this encoding
+    pub fn decode(self, bytes: &[u8]) -> Result<String, IOError> {
+        let invalid = || {
+            IOError::new(
+                ErrorKind::InvalidData,
+                format!("the file is not valid {}", self.name()),
+            )
+        };
+
+        match self {
+            Self::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|_| invalid()),
+            Self::Utf16Le | Self::Utf16Be => {
+                if !bytes.len().is_multiple_of(2) {
+                    return Err(invalid());
+                }
+
+                let units = bytes.chunks_exact(2).map(|pair| {
+                    let pair = [pair[0], pair[1]];
+
+                    if self == Self::Utf16Le {
+                        u16::from_le_bytes(pair)
+                    } else {
+                        u16::from_be_bytes(pair)
+                    }
+                });
+                let mut text: String = char::decode_utf16(units)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid())?;
+
+                if text.starts_with('\u{feff}') {
+                    text.remove(0);
+                }
+
+                Ok(text)
+            }
+            Self::Latin1 => Ok(bytes.iter().map(|&byte| char::from(byte)).collect()),
+        }
+    }
+
+    /// Converts `text` to bytes, starting UTF-16 with a byte order mark.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if a character cannot be written in this encoding
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, IOError> {
+        match self {
+            Self::Utf8 => Ok(text.as_bytes().to_vec()),
+            Self::Utf16Le | Self::Utf16Be => Ok(std::iter::once('\u{feff}')
+                .chain(text.chars())
+                .flat_map(|c| c.encode_utf16(&mut [0; 2]).to_vec())
+                .flat_map(|unit| {
+                    if self == Self::Utf16Le {
+                        unit.to_le_bytes()
+                    } else {
+                        unit.to_be_bytes()
+                    }
+                })
+                .collect()),
+            Self::Latin1 => text
+                .chars()
+                .map(|c| {
+                    u8::try_from(c).map_err(|_| {
+                        IOError::new(
+                            ErrorKind::InvalidData,
+                            format!("{c} cannot be written in {}", self.name()),
+                        )
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "utf-16" | "utf16" | "utf-16le" | "utf16le" => Ok(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Self::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" => Ok(Self::Latin1),
+            _ => Err(format!("unknown encoding: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Encoding::detect(b"plain"), Encoding::Utf8);
+        assert_eq!(Encoding::detect("café".as_bytes()), Encoding::Utf8);
+        assert_eq!(Encoding::detect(b"caf\xe9"), Encoding::Latin1);
+        assert_eq!(Encoding::detect(b"\xff\xfea\0"), Encoding::Utf16Le);
+        assert_eq!(Encoding::detect(b"\xfe\xff\0a"), Encoding::Utf16Be);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let latin1: Vec<u8> = (0..=255).collect();
+        let text = Encoding::Latin1.decode(&latin1).unwrap();
+
+        assert_eq!(Encoding::Latin1.encode(&text).unwrap(), latin1);
+        assert!(Encoding::Latin1.encode("€").is_err());
+
+        for encoding in [Encoding::Utf16Le, Encoding::Utf16Be] {
+            let bytes = encoding.encode("héllo 𝄞\n").unwrap();
+
+            assert_eq!(encoding.decode(&bytes).unwrap(), "héllo 𝄞\n");
+        }
+
+        assert_eq!(Encoding::Utf16Le.encode("a").unwrap(), b"\xff\xfea\0");
+        assert!(Encoding::Utf16Le.decode(b"a").is_err());
+        assert!(Encoding::Utf8.decode(b"caf\xe9").is_err());
+        assert_eq!("latin1".parse(), Ok(Encoding::Latin1));
+        assert_eq!("UTF-16BE".parse(), Ok(Encoding::Utf16Be));
+        assert!("ebcdic".parse::<Encoding>().is_err());
+    }
+}
@@ -1,36 +1,299 @@
 use crate::keywords;
 use std::error::Error as Err;
+use std::ffi::OsStr;
 use std::path::Path;
 use thiserror::Error;
 
 #[derive(Default)]
 pub struct HighlightOptions {
     numbers: bool,
-    strings: bool,
+    strings: Vec<char>,
+    string_prefixes: Vec<char>,
     boolean: bool,
     char: bool,
-    comments: bool,
-    multiline_comments: bool,
+    comment: Option<&'static str>,
+    multiline_comment: Option<(&'static str, &'static str)>,
     primary_keywords: Vec<String>,
     secondary_keywords: Vec<String>,
 }
 
 pub struct FileType {
     name: String,
+    badge: &'static str,
     hl_opts: HighlightOptions,
+    balanced_brackets: bool,
+    indentation: Option<(usize, bool)>,
 }
 
 #[derive(Debug, Error)]
 #[error("failed to parse extension from filename: {0}")]
 struct ParseExtensionError(String);
 
+/// How a language is recognized and highlighted, so that supporting a new
+/// one only takes an entry in `LANGUAGES`.
+struct Language {
+    name: &'static str,
+    badge: &'static str,
+    extensions: &'static [&'static str],
+    /// Whole file names recognized regardless of extension, e.g. `Makefile`.
+    filenames: &'static [&'static str],
+    /// Interpreters named by a `#!` line, without any version suffix.
+    interpreters: &'static [&'static str],
+    numbers: bool,
+    /// Quotes that open and close a string literal.
+    strings: &'static [char],
+    /// Letters that may stand in front of a quote, e.g. Python's `f"..."`.
+    string_prefixes: &'static [char],
+    /// Whether `'` delimits a character literal.
+    char: bool,
+    comment: Option<&'static str>,
+    multiline_comment: Option<(&'static str, &'static str)>,
+    primary_keywords: &'static [&'static str],
+    secondary_keywords: &'static [&'static str],
+    /// Whether brackets outside strings and comments come in pairs, so
+    /// that saving can warn about the ones that do not.
+    balanced_brackets: bool,
+    /// The tab width and whether to indent with spaces that the usual
+    /// style of the language asks for, e.g. hard tabs in a Makefile.
+    indentation: Option<(usize, bool)>,
+}
+
+const LANGUAGES: [Language; 13] = [
+    Language {
+        name: "Rust",
+        badge: "RS",
+        extensions: &["rs"],
+        filenames: &[],
+        interpreters: &[],
+        numbers: true,
+        strings: &['"'],
+        string_prefixes: &[],
+        char: true,
+        comment: Some("//"),
+        multiline_comment: Some(("/*", "*/")),
+        primary_keywords: keywords::rust::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::rust::SECONDARY_KEYWORDS,
+        balanced_brackets: true,
+        indentation: Some((4, true)),
+    },
+    Language {
+        name: "Python",
+        badge: "PY",
+        extensions: &["py", "pyi"],
+        filenames: &[],
+        interpreters: &["python"],
+        numbers: true,
+        strings: &['"', '\''],
+        string_prefixes: &['f', 'r', 'b', 'u', 'F', 'R', 'B', 'U'],
+        char: false,
+        comment: Some("#"),
+        multiline_comment: None,
+        primary_keywords: keywords::python::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::python::SECONDARY_KEYWORDS,
+        balanced_brackets: true,
+        indentation: Some((4, true)),
+    },
+    Language {
+        name: "JavaScript",
+        badge: "JS",
+        extensions: &["js", "mjs", "cjs", "jsx"],
+        filenames: &[],
+        interpreters: &["node"],
+        numbers: true,
+        strings: &['"', '\'', '`'],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("//"),
+        multiline_comment: Some(("/*", "*/")),
+        primary_keywords: keywords::javascript::PRIMARY_KEYWORDS,
+        secondary_keywords: &[],
+        balanced_brackets: true,
+        indentation: None,
+    },
+    Language {
+        name: "TypeScript",
+        badge: "TS",
+        extensions: &["ts", "mts", "cts", "tsx"],
+        filenames: &[],
+        interpreters: &["deno", "ts-node"],
+        numbers: true,
+        strings: &['"', '\'', '`'],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("//"),
+        multiline_comment: Some(("/*", "*/")),
+        primary_keywords: keywords::javascript::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::javascript::SECONDARY_KEYWORDS,
+        balanced_brackets: true,
+        indentation: None,
+    },
+    Language {
+        name: "Go",
+        badge: "GO",
+        extensions: &["go"],
+        filenames: &[],
+        interpreters: &[],
+        numbers: true,
+        strings: &['"', '`'],
+        string_prefixes: &[],
+        char: true,
+        comment: Some("//"),
+        multiline_comment: Some(("/*", "*/")),
+        primary_keywords: keywords::go::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::go::SECONDARY_KEYWORDS,
+        balanced_brackets: true,
+        indentation: None,
+    },
+    Language {
+        name: "Toml",
+        badge: "TOML",
+        extensions: &["toml"],
+        filenames: &[],
+        interpreters: &[],
+        numbers: true,
+        strings: &['"', '\''],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("#"),
+        multiline_comment: None,
+        primary_keywords: keywords::toml::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::toml::SECONDARY_KEYWORDS,
+        balanced_brackets: true,
+        indentation: None,
+    },
+    Language {
+        name: "Lock",
+        badge: "LOCK",
+        extensions: &["lock"],
+        filenames: &[],
+        interpreters: &[],
+        numbers: true,
+        strings: &['"', '\''],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("#"),
+        multiline_comment: None,
+        primary_keywords: keywords::toml::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::toml::SECONDARY_KEYWORDS,
+        balanced_brackets: false,
+        indentation: None,
+    },
+    Language {
+        name: "JSON",
+        badge: "JSON",
+        extensions: &["json"],
+        filenames: &[],
+        interpreters: &[],
+        numbers: true,
+        strings: &['"'],
+        string_prefixes: &[],
+        char: false,
+        comment: None,
+        multiline_comment: None,
+        primary_keywords: keywords::json::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::json::SECONDARY_KEYWORDS,
+        balanced_brackets: true,
+        indentation: None,
+    },
+    Language {
+        name: "YAML",
+        badge: "YML",
+        extensions: &["yml", "yaml"],
+        filenames: &[],
+        interpreters: &[],
+        numbers: true,
+        strings: &['"', '\''],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("#"),
+        multiline_comment: None,
+        primary_keywords: keywords::toml::PRIMARY_KEYWORDS,
+        secondary_keywords: &[],
+        balanced_brackets: false,
+        indentation: Some((2, true)),
+    },
+    Language {
+        name: "Markdown",
+        badge: "MD",
+        extensions: &["md"],
+        filenames: &[],
+        interpreters: &[],
+        numbers: false,
+        strings: &[],
+        string_prefixes: &[],
+        char: false,
+        comment: None,
+        multiline_comment: None,
+        primary_keywords: &[],
+        secondary_keywords: &[],
+        balanced_brackets: false,
+        indentation: None,
+    },
+    Language {
+        name: "Shell",
+        badge: "SH",
+        extensions: &["sh", "bash", "zsh"],
+        filenames: &[".bashrc", ".bash_profile", ".profile", ".zshrc"],
+        interpreters: &["sh", "bash", "zsh", "dash", "ksh"],
+        numbers: true,
+        strings: &['"', '\''],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("#"),
+        multiline_comment: None,
+        primary_keywords: keywords::shell::PRIMARY_KEYWORDS,
+        secondary_keywords: keywords::shell::SECONDARY_KEYWORDS,
+        balanced_brackets: false,
+        indentation: None,
+    },
+    Language {
+        name: "Dockerfile",
+        badge: "DOCKER",
+        extensions: &["dockerfile"],
+        filenames: &["Dockerfile", "Containerfile"],
+        interpreters: &[],
+        numbers: true,
+        strings: &['"', '\''],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("#"),
+        multiline_comment: None,
+        primary_keywords: keywords::dockerfile::PRIMARY_KEYWORDS,
+        secondary_keywords: &[],
+        balanced_brackets: false,
+        indentation: None,
+    },
+    Language {
+        name: "Makefile",
+        badge: "MAKE",
+        extensions: &["mk"],
+        filenames: &["Makefile", "makefile", "GNUmakefile"],
+        interpreters: &["make"],
+        numbers: false,
+        strings: &[],
+        string_prefixes: &[],
+        char: false,
+        comment: Some("#"),
+        multiline_comment: None,
+        primary_keywords: keywords::make::PRIMARY_KEYWORDS,
+        secondary_keywords: &[],
+        balanced_brackets: false,
+        indentation: Some((8, false)),
+    },
+];
+
 impl HighlightOptions {
     pub fn numbers(&self) -> bool {
         self.numbers
     }
 
-    pub fn strings(&self) -> bool {
-        self.strings
+    /// Returns `true` if `c` opens a string literal.
+    pub fn is_string_quote(&self, c: char) -> bool {
+        self.strings.contains(&c)
+    }
+
+    pub fn is_string_prefix(&self, c: char) -> bool {
+        self.string_prefixes.contains(&c)
     }
 
     pub fn char(&self) -> bool {
@@ -41,8 +304,14 @@ impl HighlightOptions {
         self.boolean
     }
 
-    pub fn comments(&self) -> bool {
-        self.comments
+    pub fn comment(&self) -> Option<&'static str> {
+        self.comment
+    }
+
+    /// Returns `true` for text without strings or comments, which is then
+    /// spell-checked throughout.
+    pub fn is_prose(&self) -> bool {
+        self.strings.is_empty() && self.comment.is_none() && self.multiline_comment.is_none()
     }
 
     pub fn primary_keywords(&self) -> &Vec<String> {
@@ -53,8 +322,9 @@ impl HighlightOptions {
         &self.secondary_keywords
     }
 
-    pub fn multiline_comments(&self) -> bool {
-        self.multiline_comments
+    /// Returns the delimiters opening and closing a block comment.
+    pub fn multiline_comment(&self) -> Option<(&'static str, &'static str)> {
+        self.multiline_comment
     }
 }
 
@@ -62,7 +332,10 @@ impl Default for FileType {
     fn default() -> Self {
         Self {
             name: String::from("No filetype"),
+            badge: "TXT",
             hl_opts: HighlightOptions::default(),
+            balanced_brackets: false,
+            indentation: None,
         }
     }
 }
@@ -71,7 +344,10 @@ impl FileType {
     pub fn new() -> Self {
         Self {
             name: String::new(),
+            badge: "TXT",
             hl_opts: HighlightOptions::default(),
+            balanced_brackets: false,
+            indentation: None,
         }
     }
 
@@ -79,18 +355,65 @@ impl FileType {
         self.name.clone()
     }
 
+    /// Detects the file type from the extension of `filename`, or from the
+    /// whole name for files such as `Makefile`.
     pub fn from(&self, filename: &str) -> Self {
-        match self.parse_extension(filename) {
-            Ok(ext) => match ext.as_str() {
-                "rs" => return self.create_file_type("Rust"),
-                "toml" => return self.create_file_type("Toml"),
-                "lock" => return self.create_file_type("Lock"),
-                "md" => return self.create_file_type("Markdown"),
-                "yml" => return self.create_file_type("YAML"),
-                _ => Self::default(),
-            },
-            Err(_) => return self.create_file_type(filename),
+        let path = Path::new(filename);
+        let name = path.file_name().and_then(OsStr::to_str).unwrap_or(filename);
+        let extension = self.parse_extension(filename).ok();
+
+        LANGUAGES
+            .iter()
+            .find(|language| {
+                language.filenames.contains(&name)
+                    || extension
+                        .as_deref()
+                        .is_some_and(|extension| language.extensions.contains(&extension))
+            })
+            .map_or_else(Self::default, Self::from_language)
+    }
+
+    /// Detects the file type from a `#!` line naming an interpreter, either
+    /// directly or through `env`, e.g. `#!/usr/bin/env python3`.
+    pub fn from_shebang(&self, line: &str) -> Option<Self> {
+        let mut words = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = Path::new(words.next()?).file_name()?.to_str()?;
+
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
         }
+
+        let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        LANGUAGES
+            .iter()
+            .find(|language| language.interpreters.contains(&interpreter))
+            .map(Self::from_language)
+    }
+
+    /// Returns `false` for the plain text file type used when detection
+    /// failed.
+    pub fn is_known(&self) -> bool {
+        LANGUAGES.iter().any(|language| language.name == self.name)
+    }
+
+    /// Returns the short label shown for the file type in the status bar.
+    pub fn badge(&self) -> &'static str {
+        self.badge
+    }
+
+    pub fn balanced_brackets(&self) -> bool {
+        self.balanced_brackets
+    }
+
+    /// Returns the tab width and whether to indent with spaces in the
+    /// usual style of the language, if it has one.
+    pub fn indentation(&self) -> Option<(usize, bool)> {
+        self.indentation
+    }
+
+    pub fn comment_prefix(&self) -> Option<&'static str> {
+        self.hl_opts.comment
     }
 
     pub fn highlight_options(&self) -> &HighlightOptions {
@@ -102,26 +425,73 @@ impl FileType {
 
         match path.extension() {
             Some(ext) => match ext.to_str() {
-                Some(ext) => return Ok(ext.to_owned()),
-                None => return Err(Box::new(ParseExtensionError(filename.to_owned()))),
+                Some(ext) => Ok(ext.to_owned()),
+                None => Err(Box::new(ParseExtensionError(filename.to_owned()))),
             },
-            None => return Err(Box::new(ParseExtensionError(filename.to_owned()))),
+            None => Err(Box::new(ParseExtensionError(filename.to_owned()))),
         }
     }
 
-    fn create_file_type(&self, file_type: &str) -> Self {
+    fn from_language(language: &Language) -> Self {
+        let words = |keywords: &[&str]| keywords.iter().map(|&word| word.to_owned()).collect();
+
         Self {
-            name: String::from(file_type),
+            name: String::from(language.name),
+            badge: language.badge,
             hl_opts: HighlightOptions {
-                numbers: true,
-                strings: true,
+                numbers: language.numbers,
+                strings: language.strings.to_vec(),
+                string_prefixes: language.string_prefixes.to_vec(),
                 boolean: true,
-                char: true,
-                comments: true,
-                multiline_comments: true,
-                primary_keywords: keywords::rust::primary_keywords(),
-                secondary_keywords: keywords::rust::secondary_keywords(),
+                char: language.char,
+                comment: language.comment,
+                multiline_comment: language.multiline_comment,
+                primary_keywords: words(language.primary_keywords),
+                secondary_keywords: words(language.secondary_keywords),
             },
+            balanced_brackets: language.balanced_brackets,
+            indentation: language.indentation,
         }
     }
 }
+
+#[cfg(test)]
+mod filetype_tests {
+    use super::*;
+
+    #[test]
+    fn test_from() {
+        assert_eq!(FileType::new().from("src/main.rs").name(), "Rust");
+        assert_eq!(FileType::new().from("docker/Dockerfile").badge(), "DOCKER");
+        assert_eq!(FileType::new().from("Makefile").name(), "Makefile");
+        assert!(!FileType::new().from("notes").is_known());
+        assert!(!FileType::new().from("notes.txt").is_known());
+        assert_eq!(
+            FileType::new().from("Makefile").indentation(),
+            Some((8, false))
+        );
+        assert_eq!(FileType::new().from("notes").indentation(), None);
+    }
+
+    #[test]
+    fn test_from_shebang() {
+        let detect = |line| {
+            FileType::new()
+                .from_shebang(line)
+                .map(|file_type| file_type.name())
+        };
+
+        assert_eq!(detect("#!/usr/bin/env python3"), Some("Python".to_owned()));
+        assert_eq!(
+            detect("#!/usr/bin/python3.11 -u"),
+            Some("Python".to_owned())
+        );
+        assert_eq!(detect("#!/bin/bash"), Some("Shell".to_owned()));
+        assert_eq!(
+            detect("#!/usr/bin/env -S node --inspect"),
+            Some("JavaScript".to_owned())
+        );
+        assert_eq!(detect("#!/usr/bin/perl"), None);
+        assert_eq!(detect("import os"), None);
+    }
+}
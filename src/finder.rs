@@ -0,0 +1,515 @@
+//! Finding files in the working directory by typing a few letters of their
+//! path, the way `Ctrl-P` works in other editors, and searching through
+//! their contents.
+
+use crate::glob::glob_match;
+
+use std::env;
+use std::fs;
+use std::io::Error as IOError;
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Scanning stops after this many files, so that opening the finder in
+/// a huge tree stays quick.
+pub const MAX_FILES: usize = 20_000;
+const MAX_DEPTH: usize = 32;
+/// Searching stops after this many matching lines.
+pub const MAX_MATCHES: usize = 1000;
+/// Files with a NUL byte among their first bytes are taken for binaries.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// A line of a file containing the searched text.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Match {
+    pub path: String,
+    /// The line number, counting from 1.
+    pub line: usize,
+    /// The grapheme index of the match in the line.
+    pub column: usize,
+    pub text: String,
+}
+
+/// A pattern read from a `.gitignore` file.
+struct Rule {
+    /// The directory of the `.gitignore` file, relative to the root.
+    base: String,
+    pattern: String,
+    negated: bool,
+    directory_only: bool,
+    /// Whether the pattern matches the path from `base` rather than any
+    /// file name, as patterns containing a slash do.
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(base: &str, line: &str) -> Option<Self> {
+        let line = line.trim_end();
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (directory_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+
+        Some(Self {
+            base: base.to_owned(),
+            pattern: line.trim_start_matches('/').to_owned(),
+            negated,
+            directory_only,
+            anchored,
+        })
+    }
+
+    /// Returns whether the rule applies to `path`, relative to the root.
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+
+        let Some(relative) = relative_to(path, &self.base) else {
+            return false;
+        };
+
+        if self.anchored {
+            glob_match(&self.pattern, relative)
+        } else {
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+
+            glob_match(&self.pattern, name)
+        }
+    }
+}
+
+fn relative_to<'a>(path: &'a str, base: &str) -> Option<&'a str> {
+    if base.is_empty() {
+        return Some(path);
+    }
+
+    path.strip_prefix(base)?.strip_prefix('/')
+}
+
+fn is_ignored(rules: &[Rule], path: &str, is_dir: bool) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(path, is_dir))
+        .is_some_and(|rule| !rule.negated)
+}
+
+/// Lists the files below `root` as paths relative to it, sorted, leaving
+/// out hidden entries and those ignored by `.gitignore` files. Returns
+/// whether the listing stopped at `MAX_FILES`.
+pub fn scan(root: &Path) -> (Vec<String>, bool) {
+    let mut files = Vec::new();
+    let mut rules = Vec::new();
+    let complete = scan_dir(root, "", 0, &mut rules, &mut files);
+
+    files.sort();
+    (files, !complete)
+}
+
+fn scan_dir(
+    dir: &Path,
+    relative: &str,
+    depth: usize,
+    rules: &mut Vec<Rule>,
+    files: &mut Vec<String>,
+) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return true;
+    };
+    let inherited = rules.len();
+
+    if let Ok(gitignore) = fs::read_to_string(dir.join(".gitignore")) {
+        rules.extend(
+            gitignore
+                .lines()
+                .filter_map(|line| Rule::parse(relative, line)),
+        );
+    }
+
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    let mut complete = true;
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let path = if relative.is_empty() {
+            name
+        } else {
+            format!("{relative}/{name}")
+        };
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if is_ignored(rules, &path, file_type.is_dir()) {
+            continue;
+        }
+
+        if file_type.is_dir() {
+            if depth < MAX_DEPTH && !scan_dir(&entry.path(), &path, depth + 1, rules, files) {
+                complete = false;
+                break;
+            }
+        } else if files.len() < MAX_FILES {
+            files.push(path);
+        } else {
+            complete = false;
+            break;
+        }
+    }
+
+    rules.truncate(inherited);
+    complete
+}
+
+/// Searches `files`, relative to `root`, for lines containing `query`,
+/// skipping binary files. Returns whether the search stopped at
+/// `MAX_MATCHES`.
+pub fn grep(root: &Path, files: &[String], query: &str) -> (Vec<Match>, bool) {
+    let mut matches = Vec::new();
+
+    for path in files {
+        let Ok(bytes) = fs::read(root.join(path)) else {
+            continue;
+        };
+
+        if bytes[..bytes.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+            continue;
+        }
+
+        let contents = String::from_utf8_lossy(&bytes);
+
+        for (index, line) in contents.lines().enumerate() {
+            let Some(at) = line.find(query) else {
+                continue;
+            };
+
+            if matches.len() == MAX_MATCHES {
+                return (matches, true);
+            }
+
+            matches.push(Match {
+                path: path.clone(),
+                line: index + 1,
+                column: line[..at].graphemes(true).count(),
+                text: line.to_owned(),
+            });
+        }
+    }
+
+    (matches, false)
+}
+
+/// Lists the paths `input` completes to, sorted: the entries of the
+/// directory it names whose name starts with its last component.
+/// Directories end with a slash and hidden entries are left out unless
+/// the name typed starts with a dot. A leading `~` is expanded to the home
+/// directory.
+pub fn complete_path(input: &str) -> Vec<String> {
+    let input = match (input.strip_prefix('~'), env::var("HOME")) {
+        (Some(rest), Ok(home)) if rest.is_empty() || rest.starts_with('/') => format!(
+            "{}/{}",
+            home.trim_end_matches('/'),
+            rest.trim_start_matches('/')
+        ),
+        _ => input.to_owned(),
+    };
+    let (directory, prefix) = match input.rfind('/') {
+        Some(end) => input.split_at(end + 1),
+        None => ("", input.as_str()),
+    };
+    let Ok(entries) = fs::read_dir(if directory.is_empty() { "." } else { directory }) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+
+            Some(format!("{directory}{name}{slash}"))
+        })
+        .collect();
+
+    paths.sort();
+    paths
+}
+
+/// Lists the entries of `directory` for browsing it, hidden ones included:
+/// `../` first, then the directories ending with a slash and the other
+/// files, each sorted by name.
+///
+/// # Errors
+///
+/// Will return `Error` if the directory cannot be read
+pub fn list_directory(directory: &Path) -> Result<Vec<String>, IOError> {
+    let mut directories = vec!["../".to_owned()];
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+
+        if entry.path().is_dir() {
+            directories.push(format!("{name}/"));
+        } else {
+            files.push(name);
+        }
+    }
+
+    directories[1..].sort();
+    files.sort();
+    directories.extend(files);
+
+    Ok(directories)
+}
+
+/// Scores how well `candidate` matches `query`, whose characters have to
+/// appear in it in order, ignoring case. Runs of consecutive characters and
+/// characters starting a path component or a word score higher, and so do
+/// shorter candidates. Returns `None` if the candidate does not match.
+pub fn score(candidate: &str, query: &str) -> Option<i64> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut consecutive = false;
+
+    for c in candidate.chars() {
+        let Some(wanted) = query.peek() else {
+            break;
+        };
+
+        if c.to_lowercase().eq(Some(*wanted)) {
+            let boundary = previous.is_none_or(|previous| {
+                matches!(previous, '/' | '_' | '-' | '.' | ' ')
+                    || (previous.is_lowercase() && c.is_uppercase())
+            });
+
+            score += 1 + if consecutive { 5 } else { 0 } + if boundary { 8 } else { 0 };
+            consecutive = true;
+            query.next();
+        } else {
+            consecutive = false;
+        }
+
+        previous = Some(c);
+    }
+
+    if query.peek().is_some() {
+        return None;
+    }
+
+    let length = i64::try_from(candidate.chars().count()).unwrap_or(i64::MAX);
+
+    Some(score * 100 - length)
+}
+
+/// Returns the indices of the `candidates` matching `query`, best first.
+pub fn rank(candidates: &[String], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(i64, usize)> = candidates
+        .iter()
+        .enumerate()
+        .filter_map(|(index, candidate)| Some((score(candidate, query)?, index)))
+        .collect();
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    scored.into_iter().map(|(_, index)| index).collect()
+}
+
+#[cfg(test)]
+mod finder_tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_scan() {
+        let root = env::temp_dir().join("revise_test_finder");
+
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(
+            root.join(".gitignore"),
+            "target/\n*.log\n!keep.log\n*.py[cod]\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/.gitignore"), "/generated.rs\n").unwrap();
+
+        for file in [
+            "README.md",
+            "build.log",
+            "keep.log",
+            "src/main.rs",
+            "src/cache.pyc",
+            "src/generated.rs",
+            "src/nested/generated.rs",
+            "target/debug/revise",
+            ".git/HEAD",
+        ] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        let (files, truncated) = scan(&root);
+
+        assert_eq!(
+            files,
+            vec![
+                "README.md",
+                "keep.log",
+                "src/main.rs",
+                "src/nested/generated.rs"
+            ]
+        );
+        assert!(!truncated);
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_grep() {
+        let root = env::temp_dir().join("revise_test_grep");
+
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("notes.txt"),
+            "first\n  a needle here\nélan needle\n",
+        )
+        .unwrap();
+        fs::write(root.join("binary"), b"needle\0").unwrap();
+
+        let files = ["binary", "missing", "notes.txt"].map(str::to_owned);
+        let (matches, truncated) = grep(&root, &files, "needle");
+
+        assert_eq!(
+            matches,
+            vec![
+                Match {
+                    path: "notes.txt".to_owned(),
+                    line: 2,
+                    column: 4,
+                    text: "  a needle here".to_owned(),
+                },
+                Match {
+                    path: "notes.txt".to_owned(),
+                    line: 3,
+                    column: 5,
+                    text: "élan needle".to_owned(),
+                },
+            ]
+        );
+        assert!(!truncated);
+        assert!(grep(&root, &files, "haystack").0.is_empty());
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_list_directory() {
+        let root = env::temp_dir().join("revise_test_list_directory");
+
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+
+        for file in ["main.rs", ".hidden", "Cargo.toml"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            list_directory(&root).unwrap(),
+            vec!["../", ".git/", "src/", ".hidden", "Cargo.toml", "main.rs"]
+        );
+        assert!(list_directory(&root.join("missing")).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_complete_path() {
+        let root = env::temp_dir().join("revise_test_complete");
+        let base = root.display().to_string();
+
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join("src")).unwrap();
+
+        for file in ["main.rs", "make.rs", ".hidden", "src/lib.rs"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+
+        assert_eq!(
+            complete_path(&format!("{base}/ma")),
+            vec![format!("{base}/main.rs"), format!("{base}/make.rs")]
+        );
+        assert_eq!(
+            complete_path(&format!("{base}/")),
+            vec![
+                format!("{base}/main.rs"),
+                format!("{base}/make.rs"),
+                format!("{base}/src/")
+            ]
+        );
+        assert_eq!(
+            complete_path(&format!("{base}/.h")),
+            vec![format!("{base}/.hidden")]
+        );
+        assert_eq!(
+            complete_path(&format!("{base}/src/l")),
+            vec![format!("{base}/src/lib.rs")]
+        );
+        assert!(complete_path(&format!("{base}/missing/")).is_empty());
+
+        if let Ok(home) = env::var("HOME") {
+            assert!(complete_path("~/")
+                .iter()
+                .all(|path| path.starts_with(home.trim_end_matches('/'))));
+        }
+
+        fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_rank() {
+        let candidates: Vec<String> = [
+            "src/application.rs",
+            "src/main.rs",
+            "benches/insert.rs",
+            "README.md",
+        ]
+        .map(str::to_owned)
+        .to_vec();
+
+        assert_eq!(score("src/main.rs", "xyz"), None);
+        assert!(score("src/main.rs", "MAIN").is_some());
+        assert_eq!(rank(&candidates, "main"), vec![1]);
+        assert_eq!(rank(&candidates, "sa")[0], 0);
+        assert_eq!(rank(&candidates, "").len(), 4);
+        assert_eq!(rank(&candidates, "ins")[0], 2);
+    }
+}
@@ -0,0 +1,228 @@
+use std::iter::{Chain, Rev};
+use std::ops::{Index, IndexMut, Range};
+use std::slice::{Iter, IterMut};
+
+/// A sequence that stores its elements around a movable gap, so that
+/// inserting or removing near the previous edit does not shift the whole
+/// sequence. Elements before the gap live in `front`, the ones after it in
+/// `back`, in reverse order.
+pub struct GapBuffer<T> {
+    front: Vec<T>,
+    back: Vec<T>,
+}
+
+impl<T> Default for GapBuffer<T> {
+    fn default() -> Self {
+        Self {
+            front: Vec::new(),
+            back: Vec::new(),
+        }
+    }
+}
+
+impl<T> From<Vec<T>> for GapBuffer<T> {
+    fn from(front: Vec<T>) -> Self {
+        Self {
+            front,
+            back: Vec::new(),
+        }
+    }
+}
+
+impl<T> FromIterator<T> for GapBuffer<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Vec::from_iter(iter).into()
+    }
+}
+
+impl<T> GapBuffer<T> {
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.front.is_empty() && self.back.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        match index.checked_sub(self.front.len()) {
+            None => self.front.get(index),
+            Some(offset) => self.back.get(self.back.len().checked_sub(offset + 1)?),
+        }
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        match index.checked_sub(self.front.len()) {
+            None => self.front.get_mut(index),
+            Some(offset) => {
+                let index = self.back.len().checked_sub(offset + 1)?;
+
+                self.back.get_mut(index)
+            }
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.move_gap(self.len());
+        self.front.push(value);
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if `index` is greater than the length
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len(), "insertion index out of bounds");
+
+        self.move_gap(index);
+        self.front.push(value);
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if `index` is out of bounds
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len(), "removal index out of bounds");
+
+        self.move_gap(index);
+        self.back.pop().expect("index is in bounds")
+    }
+
+    /// Replaces the elements in `range` with the ones from `values`.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `range` is out of bounds
+    pub fn splice<I: IntoIterator<Item = T>>(&mut self, range: Range<usize>, values: I) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "splice range out of bounds"
+        );
+
+        self.move_gap(range.end);
+        self.front.truncate(range.start);
+        self.front.extend(values);
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if `a` or `b` is out of bounds
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let (a, b) = (a.min(b), a.max(b));
+
+        self.move_gap(b);
+
+        let value = self.back.last_mut().expect("index is in bounds");
+
+        std::mem::swap(&mut self.front[a], value);
+    }
+
+    pub fn iter(&self) -> Chain<Iter<'_, T>, Rev<Iter<'_, T>>> {
+        self.front.iter().chain(self.back.iter().rev())
+    }
+
+    pub fn iter_mut(&mut self) -> Chain<IterMut<'_, T>, Rev<IterMut<'_, T>>> {
+        self.front.iter_mut().chain(self.back.iter_mut().rev())
+    }
+
+    /// Iterates over the elements in `range` without walking the ones before it.
+    pub fn range(&self, range: Range<usize>) -> Chain<Iter<'_, T>, Rev<Iter<'_, T>>> {
+        let split = self.front.len();
+        let front = &self.front[range.start.min(split)..range.end.min(split)];
+        let back_start = self.back.len() - range.end.max(split).saturating_sub(split);
+        let back_end = self.back.len() - range.start.max(split).saturating_sub(split);
+
+        front
+            .iter()
+            .chain(self.back[back_start..back_end].iter().rev())
+    }
+
+    /// Moves the gap so that it starts at `index`, shifting only the
+    /// elements between the old and the new position.
+    fn move_gap(&mut self, index: usize) {
+        while self.front.len() > index {
+            if let Some(value) = self.front.pop() {
+                self.back.push(value);
+            }
+        }
+
+        while self.front.len() < index {
+            match self.back.pop() {
+                Some(value) => self.front.push(value),
+                None => break,
+            }
+        }
+    }
+}
+
+impl<T> Index<usize> for GapBuffer<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<T> IndexMut<usize> for GapBuffer<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<'a, T> IntoIterator for &'a GapBuffer<T> {
+    type Item = &'a T;
+    type IntoIter = Chain<Iter<'a, T>, Rev<Iter<'a, T>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut GapBuffer<T> {
+    type Item = &'a mut T;
+    type IntoIter = Chain<IterMut<'a, T>, Rev<IterMut<'a, T>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod gap_buffer_tests {
+    use super::*;
+
+    fn contents(buffer: &GapBuffer<i32>) -> Vec<i32> {
+        buffer.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut buffer: GapBuffer<i32> = (0..6).collect();
+
+        buffer.insert(2, 10);
+        buffer.remove(5);
+        buffer.push(11);
+        buffer.insert(0, 12);
+
+        assert_eq!(contents(&buffer), vec![12, 0, 1, 10, 2, 3, 5, 11]);
+        assert_eq!(buffer.get(6), Some(&5));
+        assert_eq!(buffer.get(8), None);
+
+        buffer.swap(7, 1);
+        buffer.splice(2..5, [20, 21]);
+
+        assert_eq!(contents(&buffer), vec![12, 11, 20, 21, 3, 5, 0]);
+    }
+
+    #[test]
+    fn test_range() {
+        let mut buffer: GapBuffer<i32> = (0..6).collect();
+
+        buffer.insert(3, 10);
+
+        let range: Vec<i32> = buffer.range(1..5).copied().collect();
+
+        assert_eq!(range, vec![1, 2, 10, 3]);
+        assert_eq!(buffer.range(5..7).count(), 2);
+        assert_eq!(buffer.range(0..0).count(), 0);
+    }
+}
@@ -0,0 +1,260 @@
+//! Comparing a file with the version committed to Git, to mark the lines
+//! changed since in the gutter.
+
+use crate::encoding::Encoding;
+
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Lines differing on both sides of larger changes are all taken for
+/// modified, rather than matched line by line.
+pub const MAX_DIFF_CELLS: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    /// Lines were removed below this one, or above the first line.
+    Deleted,
+}
+
+/// Returns the lines of `filename` as committed in `HEAD`, or `None` when
+/// the file is not in a Git repository, is not committed or Git cannot be
+/// run.
+pub fn committed(filename: &str) -> Option<Vec<String>> {
+    let path = Path::new(filename);
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_str()?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .arg("show")
+        .arg(format!("HEAD:./{name}"))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let text = Encoding::detect(&output.stdout)
+        .decode(&output.stdout)
+        .ok()?;
+
+    Some(text.lines().map(str::to_owned).collect())
+}
+
+/// Marks each of the `lines` added or modified since `committed`, and the
+/// line above where committed lines were removed.
+pub fn diff(committed: &[String], lines: &[&str]) -> Vec<Option<Change>> {
+    let matches = common_lines(committed, lines, matching_lines);
+    let mut changes = vec![None; lines.len()];
+
+    for (old, new) in runs(&matches, committed.len(), lines.len()) {
+        let removed = old.len();
+
+        for (k, change) in changes[new.clone()].iter_mut().enumerate() {
+            *change = Some(if k < removed {
+                Change::Modified
+            } else {
+                Change::Added
+            });
+        }
+
+        if removed > new.len() {
+            let above = new.end.saturating_sub(1);
+
+            if let Some(change @ None) = changes.get_mut(above) {
+                *change = Some(Change::Deleted);
+            }
+        }
+    }
+
+    changes
+}
+
+/// Returns the lines `old` and `new` have in common, as pairs of their
+/// indices, in order: the lines they start and end with, and those
+/// `matching` pairs up between them.
+pub fn common_lines(
+    old: &[String],
+    new: &[&str],
+    mut matching: impl FnMut(&[String], &[&str]) -> Vec<(usize, usize)>,
+) -> Vec<(usize, usize)> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == *new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == *new)
+        .count();
+    let middle = matching(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    (0..prefix)
+        .map(|line| (line, line))
+        .chain(
+            middle
+                .into_iter()
+                .map(|(old, new)| (prefix + old, prefix + new)),
+        )
+        .chain((0..suffix).map(|line| (old.len() - suffix + line, new.len() - suffix + line)))
+        .collect()
+}
+
+/// Returns the lines of a file `old_len` lines long replaced by those of
+/// one `new_len` lines long between each of their `matches` and the next,
+/// as ranges of both. Ranges between adjacent matches are empty.
+pub fn runs(
+    matches: &[(usize, usize)],
+    old_len: usize,
+    new_len: usize,
+) -> impl Iterator<Item = (Range<usize>, Range<usize>)> + '_ {
+    let starts = [(0, 0)]
+        .into_iter()
+        .chain(matches.iter().map(|&(old, new)| (old + 1, new + 1)));
+
+    starts
+        .zip(matches.iter().copied().chain([(old_len, new_len)]))
+        .map(|((old_start, new_start), (old_end, new_end))| {
+            (old_start..old_end, new_start..new_end)
+        })
+}
+
+/// Returns the indices of the lines the longest common subsequence of
+/// `old` and `new` is made of, in order.
+pub fn matching_lines(old: &[String], new: &[&str]) -> Vec<(usize, usize)> {
+    let (n, m) = (old.len(), new.len());
+
+    if n == 0 || m == 0 || n.saturating_mul(m) > MAX_DIFF_CELLS {
+        return Vec::new();
+    }
+
+    let old: Vec<&str> = old.iter().map(String::as_str).collect();
+    let mut matches = Vec::new();
+
+    push_matches(&old, new, (0, 0), &mut matches);
+
+    matches
+}
+
+/// Pushes the matches of `old` and `new`, shifted by `start`, splitting
+/// `old` in half at each step so only two rows of lengths are kept at a
+/// time, rather than the whole table.
+fn push_matches(
+    old: &[&str],
+    new: &[&str],
+    start: (usize, usize),
+    matches: &mut Vec<(usize, usize)>,
+) {
+    if old.is_empty() || new.is_empty() {
+        return;
+    }
+
+    if let [line] = old {
+        if let Some(j) = new.iter().position(|other| other == line) {
+            matches.push((start.0, start.1 + j));
+        }
+
+        return;
+    }
+
+    let middle = old.len() / 2;
+    let forward = lcs_lengths(old[..middle].iter(), new.iter());
+    let backward = lcs_lengths(old[middle..].iter().rev(), new.iter().rev());
+    let split = (0..=new.len())
+        .max_by_key(|&j| (forward[j] + backward[new.len() - j], std::cmp::Reverse(j)))
+        .unwrap_or_default();
+
+    push_matches(&old[..middle], &new[..split], start, matches);
+    push_matches(
+        &old[middle..],
+        &new[split..],
+        (start.0 + middle, start.1 + split),
+        matches,
+    );
+}
+
+/// Returns the length of the longest common subsequence of `old` and each
+/// start of `new`, from the empty one to the whole.
+fn lcs_lengths<'a>(
+    old: impl Iterator<Item = &'a &'a str>,
+    new: impl Iterator<Item = &'a &'a str> + Clone,
+) -> Vec<u32> {
+    let mut lengths = vec![0; new.clone().count() + 1];
+
+    for line in old {
+        let mut diagonal = 0;
+
+        for (j, other) in new.clone().enumerate() {
+            let above = lengths[j + 1];
+
+            lengths[j + 1] = if line == other {
+                diagonal + 1
+            } else {
+                above.max(lengths[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    lengths
+}
+
+#[cfg(test)]
+mod git_tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let committed: Vec<String> = ["a", "b", "c", "d", "e"].map(str::to_owned).to_vec();
+        let diff_with = |lines: &[&str]| diff(&committed, lines);
+
+        assert_eq!(diff_with(&["a", "b", "c", "d", "e"]), vec![None; 5]);
+        assert_eq!(
+            diff_with(&["a", "b", "new", "c", "d", "e"]),
+            vec![None, None, Some(Change::Added), None, None, None]
+        );
+        assert_eq!(
+            diff_with(&["a", "B", "c", "d", "e"]),
+            vec![None, Some(Change::Modified), None, None, None]
+        );
+        assert_eq!(
+            diff_with(&["a", "b", "e"]),
+            vec![None, Some(Change::Deleted), None]
+        );
+        assert_eq!(
+            diff_with(&["b", "c", "d", "e"]),
+            vec![Some(Change::Deleted), None, None, None]
+        );
+        assert_eq!(
+            diff_with(&["a", "x", "c", "y", "z", "e"]),
+            vec![
+                None,
+                Some(Change::Modified),
+                None,
+                Some(Change::Modified),
+                Some(Change::Added),
+                None
+            ]
+        );
+        assert_eq!(diff(&[], &["a"]), vec![Some(Change::Added)]);
+        assert_eq!(diff_with(&[]), Vec::new());
+        assert_eq!(
+            matching_lines(&committed, &["b", "x", "d", "a", "e", "c"]),
+            vec![(1, 0), (3, 2), (4, 4)]
+        );
+    }
+}
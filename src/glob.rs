@@ -0,0 +1,135 @@
+//! Matching paths against the glob patterns of `.gitignore` and
+//! `.editorconfig` files.
+
+/// Matches `path` against `pattern`, where `*` and `?` stay within a path
+/// component, `**` crosses them, `[...]` stands for one of a set or, as
+/// `[!...]`, for one outside it, and `{a,b}` for either alternative.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let path: Vec<char> = path.chars().collect();
+
+    expand_braces(pattern)
+        .iter()
+        .any(|pattern| match_from(&pattern.chars().collect::<Vec<_>>(), &path))
+}
+
+/// Expands `{a,b}` alternatives into one pattern for each.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_owned()];
+    };
+    let mut depth = 0;
+    let mut close = None;
+    let mut alternatives = Vec::new();
+    let mut start = open + 1;
+
+    for (index, c) in pattern
+        .char_indices()
+        .skip_while(|&(index, _)| index <= open)
+    {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '}' => {
+                alternatives.push(&pattern[start..index]);
+                close = Some(index);
+                break;
+            }
+            ',' if depth == 0 => {
+                alternatives.push(&pattern[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+
+    let Some(close) = close.filter(|_| alternatives.len() > 1) else {
+        return vec![pattern.to_owned()];
+    };
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+
+    alternatives
+        .into_iter()
+        .flat_map(|alternative| expand_braces(&format!("{prefix}{alternative}{suffix}")))
+        .collect()
+}
+
+fn match_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            match_from(rest, path)
+                || (0..path.len())
+                    .any(|index| path[index] == '/' && match_from(rest, &path[index + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|skip| match_from(rest, &path[skip..])),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&skip| skip == 0 || path[skip - 1] != '/')
+            .any(|skip| match_from(rest, &path[skip..])),
+        ['?', rest @ ..] => path
+            .split_first()
+            .is_some_and(|(c, path)| *c != '/' && match_from(rest, path)),
+        ['[', class @ ..] if class.contains(&']') => {
+            let end = class.iter().position(|&c| c == ']').unwrap_or_default();
+            let (negated, set) = match &class[..end] {
+                ['!', set @ ..] => (true, set),
+                set => (false, set),
+            };
+
+            path.split_first().is_some_and(|(c, path)| {
+                *c != '/' && in_set(set, *c) != negated && match_from(&class[end + 1..], path)
+            })
+        }
+        [c, rest @ ..] => path
+            .split_first()
+            .is_some_and(|(first, path)| first == c && match_from(rest, path)),
+    }
+}
+
+fn in_set(set: &[char], c: char) -> bool {
+    let mut index = 0;
+
+    while index < set.len() {
+        if set.get(index + 1) == Some(&'-') && index + 2 < set.len() {
+            if (set[index]..=set[index + 2]).contains(&c) {
+                return true;
+            }
+
+            index += 3;
+        } else {
+            if set[index] == c {
+                return true;
+            }
+
+            index += 1;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod glob_tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.log", "debug.log"));
+        assert!(!glob_match("*.log", "logs/debug.log"));
+        assert!(glob_match("build", "build"));
+        assert!(glob_match("doc/*.txt", "doc/notes.txt"));
+        assert!(!glob_match("doc/*.txt", "doc/server/notes.txt"));
+        assert!(glob_match("**/tmp", "a/b/tmp"));
+        assert!(glob_match("**/tmp", "tmp"));
+        assert!(glob_match("a/**", "a/b/c"));
+        assert!(glob_match("src/**.rs", "src/bin/main.rs"));
+        assert!(glob_match("file?.rs", "file1.rs"));
+        assert!(!glob_match("file?.rs", "file10.rs"));
+        assert!(glob_match("*.py[cod]", "cache.pyc"));
+        assert!(!glob_match("*.py[cod]", "main.py"));
+        assert!(!glob_match("file?.[!ch]", "file1.h"));
+        assert!(glob_match("[a-c]*.md", "b.md"));
+        assert!(glob_match("*.{js,ts}", "app.ts"));
+        assert!(glob_match("{src,lib}/*.{c,h}", "lib/io.h"));
+        assert!(!glob_match("*.{js,ts}", "app.rs"));
+    }
+}
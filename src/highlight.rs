@@ -1,10 +1,3 @@
-use termion::color;
-use thiserror::Error;
-
-#[derive(Debug, Error)]
-#[error("cannot highlight content")]
-pub struct HighlightError;
-
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Type {
     None,
@@ -17,20 +10,25 @@ pub enum Type {
     MultilineComment,
     PrimaryKeywords,
     SecondaryKeywords,
+    Occurrence,
+    /// A bracket in code nested this deep, colored with `rainbow_brackets`.
+    Bracket(usize),
 }
 
-impl Type {
-    pub fn to_color(self) -> impl color::Color {
-        match self {
-            Type::Number => color::Rgb(220, 163, 163),
-            Type::Match => color::Rgb(30, 139, 210),
-            Type::String => color::Rgb(211, 54, 130),
-            Type::_Boolean => color::Rgb(0, 0, 139),
-            Type::Char => color::Rgb(108, 113, 196),
-            Type::Comment | Type::MultilineComment => color::Rgb(133, 153, 0),
-            Type::PrimaryKeywords => color::Rgb(181, 137, 0),
-            Type::SecondaryKeywords => color::Rgb(42, 161, 152),
-            Type::None => color::Rgb(255, 255, 255),
-        }
-    }
+/// A word marked wherever it appears in a row: a search query, or the
+/// symbol under the cursor, which only matches whole words.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Term {
+    Search(String),
+    Symbol(String),
+}
+
+/// A construct left open at the end of a row, which continues on the next one.
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum State {
+    #[default]
+    Normal,
+    Comment,
+    /// Inside a string opened by the given quote.
+    String(char),
 }
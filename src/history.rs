@@ -0,0 +1,405 @@
+use crate::temp;
+use crate::Position;
+
+use std::fs::{self, File};
+use std::io::{Error as IOError, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::PathBuf;
+
+pub struct Edit {
+    pub at: usize,
+    pub removed: Vec<String>,
+    pub inserted: Vec<String>,
+    pub cursor_before: Position,
+    pub cursor_after: Position,
+    /// The selection anchors before and after the edit, if text was
+    /// selected.
+    pub anchor_before: Option<Position>,
+    pub anchor_after: Option<Position>,
+}
+
+impl Edit {
+    /// Returns roughly how many bytes the edit takes in memory.
+    fn size(&self) -> usize {
+        self.removed
+            .iter()
+            .chain(&self.inserted)
+            .map(|line| line.len() + mem::size_of::<String>())
+            .sum::<usize>()
+            + mem::size_of::<Self>()
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let number = |bytes: &mut Vec<u8>, value: usize| {
+            bytes.extend_from_slice(&(value as u64).to_le_bytes());
+        };
+
+        number(&mut bytes, self.at);
+
+        for position in [&self.cursor_before, &self.cursor_after] {
+            number(&mut bytes, position.x);
+            number(&mut bytes, position.y);
+        }
+
+        for anchor in [&self.anchor_before, &self.anchor_after] {
+            match anchor {
+                Some(position) => {
+                    number(&mut bytes, 1);
+                    number(&mut bytes, position.x);
+                    number(&mut bytes, position.y);
+                }
+                None => number(&mut bytes, 0),
+            }
+        }
+
+        for lines in [&self.removed, &self.inserted] {
+            number(&mut bytes, lines.len());
+
+            for line in lines {
+                number(&mut bytes, line.len());
+                bytes.extend_from_slice(line.as_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    fn decode(mut bytes: &[u8]) -> Option<Self> {
+        let bytes = &mut bytes;
+        let at = number(bytes)?;
+        let cursor_before = position(bytes)?;
+        let cursor_after = position(bytes)?;
+        let anchor_before = anchor(bytes)?;
+        let anchor_after = anchor(bytes)?;
+
+        Some(Self {
+            at,
+            removed: lines(bytes)?,
+            inserted: lines(bytes)?,
+            cursor_before,
+            cursor_after,
+            anchor_before,
+            anchor_after,
+        })
+    }
+}
+
+/// Reads the number at the start of `bytes`, which `Edit::encode` writes
+/// in 8 bytes.
+fn number(bytes: &mut &[u8]) -> Option<usize> {
+    let (value, rest) = bytes.split_first_chunk::<8>()?;
+
+    *bytes = rest;
+    usize::try_from(u64::from_le_bytes(*value)).ok()
+}
+
+fn position(bytes: &mut &[u8]) -> Option<Position> {
+    Some(Position {
+        x: number(bytes)?,
+        y: number(bytes)?,
+    })
+}
+
+fn anchor(bytes: &mut &[u8]) -> Option<Option<Position>> {
+    match number(bytes)? {
+        0 => Some(None),
+        _ => position(bytes).map(Some),
+    }
+}
+
+fn lines(bytes: &mut &[u8]) -> Option<Vec<String>> {
+    (0..number(bytes)?)
+        .map(|_| {
+            let len = number(bytes)?;
+            let line = bytes.get(..len)?.to_vec();
+
+            *bytes = &bytes[len..];
+            String::from_utf8(line).ok()
+        })
+        .collect()
+}
+
+/// How much of the history is kept in memory. Past either limit the oldest
+/// steps are dropped, or written to a temporary file with `spill`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UndoLimit {
+    /// The number of steps, or 0 for no limit.
+    pub steps: usize,
+    /// The approximate size of the steps in bytes, or 0 for no limit.
+    pub bytes: usize,
+    pub spill: bool,
+}
+
+impl Default for UndoLimit {
+    fn default() -> Self {
+        Self {
+            steps: 1000,
+            bytes: 64 * 1024 * 1024,
+            spill: false,
+        }
+    }
+}
+
+/// The oldest undo steps, written to a temporary file that is removed
+/// with the history.
+struct Spill {
+    path: PathBuf,
+    file: File,
+    /// Where each step starts in the file, the newest last.
+    offsets: Vec<u64>,
+}
+
+impl Spill {
+    /// Creates a new spill file only the user can read, rather than opening
+    /// one another user put in the temporary directory first.
+    fn create() -> Result<Self, IOError> {
+        let (path, file) = temp::create_private("revise-undo")?;
+
+        Ok(Self {
+            path,
+            file,
+            offsets: Vec::new(),
+        })
+    }
+
+    fn push(&mut self, edit: &Edit) -> Result<(), IOError> {
+        let offset = self.file.seek(SeekFrom::End(0))?;
+
+        self.file.write_all(&edit.encode())?;
+        self.offsets.push(offset);
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Option<Edit>, IOError> {
+        let Some(offset) = self.offsets.pop() else {
+            return Ok(None);
+        };
+        let mut bytes = Vec::new();
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_to_end(&mut bytes)?;
+        self.file.set_len(offset)?;
+
+        Edit::decode(&bytes)
+            .map(Some)
+            .ok_or_else(|| IOError::new(ErrorKind::InvalidData, "corrupt undo file"))
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    pub limit: UndoLimit,
+    /// The size of the steps kept in memory.
+    bytes: usize,
+    spill: Option<Spill>,
+}
+
+impl History {
+    pub fn push(&mut self, edit: Edit) {
+        self.bytes += edit.size();
+        self.undo.push(edit);
+        self.bytes -= self.redo.drain(..).map(|edit| edit.size()).sum::<usize>();
+        self.trim();
+    }
+
+    pub fn undo(&mut self) -> Option<&Edit> {
+        let edit = match self.undo.pop() {
+            Some(edit) => edit,
+            None => {
+                let edit = self.spill.as_mut()?.pop().ok().flatten();
+
+                if edit.is_none() {
+                    self.spill = None;
+                }
+
+                let edit = edit?;
+
+                self.bytes += edit.size();
+                edit
+            }
+        };
+
+        self.redo.push(edit);
+        self.redo.last()
+    }
+
+    pub fn redo(&mut self) -> Option<&Edit> {
+        let edit = self.redo.pop()?;
+
+        self.undo.push(edit);
+        self.trim();
+        self.undo.last()
+    }
+
+    /// Drops or spills the oldest undo steps until those in memory fit in
+    /// the limit.
+    fn trim(&mut self) {
+        let UndoLimit {
+            steps,
+            bytes,
+            spill,
+        } = self.limit;
+        let mut size = self.bytes;
+        // The newest step is kept whatever its size.
+        let count = self
+            .undo
+            .iter()
+            .take(self.undo.len().saturating_sub(1))
+            .enumerate()
+            .take_while(|(index, edit)| {
+                let over =
+                    (steps > 0 && self.undo.len() - index > steps) || (bytes > 0 && size > bytes);
+
+                if over {
+                    size -= edit.size();
+                }
+
+                over
+            })
+            .count();
+
+        if count == 0 {
+            return;
+        }
+
+        for edit in self.undo.drain(..count) {
+            self.bytes -= edit.size();
+
+            if !spill {
+                continue;
+            }
+
+            if self.spill.is_none() {
+                self.spill = Spill::create().ok();
+            }
+
+            // An edit that cannot be written is lost, and with it every
+            // older one.
+            if let Some(file) = &mut self.spill {
+                if file.push(&edit).is_err() {
+                    self.spill = None;
+                }
+            }
+        }
+
+        if !spill {
+            self.spill = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+
+    fn edit(line: &str) -> Edit {
+        Edit {
+            at: 1,
+            removed: vec![String::new()],
+            inserted: vec![line.to_owned(), "second".to_owned()],
+            cursor_before: Position { x: 0, y: 1 },
+            cursor_after: Position { x: 2, y: 2 },
+            anchor_before: None,
+            anchor_after: Some(Position { x: 3, y: 0 }),
+        }
+    }
+
+    /// Returns how many steps can be undone, counting those on disk.
+    fn steps(history: &History) -> usize {
+        history.undo.len()
+            + history
+                .spill
+                .as_ref()
+                .map_or(0, |spill| spill.offsets.len())
+    }
+
+    fn undo_all(history: &mut History) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        while let Some(edit) = history.undo() {
+            lines.push(edit.inserted[0].clone());
+        }
+
+        lines
+    }
+
+    #[test]
+    fn test_encode() {
+        let edit = Edit::decode(&edit("ünïcode").encode()).unwrap();
+
+        assert_eq!(edit.at, 1);
+        assert_eq!(edit.removed, vec![String::new()]);
+        assert_eq!(edit.inserted, vec!["ünïcode", "second"]);
+        assert_eq!(edit.cursor_after, Position { x: 2, y: 2 });
+        assert_eq!(edit.anchor_before, None);
+        assert_eq!(edit.anchor_after, Some(Position { x: 3, y: 0 }));
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut history = History {
+            limit: UndoLimit {
+                steps: 2,
+                bytes: 0,
+                spill: false,
+            },
+            ..History::default()
+        };
+
+        for line in ["a", "b", "c"] {
+            history.push(edit(line));
+        }
+
+        assert_eq!(steps(&history), 2);
+        assert_eq!(undo_all(&mut history), vec!["c", "b"]);
+
+        history.limit.bytes = edit("a").size() * 2;
+
+        for line in ["d", "e", "f"] {
+            history.redo();
+            history.push(edit(line));
+        }
+
+        assert_eq!(steps(&history), 2);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn test_spill() {
+        let mut history = History {
+            limit: UndoLimit {
+                steps: 1,
+                bytes: 0,
+                spill: true,
+            },
+            ..History::default()
+        };
+
+        for line in ["a", "b", "c"] {
+            history.push(edit(line));
+        }
+
+        let path = history.spill.as_ref().unwrap().path.clone();
+
+        assert_eq!(steps(&history), 3);
+        assert_eq!(history.undo.len(), 1);
+        assert_eq!(undo_all(&mut history), vec!["c", "b", "a"]);
+        assert_eq!(history.redo().unwrap().inserted[0], "a");
+        assert_eq!(history.redo().unwrap().inserted[0], "b");
+        assert_eq!(history.undo.len(), 1);
+
+        drop(history);
+
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,86 @@
+//! Callbacks an embedder registers to be told what happens in the editor,
+//! e.g. to log edits or to run a tool after saving.
+
+use crate::terminal::catch_panic;
+use crate::Position;
+
+use std::error::Error;
+
+/// Something that happened in the editor, passed to every hook.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HookEvent<'a> {
+    /// A file was read into a buffer.
+    Opened(&'a str),
+    /// The document is about to be written to the file.
+    BeforeSave(&'a str),
+    /// The document was written to the file.
+    AfterSave(&'a str),
+    /// The cursor moved to a new position.
+    CursorMoved(&'a Position),
+    /// The text of the document changed.
+    TextChanged,
+}
+
+pub type Hook = Box<dyn Fn(&HookEvent) -> Result<(), Box<dyn Error>>>;
+
+#[derive(Default)]
+pub struct Hooks {
+    hooks: Vec<Hook>,
+}
+
+impl Hooks {
+    pub fn add(&mut self, hook: Hook) {
+        self.hooks.push(hook);
+    }
+
+    /// Calls every hook with `event`, returning the errors of the ones that
+    /// failed or, when panics unwind, panicked. The other hooks still run.
+    pub fn fire(&self, event: &HookEvent) -> Vec<String> {
+        self.hooks
+            .iter()
+            .filter_map(|hook| match catch_panic(|| hook(event)) {
+                Ok(Ok(())) => None,
+                Ok(Err(err)) => Some(err.to_string()),
+                Err(panic) => Some(panic),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod hooks_tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_fire() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut hooks = Hooks::default();
+        let log = Rc::clone(&seen);
+
+        hooks.add(Box::new(move |event| {
+            log.borrow_mut().push(format!("{event:?}"));
+            Ok(())
+        }));
+        hooks.add(Box::new(|event| match event {
+            HookEvent::BeforeSave(_) => Err("not allowed".into()),
+            _ => Ok(()),
+        }));
+        hooks.add(Box::new(|event| match event {
+            HookEvent::AfterSave(name) => panic!("cannot handle {name}"),
+            _ => Ok(()),
+        }));
+
+        assert!(hooks.fire(&HookEvent::TextChanged).is_empty());
+        assert_eq!(hooks.fire(&HookEvent::BeforeSave("a.rs")), ["not allowed"]);
+        assert_eq!(
+            hooks.fire(&HookEvent::AfterSave("a.rs")),
+            ["cannot handle a.rs"]
+        );
+        assert_eq!(
+            *seen.borrow(),
+            ["TextChanged", "BeforeSave(\"a.rs\")", "AfterSave(\"a.rs\")"]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::Position;
+
+const CAPACITY: usize = 100;
+
+/// Positions the cursor jumped away from, walked back and forth like the
+/// history of a browser.
+#[derive(Default)]
+pub struct JumpList {
+    positions: Vec<Position>,
+    index: usize,
+}
+
+impl JumpList {
+    /// Records `from` before a jump, forgetting the positions that were
+    /// walked back over.
+    pub fn push(&mut self, from: Position) {
+        self.positions
+            .truncate(self.index.saturating_add(1).min(self.positions.len()));
+
+        if self.positions.last() != Some(&from) {
+            self.positions.push(from);
+        }
+
+        if self.positions.len() > CAPACITY {
+            self.positions.remove(0);
+        }
+
+        self.index = self.positions.len();
+    }
+
+    /// Steps back from `current`, which is kept to step forward to again.
+    pub fn back(&mut self, current: &Position) -> Option<Position> {
+        if self.positions.is_empty() {
+            return None;
+        }
+
+        if self.index == self.positions.len() {
+            if self.positions.last() != Some(current) {
+                self.positions.push(current.clone());
+            }
+
+            self.index = self.positions.len() - 1;
+        }
+
+        self.index = self.index.checked_sub(1)?;
+        self.positions.get(self.index).cloned()
+    }
+
+    pub fn forward(&mut self) -> Option<Position> {
+        if self.index.saturating_add(1) >= self.positions.len() {
+            return None;
+        }
+
+        self.index += 1;
+        self.positions.get(self.index).cloned()
+    }
+}
+
+#[cfg(test)]
+mod jump_list_tests {
+    use super::*;
+
+    fn line(y: usize) -> Position {
+        Position { x: 0, y }
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut jumps = JumpList::default();
+
+        assert_eq!(jumps.back(&line(0)), None);
+
+        jumps.push(line(1));
+        jumps.push(line(1));
+        jumps.push(line(5));
+
+        assert_eq!(jumps.back(&line(9)), Some(line(5)));
+        assert_eq!(jumps.back(&line(5)), Some(line(1)));
+        assert_eq!(jumps.back(&line(1)), None);
+        assert_eq!(jumps.forward(), Some(line(5)));
+        assert_eq!(jumps.forward(), Some(line(9)));
+        assert_eq!(jumps.forward(), None);
+
+        jumps.back(&line(9));
+        jumps.push(line(6));
+
+        assert_eq!(jumps.forward(), None);
+        assert_eq!(jumps.back(&line(2)), Some(line(6)));
+        assert_eq!(jumps.back(&line(6)), Some(line(5)));
+    }
+}
@@ -0,0 +1,746 @@
+use crate::Event;
+use crate::Modifiers;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use termion::event::Key;
+use thiserror::Error;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct KeyCombo {
+    key: Key,
+    modifiers: Modifiers,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Quit,
+    Save,
+    Find,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    SelectAll,
+    DuplicateLine,
+    JoinLines,
+    MoveLineUp,
+    MoveLineDown,
+    ToggleComment,
+    MatchBracket,
+    WordCount,
+    Command,
+    Open,
+    NextBuffer,
+    PreviousBuffer,
+    AlternateBuffer,
+    SplitStacked,
+    SplitSideBySide,
+    SwitchPane,
+    SetMark,
+    JumpToMark,
+    JumpBack,
+    JumpForward,
+    CenterView,
+    Help,
+    ToggleReadOnly,
+    Filter,
+    AddCursorAbove,
+    AddCursorBelow,
+    AddNextOccurrence,
+    ToggleFold,
+    FoldAll,
+    UnfoldAll,
+    RecentFiles,
+    FindFile,
+    FindInFiles,
+    GotoReference,
+    ToggleWhitespace,
+    InsertDate,
+    UpperCase,
+    LowerCase,
+    TitleCase,
+    ToggleCase,
+    Increment,
+    Decrement,
+    Revert,
+    AddWord,
+    HalfPageUp,
+    HalfPageDown,
+    ToggleFollow,
+    PasteIndented,
+    ExpandSelection,
+    ShrinkSelection,
+    RecordMacro,
+    ReplayMacro,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum KeymapError {
+    #[error("cannot parse key binding: {0}")]
+    InvalidKey(String),
+    #[error("unknown action for {0}: {1}")]
+    UnknownAction(String, String),
+    #[error("conflicting bindings for {0}")]
+    Conflict(String),
+}
+
+/// A binding is either a single key combination or a two-key chord such as
+/// `Ctrl-K W`, whose second key is matched regardless of case.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Sequence {
+    Single(KeyCombo),
+    Chord(KeyCombo, KeyCombo),
+}
+
+pub struct Keymap {
+    bindings: HashMap<KeyCombo, Action>,
+    chords: HashMap<KeyCombo, HashMap<KeyCombo, Action>>,
+}
+
+const DEFAULT_BINDINGS: [(&str, Action); 59] = [
+    ("Ctrl-Q", Action::Quit),
+    ("Ctrl-S", Action::Save),
+    ("Ctrl-F", Action::Find),
+    ("Ctrl-Z", Action::Undo),
+    ("Ctrl-Y", Action::Redo),
+    ("Ctrl-C", Action::Copy),
+    ("Ctrl-X", Action::Cut),
+    ("Ctrl-V", Action::Paste),
+    ("Ctrl-A", Action::SelectAll),
+    ("Ctrl-D", Action::DuplicateLine),
+    ("Ctrl-J", Action::JoinLines),
+    ("Alt-Up", Action::MoveLineUp),
+    ("Alt-Down", Action::MoveLineDown),
+    ("Ctrl-/", Action::ToggleComment),
+    ("Ctrl-]", Action::MatchBracket),
+    ("Ctrl-K W", Action::WordCount),
+    ("Ctrl-E", Action::Command),
+    ("Ctrl-O", Action::Open),
+    ("Ctrl-PageDown", Action::NextBuffer),
+    ("Ctrl-PageUp", Action::PreviousBuffer),
+    ("Ctrl-^", Action::AlternateBuffer),
+    ("Ctrl-K S", Action::SplitStacked),
+    ("Ctrl-K V", Action::SplitSideBySide),
+    ("Ctrl-W", Action::SwitchPane),
+    ("Ctrl-K M", Action::SetMark),
+    ("Ctrl-K '", Action::JumpToMark),
+    ("Alt-Left", Action::JumpBack),
+    ("Alt-Right", Action::JumpForward),
+    ("Ctrl-L", Action::CenterView),
+    ("Ctrl-K ?", Action::Help),
+    ("Ctrl-K R", Action::ToggleReadOnly),
+    ("Ctrl-K !", Action::Filter),
+    ("Ctrl-Alt-Up", Action::AddCursorAbove),
+    ("Ctrl-Alt-Down", Action::AddCursorBelow),
+    ("Alt-n", Action::AddNextOccurrence),
+    ("Ctrl-K Z", Action::ToggleFold),
+    ("Ctrl-K [", Action::FoldAll),
+    ("Ctrl-K ]", Action::UnfoldAll),
+    ("Ctrl-K O", Action::RecentFiles),
+    ("Ctrl-P", Action::FindFile),
+    ("Ctrl-K F", Action::FindInFiles),
+    ("Ctrl-K G", Action::GotoReference),
+    ("Ctrl-K I", Action::ToggleWhitespace),
+    ("Ctrl-K D", Action::InsertDate),
+    ("Ctrl-K U", Action::UpperCase),
+    ("Ctrl-K L", Action::LowerCase),
+    ("Ctrl-K T", Action::TitleCase),
+    ("Ctrl-K ~", Action::ToggleCase),
+    ("Alt-a", Action::Increment),
+    ("Alt-x", Action::Decrement),
+    ("Ctrl-K E", Action::Revert),
+    ("Ctrl-K A", Action::AddWord),
+    ("Alt-u", Action::HalfPageUp),
+    ("Alt-d", Action::HalfPageDown),
+    ("Ctrl-K P", Action::PasteIndented),
+    ("Alt-=", Action::ExpandSelection),
+    ("Alt--", Action::ShrinkSelection),
+    ("Ctrl-K Q", Action::RecordMacro),
+    ("Ctrl-K @", Action::ReplayMacro),
+];
+
+impl KeyCombo {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match *event {
+            Event::Key(key) => Some(Self {
+                key,
+                modifiers: Modifiers::default(),
+            }),
+            Event::Modified(key, modifiers) => Some(Self { key, modifiers }),
+            Event::Mouse(_) | Event::Paste(_) | Event::Resize => None,
+        }
+    }
+
+    /// Describes the second key of a chord, whose case does not matter.
+    fn chord_key(self) -> String {
+        match self.key {
+            Key::Char(c) if c.is_ascii_alphabetic() => c.to_ascii_uppercase().to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    fn fold_case(self) -> Self {
+        match self.key {
+            Key::Char(c) => Self {
+                key: Key::Char(c.to_ascii_lowercase()),
+                ..self
+            },
+            _ => self,
+        }
+    }
+}
+
+impl FromStr for Sequence {
+    type Err = KeymapError;
+
+    fn from_str(descriptor: &str) -> Result<Self, Self::Err> {
+        let mut combos = descriptor.split_whitespace();
+        let first = combos
+            .next()
+            .ok_or_else(|| KeymapError::InvalidKey(descriptor.to_owned()))?
+            .parse()?;
+
+        match (combos.next(), combos.next()) {
+            (None, _) => Ok(Self::Single(first)),
+            (Some(second), None) => Ok(Self::Chord(first, second.parse::<KeyCombo>()?.fold_case())),
+            _ => Err(KeymapError::InvalidKey(descriptor.to_owned())),
+        }
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = KeymapError;
+
+    /// Parses descriptors such as `Ctrl-S`, `Alt-Up` or `Shift-Home`.
+    fn from_str(descriptor: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeymapError::InvalidKey(descriptor.to_owned());
+        let mut modifiers = Modifiers::default();
+        let mut rest = descriptor;
+
+        loop {
+            let lower = rest.to_ascii_lowercase();
+
+            if lower.starts_with("ctrl-") && rest.len() > 5 {
+                modifiers.ctrl = true;
+            } else if lower.starts_with("alt-") && rest.len() > 4 {
+                modifiers.alt = true;
+            } else if lower.starts_with("shift-") && rest.len() > 6 {
+                modifiers.shift = true;
+            } else {
+                break;
+            }
+
+            rest = &rest[lower.find('-').ok_or_else(invalid)? + 1..];
+        }
+
+        let key = match rest.to_ascii_lowercase().as_str() {
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "pageup" => Key::PageUp,
+            "pagedown" => Key::PageDown,
+            "insert" => Key::Insert,
+            "delete" => Key::Delete,
+            "backspace" => Key::Backspace,
+            "esc" => Key::Esc,
+            "tab" if modifiers == Modifiers::SHIFT => {
+                return Ok(Self {
+                    key: Key::BackTab,
+                    modifiers: Modifiers::default(),
+                })
+            }
+            "tab" => Key::Char('\t'),
+            "enter" => Key::Char('\n'),
+            "space" => Key::Char(' '),
+            name if name.len() > 1 && name.starts_with('f') => {
+                Key::F(name[1..].parse().map_err(|_| invalid())?)
+            }
+            _ => {
+                let mut chars = rest.chars();
+                let c = chars.next().ok_or_else(invalid)?;
+
+                if chars.next().is_some() {
+                    return Err(invalid());
+                }
+
+                return char_combo(c, modifiers).ok_or_else(invalid);
+            }
+        };
+
+        Ok(Self { key, modifiers })
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    /// Writes the combination the way it is parsed, e.g. `Ctrl-S` or `Alt-Up`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.ctrl {
+            write!(f, "Ctrl-")?;
+        }
+
+        if self.modifiers.alt {
+            write!(f, "Alt-")?;
+        }
+
+        if self.modifiers.shift {
+            write!(f, "Shift-")?;
+        }
+
+        match self.key {
+            Key::Up => write!(f, "Up"),
+            Key::Down => write!(f, "Down"),
+            Key::Left => write!(f, "Left"),
+            Key::Right => write!(f, "Right"),
+            Key::Home => write!(f, "Home"),
+            Key::End => write!(f, "End"),
+            Key::PageUp => write!(f, "PageUp"),
+            Key::PageDown => write!(f, "PageDown"),
+            Key::Insert => write!(f, "Insert"),
+            Key::Delete => write!(f, "Delete"),
+            Key::Backspace => write!(f, "Backspace"),
+            Key::Esc => write!(f, "Esc"),
+            Key::BackTab => write!(f, "Shift-Tab"),
+            Key::F(n) => write!(f, "F{n}"),
+            Key::Char('\t') => write!(f, "Tab"),
+            Key::Char('\n') => write!(f, "Enter"),
+            Key::Char(' ') => write!(f, "Space"),
+            Key::Char(c) => write!(f, "{c}"),
+            Key::Alt(c) => write!(f, "Alt-{c}"),
+            Key::Ctrl('4') => write!(f, "Ctrl-\\"),
+            Key::Ctrl('5') => write!(f, "Ctrl-]"),
+            Key::Ctrl('6') => write!(f, "Ctrl-^"),
+            Key::Ctrl('7') => write!(f, "Ctrl-/"),
+            Key::Ctrl(c) => write!(f, "Ctrl-{}", c.to_ascii_uppercase()),
+            _ => write!(f, "{:?}", self.key),
+        }
+    }
+}
+
+/// Maps a character with modifiers to the key termion reports for it.
+fn char_combo(c: char, modifiers: Modifiers) -> Option<KeyCombo> {
+    let plain = Modifiers::default();
+    let key = match (modifiers.ctrl, modifiers.alt, modifiers.shift) {
+        (true, false, false) => match c {
+            'a'..='z' | 'A'..='Z' => Key::Ctrl(c.to_ascii_lowercase()),
+            '\\' => Key::Ctrl('4'),
+            ']' => Key::Ctrl('5'),
+            '^' | '6' => Key::Ctrl('6'),
+            '/' | '_' | '7' => Key::Ctrl('7'),
+            _ => return None,
+        },
+        (false, true, false) => Key::Alt(c),
+        // Terminals report a shifted letter as the capital one, and other
+        // shifted keys as whatever the keyboard layout types.
+        (false, false, true) if c.is_ascii_alphabetic() => Key::Char(c.to_ascii_uppercase()),
+        (false, false, false) => Key::Char(c),
+        _ => return None,
+    };
+
+    Some(KeyCombo {
+        key,
+        modifiers: plain,
+    })
+}
+
+impl Action {
+    /// Returns `true` if a repeat count runs the action that many times.
+    pub fn is_repeatable(self) -> bool {
+        matches!(
+            self,
+            Self::Undo
+                | Self::Redo
+                | Self::Paste
+                | Self::PasteIndented
+                | Self::DuplicateLine
+                | Self::JoinLines
+                | Self::MoveLineUp
+                | Self::MoveLineDown
+                | Self::NextBuffer
+                | Self::PreviousBuffer
+                | Self::AddCursorAbove
+                | Self::AddCursorBelow
+                | Self::Increment
+                | Self::Decrement
+                | Self::HalfPageUp
+                | Self::HalfPageDown
+        )
+    }
+
+    /// Returns whether the action changes the document, which a read-only
+    /// buffer does not allow.
+    pub fn is_edit(self) -> bool {
+        matches!(
+            self,
+            Self::Undo
+                | Self::Redo
+                | Self::Cut
+                | Self::Paste
+                | Self::PasteIndented
+                | Self::DuplicateLine
+                | Self::JoinLines
+                | Self::MoveLineUp
+                | Self::MoveLineDown
+                | Self::ToggleComment
+                | Self::Filter
+                | Self::InsertDate
+                | Self::UpperCase
+                | Self::LowerCase
+                | Self::TitleCase
+                | Self::ToggleCase
+                | Self::Increment
+                | Self::Decrement
+                | Self::Revert
+        )
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::Save => "save",
+            Self::Find => "find",
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+            Self::Copy => "copy",
+            Self::Cut => "cut",
+            Self::Paste => "paste",
+            Self::SelectAll => "select_all",
+            Self::DuplicateLine => "duplicate_line",
+            Self::JoinLines => "join_lines",
+            Self::MoveLineUp => "move_line_up",
+            Self::MoveLineDown => "move_line_down",
+            Self::ToggleComment => "toggle_comment",
+            Self::MatchBracket => "match_bracket",
+            Self::WordCount => "word_count",
+            Self::Command => "command",
+            Self::Open => "open",
+            Self::NextBuffer => "next_buffer",
+            Self::PreviousBuffer => "previous_buffer",
+            Self::AlternateBuffer => "alternate_buffer",
+            Self::SplitStacked => "split_stacked",
+            Self::SplitSideBySide => "split_side_by_side",
+            Self::SwitchPane => "switch_pane",
+            Self::SetMark => "set_mark",
+            Self::JumpToMark => "jump_to_mark",
+            Self::JumpBack => "jump_back",
+            Self::JumpForward => "jump_forward",
+            Self::CenterView => "center_view",
+            Self::Help => "help",
+            Self::ToggleReadOnly => "toggle_read_only",
+            Self::Filter => "filter",
+            Self::AddCursorAbove => "add_cursor_above",
+            Self::AddCursorBelow => "add_cursor_below",
+            Self::AddNextOccurrence => "add_next_occurrence",
+            Self::ToggleFold => "toggle_fold",
+            Self::FoldAll => "fold_all",
+            Self::UnfoldAll => "unfold_all",
+            Self::RecentFiles => "recent_files",
+            Self::FindFile => "find_file",
+            Self::FindInFiles => "find_in_files",
+            Self::GotoReference => "goto_reference",
+            Self::ToggleWhitespace => "toggle_whitespace",
+            Self::InsertDate => "insert_date",
+            Self::UpperCase => "upper_case",
+            Self::LowerCase => "lower_case",
+            Self::TitleCase => "title_case",
+            Self::ToggleCase => "toggle_case",
+            Self::Increment => "increment",
+            Self::Decrement => "decrement",
+            Self::Revert => "revert",
+            Self::AddWord => "add_word",
+            Self::HalfPageUp => "half_page_up",
+            Self::HalfPageDown => "half_page_down",
+            Self::ToggleFollow => "toggle_follow",
+            Self::PasteIndented => "paste_indented",
+            Self::ExpandSelection => "expand_selection",
+            Self::ShrinkSelection => "shrink_selection",
+            Self::RecordMacro => "record_macro",
+            Self::ReplayMacro => "replay_macro",
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match name {
+            "quit" => Self::Quit,
+            "save" => Self::Save,
+            "find" => Self::Find,
+            "undo" => Self::Undo,
+            "redo" => Self::Redo,
+            "copy" => Self::Copy,
+            "cut" => Self::Cut,
+            "paste" => Self::Paste,
+            "select_all" => Self::SelectAll,
+            "duplicate_line" => Self::DuplicateLine,
+            "join_lines" => Self::JoinLines,
+            "move_line_up" => Self::MoveLineUp,
+            "move_line_down" => Self::MoveLineDown,
+            "toggle_comment" => Self::ToggleComment,
+            "match_bracket" => Self::MatchBracket,
+            "word_count" => Self::WordCount,
+            "command" => Self::Command,
+            "open" => Self::Open,
+            "next_buffer" => Self::NextBuffer,
+            "previous_buffer" => Self::PreviousBuffer,
+            "alternate_buffer" => Self::AlternateBuffer,
+            "split_stacked" => Self::SplitStacked,
+            "split_side_by_side" => Self::SplitSideBySide,
+            "switch_pane" => Self::SwitchPane,
+            "set_mark" => Self::SetMark,
+            "jump_to_mark" => Self::JumpToMark,
+            "jump_back" => Self::JumpBack,
+            "jump_forward" => Self::JumpForward,
+            "center_view" => Self::CenterView,
+            "help" => Self::Help,
+            "toggle_read_only" => Self::ToggleReadOnly,
+            "filter" => Self::Filter,
+            "add_cursor_above" => Self::AddCursorAbove,
+            "add_cursor_below" => Self::AddCursorBelow,
+            "add_next_occurrence" => Self::AddNextOccurrence,
+            "toggle_fold" => Self::ToggleFold,
+            "fold_all" => Self::FoldAll,
+            "unfold_all" => Self::UnfoldAll,
+            "recent_files" => Self::RecentFiles,
+            "find_file" => Self::FindFile,
+            "find_in_files" => Self::FindInFiles,
+            "goto_reference" => Self::GotoReference,
+            "toggle_whitespace" => Self::ToggleWhitespace,
+            "insert_date" => Self::InsertDate,
+            "upper_case" => Self::UpperCase,
+            "lower_case" => Self::LowerCase,
+            "title_case" => Self::TitleCase,
+            "toggle_case" => Self::ToggleCase,
+            "increment" => Self::Increment,
+            "decrement" => Self::Decrement,
+            "revert" => Self::Revert,
+            "add_word" => Self::AddWord,
+            "half_page_up" => Self::HalfPageUp,
+            "half_page_down" => Self::HalfPageDown,
+            "toggle_follow" => Self::ToggleFollow,
+            "paste_indented" => Self::PasteIndented,
+            "expand_selection" => Self::ExpandSelection,
+            "shrink_selection" => Self::ShrinkSelection,
+            "record_macro" => Self::RecordMacro,
+            "replay_macro" => Self::ReplayMacro,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+            chords: HashMap::new(),
+        };
+
+        for (descriptor, action) in DEFAULT_BINDINGS {
+            if let Ok(sequence) = descriptor.parse() {
+                keymap.bind(sequence, action);
+            }
+        }
+
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Builds the default keymap with `overrides` applied on top of it.
+    /// Bindings that cannot be parsed or conflict are skipped and returned
+    /// as errors.
+    pub fn new(overrides: &HashMap<String, String>) -> (Self, Vec<KeymapError>) {
+        let mut keymap = Self::default();
+        let mut errors = Vec::new();
+        let mut bound: HashMap<Sequence, &str> = HashMap::new();
+        let mut descriptors: Vec<&String> = overrides.keys().collect();
+
+        descriptors.sort();
+
+        for descriptor in descriptors {
+            let name = &overrides[descriptor];
+            let sequence = match descriptor.parse::<Sequence>() {
+                Ok(sequence) => sequence,
+                Err(err) => {
+                    errors.push(err);
+                    continue;
+                }
+            };
+            let Ok(action) = name.parse::<Action>() else {
+                errors.push(KeymapError::UnknownAction(descriptor.clone(), name.clone()));
+                continue;
+            };
+
+            if bound
+                .get(&sequence)
+                .is_some_and(|previous| previous != name)
+            {
+                errors.push(KeymapError::Conflict(descriptor.clone()));
+                continue;
+            }
+
+            bound.insert(sequence, name);
+            keymap.bind(sequence, action);
+        }
+
+        (keymap, errors)
+    }
+
+    pub fn action(&self, event: &Event) -> Option<Action> {
+        self.bindings.get(&KeyCombo::from_event(event)?).copied()
+    }
+
+    /// Returns the key combination of `event` if it starts a chord.
+    pub fn prefix(&self, event: &Event) -> Option<KeyCombo> {
+        KeyCombo::from_event(event).filter(|combo| self.chords.contains_key(combo))
+    }
+
+    /// Returns the action bound to `event` following the chord `prefix`.
+    pub fn chord_action(&self, prefix: KeyCombo, event: &Event) -> Option<Action> {
+        let combo = KeyCombo::from_event(event)?.fold_case();
+
+        self.chords.get(&prefix)?.get(&combo).copied()
+    }
+
+    /// Returns every binding as its descriptor and action, ordered by the
+    /// name of the action.
+    pub fn bindings(&self) -> Vec<(String, Action)> {
+        let chords = self.chords.iter().flat_map(|(prefix, combos)| {
+            combos
+                .iter()
+                .map(move |(combo, action)| (format!("{prefix} {}", combo.chord_key()), *action))
+        });
+        let mut bindings: Vec<(String, Action)> = self
+            .bindings
+            .iter()
+            .map(|(combo, action)| (combo.to_string(), *action))
+            .chain(chords)
+            .collect();
+
+        bindings.sort_by(|a, b| a.1.name().cmp(b.1.name()).then_with(|| a.0.cmp(&b.0)));
+        bindings
+    }
+
+    fn bind(&mut self, sequence: Sequence, action: Action) {
+        match sequence {
+            Sequence::Single(combo) => {
+                self.bindings.insert(combo, action);
+            }
+            Sequence::Chord(prefix, combo) => {
+                self.chords.entry(prefix).or_default().insert(combo, action);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod keymap_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_combo() {
+        assert_eq!(
+            "Ctrl-S".parse(),
+            Ok(KeyCombo {
+                key: Key::Ctrl('s'),
+                modifiers: Modifiers::default(),
+            })
+        );
+        assert_eq!(
+            "alt-up".parse(),
+            Ok(KeyCombo {
+                key: Key::Up,
+                modifiers: Modifiers::ALT,
+            })
+        );
+        assert_eq!(
+            "Ctrl-/".parse::<KeyCombo>().map(|combo| combo.key),
+            Ok(Key::Ctrl('7'))
+        );
+        assert_eq!(
+            "Alt-j".parse::<KeyCombo>().map(|combo| combo.key),
+            Ok(Key::Alt('j'))
+        );
+        assert_eq!(
+            "Shift-a".parse::<KeyCombo>().map(|combo| combo.key),
+            Ok(Key::Char('A'))
+        );
+        assert!("Shift-1".parse::<KeyCombo>().is_err());
+        assert!("Ctrl-".parse::<KeyCombo>().is_err());
+        assert!("Hyper-X".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn test_keymap() {
+        let mut overrides = HashMap::new();
+
+        overrides.insert("Ctrl-W".to_owned(), "save".to_owned());
+        overrides.insert("ctrl-w".to_owned(), "quit".to_owned());
+        overrides.insert("Ctrl-E".to_owned(), "explode".to_owned());
+
+        let (keymap, errors) = Keymap::new(&overrides);
+
+        assert_eq!(
+            keymap.action(&Event::Key(Key::Ctrl('w'))),
+            Some(Action::Save)
+        );
+        assert_eq!(
+            keymap.action(&Event::Key(Key::Ctrl('q'))),
+            Some(Action::Quit)
+        );
+        assert_eq!(errors.len(), 2);
+        assert!(errors.contains(&KeymapError::Conflict("ctrl-w".to_owned())));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let keymap = Keymap::default();
+        let bindings = keymap.bindings();
+
+        assert_eq!(bindings.len(), DEFAULT_BINDINGS.len());
+        assert!(bindings.contains(&("Ctrl-/".to_owned(), Action::ToggleComment)));
+        assert!(bindings.contains(&("Alt-Up".to_owned(), Action::MoveLineUp)));
+        assert!(bindings.contains(&("Ctrl-K W".to_owned(), Action::WordCount)));
+        assert!(bindings.contains(&("Ctrl-K ?".to_owned(), Action::Help)));
+        assert!(bindings.contains(&("Ctrl-^".to_owned(), Action::AlternateBuffer)));
+        assert_eq!(bindings[0].1, Action::AddCursorAbove);
+
+        for (descriptor, action) in DEFAULT_BINDINGS {
+            assert_eq!(action.name().parse(), Ok(action));
+            assert!(bindings.contains(&(descriptor.to_owned(), action)));
+        }
+    }
+
+    #[test]
+    fn test_chords() {
+        let mut overrides = HashMap::new();
+
+        overrides.insert("Ctrl-K s".to_owned(), "save".to_owned());
+        overrides.insert("Ctrl-K s x".to_owned(), "quit".to_owned());
+
+        let (keymap, errors) = Keymap::new(&overrides);
+        let prefix = keymap.prefix(&Event::Key(Key::Ctrl('k'))).unwrap();
+
+        assert_eq!(
+            keymap.chord_action(prefix, &Event::Key(Key::Char('S'))),
+            Some(Action::Save)
+        );
+        assert_eq!(
+            keymap.chord_action(prefix, &Event::Key(Key::Char('w'))),
+            Some(Action::WordCount)
+        );
+        assert_eq!(
+            keymap.chord_action(prefix, &Event::Key(Key::Char('j'))),
+            None
+        );
+        assert_eq!(keymap.prefix(&Event::Key(Key::Ctrl('s'))), None);
+        assert_eq!(errors.len(), 1);
+    }
+}
@@ -1,93 +1,267 @@
 pub mod rust {
-    pub fn primary_keywords() -> Vec<String> {
-        vec![
-            "as".to_owned(),
-            "break".to_owned(),
-            "const".to_owned(),
-            "continue".to_owned(),
-            "crate".to_owned(),
-            "else".to_owned(),
-            "enum".to_owned(),
-            "extern".to_owned(),
-            "false".to_owned(),
-            "fn".to_owned(),
-            "for".to_owned(),
-            "if".to_owned(),
-            "impl".to_owned(),
-            "in".to_owned(),
-            "let".to_owned(),
-            "loop".to_owned(),
-            "match".to_owned(),
-            "mod".to_owned(),
-            "move".to_owned(),
-            "mut".to_owned(),
-            "pub".to_owned(),
-            "ref".to_owned(),
-            "return".to_owned(),
-            "self".to_owned(),
-            "Self".to_owned(),
-            "static".to_owned(),
-            "struct".to_owned(),
-            "super".to_owned(),
-            "trait".to_owned(),
-            "true".to_owned(),
-            "true".to_owned(),
-            "type".to_owned(),
-            "unsafe".to_owned(),
-            "use".to_owned(),
-            "where".to_owned(),
-            "while".to_owned(),
-            "dyn".to_owned(),
-            "abstract".to_owned(),
-            "become".to_owned(),
-            "box".to_owned(),
-            "do".to_owned(),
-            "final".to_owned(),
-            "macro".to_owned(),
-            "override".to_owned(),
-            "priv".to_owned(),
-            "typeof".to_owned(),
-            "unsized".to_owned(),
-            "virtual".to_owned(),
-            "yield".to_owned(),
-            "async".to_owned(),
-            "await".to_owned(),
-            "try".to_owned(),
-        ]
-    }
+    pub const PRIMARY_KEYWORDS: &[&str] = &[
+        "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
+        "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+        "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+        "use", "where", "while", "dyn", "abstract", "become", "box", "do", "final", "macro",
+        "override", "priv", "typeof", "unsized", "virtual", "yield", "async", "await", "try",
+    ];
 
-    pub fn secondary_keywords() -> Vec<String> {
-        vec![
-            "bool".to_owned(),
-            "char".to_owned(),
-            "i8".to_owned(),
-            "i16".to_owned(),
-            "i32".to_owned(),
-            "i64".to_owned(),
-            "isize".to_owned(),
-            "u8".to_owned(),
-            "u16".to_owned(),
-            "u32".to_owned(),
-            "u64".to_owned(),
-            "usize".to_owned(),
-            "f32".to_owned(),
-            "f64".to_owned(),
-            "String".to_owned(),
-            "&str".to_owned(),
-            "Vec".to_owned(),
-            "std".to_owned(),
-            "core".to_owned(),
-            "alloc".to_owned(),
-            "Result".to_owned(),
-            "Box".to_owned(),
-            "Error".to_owned(),
-            "Option".to_owned(),
-            "Default".to_owned(),
-            "Clone".to_owned(),
-            "Copy".to_owned(),
-            "PartialEq".to_owned(),
-            "Debug".to_owned(),
-            "Instant".to_owned(),
-        ]
-    }
+    pub const SECONDARY_KEYWORDS: &[&str] = &[
+        "bool",
+        "char",
+        "i8",
+        "i16",
+        "i32",
+        "i64",
+        "isize",
+        "u8",
+        "u16",
+        "u32",
+        "u64",
+        "usize",
+        "f32",
+        "f64",
+        "String",
+        "&str",
+        "Vec",
+        "std",
+        "core",
+        "alloc",
+        "Result",
+        "Box",
+        "Error",
+        "Option",
+        "Default",
+        "Clone",
+        "Copy",
+        "PartialEq",
+        "Debug",
+        "Instant",
+    ];
+}
+
+pub mod python {
+    pub const PRIMARY_KEYWORDS: &[&str] = &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "False", "finally", "for", "from", "global", "if", "import",
+        "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise", "return", "True",
+        "try", "while", "with", "yield",
+    ];
+
+    pub const SECONDARY_KEYWORDS: &[&str] = &[
+        "bool",
+        "bytes",
+        "dict",
+        "float",
+        "int",
+        "list",
+        "object",
+        "print",
+        "range",
+        "self",
+        "set",
+        "str",
+        "super",
+        "tuple",
+        "type",
+        "len",
+        "isinstance",
+        "Exception",
+    ];
+}
+
+pub mod javascript {
+    pub const PRIMARY_KEYWORDS: &[&str] = &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "debugger",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "false",
+        "finally",
+        "for",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "let",
+        "new",
+        "null",
+        "of",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "true",
+        "try",
+        "typeof",
+        "undefined",
+        "var",
+        "void",
+        "while",
+        "yield",
+    ];
+
+    pub const SECONDARY_KEYWORDS: &[&str] = &[
+        "any",
+        "bigint",
+        "boolean",
+        "enum",
+        "implements",
+        "interface",
+        "keyof",
+        "namespace",
+        "never",
+        "number",
+        "readonly",
+        "string",
+        "symbol",
+        "type",
+        "unknown",
+        "Array",
+        "Map",
+        "Object",
+        "Promise",
+        "Set",
+        "console",
+    ];
+}
+
+pub mod go {
+    pub const PRIMARY_KEYWORDS: &[&str] = &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "false",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "iota",
+        "map",
+        "nil",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "true",
+        "type",
+        "var",
+    ];
+
+    pub const SECONDARY_KEYWORDS: &[&str] = &[
+        "bool",
+        "byte",
+        "complex64",
+        "complex128",
+        "error",
+        "float32",
+        "float64",
+        "int",
+        "int8",
+        "int16",
+        "int32",
+        "int64",
+        "rune",
+        "string",
+        "uint",
+        "uint8",
+        "uint16",
+        "uint32",
+        "uint64",
+        "uintptr",
+        "any",
+        "append",
+        "cap",
+        "close",
+        "copy",
+        "delete",
+        "len",
+        "make",
+        "new",
+        "panic",
+        "print",
+        "println",
+        "recover",
+    ];
+}
+
+pub mod toml {
+    pub const PRIMARY_KEYWORDS: &[&str] = &["true", "false"];
+
+    pub const SECONDARY_KEYWORDS: &[&str] = &[];
+}
+
+pub mod json {
+    pub const PRIMARY_KEYWORDS: &[&str] = &["true", "false", "null"];
+
+    pub const SECONDARY_KEYWORDS: &[&str] = &[];
+}
+
+pub mod shell {
+    pub const PRIMARY_KEYWORDS: &[&str] = &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "function", "in", "return", "local", "export", "readonly", "select", "break", "continue",
+        "exit",
+    ];
+
+    pub const SECONDARY_KEYWORDS: &[&str] = &[
+        "echo", "printf", "cd", "read", "set", "unset", "shift", "source", "test", "eval", "exec",
+        "trap",
+    ];
+}
+
+pub mod dockerfile {
+    pub const PRIMARY_KEYWORDS: &[&str] = &[
+        "FROM",
+        "AS",
+        "RUN",
+        "CMD",
+        "LABEL",
+        "EXPOSE",
+        "ENV",
+        "ADD",
+        "COPY",
+        "ENTRYPOINT",
+        "VOLUME",
+        "USER",
+        "WORKDIR",
+        "ARG",
+        "ONBUILD",
+        "STOPSIGNAL",
+        "HEALTHCHECK",
+        "SHELL",
+    ];
+}
+
+pub mod make {
+    pub const PRIMARY_KEYWORDS: &[&str] = &[
+        "ifeq", "ifneq", "ifdef", "ifndef", "else", "endif", "include", "define", "endef",
+        "export", "unexport", "override", "vpath",
+    ];
 }
@@ -0,0 +1,53 @@
+#![warn(clippy::all)]
+//! Revise is a minimalist terminal text editor. Besides the `revise` binary,
+//! the editor can be embedded or scripted through [`Revise`], e.g. over an
+//! in-memory [`Document`] drawn to a [`TestBackend`].
+mod application;
+mod args;
+mod backend;
+mod case;
+mod clipboard;
+mod clock;
+mod config;
+mod diff;
+mod document;
+mod editorconfig;
+mod encoding;
+mod filetype;
+mod finder;
+mod gap_buffer;
+mod git;
+mod glob;
+mod highlight;
+mod history;
+mod hooks;
+mod jump_list;
+mod keymap;
+mod keywords;
+mod line_index;
+mod palette;
+mod recent;
+mod row;
+mod spelling;
+mod swap;
+mod temp;
+mod terminal;
+mod theme;
+mod width;
+
+pub use application::Position;
+pub use application::Revise;
+pub use application::SearchDirection;
+pub use backend::Backend;
+pub use backend::TestBackend;
+pub use document::Document;
+pub use document::SaveError;
+pub use filetype::FileType;
+pub use filetype::HighlightOptions;
+pub use hooks::HookEvent;
+pub use palette::ColorSupport;
+pub use row::Row;
+pub use terminal::Event;
+pub use terminal::Modifiers;
+pub use terminal::Size;
+pub use terminal::Terminal;
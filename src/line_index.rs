@@ -0,0 +1,288 @@
+//! Line offsets of a file too large to read at once, so that its rows can
+//! be read as they come into view and dropped once they leave it.
+
+use crate::Row;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Error as IOError, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Rows kept loaded before those out of view are dropped.
+const MAX_LOADED_ROWS: usize = 10_000;
+/// Lines a search reads at once.
+const SEARCH_CHUNK_LINES: usize = 4096;
+
+pub struct LineIndex {
+    file: File,
+    /// The start of every line, followed by the end of the file.
+    offsets: Vec<u64>,
+    /// The rows read so far, by index.
+    rows: BTreeMap<usize, Row>,
+    /// What the rows not read yet are shown as.
+    empty: Row,
+}
+
+/// Rows read ahead by a search, which are not kept loaded.
+#[derive(Default)]
+pub struct Chunk {
+    start: usize,
+    rows: Vec<Row>,
+}
+
+impl LineIndex {
+    /// Scans `file` for line breaks, splitting lines the way `str::lines`
+    /// does, and calls `progress` with the number of bytes read so far.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read
+    pub fn new(mut file: File, mut progress: impl FnMut(u64)) -> Result<Self, IOError> {
+        let mut offsets = vec![0];
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let mut position = 0;
+
+        loop {
+            let read = file.read(&mut chunk)?;
+
+            if read == 0 {
+                break;
+            }
+
+            for (index, _) in chunk[..read]
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| **b == b'\n')
+            {
+                offsets.push(position + index as u64 + 1);
+            }
+
+            position += read as u64;
+            progress(position);
+        }
+
+        if offsets.last() != Some(&position) {
+            offsets.push(position);
+        }
+
+        Ok(Self {
+            file,
+            offsets,
+            rows: BTreeMap::new(),
+            empty: Row::default(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Returns the size of the file, in bytes.
+    pub fn byte_len(&self) -> u64 {
+        self.offsets.last().copied().unwrap_or(0)
+    }
+
+    /// Returns whether the file ends without a line break.
+    pub fn missing_final_newline(&self) -> Result<bool, IOError> {
+        if self.len() == 0 {
+            return Ok(false);
+        }
+
+        Ok(self.read(self.len() - 1..self.len())?.last() != Some(&b'\n'))
+    }
+
+    /// Returns the row at `index`, empty if it was not loaded.
+    pub fn row(&self, index: usize) -> Option<&Row> {
+        (index < self.len()).then(|| self.rows.get(&index).unwrap_or(&self.empty))
+    }
+
+    /// Returns the loaded rows before `end`, in order.
+    pub fn loaded_rows_mut(&mut self, end: usize) -> impl Iterator<Item = &mut Row> {
+        self.rows.range_mut(..end).map(|(_, row)| row)
+    }
+
+    /// Reads the lines in `ranges` that are not loaded yet. Once too many
+    /// rows are loaded, the ones outside `ranges` are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read
+    pub fn load(&mut self, ranges: &[Range<usize>]) -> Result<(), IOError> {
+        for range in ranges {
+            let end = range.end.min(self.len());
+            let missing = |index: &usize| !self.rows.contains_key(index);
+            let Some(start) = (range.start..end).find(missing) else {
+                continue;
+            };
+            let end = (start..end).rfind(missing).map_or(end, |last| last + 1);
+
+            for (index, line) in (start..end).zip(self.lines(start..end)?) {
+                self.rows
+                    .entry(index)
+                    .or_insert_with(|| Row::from(line.as_str()));
+            }
+        }
+
+        if self.rows.len() > MAX_LOADED_ROWS {
+            self.rows
+                .retain(|index, _| ranges.iter().any(|range| range.contains(index)));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the row at `index` for a search, reading it with the lines
+    /// around it into `chunk` when it was neither loaded nor read already.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read
+    pub fn search_row<'a>(
+        &'a self,
+        index: usize,
+        chunk: &'a mut Chunk,
+    ) -> Result<&'a Row, IOError> {
+        if let Some(row) = self.rows.get(&index) {
+            return Ok(row);
+        }
+
+        if !(chunk.start..chunk.start + chunk.rows.len()).contains(&index) {
+            let start = index - index % SEARCH_CHUNK_LINES;
+
+            chunk.rows = self
+                .lines(start..start + SEARCH_CHUNK_LINES)?
+                .iter()
+                .map(|line| Row::from(line.as_str()))
+                .collect();
+            chunk.start = start;
+        }
+
+        Ok(chunk.rows.get(index - chunk.start).unwrap_or(&self.empty))
+    }
+
+    /// Reads the lines in `range` without loading them.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the file cannot be read
+    pub fn lines(&self, range: Range<usize>) -> Result<Vec<String>, IOError> {
+        let range = range.start.min(self.len())..range.end.min(self.len());
+        let base = self.offsets[range.start];
+        let bytes = self.read(range.clone())?;
+
+        Ok(range
+            .map(|index| {
+                let start = (self.offsets[index] - base) as usize;
+                let end = (self.offsets[index + 1] - base) as usize;
+                let line = &bytes[start..end];
+                let line = line.strip_suffix(b"\n").unwrap_or(line);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+                String::from_utf8_lossy(line).into_owned()
+            })
+            .collect())
+    }
+
+    fn read(&self, range: Range<usize>) -> Result<Vec<u8>, IOError> {
+        let start = self.offsets[range.start];
+        let mut bytes = vec![0; (self.offsets[range.end] - start) as usize];
+        let mut file = &self.file;
+
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod line_index_tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn index(name: &str, contents: &str) -> LineIndex {
+        let path = env::temp_dir().join(name);
+
+        fs::write(&path, contents).unwrap();
+
+        let index = LineIndex::new(File::open(&path).unwrap(), |_| {}).unwrap();
+
+        fs::remove_file(path).ok();
+        index
+    }
+
+    #[test]
+    fn test_lines() {
+        let lines = index("revise_line_index.txt", "one\r\ntwo\n\nfour");
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines.lines(0..4).unwrap(), vec!["one", "two", "", "four"]);
+        assert_eq!(lines.lines(3..9).unwrap(), vec!["four"]);
+        assert!(lines.missing_final_newline().unwrap());
+        assert_eq!(index("revise_line_index_empty.txt", "").len(), 0);
+
+        let lines = index("revise_line_index_newline.txt", "one\ntwo\n");
+
+        assert_eq!(lines.len(), 2);
+        assert!(!lines.missing_final_newline().unwrap());
+    }
+
+    #[test]
+    fn test_load() {
+        let contents: String = (0..MAX_LOADED_ROWS + 10)
+            .map(|n| format!("{n}\n"))
+            .collect();
+        let mut lines = index("revise_line_index_load.txt", &contents);
+        let row = |lines: &LineIndex, index| lines.row(index).map(|row| row.as_string().clone());
+
+        let visible = 1..3;
+
+        lines.load(&[visible]).unwrap();
+
+        assert_eq!(row(&lines, 2), Some("2".to_owned()));
+        assert_eq!(row(&lines, 3), Some(String::new()));
+        assert_eq!(row(&lines, MAX_LOADED_ROWS + 10), None);
+
+        lines.load(&[0..4, 7..8]).unwrap();
+
+        assert_eq!(row(&lines, 3), Some("3".to_owned()));
+        assert_eq!(row(&lines, 7), Some("7".to_owned()));
+        assert_eq!(lines.rows.len(), 5);
+
+        let visible = 10..MAX_LOADED_ROWS + 10;
+
+        lines.load(&[visible]).unwrap();
+
+        assert_eq!(row(&lines, 3), Some(String::new()));
+        assert_eq!(lines.rows.len(), MAX_LOADED_ROWS);
+    }
+
+    #[test]
+    fn test_search_row() {
+        let contents: String = (0..SEARCH_CHUNK_LINES + 10)
+            .map(|n| format!("{n}\n"))
+            .collect();
+        let mut lines = index("revise_line_index_search.txt", &contents);
+        let mut chunk = Chunk::default();
+
+        let visible = 0..1;
+
+        lines.load(&[visible]).unwrap();
+
+        assert_eq!(lines.search_row(0, &mut chunk).unwrap().as_string(), "0");
+        assert!(chunk.rows.is_empty());
+        assert_eq!(lines.search_row(5, &mut chunk).unwrap().as_string(), "5");
+        assert_eq!(chunk.rows.len(), SEARCH_CHUNK_LINES);
+        assert_eq!(
+            lines
+                .search_row(SEARCH_CHUNK_LINES + 1, &mut chunk)
+                .unwrap()
+                .as_string(),
+            &(SEARCH_CHUNK_LINES + 1).to_string()
+        );
+        assert_eq!(chunk.start, SEARCH_CHUNK_LINES);
+        assert_eq!(lines.rows.len(), 1);
+    }
+}
@@ -0,0 +1,251 @@
+//! Drawing the theme's 24-bit colors on terminals that show fewer, or
+//! without colors when `NO_COLOR` is set.
+
+use std::borrow::Cow;
+use std::env;
+use termion::color::Rgb;
+
+/// The 16 colors of the basic palette as xterm shows them by default, in
+/// the order of their numbers.
+const ANSI_COLORS: [Rgb; 16] = [
+    Rgb(0, 0, 0),
+    Rgb(205, 0, 0),
+    Rgb(0, 205, 0),
+    Rgb(205, 205, 0),
+    Rgb(0, 0, 238),
+    Rgb(205, 0, 205),
+    Rgb(0, 205, 205),
+    Rgb(229, 229, 229),
+    Rgb(127, 127, 127),
+    Rgb(255, 0, 0),
+    Rgb(0, 255, 0),
+    Rgb(255, 255, 0),
+    Rgb(92, 92, 255),
+    Rgb(255, 0, 255),
+    Rgb(0, 255, 255),
+    Rgb(255, 255, 255),
+];
+/// The levels of each channel in the 6×6×6 color cube of 256-color
+/// terminals.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSupport {
+    #[default]
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    /// Text is drawn in the terminal's own colors, keeping styles such as
+    /// the inverted selection.
+    None,
+}
+
+impl ColorSupport {
+    /// Tells the colors the terminal shows from `NO_COLOR`, `COLORTERM`
+    /// and `TERM`.
+    pub fn detect() -> Self {
+        Self::from_env(
+            env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()),
+            &env::var("COLORTERM").unwrap_or_default(),
+            &env::var("TERM").unwrap_or_default(),
+        )
+    }
+
+    fn from_env(no_color: bool, colorterm: &str, term: &str) -> Self {
+        if no_color || term == "dumb" {
+            Self::None
+        } else if matches!(colorterm, "truecolor" | "24bit") || term.ends_with("-direct") {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// Parses the `color_support` setting other than `auto`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" => Some(Self::TrueColor),
+            "256" => Some(Self::Ansi256),
+            "16" => Some(Self::Ansi16),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    /// Rewrites the 24-bit color sequences in `text` into ones the
+    /// terminal shows, or leaves them out.
+    pub fn downgrade(self, text: &str) -> Cow<'_, str> {
+        if self == Self::TrueColor || !text.contains("\x1b[") {
+            return Cow::Borrowed(text);
+        }
+
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find("\x1b[") {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            match parse_rgb(rest) {
+                Some((background, color, len)) => {
+                    result.push_str(&self.sequence(background, color));
+                    rest = &rest[len..];
+                }
+                None => {
+                    result.push_str("\x1b[");
+                    rest = &rest[2..];
+                }
+            }
+        }
+
+        result.push_str(rest);
+        Cow::Owned(result)
+    }
+
+    fn sequence(self, background: bool, color: Rgb) -> String {
+        let layer = if background { 48 } else { 38 };
+
+        match self {
+            Self::TrueColor => {
+                let Rgb(r, g, b) = color;
+
+                format!("\x1b[{layer};2;{r};{g};{b}m")
+            }
+            Self::Ansi256 => format!("\x1b[{layer};5;{}m", to_256(color)),
+            Self::Ansi16 => {
+                let index = to_16(color);
+                let base: u8 = match (background, index < 8) {
+                    (false, true) => 30,
+                    (false, false) => 90 - 8,
+                    (true, true) => 40,
+                    (true, false) => 100 - 8,
+                };
+
+                format!("\x1b[{}m", base + index)
+            }
+            Self::None => String::new(),
+        }
+    }
+}
+
+/// Reads a `38;2;r;g;b` or `48;2;r;g;b` sequence at the start of `text`,
+/// returning whether it sets the background, its color and its length.
+fn parse_rgb(text: &str) -> Option<(bool, Rgb, usize)> {
+    let body = text.strip_prefix("\x1b[")?;
+    let background = match body.get(..5)? {
+        "38;2;" => false,
+        "48;2;" => true,
+        _ => return None,
+    };
+    let end = body.find('m')?;
+    let channels: Vec<u8> = body[5..end]
+        .split(';')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+
+    Some((background, Rgb(r, g, b), end + 3))
+}
+
+fn distance(Rgb(r1, g1, b1): Rgb, Rgb(r2, g2, b2): Rgb) -> u32 {
+    let channel = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+
+    channel(r1, r2) + channel(g1, g2) + channel(b1, b2)
+}
+
+/// Returns the closest color of the 256-color palette, from its color cube
+/// or its gray ramp.
+fn to_256(color: Rgb) -> u8 {
+    let level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|index| CUBE_LEVELS[*index].abs_diff(value))
+            .unwrap_or_default()
+    };
+    let Rgb(r, g, b) = color;
+    let (red, green, blue) = (level(r), level(g), level(b));
+    let cube = Rgb(CUBE_LEVELS[red], CUBE_LEVELS[green], CUBE_LEVELS[blue]);
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray = u8::try_from(average.saturating_sub(3) / 10)
+        .unwrap_or_default()
+        .min(23);
+    let level = 8 + gray * 10;
+    let cube_index = 16 + 36 * red + 6 * green + blue;
+
+    if distance(color, Rgb(level, level, level)) < distance(color, cube) {
+        232 + gray
+    } else {
+        u8::try_from(cube_index).unwrap_or_default()
+    }
+}
+
+/// Returns the number of the closest of the 16 basic colors.
+fn to_16(color: Rgb) -> u8 {
+    (0..ANSI_COLORS.len())
+        .min_by_key(|index| distance(color, ANSI_COLORS[*index]))
+        .and_then(|index| u8::try_from(index).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod palette_tests {
+    use super::*;
+    use termion::color;
+
+    #[test]
+    fn test_from_env() {
+        assert_eq!(
+            ColorSupport::from_env(true, "truecolor", "xterm-256color"),
+            ColorSupport::None
+        );
+        assert_eq!(
+            ColorSupport::from_env(false, "truecolor", "xterm"),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            ColorSupport::from_env(false, "", "screen-256color"),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            ColorSupport::from_env(false, "", "linux"),
+            ColorSupport::Ansi16
+        );
+        assert_eq!(
+            ColorSupport::from_env(false, "", "dumb"),
+            ColorSupport::None
+        );
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let text = format!(
+            "{}a{}b{}c\x1b[1m",
+            color::Fg(Rgb(255, 0, 0)),
+            color::Bg(Rgb(128, 128, 128)),
+            color::Fg(color::Reset)
+        );
+
+        assert_eq!(ColorSupport::TrueColor.downgrade(&text), text);
+        assert_eq!(
+            ColorSupport::Ansi256.downgrade(&text),
+            "\x1b[38;5;196ma\x1b[48;5;244mb\x1b[39mc\x1b[1m"
+        );
+        assert_eq!(
+            ColorSupport::Ansi16.downgrade(&text),
+            "\x1b[91ma\x1b[100mb\x1b[39mc\x1b[1m"
+        );
+        assert_eq!(ColorSupport::None.downgrade(&text), "ab\x1b[39mc\x1b[1m");
+    }
+
+    #[test]
+    fn test_to_256() {
+        assert_eq!(to_256(Rgb(0, 0, 0)), 16);
+        assert_eq!(to_256(Rgb(255, 255, 255)), 231);
+        assert_eq!(to_256(Rgb(0, 135, 255)), 33);
+        assert_eq!(to_256(Rgb(88, 88, 88)), 240);
+    }
+}
@@ -0,0 +1,127 @@
+use crate::config::Config;
+
+use std::fs;
+use std::io::Error as IOError;
+use std::path::PathBuf;
+
+const CAPACITY: usize = 20;
+
+/// The files opened most recently, newest first, kept in `recent` next to
+/// the config file so that they can be reopened in later sessions.
+#[derive(Default)]
+pub struct RecentFiles {
+    path: Option<PathBuf>,
+    files: Vec<String>,
+}
+
+impl RecentFiles {
+    /// Reads the list from the config directory, starting an empty one if
+    /// there is none yet.
+    pub fn load() -> Self {
+        Self::at(Config::path().and_then(|path| Some(path.parent()?.join("recent"))))
+    }
+
+    /// Reads the list kept at `path`, or keeps it in memory if `path` is
+    /// `None`.
+    pub fn at(path: Option<PathBuf>) -> Self {
+        let files = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .take(CAPACITY)
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { path, files }
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Moves `filename` to the front of the list by its absolute path,
+    /// dropping the oldest entries past the capacity.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the list cannot be written
+    pub fn add(&mut self, filename: &str) -> Result<(), IOError> {
+        let filename = fs::canonicalize(filename)
+            .map_or_else(|_| filename.to_owned(), |path| path.display().to_string());
+
+        self.files.retain(|file| *file != filename);
+        self.files.insert(0, filename);
+        self.files.truncate(CAPACITY);
+        self.save()
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Error` if the list cannot be written
+    pub fn remove(&mut self, filename: &str) -> Result<(), IOError> {
+        self.files.retain(|file| file != filename);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), IOError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut contents = self.files.join("\n");
+
+        contents.push('\n');
+        fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod recent_tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_add() {
+        let path = env::temp_dir().join("revise_test_recent");
+        let mut recent = RecentFiles::at(Some(path.clone()));
+
+        fs::remove_file(&path).ok();
+        recent.files.clear();
+
+        for index in 0..CAPACITY + 2 {
+            recent.add(&format!("/no/such/file{index}")).unwrap();
+        }
+
+        recent.add("/no/such/file5").unwrap();
+
+        assert_eq!(recent.files().len(), CAPACITY);
+        assert_eq!(recent.files()[0], "/no/such/file5");
+        assert_eq!(recent.files()[1], format!("/no/such/file{}", CAPACITY + 1));
+        assert_eq!(
+            recent
+                .files()
+                .iter()
+                .filter(|file| *file == "/no/such/file5")
+                .count(),
+            1
+        );
+
+        recent.remove("/no/such/file5").unwrap();
+
+        let reloaded = RecentFiles::at(Some(path.clone()));
+
+        assert_eq!(reloaded.files(), recent.files());
+        assert_eq!(reloaded.files().len(), CAPACITY - 1);
+
+        fs::remove_file(path).ok();
+    }
+}
@@ -1,18 +1,129 @@
 use crate::highlight;
+use crate::spelling::{self, Dictionary};
+use crate::theme::Theme;
+use crate::width::{control_notation, grapheme_width};
 use crate::HighlightOptions;
 use crate::SearchDirection;
 
 use std::cmp;
-use termion::color;
+use std::ops::Range;
+use termion::color::{self, Rgb};
+use termion::style;
 use thiserror::Error;
-use unicode_segmentation::UnicodeSegmentation;
+use unicode_segmentation::{Graphemes, UnicodeSegmentation};
 
+/// Rows with more graphemes, e.g. minified code on a single line, are not
+/// highlighted: only search matches and occurrences are marked, in the part
+/// that is drawn.
+const MAX_HIGHLIGHT_LEN: usize = 20_000;
+
+/// Rows cache their highlighting together with the inputs it was computed
+/// from, so that only edited rows and rows whose incoming multiline state
+/// changed are highlighted again.
 #[derive(Default)]
 pub struct Row {
-    pub is_highlighted: bool,
+    is_highlighted: bool,
+    starts_in: highlight::State,
+    ends_in: highlight::State,
+    /// The search query or symbol whose occurrences are marked as the row
+    /// is drawn.
+    matched_word: Option<highlight::Term>,
     string: String,
-    len: usize,
+    boundaries: Vec<usize>,
+    /// The indices of the graphemes not drawn one column wide, such as tabs
+    /// and wide characters, so that display columns are found without going
+    /// over every grapheme of long rows.
+    wide: Vec<usize>,
     highlight: Vec<highlight::Type>,
+    /// Whether the row ends in whitespace or mixes tabs and spaces in its
+    /// indentation, found while highlighting.
+    whitespace_warning: bool,
+    /// The graphemes of misspelled words, found once the row is highlighted.
+    misspelled: Vec<Range<usize>>,
+    spell_checked: bool,
+    /// The nesting depth the brackets were colored from and the one at the
+    /// end of the row, while they are.
+    bracket_depths: Option<(usize, usize)>,
+    /// Whether the row was changed since the file was opened.
+    edited: bool,
+}
+
+/// Per-frame decorations drawn on top of the syntax highlighting.
+#[derive(Default)]
+pub struct Overlay {
+    pub selection: Option<(usize, usize)>,
+    pub brackets: Vec<(usize, bool)>,
+    /// Columns of the cursors other than the terminal's own, and the
+    /// ranges they select.
+    pub cursors: Vec<usize>,
+    pub selections: Vec<(usize, usize)>,
+    /// The display column past the line-length limit, from which the
+    /// background is colored.
+    pub ruler: Option<usize>,
+    /// The background the row is tinted with across the view, as the
+    /// cursor line or a line differing from the other file of a diff, and
+    /// the display column of the cursor to tint in every row.
+    pub line_background: Option<Rgb>,
+    pub cursor_column: Option<usize>,
+    /// The symbols drawn for a tab and a space, when whitespace is shown.
+    pub whitespace: Option<(&'static str, &'static str)>,
+    /// The indentation width and the symbol drawn at each level of the
+    /// leading whitespace, when indent guides are shown.
+    pub indent_guides: Option<(usize, &'static str)>,
+    /// How many columns apart tab stops are. Tabs take a single column
+    /// when it is not set.
+    pub tab_width: usize,
+    /// Whether the terminal is drawn without colors, in which case search
+    /// matches and occurrences are inverted, as the selection is.
+    pub colorless: bool,
+}
+
+impl Overlay {
+    fn is_selected(&self, index: usize) -> bool {
+        self.selection
+            .is_some_and(|(from, to)| index >= from && index < to)
+            || self.cursors.contains(&index)
+            || self
+                .selections
+                .iter()
+                .any(|(from, to)| index >= *from && index < *to)
+    }
+
+    /// Returns the background of the grapheme at `index`, which takes the
+    /// display columns from `column` on for `width`.
+    fn background(&self, index: usize, column: usize, width: usize, theme: &Theme) -> Option<Rgb> {
+        let bracket = self
+            .brackets
+            .iter()
+            .find(|(bracket, _)| *bracket == index)
+            .map(|(_, matched)| *matched);
+        let on_cursor_column = self
+            .cursor_column
+            .is_some_and(|cursor| (column..column + width.max(1)).contains(&cursor));
+
+        match bracket {
+            Some(true) => Some(theme.matched_bracket),
+            Some(false) => Some(theme.unmatched_bracket),
+            None if on_cursor_column => Some(theme.cursor_column),
+            None => self
+                .ruler
+                .filter(|ruler| column >= *ruler)
+                .map(|_| theme.ruler)
+                .or(self.line_background),
+        }
+    }
+
+    /// Returns the background of the display column `column` past the end
+    /// of the row.
+    fn tail_background(&self, column: usize, theme: &Theme) -> Option<Rgb> {
+        if self.cursor_column == Some(column) {
+            Some(theme.cursor_column)
+        } else if self.ruler == Some(column) {
+            Some(theme.ruler)
+        } else {
+            self.line_background
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -25,47 +136,198 @@ pub enum RowError {
 
 impl From<&str> for Row {
     fn from(slice: &str) -> Self {
-        Self {
-            is_highlighted: false,
+        let mut row = Self {
             string: String::from(slice),
-            len: slice.graphemes(true).count(),
-            highlight: Vec::new(),
-        }
+            ..Self::default()
+        };
+
+        row.update_boundaries();
+        row
     }
 }
 
 impl Row {
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = cmp::min(end, self.string.len());
-        let start = cmp::min(start, end);
+    /// Renders the part of the row between the display columns `start` and
+    /// `end`. A wide grapheme cut by `start` is drawn as blank columns.
+    pub fn render(&self, start: usize, end: usize, overlay: &Overlay, theme: &Theme) -> String {
         let mut result = String::new();
-        let mut current_highlight = &highlight::Type::None;
+        let mut current_highlight = highlight::Type::None;
+        let mut in_selection = false;
+        let mut in_misspelling = false;
+        let mut current_background = None;
+        let indentation = self.indentation();
+        // Drawing starts at the first grapheme shown, or at the start of the
+        // row to count the indentation levels the guides mark.
+        let first = match self.index_at(start, overlay.tab_width) {
+            first if first < indentation && overlay.indent_guides.is_some() => 0,
+            first => first,
+        };
+        let mut column = self.width_to(first, overlay.tab_width);
+        let terms = self.term_highlights(first..self.index_at(end, overlay.tab_width) + 1);
+        // Spaces since the last tab, as each tab starts an indentation level.
+        let mut spaces = 0;
+
+        for index in first..self.len() {
+            let grapheme = self.grapheme(index).unwrap_or_default();
+            let guide = match overlay.indent_guides {
+                Some((width, symbol)) if index < indentation => {
+                    let level = grapheme == "\t" || spaces % width.max(1) == 0;
+
+                    spaces = if grapheme == "\t" { 0 } else { spaces + 1 };
+                    level.then_some(symbol)
+                }
+                _ => None,
+            };
+            let width = display_width(grapheme, column, overlay.tab_width);
+
+            column += width;
+
+            if column <= start {
+                continue;
+            }
+
+            if column > end {
+                break;
+            }
+
+            if column - width < start {
+                result.push_str(&" ".repeat(column - start));
+
+                continue;
+            }
 
-        for (index, grapheme) in self.string[..]
-            .graphemes(true)
-            .enumerate()
-            .skip(start)
-            .take(end - start)
-        {
             if let Some(c) = grapheme.chars().next() {
-                let highlight_type = self.highlight.get(index).unwrap_or(current_highlight);
+                let highlight_type = terms
+                    .get(index - first)
+                    .copied()
+                    .flatten()
+                    .or_else(|| self.highlight.get(index).copied())
+                    .unwrap_or(highlight::Type::None);
+                let selected = overlay.is_selected(index)
+                    || overlay.colorless
+                        && matches!(
+                            highlight_type,
+                            highlight::Type::Match | highlight::Type::Occurrence
+                        );
+                let background = overlay.background(index, column - width, width, theme);
+
+                if background != current_background {
+                    current_background = background;
+
+                    match background {
+                        Some(background) => {
+                            result.push_str(&format!("{}", color::Bg(background)));
+                        }
+                        None => result.push_str(&format!("{}", color::Bg(color::Reset))),
+                    }
+                }
+
+                if selected != in_selection {
+                    in_selection = selected;
+
+                    if selected {
+                        result.push_str(&format!("{}", style::Invert));
+                    } else {
+                        result.push_str(&format!("{}", style::NoInvert));
+                    }
+                }
+
+                let misspelled = self.is_misspelled(index);
+
+                if misspelled != in_misspelling {
+                    in_misspelling = misspelled;
+
+                    if misspelled {
+                        result.push_str(&format!("{}", style::Underline));
+                    } else {
+                        result.push_str(&format!("{}", style::NoUnderline));
+                    }
+                }
 
                 if highlight_type != current_highlight {
                     current_highlight = highlight_type;
 
-                    let start_highlight = format!("{}", color::Fg(highlight_type.to_color()),);
+                    let start_highlight = format!("{}", color::Fg(theme.color(highlight_type)),);
 
                     result.push_str(&start_highlight[..]);
                 }
 
+                if let Some(notation) = control_notation(grapheme) {
+                    result.push_str(&format!(
+                        "{}{notation}{}",
+                        color::Fg(theme.control),
+                        color::Fg(theme.color(current_highlight))
+                    ));
+
+                    continue;
+                }
+
+                match (c, overlay.whitespace, guide) {
+                    ('\t', Some((tab, _)), _) => result.push_str(tab),
+                    (' ', Some((_, space)), _) => result.push_str(space),
+                    (_, None, Some(guide)) => result.push_str(&format!(
+                        "{}{guide}{}",
+                        color::Fg(theme.indent_guide),
+                        color::Fg(theme.color(current_highlight))
+                    )),
+                    ('\t', None, None) => result.push(' '),
+                    _ => result.push_str(grapheme),
+                }
+
                 if c == '\t' {
-                    result.push(' ');
-                } else {
-                    result.push(c);
+                    result.push_str(&" ".repeat(width.saturating_sub(1)));
                 }
             }
         }
 
+        if in_selection {
+            result.push_str(&format!("{}", style::NoInvert));
+        }
+
+        if in_misspelling {
+            result.push_str(&format!("{}", style::NoUnderline));
+        }
+
+        if current_background.is_some() {
+            result.push_str(&format!("{}", color::Bg(color::Reset)));
+        }
+
+        let width = self.width(overlay.tab_width);
+        let mut drawn = width.max(start);
+
+        if overlay.cursors.contains(&self.len()) && (start..end).contains(&width) {
+            result.push_str(&format!("{} {}", style::Invert, style::NoInvert));
+            drawn += 1;
+        }
+
+        // The background past the text is drawn up to the last column
+        // tinted.
+        let tail: Vec<Option<Rgb>> = (drawn..end)
+            .map(|column| overlay.tail_background(column, theme))
+            .collect();
+        let tinted = tail
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |last| last + 1);
+        let mut background = None;
+
+        for column in &tail[..tinted] {
+            if *column != background {
+                background = *column;
+
+                match background {
+                    Some(color) => result.push_str(&format!("{}", color::Bg(color))),
+                    None => result.push_str(&format!("{}", color::Bg(color::Reset))),
+                }
+            }
+
+            result.push(' ');
+        }
+
+        if background.is_some() {
+            result.push_str(&format!("{}", color::Bg(color::Reset)));
+        }
+
         let end_highlight = format!("{}", color::Fg(color::Reset),);
 
         result.push_str(&end_highlight[..]);
@@ -74,37 +336,64 @@ impl Row {
     }
     
     pub fn len(&self) -> usize {
-        self.len
+        self.boundaries.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.len == 0
+        self.boundaries.is_empty()
     }
 
-    pub fn insert(&mut self, at: usize, c: char) {
-        if at >= self.len() {
-            self.string.push(c);
-            self.len += 1;
+    /// Returns the number of terminal columns the row occupies with tab
+    /// stops `tab_width` columns apart.
+    pub fn width(&self, tab_width: usize) -> usize {
+        self.width_to(self.len(), tab_width)
+    }
 
-            return;
+    /// Returns the display column at which the grapheme at `index` starts
+    /// when tabs advance to the next multiple of `tab_width`.
+    pub fn width_to(&self, index: usize, tab_width: usize) -> usize {
+        let index = cmp::min(index, self.len());
+        let mut column = 0;
+        let mut next = 0;
+
+        for &at in self.wide.iter().take_while(|at| **at < index) {
+            column += at - next;
+            column += display_width(self.grapheme(at).unwrap_or_default(), column, tab_width);
+            next = at + 1;
         }
 
-        let mut result: String = String::new();
-        let mut length = 0;
+        column + index - next
+    }
+
+    /// Returns the index of the grapheme covering the display `column`, or
+    /// the length of the row if `column` is past its end.
+    pub fn index_at(&self, column: usize, tab_width: usize) -> usize {
+        let mut width = 0;
+        let mut next = 0;
 
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            length += 1;
+        for &at in &self.wide {
+            // The graphemes up to the next wide one take a column each.
+            if column < width + at - next {
+                return next + column - width;
+            }
+
+            width += at - next;
+            width += display_width(self.grapheme(at).unwrap_or_default(), width, tab_width);
 
-            if index == at {
-                length += 1;
-                result.push(c);
+            if width > column {
+                return at;
             }
 
-            result.push_str(grapheme);
+            next = at + 1;
         }
 
-        self.len = length;
-        self.string = result;
+        cmp::min(next + column - width, self.len())
+    }
+
+    pub fn insert(&mut self, at: usize, c: char) {
+        let byte_index = self.byte_index(at);
+
+        self.replace(byte_index..byte_index, c.encode_utf8(&mut [0; 4]));
     }
 
     pub fn delete(&mut self, at: usize) {
@@ -112,51 +401,27 @@ impl Row {
             return;
         }
 
-        let mut result: String = String::new();
-        let mut length = 0;
-
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            if index != at {
-                length += 1;
-                result.push_str(grapheme);
-            }
-        }
-
-        self.len = length;
-        self.string = result;
+        self.replace(self.byte_index(at)..self.byte_index(at + 1), "");
     }
 
     pub fn append(&mut self, new: &Self) {
-        self.string = format!("{}{}", self.string, new.string);
-        self.len += new.len;
+        let end = self.string.len();
+
+        self.replace(end..end, &new.string);
     }
 
     pub fn split(&mut self, at: usize) -> Self {
-        let mut row: String = String::new();
-        let mut length = 0;
-        let mut splitted_row: String = String::new();
-        let mut splitted_length = 0;
-
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            if index < at {
-                length += 1;
-                row.push_str(grapheme);
-            } else {
-                splitted_length += 1;
-                splitted_row.push_str(grapheme);
-            }
-        }
-
-        self.string = row;
-        self.len = length;
+        let at = cmp::min(at, self.len());
+        let splitted_row = self.string.split_off(self.byte_index(at));
+
+        // What is left keeps its graphemes, which only depend on the text
+        // before each boundary and the character after it.
+        self.boundaries.truncate(at);
+        self.wide
+            .truncate(self.wide.partition_point(|wide| *wide < at));
         self.is_highlighted = false;
 
-        Self {
-            is_highlighted: false,
-            string: splitted_row,
-            len: splitted_length,
-            highlight: Vec::new(),
-        }
+        Self::from(&splitted_row[..])
     }
 
     pub fn as_bytes(&self) -> &[u8] {
@@ -164,7 +429,7 @@ impl Row {
     }
     
     pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
-        if at > self.len {
+        if at > self.len() {
             return None;
         }
 
@@ -174,86 +439,103 @@ impl Row {
             0
         };
         let end = if direction == SearchDirection::Forward {
-            self.len
+            self.len()
         } else {
             at
         };
-        let substring: String = self.string[..]
-            .graphemes(true)
-            .skip(start)
-            .take(end - start)
-            .collect();
-        let matching_byte_index = if direction == SearchDirection::Forward {
-            substring.find(query)
-        } else {
-            substring.rfind(query)
+
+        self.find_between(query, start..end, direction)
+    }
+
+    /// Finds `query` within the graphemes in `range`, searching the bytes
+    /// between them so that long rows are not copied.
+    fn find_between(
+        &self,
+        query: &str,
+        range: Range<usize>,
+        direction: SearchDirection,
+    ) -> Option<usize> {
+        let from = self.byte_index(range.start);
+        let to = self.byte_index(range.end);
+        let haystack = &self.string[from..to];
+        let index_of = |byte_index: usize| match self.boundaries.binary_search(&byte_index) {
+            Ok(index) => Some(index),
+            Err(index) if byte_index == to => Some(index),
+            Err(_) => None,
         };
+        // Only matches of whole graphemes count, so that `e` is not found
+        // in the middle of `e\u{301}`.
+        let aligned = |(offset, _): (usize, &str)| {
+            let index = index_of(from + offset)?;
 
-        if let Some(matching_byte_index) = matching_byte_index {
-            for (grapheme_index, (byte_index, _)) in substring.grapheme_indices(true).enumerate() {
-                if matching_byte_index == byte_index {
-                    return Some(start + grapheme_index);
-                }
-            }
-        }
+            index_of(from + offset + query.len()).map(|_| index)
+        };
 
-        None
+        if direction == SearchDirection::Forward {
+            haystack.match_indices(query).find_map(aligned)
+        } else {
+            haystack.rmatch_indices(query).find_map(aligned)
+        }
     }
 
+    /// Highlights the row, starting inside the construct left open by the
+    /// previous row, and returns the state the next row starts in. The
+    /// occurrences of `term` are only marked as the row is drawn, so that a
+    /// new term does not highlight it again.
     pub fn highlight(
         &mut self,
         opts: &HighlightOptions,
-        word: &Option<String>,
-        start_with_comment: bool,
-    ) -> bool {
-        let len = self.string.len();
-        let chars: Vec<char> = self.string.chars().collect();
+        term: &Option<highlight::Term>,
+        state: highlight::State,
+    ) -> highlight::State {
+        self.matched_word.clone_from(term);
 
-        if self.is_highlighted && word.is_none() {
-            if let Some(hl_type) = self.highlight.last() {
-                if *hl_type == highlight::Type::MultilineComment
-                    && len > 1
-                    && self.string[len - 2..] == *"*/"
-                {
-                    return true;
-                }
-            }
+        if self.is_highlighted && self.starts_in == state {
+            return self.ends_in;
+        }
 
-            return false;
+        if self.len() > MAX_HIGHLIGHT_LEN {
+            self.highlight.clear();
+            self.whitespace_warning = self.string.ends_with(char::is_whitespace);
+            self.is_highlighted = true;
+            self.spell_checked = false;
+            self.bracket_depths = None;
+            self.starts_in = state;
+            self.ends_in = state;
+
+            return state;
         }
 
+        let chars: Vec<char> = self.string.chars().collect();
+
         self.highlight = Vec::new();
         let mut index = 0;
-        let mut in_ml_comment = start_with_comment;
-
-        if in_ml_comment {
-            let closing_index = if let Some(closing_index) = self.string.find("*/") {
-                closing_index + 2
-            } else {
-                chars.len()
-            };
-
-            for _ in 0..closing_index {
-                self.highlight.push(highlight::Type::MultilineComment);
+        let mut ends_in = match state {
+            highlight::State::Comment
+                if !self.close_multiline_comment(&mut index, opts, &chars) =>
+            {
+                state
             }
-
-            index = closing_index;
-        }
+            highlight::State::String(quote) if !self.close_string(&mut index, quote, &chars) => {
+                state
+            }
+            _ => highlight::State::Normal,
+        };
 
         while let Some(c) = chars.get(index) {
-            if self.highlight_multiline_comment(&mut index, opts, *c, &chars) {
-                in_ml_comment = true;
+            if let Some(open) = self
+                .highlight_multiline_comment(&mut index, opts, *c, &chars)
+                .or_else(|| self.highlight_string(&mut index, opts, *c, &chars))
+            {
+                ends_in = open;
 
                 continue;
             }
 
-            in_ml_comment = false;
-
             if self.highlight_char(&mut index, opts, *c, &chars)
                 || self.highlight_comment(&mut index, opts, *c, &chars)
                 || self.highlight_primary_keywords(&mut index, opts, &chars)
                 || self.highlight_secondary_keywords(&mut index, opts, &chars)
-                || self.highlight_string(&mut index, opts, *c, &chars)
                 || self.highlight_number(&mut index, opts, *c, &chars)
             {
                 continue;
@@ -263,42 +545,373 @@ impl Row {
             index += 1;
         }
 
-        self.highlight_match(word);
+        let indentation = &self.string[..self.string.len() - self.string.trim_start().len()];
 
-        if in_ml_comment && &self.string[self.string.len().saturating_sub(2)..] != "*/" {
-            return true;
+        self.whitespace_warning = self.string.ends_with(char::is_whitespace)
+            || (indentation.contains(' ') && indentation.contains('\t'));
+        self.is_highlighted = true;
+        self.spell_checked = false;
+        self.bracket_depths = None;
+        self.starts_in = state;
+        self.ends_in = ends_in;
+
+        ends_in
+    }
+
+    /// Colors the brackets outside strings and comments by how deeply they
+    /// are nested, counting from `depth` at the start of the row, and
+    /// returns the depth at its end. The row has to be highlighted first.
+    pub fn color_brackets(&mut self, depth: usize) -> usize {
+        if let Some((from, to)) = self.bracket_depths {
+            if from == depth {
+                return to;
+            }
         }
 
-        self.is_highlighted = true;
+        let mut nested = depth;
 
-        false
+        for (c, hl_type) in self.string.chars().zip(self.highlight.iter_mut()) {
+            if !matches!(hl_type, highlight::Type::None | highlight::Type::Bracket(_)) {
+                continue;
+            }
+
+            match c {
+                '(' | '[' | '{' => {
+                    *hl_type = highlight::Type::Bracket(nested);
+                    nested += 1;
+                }
+                ')' | ']' | '}' => {
+                    nested = nested.saturating_sub(1);
+                    *hl_type = highlight::Type::Bracket(nested);
+                }
+                _ => (),
+            }
+        }
+
+        self.bracket_depths = Some((depth, nested));
+
+        nested
+    }
+
+    /// Takes the colors given by `color_brackets` away again.
+    pub fn clear_bracket_colors(&mut self) {
+        if self.bracket_depths.take().is_none() {
+            return;
+        }
+
+        for hl_type in &mut self.highlight {
+            if matches!(hl_type, highlight::Type::Bracket(_)) {
+                *hl_type = highlight::Type::None;
+            }
+        }
+    }
+
+    /// Finds the words `dictionary` does not know in the comments and
+    /// strings of the highlighted row, or anywhere in it with `everywhere`.
+    /// They are found again only once the row is highlighted again or
+    /// `recheck_spelling` is called.
+    pub fn check_spelling(&mut self, dictionary: &Dictionary, everywhere: bool) {
+        if self.spell_checked || self.len() > MAX_HIGHLIGHT_LEN {
+            return;
+        }
+
+        self.misspelled = spelling::words(&self.grapheme_chars())
+            .into_iter()
+            .filter(|(start, word)| {
+                (everywhere || !self.is_code(*start)) && !dictionary.knows(word)
+            })
+            .map(|(start, word)| start..start + word.chars().count())
+            .collect();
+        self.spell_checked = true;
+    }
+
+    pub fn recheck_spelling(&mut self) {
+        self.spell_checked = false;
+    }
+
+    pub fn clear_spelling(&mut self) {
+        self.misspelled.clear();
+        self.spell_checked = false;
+    }
+
+    pub fn is_misspelled(&self, index: usize) -> bool {
+        self.misspelled.iter().any(|word| word.contains(&index))
+    }
+
+    pub fn has_whitespace_warning(&self) -> bool {
+        self.whitespace_warning
+    }
+
+    pub fn is_edited(&self) -> bool {
+        self.edited
+    }
+
+    pub fn set_edited(&mut self, edited: bool) {
+        self.edited = edited;
+    }
+
+    /// Forces the row to be highlighted again, e.g. after its file type changed.
+    pub fn unhighlight(&mut self) {
+        self.is_highlighted = false;
     }
 
     pub fn as_string(&self) -> &String {
         &self.string
     }
 
-    fn highlight_match(&mut self, word: &Option<String>) {
-        if let Some(word) = word {
-            if word.is_empty() {
-                return;
+    pub fn graphemes(&self) -> Graphemes<'_> {
+        self.string[..].graphemes(true)
+    }
+
+    pub fn grapheme(&self, index: usize) -> Option<&str> {
+        let start = *self.boundaries.get(index)?;
+
+        Some(&self.string[start..self.byte_index(index + 1)])
+    }
+
+    /// Finds the integer at or after the grapheme `index`, as a decimal
+    /// number with an optional minus sign or with a `0x`, `0o` or `0b`
+    /// prefix, and returns where it lies with its text once `delta` is
+    /// added. Leading zeros and the case of hexadecimal digits are kept.
+    pub fn add_to_number(&self, index: usize, delta: i128) -> Option<(Range<usize>, String)> {
+        let chars = self.grapheme_chars();
+        let mut word_start = index.min(chars.len());
+
+        while word_start > 0 && is_word_char(chars[word_start - 1]) {
+            word_start -= 1;
+        }
+
+        let start = (word_start..chars.len()).find(|&at| {
+            chars[at].is_ascii_digit()
+                && (at == 0 || !is_word_char(chars[at - 1]))
+                && number_len(&chars, at).is_some()
+        })?;
+        let radix = match chars.get(start + 1) {
+            Some('x' | 'X') if chars[start] == '0' => 16,
+            Some('o' | 'O') if chars[start] == '0' => 8,
+            Some('b' | 'B') if chars[start] == '0' => 2,
+            _ => 10,
+        };
+        let prefix = if radix == 10 { 0 } else { 2 };
+        let digits_end = (start + prefix..chars.len())
+            .find(|&at| !chars[at].is_digit(radix))
+            .unwrap_or(chars.len());
+        let digits: String = chars[start + prefix..digits_end].iter().collect();
+
+        if digits.is_empty() {
+            return None;
+        }
+
+        let negative = radix == 10
+            && start > 0
+            && chars[start - 1] == '-'
+            && (start == 1 || !is_word_char(chars[start - 2]));
+        let value = i128::from_str_radix(&digits, radix).ok()?;
+        let value = if negative { -value } else { value };
+        let result = value.checked_add(delta)?;
+        let width = if digits.starts_with('0') {
+            digits.len()
+        } else {
+            0
+        };
+        let base: String = chars[start..start + prefix].iter().collect();
+        let text = match radix {
+            10 => {
+                let sign = if result < 0 { "-" } else { "" };
+
+                format!("{sign}{:0width$}", result.unsigned_abs())
             }
+            _ if result < 0 => return None,
+            16 if digits.chars().any(|c| c.is_ascii_uppercase()) => {
+                format!("{base}{result:0width$X}")
+            }
+            16 => format!("{base}{result:0width$x}"),
+            8 => format!("{base}{result:0width$o}"),
+            _ => format!("{base}{result:0width$b}"),
+        };
+        let start = if negative { start - 1 } else { start };
 
-            let mut index = 0;
+        Some((start..digits_end, text))
+    }
 
-            while let Some(search_match) = self.find(word, index, SearchDirection::Forward) {
-                if let Some(next_index) = search_match.checked_add(word[..].graphemes(true).count())
-                {
-                    for i in index.saturating_add(search_match)..next_index {
-                        self.highlight[i] = highlight::Type::Match;
-                    }
+    /// Returns a character for each grapheme, `\0` for the ones made of
+    /// several.
+    fn grapheme_chars(&self) -> Vec<char> {
+        self.graphemes()
+            .map(|grapheme| {
+                let mut chars = grapheme.chars();
+
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => '\0',
+                }
+            })
+            .collect()
+    }
+
+    /// Returns `false` if the grapheme at `index` was highlighted as part of a
+    /// string, character literal or comment.
+    pub fn is_code(&self, index: usize) -> bool {
+        !matches!(
+            self.highlight.get(index),
+            Some(
+                highlight::Type::String
+                    | highlight::Type::Char
+                    | highlight::Type::Comment
+                    | highlight::Type::MultilineComment
+            )
+        )
+    }
+
+    /// Returns `true` if the grapheme at `index` was highlighted as part of a
+    /// string or character literal.
+    pub fn is_literal(&self, index: usize) -> bool {
+        matches!(
+            self.highlight.get(index),
+            Some(highlight::Type::String | highlight::Type::Char)
+        )
+    }
+
+    /// Returns the graphemes in `start..end` as a new string.
+    pub fn substring(&self, start: usize, end: usize) -> String {
+        self.slice(start, end).to_owned()
+    }
+
+    /// Borrows the graphemes from `start` up to `end`.
+    pub fn slice(&self, start: usize, end: usize) -> &str {
+        let end = self.byte_index(end);
+        let start = cmp::min(self.byte_index(start), end);
+
+        &self.string[start..end]
+    }
+
+    /// Returns the number of leading whitespace graphemes.
+    pub fn indentation(&self) -> usize {
+        self.string[..]
+            .graphemes(true)
+            .take_while(|grapheme| grapheme.chars().all(char::is_whitespace))
+            .count()
+    }
+
+    /// Returns the byte offset of the grapheme at `index`, or the length of
+    /// the string if `index` is past its end.
+    fn byte_index(&self, index: usize) -> usize {
+        self.boundaries
+            .get(index)
+            .copied()
+            .unwrap_or(self.string.len())
+    }
+
+    /// Builds the grapheme boundary index of the whole string.
+    fn update_boundaries(&mut self) {
+        self.boundaries = self
+            .string
+            .grapheme_indices(true)
+            .map(|(index, _)| index)
+            .collect();
+        self.wide = (0..self.len())
+            .filter(|index| !is_narrow(self.grapheme(*index).unwrap_or_default()))
+            .collect();
+    }
+
+    /// Replaces the bytes in `range`, which start and end on grapheme
+    /// boundaries, with `text`. Edits may merge graphemes, e.g. when
+    /// inserting a combining mark, so the graphemes are found again from
+    /// the one before the edit up to where they line up with the old ones,
+    /// and the boundaries after that are shifted.
+    fn replace(&mut self, range: Range<usize>, text: &str) {
+        let edited = self
+            .boundaries
+            .partition_point(|boundary| *boundary <= range.start)
+            .saturating_sub(1);
+        let from = edited.saturating_sub(1);
+        let start = self.byte_index(from);
+        let old_end = self
+            .boundaries
+            .partition_point(|boundary| *boundary < range.end);
+        let new_end = range.start + text.len();
+
+        self.string.replace_range(range.clone(), text);
+
+        let mut found = Vec::new();
+        let mut to = self.boundaries.len();
+
+        for (offset, _) in self.string[start..].grapheme_indices(true) {
+            let boundary = start + offset;
+
+            if boundary >= new_end {
+                let old = boundary - new_end + range.end;
+
+                if let Ok(index) = self.boundaries[old_end..].binary_search(&old) {
+                    to = old_end + index;
 
-                    index = next_index;
-                } else {
                     break;
                 }
             }
+
+            found.push(boundary);
         }
+
+        let removed = to - from;
+        let added = found.len();
+
+        for boundary in &mut self.boundaries[to..] {
+            *boundary = *boundary - range.end + new_end;
+        }
+
+        self.boundaries.splice(from..to, found);
+
+        let first = self.wide.partition_point(|wide| *wide < from);
+        let last = self.wide.partition_point(|wide| *wide < to);
+        let wide: Vec<usize> = (from..from + added)
+            .filter(|index| !is_narrow(self.grapheme(*index).unwrap_or_default()))
+            .collect();
+
+        for index in &mut self.wide[last..] {
+            *index = *index - removed + added;
+        }
+
+        self.wide.splice(first..last, wide);
+        self.is_highlighted = false;
+    }
+
+    /// Marks the search matches, or the occurrences of the symbol under the
+    /// cursor that are not part of a longer word, among the graphemes in
+    /// `range`, leaving the others to the syntax highlighting.
+    fn term_highlights(&self, range: Range<usize>) -> Vec<Option<highlight::Type>> {
+        let (word, hl_type, whole_words) = match &self.matched_word {
+            Some(highlight::Term::Search(word)) => (word, highlight::Type::Match, false),
+            Some(highlight::Term::Symbol(word)) => (word, highlight::Type::Occurrence, true),
+            None => return Vec::new(),
+        };
+        let len = word.graphemes(true).count();
+        let end = cmp::min(range.end, self.len());
+        let mut terms = vec![None; end.saturating_sub(range.start)];
+
+        if len == 0 {
+            return terms;
+        }
+
+        let is_word_at = |index: Option<usize>| {
+            index
+                .and_then(|index| self.grapheme(index))
+                .is_some_and(|grapheme| grapheme.chars().all(is_word_char))
+        };
+        let mut index = range.start.saturating_sub(len - 1);
+        let until = cmp::min(end + len - 1, self.len());
+
+        while let Some(start) = self.find_between(word, index..until, SearchDirection::Forward) {
+            if !whole_words || (!is_word_at(start.checked_sub(1)) && !is_word_at(Some(start + len)))
+            {
+                for at in start.max(range.start)..cmp::min(start + len, end) {
+                    terms[at - range.start] = Some(hl_type);
+                }
+            }
+
+            index = start + len;
+        }
+
+        terms
     }
 
     fn highlight_str(
@@ -368,47 +981,72 @@ impl Row {
         c: char,
         chars: &[char],
     ) -> bool {
-        if opts.comments() && c == '/' && *index < chars.len() {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                if *next_char == '/' {
-                    for _ in *index..chars.len() {
-                        self.highlight.push(highlight::Type::Comment);
-                        *index += 1;
-                    }
-
-                    return true;
+        match opts.comment() {
+            Some(prefix) if prefix.starts_with(c) && matches_at(chars, *index, prefix) => {
+                for _ in *index..chars.len() {
+                    self.highlight.push(highlight::Type::Comment);
+                    *index += 1;
                 }
+
+                true
             }
+            _ => false,
         }
-
-        false
     }
 
+    /// Highlights a string starting at `index` and returns `State::String`
+    /// if it is still open at the end of the row.
     fn highlight_string(
         &mut self,
         index: &mut usize,
         opts: &HighlightOptions,
         c: char,
         chars: &[char],
-    ) -> bool {
-        if opts.strings() && c == '"' {
-            loop {
-                self.highlight.push(highlight::Type::String);
-                *index += 1;
-
-                if let Some(next_char) = chars.get(*index) {
-                    if *next_char == '"' {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
+    ) -> Option<highlight::State> {
+        let is_prefixed = opts.is_string_prefix(c)
+            && index
+                .checked_sub(1)
+                .is_none_or(|previous| is_separator(chars[previous]))
+            && chars
+                .get(*index + 1)
+                .is_some_and(|next| opts.is_string_quote(*next));
+
+        if !is_prefixed && !opts.is_string_quote(c) {
+            return None;
+        }
 
+        if is_prefixed {
             self.highlight.push(highlight::Type::String);
             *index += 1;
+        }
 
-            return true;
+        let quote = chars[*index];
+
+        self.highlight.push(highlight::Type::String);
+        *index += 1;
+
+        if self.close_string(index, quote, chars) {
+            Some(highlight::State::Normal)
+        } else {
+            Some(highlight::State::String(quote))
+        }
+    }
+
+    /// Highlights up to and including the closing `quote`, skipping escaped
+    /// ones. Returns `false` if the string runs past the end of the row.
+    fn close_string(&mut self, index: &mut usize, quote: char, chars: &[char]) -> bool {
+        while let Some(c) = chars.get(*index) {
+            let escaped = *c == '\\' && *index + 1 < chars.len();
+            let step = if escaped { 2 } else { 1 };
+
+            for _ in 0..step {
+                self.highlight.push(highlight::Type::String);
+                *index += 1;
+            }
+
+            if *c == quote {
+                return true;
+            }
         }
 
         false
@@ -421,36 +1059,27 @@ impl Row {
         c: char,
         chars: &[char],
     ) -> bool {
-        if opts.numbers() && c.is_ascii_digit() {
-            if *index > 0 {
-                let prev_char = chars.get(*index + 1);
-
-                match prev_char {
-                    Some(c) => {
-                        if !is_separator(*c) {
-                            return false;
-                        }
-                    }
-                    None => return false,
-                }
-            }
+        if !opts.numbers() || !c.is_ascii_digit() {
+            return false;
+        }
 
-            loop {
-                self.highlight.push(highlight::Type::Number);
-                *index += 1;
-                if let Some(next_char) = chars.get(*index) {
-                    if *next_char != '.' && !next_char.is_ascii_digit() {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-            }
+        if index
+            .checked_sub(1)
+            .is_some_and(|previous| is_word_char(chars[previous]))
+        {
+            return false;
+        }
 
-            return true;
+        let Some(len) = number_len(chars, *index) else {
+            return false;
+        };
+
+        for _ in 0..len {
+            self.highlight.push(highlight::Type::Number);
+            *index += 1;
         }
 
-        false
+        true
     }
 
     fn highlight_keywords(
@@ -518,44 +1147,181 @@ impl Row {
         )
     }
 
+    /// Highlights a block comment starting at `index` and returns
+    /// `State::Comment` if it is still open at the end of the row.
     fn highlight_multiline_comment(
         &mut self,
         index: &mut usize,
         opts: &HighlightOptions,
         c: char,
         chars: &[char],
+    ) -> Option<highlight::State> {
+        let (open, _) = opts.multiline_comment()?;
+
+        if !open.starts_with(c) || !matches_at(chars, *index, open) {
+            return None;
+        }
+
+        for _ in open.chars() {
+            self.highlight.push(highlight::Type::MultilineComment);
+            *index += 1;
+        }
+
+        if self.close_multiline_comment(index, opts, chars) {
+            Some(highlight::State::Normal)
+        } else {
+            Some(highlight::State::Comment)
+        }
+    }
+
+    /// Highlights up to and including the closing delimiter, e.g. `*/`.
+    /// Returns `false` if the comment runs past the end of the row.
+    fn close_multiline_comment(
+        &mut self,
+        index: &mut usize,
+        opts: &HighlightOptions,
+        chars: &[char],
     ) -> bool {
-        if opts.comments() && c == '/' && *index < chars.len() {
-            if let Some(next_char) = chars.get(index.saturating_add(1)) {
-                if *next_char == '*' {
-                    let closing_index =
-                        if let Some(closing_index) = self.string[*index + 2..].find("*/") {
-                            *index + closing_index + 4
-                        } else {
-                            chars.len()
-                        };
-
-                    for _ in *index..closing_index {
-                        self.highlight.push(highlight::Type::MultilineComment);
-                        *index += 1;
-                    }
+        let Some((_, close)) = opts.multiline_comment() else {
+            return true;
+        };
 
-                    return true;
-                }
+        while *index < chars.len() {
+            let step = if matches_at(chars, *index, close) {
+                close.chars().count()
+            } else {
+                1
             };
+
+            for _ in 0..step {
+                self.highlight.push(highlight::Type::MultilineComment);
+                *index += 1;
+            }
+
+            if step > 1 {
+                return true;
+            }
         }
 
         false
     }
 }
 
+const NUMBER_SUFFIXES: [&str; 12] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
 fn is_separator(c: char) -> bool {
     c.is_ascii_punctuation() || c.is_ascii_whitespace()
 }
 
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the length of the numeric literal starting at `start`, e.g.
+/// `0xFF`, `1_000`, `1.5e-3` or `42u8`, or `None` if the word there is not
+/// a number, like `3d`.
+fn number_len(chars: &[char], start: usize) -> Option<usize> {
+    let radix = match chars.get(start + 1) {
+        Some('x' | 'X') if chars[start] == '0' => 16,
+        Some('o' | 'O') if chars[start] == '0' => 8,
+        Some('b' | 'B') if chars[start] == '0' => 2,
+        _ => 10,
+    };
+    let is_digit = |c: Option<&char>| c.is_some_and(char::is_ascii_digit);
+    let mut end = start;
+
+    while let Some(&c) = chars.get(end) {
+        let continues = is_word_char(c)
+            || (c == '.' && radix == 10 && is_digit(chars.get(end + 1)))
+            || (matches!(c, '+' | '-')
+                && radix == 10
+                && matches!(chars.get(end - 1), Some('e' | 'E'))
+                && is_digit(chars.get(end + 1)));
+
+        if !continues {
+            break;
+        }
+
+        end += 1;
+    }
+
+    let token = &chars[start..end];
+    let mut at = if radix == 10 { 0 } else { 2 };
+    let digits = |at: &mut usize| {
+        let from = *at;
+
+        while token
+            .get(*at)
+            .is_some_and(|c| c.is_digit(radix) || *c == '_')
+        {
+            *at += 1;
+        }
+
+        token[from..*at].iter().any(|c| c.is_digit(radix))
+    };
+
+    if !digits(&mut at) {
+        return None;
+    }
+
+    if radix == 10 && token.get(at) == Some(&'.') {
+        at += 1;
+
+        if !digits(&mut at) {
+            return None;
+        }
+    }
+
+    if radix == 10 && matches!(token.get(at), Some('e' | 'E')) {
+        at += 1;
+
+        if matches!(token.get(at), Some('+' | '-')) {
+            at += 1;
+        }
+
+        if !digits(&mut at) {
+            return None;
+        }
+    }
+
+    let suffix: String = token[at..].iter().collect();
+    let is_suffix = suffix.is_empty()
+        || NUMBER_SUFFIXES.contains(&suffix.as_str())
+        || (radix == 10 && matches!(suffix.as_str(), "f32" | "f64"));
+
+    is_suffix.then_some(end - start)
+}
+
+/// Returns `true` if `chars` continue with `s` from `index` on.
+fn matches_at(chars: &[char], index: usize, s: &str) -> bool {
+    s.chars()
+        .enumerate()
+        .all(|(offset, c)| chars.get(index + offset) == Some(&c))
+}
+
+/// Returns `true` if `grapheme` is always drawn one column wide.
+fn is_narrow(grapheme: &str) -> bool {
+    grapheme != "\t" && grapheme_width(grapheme) == 1
+}
+
+/// Returns how many columns `grapheme` takes when drawn at `column`: a
+/// tab reaches the next multiple of `tab_width`.
+fn display_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        let tab_width = tab_width.max(1);
+
+        tab_width - column % tab_width
+    } else {
+        grapheme_width(grapheme)
+    }
+}
+
 #[cfg(test)]
 mod row_tests {
     use super::*;
+    use crate::FileType;
 
     #[test]
     fn test_find() {
@@ -564,33 +1330,558 @@ mod row_tests {
         assert_eq!(row.find("t", 0, SearchDirection::Forward), Some(0));
         assert_eq!(row.find("t", 2, SearchDirection::Forward), Some(3));
         assert_eq!(row.find("t", 5, SearchDirection::Forward), None);
+        assert_eq!(row.find("test1234", 0, SearchDirection::Forward), None);
+
+        let row = Row::from("a b  a b a b");
+
+        assert_eq!(row.find("a b", 1, SearchDirection::Forward), Some(5));
+        assert_eq!(row.find("a b", 9, SearchDirection::Backward), Some(5));
+        assert_eq!(row.find("b  a", 0, SearchDirection::Forward), Some(2));
+
+        let row = Row::from("cafe\u{301} cafe");
+
+        assert_eq!(row.find("cafe", 0, SearchDirection::Forward), Some(5));
+        assert_eq!(row.find("e", 0, SearchDirection::Forward), Some(8));
+    }
+
+    #[test]
+    fn test_edit_multibyte() {
+        let mut row = Row::from("cafe\u{301} \u{1f600}!");
+
+        assert_eq!(row.len(), 7);
+
+        row.insert(4, 's');
+        row.insert(5, '\u{308}');
+
+        assert_eq!(row.as_string(), "cafe\u{301}s\u{308} \u{1f600}!");
+        assert_eq!(row.len(), 8);
+        assert_eq!(row.grapheme(3), Some("e\u{301}"));
+        assert_eq!(row.grapheme(4), Some("s\u{308}"));
+
+        row.delete(6);
+        row.delete(3);
+
+        assert_eq!(row.as_string(), "cafs\u{308} !");
+
+        let tail = row.split(4);
+
+        assert_eq!(row.as_string(), "cafs\u{308}");
+        assert_eq!(tail.as_string(), " !");
+        assert_eq!(tail.len(), 2);
+    }
+
+    #[test]
+    fn test_width() {
+        let row = Row::from("ab漢字c");
+
+        assert_eq!(row.width(4), 7);
+        assert_eq!(row.width_to(3, 4), 4);
+        assert_eq!(row.index_at(3, 4), 2);
+        assert_eq!(row.index_at(9, 4), 5);
+        assert_eq!(
+            row.render(3, 7, &Overlay::default(), &Theme::default())
+                .matches('字')
+                .count(),
+            1
+        );
+
+        let tabs = Row::from("\tab\tc");
+
+        assert_eq!(tabs.width_to(4, 4), 8);
+        assert_eq!(tabs.width_to(4, 8), 16);
+        assert_eq!(tabs.width_to(4, 0), 4);
+        assert_eq!(tabs.index_at(3, 4), 0);
+        assert_eq!(tabs.index_at(5, 4), 2);
+        assert_eq!(
+            tabs.render(
+                0,
+                20,
+                &Overlay {
+                    tab_width: 4,
+                    ..Overlay::default()
+                },
+                &Theme::default()
+            )
+            .matches(' ')
+            .count(),
+            6
+        );
+    }
+
+    #[test]
+    fn test_render_selection() {
+        let row = Row::from("abcd");
+        let overlay = Overlay {
+            selection: Some((1, 3)),
+            ..Overlay::default()
+        };
+        let rendered = row.render(0, 4, &overlay, &Theme::default());
+        let expected = format!("a{}bc{}d", style::Invert, style::NoInvert);
+
+        assert!(rendered.contains(&expected));
+
+        let overlay = Overlay {
+            cursors: vec![0, 4],
+            ..Overlay::default()
+        };
+        let rendered = row.render(0, 8, &overlay, &Theme::default());
+
+        assert!(rendered.contains(&format!("{}a{}", style::Invert, style::NoInvert)));
+        assert!(rendered.contains(&format!("{} {}", style::Invert, style::NoInvert)));
+    }
+
+    #[test]
+    fn test_render_ruler() {
+        let theme = Theme::default();
+        let overlay = Overlay {
+            ruler: Some(4),
+            ..Overlay::default()
+        };
+        let ruler = format!("{} {}", color::Bg(theme.ruler), color::Bg(color::Reset));
+
+        assert!(Row::from("ab")
+            .render(0, 8, &overlay, &theme)
+            .contains(&format!("ab  {ruler}")));
+        assert!(Row::from("abcdef")
+            .render(0, 8, &overlay, &theme)
+            .contains(&format!("d{}ef", color::Bg(theme.ruler))));
+        assert!(!Row::from("ab")
+            .render(5, 8, &overlay, &theme)
+            .contains(&ruler));
+        assert!(!Row::from("ab")
+            .render(0, 8, &Overlay::default(), &theme)
+            .contains(&ruler));
+    }
+
+    #[test]
+    fn test_render_crosshair() {
+        let theme = Theme {
+            cursor_column: Rgb(60, 60, 60),
+            ..Theme::default()
+        };
+        let overlay = Overlay {
+            line_background: Some(theme.cursor_line),
+            cursor_column: Some(3),
+            tab_width: 4,
+            ..Overlay::default()
+        };
+        let line = color::Bg(theme.cursor_line);
+        let column = color::Bg(theme.cursor_column);
+        let reset = color::Bg(color::Reset);
+
+        assert_eq!(
+            Row::from("abcdef").render(0, 6, &overlay, &theme),
+            format!(
+                "{line}abc{column}d{line}ef{reset}{}",
+                color::Fg(color::Reset)
+            )
+        );
+        assert!(Row::from("\tx")
+            .render(0, 6, &overlay, &theme)
+            .starts_with(&format!("{column}")));
+        assert!(Row::from("a")
+            .render(0, 6, &overlay, &theme)
+            .ends_with(&format!(
+                "{reset}{line}  {column} {line}  {reset}{}",
+                color::Fg(color::Reset)
+            )));
+
+        let overlay = Overlay {
+            cursor_column: Some(3),
+            ..Overlay::default()
+        };
+
+        assert!(Row::from("a")
+            .render(0, 6, &overlay, &theme)
+            .ends_with(&format!("  {column} {reset}{}", color::Fg(color::Reset))));
+    }
+
+    #[test]
+    fn test_render_whitespace() {
+        let row = Row::from("\tif a  b");
+        let overlay = Overlay {
+            whitespace: Some((">", ".")),
+            ..Overlay::default()
+        };
+
+        assert!(row
+            .render(0, 8, &overlay, &Theme::default())
+            .ends_with(&format!(">if.a..b{}", color::Fg(color::Reset))));
+        assert!(row
+            .render(0, 8, &Overlay::default(), &Theme::default())
+            .contains(" if a  b"));
+    }
+
+    #[test]
+    fn test_render_control() {
+        let theme = Theme::default();
+        let row = Row::from("\u{feff}a\u{1}b");
+
+        assert_eq!(row.width(4), 10);
+        assert!(row
+            .render(0, 10, &Overlay::default(), &theme)
+            .contains(&format!(
+                "{}^A{}b",
+                color::Fg(theme.control),
+                color::Fg(theme.text)
+            )));
+        assert!(row
+            .render(0, 10, &Overlay::default(), &theme)
+            .contains("<feff>"));
+    }
+
+    #[test]
+    fn test_add_to_number() {
+        let add = |text: &str, index: usize, delta: i128| {
+            Row::from(text)
+                .add_to_number(index, delta)
+                .map(|(range, number)| {
+                    let row = Row::from(text);
+
+                    format!(
+                        "{}{number}{}",
+                        row.substring(0, range.start),
+                        row.substring(range.end, row.len())
+                    )
+                })
+        };
+
+        assert_eq!(add("x = 41;", 0, 1).as_deref(), Some("x = 42;"));
+        assert_eq!(add("x = 41;", 5, -1).as_deref(), Some("x = 40;"));
+        assert_eq!(add("x = -1;", 0, 3).as_deref(), Some("x = 2;"));
+        assert_eq!(add("x = 1;", 0, -3).as_deref(), Some("x = -2;"));
+        assert_eq!(add("a-1", 0, 1).as_deref(), Some("a-2"));
+        assert_eq!(add("id 007", 1, 3).as_deref(), Some("id 010"));
+        assert_eq!(add("0x0F", 3, 1).as_deref(), Some("0x10"));
+        assert_eq!(add("0xff", 0, 1).as_deref(), Some("0x100"));
+        assert_eq!(add("0b0111", 0, 1).as_deref(), Some("0b1000"));
+        assert_eq!(add("a2 3d 4", 0, 1).as_deref(), Some("a2 3d 5"));
+        assert_eq!(add("3 4", 2, 1).as_deref(), Some("3 5"));
+        assert_eq!(add("0x0", 0, -1), None);
+        assert_eq!(add("none", 0, 1), None);
+    }
+
+    #[test]
+    fn test_render_indent_guides() {
+        let theme = Theme::default();
+        let overlay = Overlay {
+            indent_guides: Some((2, "|")),
+            ..Overlay::default()
+        };
+        let guide = format!(
+            "{}|{}",
+            color::Fg(theme.indent_guide),
+            color::Fg(theme.text)
+        );
+
+        assert!(Row::from("     x")
+            .render(0, 8, &overlay, &theme)
+            .contains(&format!("{guide} {guide} {guide}x")));
+        assert!(Row::from("\t  x")
+            .render(0, 8, &overlay, &theme)
+            .contains(&format!("{guide}{guide} x")));
+        assert!(Row::from("    x")
+            .render(1, 8, &overlay, &theme)
+            .contains(&format!(" {guide} x")));
+        assert!(!Row::from("x  y")
+            .render(0, 8, &overlay, &theme)
+            .contains(&guide));
+    }
+
+    #[test]
+    fn test_indentation() {
+        assert_eq!(Row::from("    let x;").indentation(), 4);
+        assert_eq!(Row::from("\t x").indentation(), 2);
+        assert_eq!(Row::from("   ").indentation(), 3);
+        assert_eq!(Row::from("x").indentation(), 0);
+    }
+
+    #[test]
+    fn test_whitespace_warning() {
+        let opts = HighlightOptions::default();
+        let warns = |text: &str| {
+            let mut row = Row::from(text);
+
+            row.highlight(&opts, &None, highlight::State::Normal);
+            row.has_whitespace_warning()
+        };
+
+        assert!(warns("let x; "));
+        assert!(warns("x\t"));
+        assert!(warns("\t    x"));
+        assert!(!warns("\t\tx"));
+        assert!(!warns("    x"));
+        assert!(!warns(""));
     }
 
     #[test]
     fn test_highlight_match() {
         let mut row = Row::from("test123");
 
-        row.highlight = vec![
-            highlight::Type::Char,
-            highlight::Type::Char,
-            highlight::Type::Char,
-            highlight::Type::Char,
-            highlight::Type::Number,
-            highlight::Type::Number,
-            highlight::Type::Number,
-        ];
-        row.highlight_match(&Some("t".to_string()));
+        row.highlight(
+            &HighlightOptions::default(),
+            &Some(highlight::Term::Search("t".to_owned())),
+            highlight::State::Normal,
+        );
+
         assert_eq!(
+            row.term_highlights(0..5),
             vec![
-                highlight::Type::Match,
-                highlight::Type::Char,
-                highlight::Type::Char,
-                highlight::Type::Char,
-                highlight::Type::Number,
-                highlight::Type::Number,
-                highlight::Type::Number,
-            ],
+                Some(highlight::Type::Match),
+                None,
+                None,
+                Some(highlight::Type::Match),
+                None,
+            ]
+        );
+        assert_eq!(row.highlight[0], highlight::Type::None);
+    }
+
+    #[test]
+    fn test_highlight_multiline() {
+        let opts = FileType::new().from("main.rs");
+        let opts = opts.highlight_options();
+        let mut row = Row::from(r#"let s = "a \" b"#);
+
+        assert_eq!(
+            row.highlight(opts, &None, highlight::State::Normal),
+            highlight::State::String('"')
+        );
+        assert!(row.is_code(7));
+        assert!(!row.is_code(13));
+
+        let mut row = Row::from(r#"end"; x /* open"#);
+
+        assert_eq!(
+            row.highlight(opts, &None, highlight::State::String('"')),
+            highlight::State::Comment
+        );
+        assert!(!row.is_code(0));
+        assert!(row.is_code(6));
+        assert!(!row.is_code(10));
+
+        let mut row = Row::from("*/ y");
+
+        assert_eq!(
+            row.highlight(opts, &None, highlight::State::Comment),
+            highlight::State::Normal
+        );
+        assert!(!row.is_code(1));
+        assert!(row.is_code(3));
+    }
+
+    #[test]
+    fn test_check_spelling() {
+        let file_type = FileType::new().from("main.rs");
+        let opts = file_type.highlight_options();
+        let mut dictionary = Dictionary::from_words("a\nlet\nsay\nhello\n", None);
+        let mut row = Row::from(r#"let helo = "say helo"; // sya"#);
+
+        row.highlight(opts, &None, highlight::State::Normal);
+        row.check_spelling(&dictionary, false);
+
+        assert!(!row.is_misspelled(4));
+        assert!(row.is_misspelled(16));
+        assert!(row.is_misspelled(19));
+        assert!(!row.is_misspelled(20));
+        assert!(row.is_misspelled(26));
+        assert!(row
+            .render(0, 30, &Overlay::default(), &Theme::default())
+            .contains(&format!("{}sya{}", style::Underline, style::NoUnderline)));
+
+        dictionary.add("helo").unwrap();
+        row.check_spelling(&dictionary, false);
+
+        assert!(row.is_misspelled(16));
+
+        row.recheck_spelling();
+        row.check_spelling(&dictionary, false);
+
+        assert!(!row.is_misspelled(16));
+        assert!(row.is_misspelled(26));
+
+        row.check_spelling(&dictionary, true);
+        row.clear_spelling();
+
+        assert!(!row.is_misspelled(26));
+
+        row.check_spelling(&Dictionary::default(), true);
+
+        assert!(row.is_misspelled(0));
+    }
+
+    #[test]
+    fn test_color_brackets() {
+        let opts = FileType::new().from("main.rs");
+        let opts = opts.highlight_options();
+        let mut row = Row::from(r#"f(a[0], "(") {"#);
+
+        row.highlight(opts, &None, highlight::State::Normal);
+
+        assert_eq!(row.color_brackets(1), 2);
+        assert_eq!(row.highlight[1], highlight::Type::Bracket(1));
+        assert_eq!(row.highlight[3], highlight::Type::Bracket(2));
+        assert_eq!(row.highlight[5], highlight::Type::Bracket(2));
+        assert_eq!(row.highlight[9], highlight::Type::String);
+        assert_eq!(row.highlight[11], highlight::Type::Bracket(1));
+        assert_eq!(row.highlight[13], highlight::Type::Bracket(1));
+        assert_eq!(row.color_brackets(0), 1);
+        assert_eq!(row.highlight[1], highlight::Type::Bracket(0));
+
+        row.clear_bracket_colors();
+
+        assert_eq!(row.highlight[1], highlight::Type::None);
+    }
+
+    #[test]
+    fn test_highlight_python() {
+        let file_type = FileType::new().from("main.py");
+        let opts = file_type.highlight_options();
+        let mut row = Row::from("def f(): return f'{x}' # done");
+
+        assert_eq!(
+            row.highlight(opts, &None, highlight::State::Normal),
+            highlight::State::Normal
+        );
+        assert_eq!(row.highlight[0], highlight::Type::PrimaryKeywords);
+        assert_eq!(row.highlight[4], highlight::Type::None);
+        assert_eq!(row.highlight[9], highlight::Type::PrimaryKeywords);
+        assert!(!row.is_code(16));
+        assert!(!row.is_code(21));
+        assert_eq!(row.highlight[24], highlight::Type::Comment);
+
+        let mut row = Row::from("'open");
+
+        assert_eq!(
+            row.highlight(opts, &None, highlight::State::Normal),
+            highlight::State::String('\'')
+        );
+        assert_eq!(file_type.badge(), "PY");
+        assert_eq!(file_type.comment_prefix(), Some("#"));
+    }
+
+    #[test]
+    fn test_highlight_numbers() {
+        let file_type = FileType::new().from("main.rs");
+        let opts = file_type.highlight_options();
+        let numbers = |text: &str| {
+            let mut row = Row::from(text);
+
+            row.highlight(opts, &None, highlight::State::Normal);
+
             row.highlight
-        )
+                .iter()
+                .map(|hl_type| {
+                    if *hl_type == highlight::Type::Number {
+                        '#'
+                    } else {
+                        ' '
+                    }
+                })
+                .collect::<String>()
+        };
+
+        assert_eq!(numbers("0xFF 0o17 0b1010"), "#### #### ######");
+        assert_eq!(numbers("1.5e-3 1_000_000"), "###### #########");
+        assert_eq!(numbers("42u8 3.0f64 7.e"), "#### ###### #  ");
+        assert_eq!(numbers("x1 3d 0b12 1..2"), "           #  #");
+        assert_eq!(numbers("a[0].1"), "  #  #");
+    }
+
+    #[test]
+    fn test_highlight_symbol() {
+        let mut row = Row::from("foo(foobar, foo)");
+
+        row.highlight(
+            &HighlightOptions::default(),
+            &Some(highlight::Term::Symbol("foo".to_owned())),
+            highlight::State::Normal,
+        );
+
+        let terms = row.term_highlights(0..row.len());
+        let marked: Vec<usize> = (0..row.len())
+            .filter(|index| terms[*index] == Some(highlight::Type::Occurrence))
+            .collect();
+
+        assert_eq!(marked, vec![0, 1, 2, 12, 13, 14]);
+
+        // A new term is marked without highlighting the row again.
+        row.highlight[0] = highlight::Type::Number;
+        row.highlight(
+            &HighlightOptions::default(),
+            &Some(highlight::Term::Symbol("foobar".to_owned())),
+            highlight::State::Normal,
+        );
+
+        assert_eq!(row.highlight[0], highlight::Type::Number);
+        assert_eq!(
+            row.term_highlights(4..5),
+            vec![Some(highlight::Type::Occurrence)]
+        );
+    }
+
+    #[test]
+    fn test_edit_keeps_index() {
+        let mut row = Row::from("a\tb\u{1f1eb}\u{1f1f7}漢");
+        let check = |row: &Row| {
+            let fresh = Row::from(row.as_string().as_str());
+
+            assert_eq!(row.boundaries, fresh.boundaries);
+            assert_eq!(row.wide, fresh.wide);
+        };
+
+        check(&row);
+        row.insert(1, '\u{301}');
+        check(&row);
+        row.insert(3, '\u{1f1e9}');
+        check(&row);
+        row.insert(5, '\u{1f1ea}');
+        check(&row);
+        row.delete(3);
+        check(&row);
+        row.delete(0);
+        check(&row);
+        row.append(&Row::from("\u{308}\tz"));
+        check(&row);
+
+        let tail = row.split(3);
+
+        check(&row);
+        check(&tail);
+    }
+
+    #[test]
+    fn test_long_row() {
+        let mut row = Row::from("ab ".repeat(MAX_HIGHLIGHT_LEN).as_str());
+        let theme = Theme::default();
+        let state = row.highlight(
+            FileType::new().from("main.rs").highlight_options(),
+            &Some(highlight::Term::Search("b ".to_owned())),
+            highlight::State::Comment,
+        );
+
+        assert_eq!(state, highlight::State::Comment);
+        assert!(row.highlight.is_empty());
+        assert_eq!(
+            row.term_highlights(4..8),
+            vec![
+                Some(highlight::Type::Match),
+                Some(highlight::Type::Match),
+                None,
+                Some(highlight::Type::Match)
+            ]
+        );
+
+        let far = row.width(4) - 6;
+        let rendered = row.render(far, far + 3, &Overlay::default(), &theme);
+
+        assert!(rendered.contains(&format!("{}", color::Fg(theme.search_match))));
+        assert_eq!(rendered.matches(['a', 'b']).count(), 2);
+
+        row.insert(0, '\t');
+
+        assert_eq!(row.width_to(row.len(), 4), row.len() + 3);
+        assert_eq!(row.index_at(2, 4), 0);
+        assert_eq!(row.index_at(10, 4), 7);
     }
 }
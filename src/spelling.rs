@@ -0,0 +1,218 @@
+//! Checking the spelling of words in comments and strings against the
+//! system word list, and a personal one kept next to the config file.
+
+use crate::config::Config;
+
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{Error as IOError, ErrorKind, Write};
+use std::path::PathBuf;
+
+/// Where word lists are installed, e.g. by the `words` package.
+const SYSTEM_WORD_LISTS: [&str; 2] = ["/usr/share/dict/words", "/usr/dict/words"];
+
+#[derive(Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+    /// The file words added with `add` are written to.
+    personal: Option<PathBuf>,
+}
+
+impl Dictionary {
+    /// Reads the system word list together with the personal one in the
+    /// config directory.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if there is no system word list or it cannot be
+    /// read
+    pub fn load() -> Result<Self, IOError> {
+        let Some(list) = SYSTEM_WORD_LISTS
+            .iter()
+            .find(|list| fs::metadata(list).is_ok())
+        else {
+            return Err(IOError::new(
+                ErrorKind::NotFound,
+                format!("no word list in {}", SYSTEM_WORD_LISTS.join(" or ")),
+            ));
+        };
+        let personal = Config::path().and_then(|path| Some(path.parent()?.join("words")));
+        let mut dictionary = Self::from_words(&fs::read_to_string(list)?, personal);
+
+        if let Some(words) = dictionary
+            .personal
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+        {
+            dictionary.extend(&words);
+        }
+
+        Ok(dictionary)
+    }
+
+    /// Builds a dictionary of the lines of `words`, adding words to the
+    /// file at `personal` if given.
+    pub fn from_words(words: &str, personal: Option<PathBuf>) -> Self {
+        let mut dictionary = Self {
+            words: HashSet::new(),
+            personal,
+        };
+
+        dictionary.extend(words);
+        dictionary
+    }
+
+    /// Returns whether `word` is spelled right: it is listed as it is, in
+    /// lower case as at the start of a sentence, or without a trailing `'s`.
+    pub fn knows(&self, word: &str) -> bool {
+        let word = word.replace('’', "'");
+
+        self.words.contains(&word)
+            || self.words.contains(&word.to_lowercase())
+            || word.strip_suffix("'s").is_some_and(|stem| self.knows(stem))
+    }
+
+    /// Accepts `word` from now on, in this and later sessions.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the personal word list cannot be written
+    pub fn add(&mut self, word: &str) -> Result<(), IOError> {
+        self.words.insert(word.to_owned());
+
+        let Some(path) = &self.personal else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.exists()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        writeln!(file, "{word}")
+    }
+
+    fn extend(&mut self, words: &str) {
+        self.words.extend(
+            words
+                .lines()
+                .map(str::trim)
+                .filter(|word| !word.is_empty())
+                .map(str::to_owned),
+        );
+    }
+}
+
+/// Splits `text` into the words worth checking, with the index of the
+/// character each starts at. Identifiers with digits, underscores or
+/// capitals past their first letter are left out, as are single letters
+/// and parts of paths, addresses and file names such as `main.rs`.
+pub fn words(text: &[char]) -> Vec<(usize, String)> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '\'' || c == '’';
+    let joins = |at: Option<usize>, next: Option<usize>| {
+        at.and_then(|at| text.get(at))
+            .is_some_and(|c| matches!(c, '.' | '/' | '\\' | '@' | ':'))
+            && next
+                .and_then(|next| text.get(next))
+                .is_some_and(|c| !c.is_whitespace())
+    };
+    let mut words = Vec::new();
+    let mut index = 0;
+
+    while index < text.len() {
+        if !is_word_char(text[index]) {
+            index += 1;
+
+            continue;
+        }
+
+        let mut end = index;
+
+        while end < text.len() && is_word_char(text[end]) {
+            end += 1;
+        }
+
+        let quote = |c: &char| *c == '\'' || *c == '’';
+        let start = index + text[index..end].iter().take_while(|c| quote(c)).count();
+        let stop = end
+            - text[start..end]
+                .iter()
+                .rev()
+                .take_while(|c| quote(c))
+                .count();
+        let word = &text[start..stop];
+        let checked = word.len() > 1
+            && word.iter().all(|c| c.is_alphabetic() || quote(c))
+            && !word[1..].iter().any(|c| c.is_uppercase())
+            && !joins(index.checked_sub(1), index.checked_sub(2))
+            && !joins(Some(end), Some(end + 1));
+
+        if checked {
+            words.push((start, word.iter().collect()));
+        }
+
+        index = end;
+    }
+
+    words
+}
+
+#[cfg(test)]
+mod spelling_tests {
+    use super::*;
+
+    #[test]
+    fn test_knows() {
+        let mut dictionary = Dictionary::from_words("hello\nworld\nParis\ndon't\n", None);
+
+        assert!(dictionary.knows("hello"));
+        assert!(dictionary.knows("Hello"));
+        assert!(dictionary.knows("world's"));
+        assert!(dictionary.knows("don’t"));
+        assert!(dictionary.knows("Paris"));
+        assert!(!dictionary.knows("paris"));
+        assert!(!dictionary.knows("wrold"));
+
+        dictionary.add("wrold").unwrap();
+
+        assert!(dictionary.knows("wrold"));
+    }
+
+    #[test]
+    fn test_add_persists() {
+        let dir = std::env::temp_dir().join(format!("revise-spelling-{}", std::process::id()));
+        let path = dir.join("words");
+        let mut dictionary = Dictionary::from_words("", Some(path.clone()));
+
+        dictionary.add("revise").unwrap();
+        dictionary.add("rustfmt").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "revise\nrustfmt\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_words() {
+        let words =
+            |text: &str| -> Vec<(usize, String)> { words(&text.chars().collect::<Vec<_>>()) };
+
+        assert_eq!(
+            words("Teh 'quick' fox's a x"),
+            vec![
+                (0, "Teh".to_owned()),
+                (5, "quick".to_owned()),
+                (12, "fox's".to_owned())
+            ]
+        );
+        assert_eq!(
+            words("see main.rs, foo_bar, x2 and camelCase."),
+            vec![(0, "see".to_owned()), (25, "and".to_owned())]
+        );
+        assert_eq!(
+            words("at https://example.com or me@host"),
+            vec![(0, "at".to_owned()), (23, "or".to_owned())]
+        );
+    }
+}
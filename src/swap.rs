@@ -0,0 +1,115 @@
+use crate::Document;
+
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{Error as IOError, ErrorKind};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Mirrors unsaved changes to `.<name>.revise.swp` next to the edited file
+/// so they can be recovered after a crash.
+pub struct SwapFile {
+    path: PathBuf,
+}
+
+impl SwapFile {
+    pub fn for_file(filename: &str) -> Self {
+        let file = Path::new(filename);
+        let name = file
+            .file_name()
+            .map_or_else(|| filename.into(), |name| name.to_string_lossy());
+
+        Self {
+            path: file.with_file_name(format!(".{name}.revise.swp")),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// Writes `document` to the swap file, which only the user can read
+    /// since it holds the unsaved text.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the swap file cannot be written
+    pub fn write(&self, document: &Document) -> Result<(), IOError> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&self.path)?;
+
+        file.set_permissions(Permissions::from_mode(0o600))?;
+        document.write_to(&mut file, false)
+    }
+
+    /// Replaces the contents of `document` with the ones kept in the swap file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Error` if the swap file cannot be read
+    pub fn recover(&self, document: &mut Document) -> Result<(), IOError> {
+        document.restore(File::open(&self.path)?)
+    }
+
+    /// # Errors
+    ///
+    /// Will return `Error` if an existing swap file cannot be removed
+    pub fn remove(&self) -> Result<(), IOError> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod swap_tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_for_file() {
+        assert_eq!(
+            SwapFile::for_file("src/main.rs").path(),
+            Path::new("src/.main.rs.revise.swp")
+        );
+        assert_eq!(
+            SwapFile::for_file("notes").path(),
+            Path::new(".notes.revise.swp")
+        );
+    }
+
+    #[test]
+    fn test_recover() {
+        let filename = env::temp_dir().join("revise_test_swap.txt");
+        let swap = SwapFile::for_file(&filename.to_string_lossy());
+        let mut document = Document::from_reader("unsaved\nchanges\n".as_bytes()).unwrap();
+
+        swap.write(&document).unwrap();
+        assert!(swap.exists());
+        assert_eq!(
+            fs::metadata(swap.path()).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        document.delete_range(&Default::default(), &crate::Position { x: 0, y: 2 });
+        swap.recover(&mut document).unwrap();
+
+        assert_eq!(
+            document.row(1).map(|row| row.as_string().as_str()),
+            Some("changes")
+        );
+        assert!(document.is_changed());
+
+        swap.remove().unwrap();
+        assert!(!swap.exists());
+        assert!(swap.remove().is_ok());
+    }
+}
@@ -0,0 +1,58 @@
+//! Files in the temporary directory only the user can read, for the undo
+//! history spilled to disk and the copies saves with `ELEVATE` write.
+
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Error as IOError, ErrorKind};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers the temporary files of one process.
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Creates a new file named after `prefix` that only the user can read and
+/// write, rather than opening one another user put in the temporary
+/// directory first.
+pub fn create_private(prefix: &str) -> Result<(PathBuf, File), IOError> {
+    loop {
+        let path = env::temp_dir().join(format!(
+            "{prefix}-{}-{}",
+            process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod temp_tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_create_private() {
+        let (first, _) = create_private("revise-test-temp").unwrap();
+        let (second, _) = create_private("revise-test-temp").unwrap();
+        let mode = fs::metadata(&first).unwrap().permissions().mode();
+
+        assert_ne!(first, second);
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+    }
+}
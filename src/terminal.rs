@@ -1,20 +1,99 @@
+use crate::palette::ColorSupport;
+use crate::Backend;
 use crate::Position;
 
-use std::io::{stdin, stdout, Error, Stdout, Write};
-use termion::event::Key;
-use termion::input::TermRead;
+use std::cell::Cell;
+use std::cmp;
+use std::io::{stdin, stdout, Error, ErrorKind, Read, Stdout, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+use termion::event::{Event as TermEvent, Key, MouseEvent};
+use termion::input::{MouseTerminal, TermReadEventsAndRaw};
 use termion::raw::{IntoRawMode, RawTerminal};
-use termion::terminal_size;
-use termion::{clear, color, cursor};
+use termion::{clear, color, cursor, screen};
+use termion::{get_tty, is_tty, terminal_size};
 
+/// How often the terminal size is checked while waiting for input.
+const RESIZE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Brackets text pasted while bracketed paste mode is on.
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub struct Size {
     pub width: u16,
     pub height: u16,
 }
 
+/// Modifiers held with a key that termion does not decode by itself,
+/// such as `Alt-Up` or `Shift-Home`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+impl Modifiers {
+    pub const SHIFT: Self = Self {
+        shift: true,
+        alt: false,
+        ctrl: false,
+    };
+
+    pub const ALT: Self = Self {
+        shift: false,
+        alt: true,
+        ctrl: false,
+    };
+
+    pub const ALT_SHIFT: Self = Self {
+        shift: true,
+        alt: true,
+        ctrl: false,
+    };
+
+    pub const CTRL: Self = Self {
+        shift: false,
+        alt: false,
+        ctrl: true,
+    };
+
+    fn from_parameter(parameter: u8) -> Self {
+        let bits = parameter.saturating_sub(1);
+
+        Self {
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            ctrl: bits & 4 != 0,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    Key(Key),
+    Modified(Key, Modifiers),
+    Mouse(MouseEvent),
+    /// Text pasted into the terminal in one go, with bracketed paste.
+    Paste(String),
+    Resize,
+}
+
+type RawEvent = Result<(TermEvent, Vec<u8>), Error>;
+
 pub struct Terminal {
     size: Size,
-    _stdout: RawTerminal<Stdout>,
+    bar_rows: usize,
+    events: Receiver<RawEvent>,
+    /// An event received to tell whether input is pending, to be read next.
+    pending: Option<RawEvent>,
+    colors: ColorSupport,
+    _stdout: Arc<MouseTerminal<RawTerminal<Stdout>>>,
 }
 
 impl Terminal {
@@ -23,27 +102,149 @@ impl Terminal {
     /// Will return `Error` if it fails to get terminal size  
     /// or if it fails to switch to raw mode
     pub fn new() -> Result<Self, Error> {
-        let size = terminal_size()?;
+        let (sender, events) = mpsc::channel();
+        // Keys come from the controlling terminal when stdin is a pipe.
+        let input: Box<dyn Read + Send> = if is_tty(&stdin()) {
+            Box::new(stdin())
+        } else {
+            Box::new(get_tty()?)
+        };
+
+        thread::spawn(move || {
+            for event in input.events_and_raw() {
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let stdout = Arc::new(MouseTerminal::from(stdout().into_raw_mode()?));
+
+        // Draw on the alternate screen, leaving the shell's scrollback
+        // as it was once the editor exits.
+        print!("{}\x1b[?2004h", screen::ToAlternateScreen);
+        set_panic_hook(Arc::downgrade(&stdout));
+
+        let (size, bar_rows) = current_size()?;
 
         Ok(Self {
-            size: Size {
-                width: size.0,
-                height: size.1.saturating_sub(2),
-            },
-            _stdout: stdout().into_raw_mode()?,
+            size,
+            bar_rows,
+            events,
+            pending: None,
+            colors: ColorSupport::detect(),
+            _stdout: stdout,
         })
     }
 
-    pub fn size(&self) -> &Size {
+    /// Collects the keys of a bracketed paste up to its end marker.
+    fn read_paste(&mut self) -> Result<Event, Error> {
+        let mut raw = Vec::new();
+
+        loop {
+            match self.pending.take().map_or_else(|| self.events.recv(), Ok) {
+                Ok(event) => match event? {
+                    (TermEvent::Unsupported(sequence), _) if sequence == PASTE_END => break,
+                    (_, bytes) => raw.extend(bytes),
+                },
+                Err(_) => return Err(Error::new(ErrorKind::UnexpectedEof, "input closed")),
+            }
+        }
+
+        Ok(Event::Paste(paste_text(&raw)))
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Whether the title the terminal had before was saved, to be restored
+/// on exit.
+static TITLE_SAVED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Set while `catch_panic` runs, so that a panic it catches leaves the
+    /// terminal as it is in builds that unwind.
+    static CATCHING_PANIC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f`, returning the message of a panic in it as an error instead of
+/// restoring the terminal and printing it. This only applies to builds
+/// that unwind: with `panic = "abort"`, as `revise` is released, the panic
+/// still aborts.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    let catching = CATCHING_PANIC.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+
+    CATCHING_PANIC.set(catching);
+    result.map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|message| (*message).to_owned())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panicked".to_owned())
+    })
+}
+
+/// Leaves raw mode and restores the terminal before the panic message is
+/// printed, which would otherwise be garbled and lost with the alternate
+/// screen.
+fn set_panic_hook(raw: Weak<MouseTerminal<RawTerminal<Stdout>>>) {
+    let hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        // Without unwinding `catch_panic` cannot catch it, and the process
+        // aborts once the hook returns.
+        if cfg!(panic = "unwind") && CATCHING_PANIC.get() {
+            return;
+        }
+
+        if let Some(raw) = raw.upgrade() {
+            raw.suspend_raw_mode().ok();
+        }
+
+        restore();
+        hook(info);
+    }));
+}
+
+/// Resets what the editor changed in the terminal, other than raw mode
+/// and mouse reporting which are undone when `Terminal` is dropped.
+fn restore() {
+    if TITLE_SAVED.swap(false, Ordering::Relaxed) {
+        print!("\x1b[23;0t");
+    }
+
+    print!(
+        "{}{}{}\x1b[?2004l{}",
+        color::Fg(color::Reset),
+        color::Bg(color::Reset),
+        cursor::Show,
+        screen::ToMainScreen
+    );
+    stdout().flush().ok();
+}
+
+/// Resizes are detected by polling the terminal size while waiting for
+/// input and reported as `Event::Resize` once the new size is recorded.
+impl Backend for Terminal {
+    fn size(&self) -> &Size {
         &self.size
     }
 
-    pub fn clear_screen() {
+    fn bar_rows(&self) -> usize {
+        self.bar_rows
+    }
+
+    fn clear_screen(&mut self) {
         print!("{}", clear::All);
     }
 
     #[allow(clippy::cast_possible_truncation)]
-    pub fn cursor_position(position: &Position) {
+    fn cursor_position(&mut self, position: &Position) {
         let Position { mut x, mut y } = position;
 
         x = x.saturating_add(1);
@@ -55,51 +256,193 @@ impl Terminal {
         print!("{}", cursor::Goto(x, y));
     }
 
-    /// # Errors
-    ///
-    /// Will return an error if not
-    /// all bytes could be written due to I/O errors
-    /// or EOF being reached.
-    pub fn flush() -> Result<(), Error> {
+    fn flush(&mut self) -> Result<(), Error> {
         stdout().flush()
     }
 
-    /// # Errors
-    ///
-    /// Will return an error if it fails to read key
-    pub fn read_key() -> Result<Key, Error> {
+    fn read_event(&mut self) -> Result<Event, Error> {
+        loop {
+            if let Some(event) = self.read_event_timeout(RESIZE_POLL_INTERVAL)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    fn read_event_timeout(&mut self, timeout: Duration) -> Result<Option<Event>, Error> {
+        let deadline = Instant::now() + timeout;
+
         loop {
-            if let Some(key) = stdin().lock().keys().next() {
-                return key;
+            if let Ok((size, bar_rows)) = current_size() {
+                if (size, bar_rows) != (self.size, self.bar_rows) {
+                    self.size = size;
+                    self.bar_rows = bar_rows;
+                    return Ok(Some(Event::Resize));
+                }
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let received = self.pending.take().map_or_else(
+                || {
+                    self.events
+                        .recv_timeout(cmp::min(remaining, RESIZE_POLL_INTERVAL))
+                },
+                Ok,
+            );
+            let (event, raw) = match received {
+                Ok(event) => event?,
+                Err(RecvTimeoutError::Timeout) if remaining.is_zero() => return Ok(None),
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::new(ErrorKind::UnexpectedEof, "input closed"))
+                }
+            };
+
+            match event {
+                // Enter sends `\r` in raw mode, so a bare `\n` is Ctrl-J.
+                TermEvent::Key(Key::Char('\n')) if raw == b"\n" => {
+                    return Ok(Some(Event::Key(Key::Ctrl('j'))))
+                }
+                TermEvent::Key(key) => return Ok(Some(Event::Key(key))),
+                TermEvent::Unsupported(sequence) if sequence == PASTE_START => {
+                    return self.read_paste().map(Some)
+                }
+                TermEvent::Unsupported(sequence) => {
+                    if let Some(event) = decode_sequence(&sequence) {
+                        return Ok(Some(event));
+                    }
+                }
+                TermEvent::Mouse(mouse) => return Ok(Some(Event::Mouse(mouse))),
             }
         }
     }
 
-    pub fn cursor_hide() {
+    fn has_pending_input(&mut self) -> bool {
+        if self.pending.is_none() {
+            self.pending = self.events.try_recv().ok();
+        }
+
+        self.pending.is_some()
+    }
+
+    fn cursor_hide(&mut self) {
         print!("{}", cursor::Hide);
     }
 
-    pub fn cursor_show() {
+    fn cursor_show(&mut self) {
         print!("{}", cursor::Show);
     }
 
-    pub fn clear_current_line() {
+    fn clear_current_line(&mut self) {
         print!("{}", clear::CurrentLine);
     }
 
-    pub fn set_bg_color(color: color::Rgb) {
-        print!("{}", color::Bg(color));
+    fn set_bg_color(&mut self, color: color::Rgb) {
+        self.print(&color::Bg(color).to_string());
     }
 
-    pub fn reset_bg_color() {
+    fn reset_bg_color(&mut self) {
         print!("{}", color::Bg(color::Reset));
     }
 
-    pub fn set_fg_color(color: color::Rgb) {
-        print!("{}", color::Fg(color));
+    fn set_fg_color(&mut self, color: color::Rgb) {
+        self.print(&color::Fg(color).to_string());
     }
 
-    pub fn reset_fg_color() {
+    fn reset_fg_color(&mut self) {
         print!("{}", color::Fg(color::Reset));
     }
+
+    fn print(&mut self, text: &str) {
+        print!("{}", self.colors.downgrade(text));
+    }
+
+    fn set_color_support(&mut self, colors: ColorSupport) {
+        self.colors = colors;
+    }
+
+    /// Saves the title the terminal had on the title stack of xterm and
+    /// the terminals following it before the first title is set.
+    fn set_title(&mut self, title: &str) {
+        if !TITLE_SAVED.swap(true, Ordering::Relaxed) {
+            print!("\x1b[22;0t");
+        }
+
+        print!("\x1b]2;{title}\x1b\\");
+    }
+}
+
+/// Returns the size of the text area, leaving room for the status
+/// and message bars, and how many of the bars fit.
+fn current_size() -> Result<(Size, usize), Error> {
+    let (width, height) = terminal_size()?;
+    let size = Size {
+        width,
+        height: height.saturating_sub(2),
+    };
+
+    Ok((size, cmp::min(usize::from(height), 2)))
+}
+
+/// Turns pasted bytes into text, with the `\r` terminals send for line
+/// breaks read as newlines.
+fn paste_text(raw: &[u8]) -> String {
+    String::from_utf8_lossy(raw)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+/// Decodes xterm-style modified keys such as `ESC [ 1 ; 3 A` (Alt-Up)
+/// or `ESC [ 5 ; 5 ~` (Ctrl-PageUp).
+fn decode_sequence(sequence: &[u8]) -> Option<Event> {
+    let body = sequence.strip_prefix(b"\x1b[")?;
+    let (last, parameters) = body.split_last()?;
+    let parameters = std::str::from_utf8(parameters).ok()?;
+    let mut parameters = parameters.split(';').map(str::parse::<u8>);
+    let code = parameters.next()?.ok()?;
+    let modifiers = Modifiers::from_parameter(parameters.next()?.ok()?);
+    let key = match (last, code) {
+        (b'A', 1) => Key::Up,
+        (b'B', 1) => Key::Down,
+        (b'C', 1) => Key::Right,
+        (b'D', 1) => Key::Left,
+        (b'H', 1) | (b'~', 1 | 7) => Key::Home,
+        (b'F', 1) | (b'~', 4 | 8) => Key::End,
+        (b'~', 2) => Key::Insert,
+        (b'~', 3) => Key::Delete,
+        (b'~', 5) => Key::PageUp,
+        (b'~', 6) => Key::PageDown,
+        _ => return None,
+    };
+
+    Some(Event::Modified(key, modifiers))
+}
+
+#[cfg(test)]
+mod terminal_tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_sequence() {
+        assert_eq!(
+            decode_sequence(b"\x1b[1;3A"),
+            Some(Event::Modified(Key::Up, Modifiers::ALT))
+        );
+        assert_eq!(
+            decode_sequence(b"\x1b[5;5~"),
+            Some(Event::Modified(
+                Key::PageUp,
+                Modifiers {
+                    ctrl: true,
+                    ..Modifiers::default()
+                }
+            ))
+        );
+        assert_eq!(decode_sequence(PASTE_START), None);
+    }
+
+    #[test]
+    fn test_paste_text() {
+        assert_eq!(paste_text(b"one\rtwo\r\n\tthree"), "one\ntwo\n\tthree");
+        assert_eq!(paste_text("caf\u{e9}".as_bytes()), "caf\u{e9}");
+    }
 }
@@ -0,0 +1,277 @@
+use crate::highlight;
+
+use std::collections::HashMap;
+use termion::color::Rgb;
+use thiserror::Error;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+    pub text: Rgb,
+    pub number: Rgb,
+    pub search_match: Rgb,
+    pub string: Rgb,
+    pub boolean: Rgb,
+    pub char: Rgb,
+    pub comment: Rgb,
+    pub primary_keyword: Rgb,
+    pub secondary_keyword: Rgb,
+    pub status_fg: Rgb,
+    pub status_bg: Rgb,
+    pub modified: Rgb,
+    pub line_number: Rgb,
+    pub occurrence: Rgb,
+    pub matched_bracket: Rgb,
+    pub unmatched_bracket: Rgb,
+    pub whitespace_warning: Rgb,
+    pub ruler: Rgb,
+    pub cursor_line: Rgb,
+    pub cursor_column: Rgb,
+    /// The backgrounds of the lines a diff marks.
+    pub diff_added: Rgb,
+    pub diff_removed: Rgb,
+    pub diff_changed: Rgb,
+    pub indent_guide: Rgb,
+    pub added: Rgb,
+    pub deleted: Rgb,
+    pub control: Rgb,
+    pub empty_line: Rgb,
+    /// The colors brackets cycle through by nesting depth.
+    pub rainbow: [Rgb; 4],
+}
+
+#[derive(Debug, Error)]
+pub enum ThemeError {
+    #[error("unknown theme: {0}")]
+    UnknownTheme(String),
+    #[error("unknown theme color slot: {0}")]
+    UnknownSlot(String),
+    #[error("invalid color for {0}: {1}")]
+    InvalidColor(String, String),
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            text: Rgb(255, 255, 255),
+            number: Rgb(220, 163, 163),
+            search_match: Rgb(30, 139, 210),
+            string: Rgb(211, 54, 130),
+            boolean: Rgb(0, 0, 139),
+            char: Rgb(108, 113, 196),
+            comment: Rgb(133, 153, 0),
+            primary_keyword: Rgb(181, 137, 0),
+            secondary_keyword: Rgb(42, 161, 152),
+            status_fg: Rgb(63, 63, 63),
+            status_bg: Rgb(239, 239, 239),
+            modified: Rgb(203, 75, 22),
+            line_number: Rgb(110, 110, 110),
+            occurrence: Rgb(215, 186, 125),
+            matched_bracket: Rgb(88, 88, 88),
+            unmatched_bracket: Rgb(170, 40, 40),
+            whitespace_warning: Rgb(230, 180, 40),
+            ruler: Rgb(50, 50, 50),
+            cursor_line: Rgb(38, 38, 38),
+            cursor_column: Rgb(38, 38, 38),
+            diff_added: Rgb(25, 60, 30),
+            diff_removed: Rgb(70, 30, 30),
+            diff_changed: Rgb(60, 55, 25),
+            indent_guide: Rgb(70, 70, 70),
+            added: Rgb(87, 171, 90),
+            deleted: Rgb(200, 60, 60),
+            control: Rgb(230, 120, 60),
+            empty_line: Rgb(90, 90, 90),
+            rainbow: [
+                Rgb(255, 215, 0),
+                Rgb(218, 112, 214),
+                Rgb(23, 159, 255),
+                Rgb(110, 200, 110),
+            ],
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            text: Rgb(40, 40, 40),
+            number: Rgb(152, 60, 60),
+            search_match: Rgb(0, 95, 175),
+            string: Rgb(163, 21, 89),
+            boolean: Rgb(0, 0, 139),
+            char: Rgb(68, 73, 156),
+            comment: Rgb(95, 110, 0),
+            primary_keyword: Rgb(140, 95, 0),
+            secondary_keyword: Rgb(20, 120, 112),
+            status_fg: Rgb(239, 239, 239),
+            status_bg: Rgb(63, 63, 63),
+            modified: Rgb(255, 135, 0),
+            line_number: Rgb(150, 150, 150),
+            occurrence: Rgb(150, 100, 20),
+            matched_bracket: Rgb(200, 200, 200),
+            unmatched_bracket: Rgb(240, 128, 128),
+            whitespace_warning: Rgb(190, 120, 0),
+            ruler: Rgb(228, 228, 228),
+            cursor_line: Rgb(242, 242, 242),
+            cursor_column: Rgb(242, 242, 242),
+            diff_added: Rgb(220, 245, 220),
+            diff_removed: Rgb(250, 222, 222),
+            diff_changed: Rgb(245, 238, 200),
+            indent_guide: Rgb(205, 205, 205),
+            added: Rgb(40, 130, 50),
+            deleted: Rgb(190, 30, 30),
+            control: Rgb(200, 80, 0),
+            empty_line: Rgb(190, 190, 190),
+            rainbow: [
+                Rgb(175, 125, 0),
+                Rgb(150, 50, 150),
+                Rgb(0, 110, 200),
+                Rgb(40, 140, 40),
+            ],
+        }
+    }
+
+    /// Builds the theme named `name` with `colors` overriding individual slots.
+    ///
+    /// # Errors
+    ///
+    /// Will return `ThemeError` if the theme, a slot or a color is unknown
+    pub fn load(name: &str, colors: &HashMap<String, String>) -> Result<Self, ThemeError> {
+        let mut theme = match name {
+            "dark" => Self::dark(),
+            "light" => Self::light(),
+            _ => return Err(ThemeError::UnknownTheme(name.to_owned())),
+        };
+
+        for (slot, value) in colors {
+            let color = parse_color(value)
+                .ok_or_else(|| ThemeError::InvalidColor(slot.clone(), value.clone()))?;
+
+            *theme.slot_mut(slot)? = color;
+        }
+
+        Ok(theme)
+    }
+
+    pub fn color(&self, hl_type: highlight::Type) -> Rgb {
+        match hl_type {
+            highlight::Type::Number => self.number,
+            highlight::Type::Match => self.search_match,
+            highlight::Type::String => self.string,
+            highlight::Type::_Boolean => self.boolean,
+            highlight::Type::Char => self.char,
+            highlight::Type::Comment | highlight::Type::MultilineComment => self.comment,
+            highlight::Type::PrimaryKeywords => self.primary_keyword,
+            highlight::Type::SecondaryKeywords => self.secondary_keyword,
+            highlight::Type::Occurrence => self.occurrence,
+            highlight::Type::Bracket(depth) => self.rainbow[depth % self.rainbow.len()],
+            highlight::Type::None => self.text,
+        }
+    }
+
+    fn slot_mut(&mut self, slot: &str) -> Result<&mut Rgb, ThemeError> {
+        Ok(match slot {
+            "text" => &mut self.text,
+            "number" => &mut self.number,
+            "match" => &mut self.search_match,
+            "string" => &mut self.string,
+            "boolean" => &mut self.boolean,
+            "char" => &mut self.char,
+            "comment" => &mut self.comment,
+            "primary_keyword" => &mut self.primary_keyword,
+            "secondary_keyword" => &mut self.secondary_keyword,
+            "status_fg" => &mut self.status_fg,
+            "status_bg" => &mut self.status_bg,
+            "modified" => &mut self.modified,
+            "line_number" => &mut self.line_number,
+            "occurrence" => &mut self.occurrence,
+            "matched_bracket" => &mut self.matched_bracket,
+            "unmatched_bracket" => &mut self.unmatched_bracket,
+            "whitespace_warning" => &mut self.whitespace_warning,
+            "ruler" => &mut self.ruler,
+            "cursor_line" => &mut self.cursor_line,
+            "cursor_column" => &mut self.cursor_column,
+            "diff_added" => &mut self.diff_added,
+            "diff_removed" => &mut self.diff_removed,
+            "diff_changed" => &mut self.diff_changed,
+            "indent_guide" => &mut self.indent_guide,
+            "added" => &mut self.added,
+            "deleted" => &mut self.deleted,
+            "control" => &mut self.control,
+            "empty_line" => &mut self.empty_line,
+            "rainbow_1" => &mut self.rainbow[0],
+            "rainbow_2" => &mut self.rainbow[1],
+            "rainbow_3" => &mut self.rainbow[2],
+            "rainbow_4" => &mut self.rainbow[3],
+            _ => return Err(ThemeError::UnknownSlot(slot.to_owned())),
+        })
+    }
+}
+
+/// Parses `#rrggbb` or one of the basic color names.
+fn parse_color(value: &str) -> Option<Rgb> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+
+        let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+
+        return Some(Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+
+    Some(match value.to_ascii_lowercase().as_str() {
+        "black" => Rgb(0, 0, 0),
+        "red" => Rgb(205, 49, 49),
+        "green" => Rgb(13, 188, 121),
+        "yellow" => Rgb(229, 229, 16),
+        "blue" => Rgb(36, 114, 200),
+        "magenta" => Rgb(188, 63, 188),
+        "cyan" => Rgb(17, 168, 205),
+        "white" => Rgb(229, 229, 229),
+        "gray" | "grey" => Rgb(128, 128, 128),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod theme_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#1e8bd2"), Some(Rgb(30, 139, 210)));
+        assert_eq!(parse_color("Red"), Some(Rgb(205, 49, 49)));
+        assert_eq!(parse_color("#12345"), None);
+        assert_eq!(parse_color("purpleish"), None);
+    }
+
+    #[test]
+    fn test_load() {
+        let mut colors = HashMap::new();
+
+        colors.insert("string".to_owned(), "#000000".to_owned());
+
+        let theme = Theme::load("light", &colors).unwrap();
+
+        assert_eq!(theme.string, Rgb(0, 0, 0));
+        assert_eq!(theme.status_bg, Theme::light().status_bg);
+
+        colors.insert("rainbow_2".to_owned(), "blue".to_owned());
+
+        let theme = Theme::load("light", &colors).unwrap();
+
+        assert_eq!(theme.color(highlight::Type::Bracket(5)), Rgb(36, 114, 200));
+        assert!(Theme::load("solarized", &HashMap::new()).is_err());
+
+        colors.insert("keyword".to_owned(), "red".to_owned());
+
+        assert!(matches!(
+            Theme::load("dark", &colors),
+            Err(ThemeError::UnknownSlot(_))
+        ));
+    }
+}
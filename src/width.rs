@@ -0,0 +1,254 @@
+//! Terminal column widths of graphemes, following the East Asian Width
+//! property: wide and fullwidth characters, and emoji shown in emoji
+//! presentation, take two columns, everything else takes one. Control
+//! characters take the columns of the notation they are drawn in.
+
+use std::cmp;
+use unicode_segmentation::UnicodeSegmentation;
+
+const VARIATION_SELECTOR_EMOJI: char = '\u{fe0f}';
+
+/// The ranges of the characters whose East Asian Width is wide or
+/// fullwidth as of Unicode 14, with unassigned code points between them
+/// taken in, and the CJK planes where unassigned ones default to wide.
+const WIDE: [(u32, u32); 82] = [
+    (0x1100, 0x115f),
+    (0x231a, 0x231b),
+    (0x2329, 0x232a),
+    (0x23e9, 0x23ec),
+    (0x23f0, 0x23f0),
+    (0x23f3, 0x23f3),
+    (0x25fd, 0x25fe),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267f, 0x267f),
+    (0x2693, 0x2693),
+    (0x26a1, 0x26a1),
+    (0x26aa, 0x26ab),
+    (0x26bd, 0x26be),
+    (0x26c4, 0x26c5),
+    (0x26ce, 0x26ce),
+    (0x26d4, 0x26d4),
+    (0x26ea, 0x26ea),
+    (0x26f2, 0x26f3),
+    (0x26f5, 0x26f5),
+    (0x26fa, 0x26fa),
+    (0x26fd, 0x26fd),
+    (0x2705, 0x2705),
+    (0x270a, 0x270b),
+    (0x2728, 0x2728),
+    (0x274c, 0x274c),
+    (0x274e, 0x274e),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27b0, 0x27b0),
+    (0x27bf, 0x27bf),
+    (0x2b1b, 0x2b1c),
+    (0x2b50, 0x2b50),
+    (0x2b55, 0x2b55),
+    (0x2e80, 0x303e),
+    (0x3041, 0x3247),
+    (0x3250, 0x4dbf),
+    (0x4e00, 0xa4c6),
+    (0xa960, 0xa97c),
+    (0xac00, 0xd7a3),
+    (0xf900, 0xfad9),
+    (0xfe10, 0xfe19),
+    (0xfe30, 0xfe6b),
+    (0xff01, 0xff60),
+    (0xffe0, 0xffe6),
+    (0x16fe0, 0x1b2fb),
+    (0x1f004, 0x1f004),
+    (0x1f0cf, 0x1f0cf),
+    (0x1f18e, 0x1f18e),
+    (0x1f191, 0x1f19a),
+    (0x1f200, 0x1f320),
+    (0x1f32d, 0x1f335),
+    (0x1f337, 0x1f37c),
+    (0x1f37e, 0x1f393),
+    (0x1f3a0, 0x1f3ca),
+    (0x1f3cf, 0x1f3d3),
+    (0x1f3e0, 0x1f3f0),
+    (0x1f3f4, 0x1f3f4),
+    (0x1f3f8, 0x1f43e),
+    (0x1f440, 0x1f440),
+    (0x1f442, 0x1f4fc),
+    (0x1f4ff, 0x1f53d),
+    (0x1f54b, 0x1f54e),
+    (0x1f550, 0x1f567),
+    (0x1f57a, 0x1f57a),
+    (0x1f595, 0x1f596),
+    (0x1f5a4, 0x1f5a4),
+    (0x1f5fb, 0x1f64f),
+    (0x1f680, 0x1f6c5),
+    (0x1f6cc, 0x1f6cc),
+    (0x1f6d0, 0x1f6d2),
+    (0x1f6d5, 0x1f6df),
+    (0x1f6eb, 0x1f6ec),
+    (0x1f6f4, 0x1f6fc),
+    (0x1f7e0, 0x1f7f0),
+    (0x1f90c, 0x1f93a),
+    (0x1f93c, 0x1f945),
+    (0x1f947, 0x1f9ff),
+    (0x1fa70, 0x1faf6),
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+/// Returns the number of terminal columns `grapheme` occupies.
+pub fn grapheme_width(grapheme: &str) -> usize {
+    let Some(c) = grapheme.chars().next() else {
+        return 0;
+    };
+
+    if let Some(notation) = control_notation(grapheme) {
+        notation.len()
+    } else if is_wide(c) || grapheme.contains(VARIATION_SELECTOR_EMOJI) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Returns the number of terminal columns `text` occupies on one line.
+pub fn text_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+/// Returns the longest start of `text` that fits in `width` columns.
+pub fn take_width(text: &str, width: usize) -> &str {
+    let mut used = 0;
+
+    for (index, grapheme) in text.grapheme_indices(true) {
+        used += grapheme_width(grapheme);
+
+        if used > width {
+            return &text[..index];
+        }
+    }
+
+    text
+}
+
+/// Returns what is left of `text` after its first `columns` columns, with
+/// a wide character straddling them left out as well.
+pub fn skip_width(text: &str, columns: usize) -> &str {
+    let mut skipped = 0;
+
+    for (index, grapheme) in text.grapheme_indices(true) {
+        if skipped >= columns {
+            return &text[index..];
+        }
+
+        skipped += grapheme_width(grapheme);
+    }
+
+    ""
+}
+
+/// Returns how a control character is drawn: in caret notation, e.g. `^A`
+/// for `\u{1}` and `^?` for delete, or as `<feff>` for a byte order mark.
+/// Tabs are not included, as they are drawn as blanks.
+pub fn control_notation(grapheme: &str) -> Option<String> {
+    let mut chars = grapheme.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+
+    match c {
+        '\t' => None,
+        '\u{7f}' => Some("^?".to_owned()),
+        '\u{feff}' => Some("<feff>".to_owned()),
+        _ if c.is_ascii_control() => Some(format!("^{}", char::from(c as u8 + 0x40))),
+        _ => None,
+    }
+}
+
+/// Returns `text` as it can be put on a single line of the terminal: tabs
+/// become spaces and other control characters are drawn in caret notation,
+/// or by their code as `<9b>` when they have none.
+pub fn printable(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\t' => result.push(' '),
+            _ if c.is_control() => match control_notation(c.encode_utf8(&mut [0; 4])) {
+                Some(notation) => result.push_str(&notation),
+                None => result.push_str(&format!("<{:x}>", u32::from(c))),
+            },
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn is_wide(c: char) -> bool {
+    let code = u32::from(c);
+
+    WIDE.binary_search_by(|(first, last)| {
+        if code < *first {
+            cmp::Ordering::Greater
+        } else if code > *last {
+            cmp::Ordering::Less
+        } else {
+            cmp::Ordering::Equal
+        }
+    })
+    .is_ok()
+}
+
+#[cfg(test)]
+mod width_tests {
+    use super::*;
+
+    #[test]
+    fn test_grapheme_width() {
+        assert_eq!(grapheme_width("a"), 1);
+        assert_eq!(grapheme_width("e\u{301}"), 1);
+        assert_eq!(grapheme_width("漢"), 2);
+        assert_eq!(grapheme_width("Ａ"), 2);
+        assert_eq!(grapheme_width("\u{1f600}"), 2);
+        assert_eq!(grapheme_width("\u{a960}"), 2);
+        assert_eq!(grapheme_width("\u{1faf6}"), 2);
+        assert_eq!(grapheme_width("\u{16fe0}"), 2);
+        assert_eq!(grapheme_width("\u{303f}"), 1);
+        assert_eq!(grapheme_width("\u{2764}\u{fe0f}"), 2);
+        assert_eq!(grapheme_width(""), 0);
+        assert_eq!(grapheme_width("\u{1}"), 2);
+        assert_eq!(grapheme_width("\t"), 1);
+        assert_eq!(grapheme_width("\u{feff}"), 6);
+    }
+
+    #[test]
+    fn test_text_width() {
+        assert_eq!(text_width("ab漢"), 4);
+        assert_eq!(take_width("ab漢c", 3), "ab");
+        assert_eq!(take_width("ab漢c", 4), "ab漢");
+        assert_eq!(take_width("ab", 5), "ab");
+        assert_eq!(skip_width("漢字ab", 1), "字ab");
+        assert_eq!(skip_width("漢字ab", 4), "ab");
+        assert_eq!(skip_width("ab", 3), "");
+    }
+
+    #[test]
+    fn test_control_notation() {
+        assert_eq!(control_notation("\u{1}").as_deref(), Some("^A"));
+        assert_eq!(control_notation("\u{1b}").as_deref(), Some("^["));
+        assert_eq!(control_notation("\0").as_deref(), Some("^@"));
+        assert_eq!(control_notation("\u{7f}").as_deref(), Some("^?"));
+        assert_eq!(control_notation("\u{feff}").as_deref(), Some("<feff>"));
+        assert_eq!(control_notation("\t"), None);
+        assert_eq!(control_notation("a"), None);
+    }
+
+    #[test]
+    fn test_printable() {
+        assert_eq!(printable("a\tb"), "a b");
+        assert_eq!(printable("\u{1b}]2;title\u{7}"), "^[]2;title^G");
+        assert_eq!(printable("\u{9b}31m\r\n"), "<9b>31m^M^J");
+        assert_eq!(printable("漢字"), "漢字");
+    }
+}